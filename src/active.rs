@@ -0,0 +1,82 @@
+//! Resources in active use: those of a running compose project, volumes
+//! mounted by running containers, and containers docker restarts by itself
+//! with their images. `--nuclear` and `select` leave them alone unless
+//! `--include-active` is given, and then remove them only once that is
+//! confirmed a second time.
+
+use crate::display::{confirm, print_active, print_text};
+use crate::report::{CleanupReport, ReportItem};
+use colored::Colorize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INCLUDE: AtomicBool = AtomicBool::new(false);
+
+/// Note on active resources a cleanup left alone.
+pub const ACTIVE_NOTE: &str = "In active use";
+
+/// Remove active resources too (from `--include-active`).
+pub fn include_active() {
+    INCLUDE.store(true, Ordering::SeqCst);
+}
+
+pub fn is_including() -> bool {
+    INCLUDE.load(Ordering::SeqCst)
+}
+
+/// A resource a cleanup was about to remove that is in active use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveItem {
+    pub category: &'static str,
+    pub name: String,
+    pub reason: String,
+}
+
+impl ActiveItem {
+    /// The item for `name`, if `reason` says it is active.
+    pub fn new(category: &'static str, name: &str, reason: Option<String>) -> Option<Self> {
+        Some(Self {
+            category,
+            name: name.to_string(),
+            reason: reason?,
+        })
+    }
+}
+
+/// Show the `active` resources and decide whether they go too: only with
+/// `--include-active`, and then after a second confirmation unless
+/// `force` (a dry run just shows them included).
+pub fn include(active: &[ActiveItem], force: bool, dry_run: bool) -> Result<bool, String> {
+    if active.is_empty() {
+        return Ok(true);
+    }
+    print_active(active);
+    if !is_including() {
+        print_text(&format!(
+            "{}",
+            "  Leaving them alone; pass --include-active to remove them too".dimmed()
+        ));
+        print_text("");
+        return Ok(false);
+    }
+    if force || dry_run {
+        print_text("");
+        return Ok(true);
+    }
+    let confirmed = confirm(
+        &format!("Remove these {} active resources too?", active.len()),
+        false,
+    )?;
+    print_text("");
+    Ok(confirmed)
+}
+
+/// Record the `active` resources as left alone.
+pub fn skip(report: &mut CleanupReport, active: &[ActiveItem]) {
+    for item in active {
+        report.items.push(ReportItem::skipped(
+            item.category,
+            &item.name,
+            &format!("{}: {}", ACTIVE_NOTE, item.reason),
+        ));
+    }
+}
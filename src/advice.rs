@@ -0,0 +1,238 @@
+//! Next steps printed after a cleanup: what is still taking space and the
+//! command that would deal with it, so a run ends with a suggestion rather
+//! than a dead end. Only what the cleanup dealt with is measured again,
+//! Docker or the system caches, so the advice costs little; nothing is
+//! removed.
+
+use crate::commands::reclaim;
+use crate::config::Config;
+use crate::display::{format_bytes, is_text, print_advice};
+use crate::docker::Docker;
+use crate::report::Report;
+use crate::resources::DiskUsage;
+use crate::system::{discover_quick_caches, outermost, CacheInfo};
+
+/// Leftovers smaller than this aren't worth a suggestion.
+const ADVISE_ABOVE: u64 = 1_000_000_000;
+
+/// Docker Desktop's disk image may hold this much beyond Docker's own data
+/// before compacting it is suggested; some slack is normal.
+const DISK_IMAGE_SLACK: u64 = 10_000_000_000;
+
+/// What is left after a run, as far as it could be measured.
+#[derive(Debug, Default)]
+pub struct Remaining {
+    pub usage: Option<DiskUsage>,
+    /// Named volumes no container mounts
+    pub unmounted_volumes: usize,
+    /// Docker Desktop's disk image beyond what Docker's data needs
+    pub disk_image_excess: Option<u64>,
+    /// System cache bytes not used in the last 30 days
+    pub stale_caches: u64,
+}
+
+impl Remaining {
+    /// Measure what a cleanup by `command` (as in cleanup reports) dealt
+    /// with: the system caches after `system` commands, else the daemon (if
+    /// reachable).
+    pub fn measure(config: &Config, command: &str) -> Self {
+        let mut remaining = Self::default();
+        if command.starts_with("system ") {
+            let caches = discover_quick_caches(config);
+            let purgeable: Vec<&CacheInfo> = caches.iter().filter(|c| c.is_purgeable()).collect();
+            remaining.stale_caches = outermost(&purgeable).iter().map(|c| c.stale_size).sum();
+        } else if Docker::is_available() {
+            remaining.usage = Docker::get_disk_usage().ok();
+            remaining.unmounted_volumes = Docker::unused_volumes(&[])
+                .map(|v| v.unmounted.len())
+                .unwrap_or_default();
+            if Docker::is_local() {
+                remaining.disk_image_excess = reclaim::find().and_then(|image| {
+                    let usage = remaining.usage.as_ref()?;
+                    Some(image.excess(usage.total_size()))
+                });
+            }
+        }
+        remaining
+    }
+}
+
+/// A suggested next step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advice {
+    pub message: String,
+    pub command: String,
+}
+
+impl Advice {
+    fn new(message: String, command: &str) -> Self {
+        Self {
+            message,
+            command: command.to_string(),
+        }
+    }
+}
+
+/// Print the next steps after `report`, for cleanups that changed
+/// something; measuring takes a moment, so only when someone reads it.
+pub fn after(report: &Report, config: &Config) {
+    let Report::Cleanup(cleanup) = report else {
+        return;
+    };
+    if !is_text() || cleanup.dry_run || cleanup.aborted {
+        return;
+    }
+    print_advice(&recommend(
+        &Remaining::measure(config, &cleanup.command),
+        &cleanup.command,
+    ));
+}
+
+/// Suggestions for what `remaining` still holds, most space first. The
+/// command that just ran (`ran`, as in cleanup reports) isn't suggested
+/// again: whatever it left, it left on purpose.
+pub fn recommend(remaining: &Remaining, ran: &str) -> Vec<Advice> {
+    let mut advice: Vec<(u64, Advice)> = Vec::new();
+    if let Some(usage) = &remaining.usage {
+        let leftovers = usage.build_cache_reclaimable + usage.containers_reclaimable;
+        if leftovers >= ADVISE_ABOVE {
+            advice.push((
+                leftovers,
+                Advice::new(
+                    format!(
+                        "{} of stopped containers and build cache left",
+                        format_bytes(leftovers)
+                    ),
+                    "dockerase purge",
+                ),
+            ));
+        }
+        if usage.images_reclaimable >= ADVISE_ABOVE {
+            advice.push((
+                usage.images_reclaimable,
+                Advice::new(
+                    format!(
+                        "{} of images no container uses",
+                        format_bytes(usage.images_reclaimable)
+                    ),
+                    "dockerase select",
+                ),
+            ));
+        }
+        if remaining.unmounted_volumes > 0 && usage.volumes_reclaimable >= ADVISE_ABOVE {
+            advice.push((
+                usage.volumes_reclaimable,
+                Advice::new(
+                    format!(
+                        "{} in volumes no container mounts ({} named)",
+                        format_bytes(usage.volumes_reclaimable),
+                        remaining.unmounted_volumes
+                    ),
+                    "dockerase purge --named-volumes",
+                ),
+            ));
+        }
+    }
+    if let Some(excess) = remaining
+        .disk_image_excess
+        .filter(|e| *e >= DISK_IMAGE_SLACK)
+    {
+        advice.push((
+            excess,
+            Advice::new(
+                format!(
+                    "Docker.raw is {} larger than its contents",
+                    format_bytes(excess)
+                ),
+                "dockerase reclaim",
+            ),
+        ));
+    }
+    if remaining.stale_caches >= ADVISE_ABOVE {
+        advice.push((
+            remaining.stale_caches,
+            Advice::new(
+                format!(
+                    "{} of system caches unused for 30+ days",
+                    format_bytes(remaining.stale_caches)
+                ),
+                "dockerase system purge --older-than 30d",
+            ),
+        ));
+    }
+
+    advice.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+    let ran = format!("dockerase {}", ran);
+    advice
+        .into_iter()
+        .map(|(_, a)| a)
+        .filter(|a| a.command != ran)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1_000_000_000;
+
+    fn commands(advice: &[Advice]) -> Vec<&str> {
+        advice.iter().map(|a| a.command.as_str()).collect()
+    }
+
+    #[test]
+    fn test_recommend_suggests_a_command_per_leftover() {
+        let remaining = Remaining {
+            usage: Some(DiskUsage {
+                images_reclaimable: 3 * GB,
+                build_cache_reclaimable: 18 * GB,
+                volumes_reclaimable: 2 * GB,
+                ..DiskUsage::default()
+            }),
+            unmounted_volumes: 2,
+            disk_image_excess: Some(60 * GB),
+            stale_caches: GB / 2,
+        };
+        let advice = recommend(&remaining, "nuclear");
+        assert_eq!(
+            commands(&advice),
+            [
+                "dockerase reclaim",
+                "dockerase purge",
+                "dockerase select",
+                "dockerase purge --named-volumes",
+            ]
+        );
+        assert_eq!(
+            advice[0].message,
+            "Docker.raw is 60.0 GB larger than its contents"
+        );
+
+        // Not the command that just ran
+        let after_purge = recommend(&remaining, "purge");
+        assert_eq!(
+            commands(&after_purge),
+            [
+                "dockerase reclaim",
+                "dockerase select",
+                "dockerase purge --named-volumes"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recommend_nothing_when_little_is_left() {
+        let remaining = Remaining {
+            usage: Some(DiskUsage {
+                images_reclaimable: GB / 2,
+                volumes_reclaimable: 5 * GB,
+                ..DiskUsage::default()
+            }),
+            // Anonymous volumes only, which purge already removes
+            unmounted_volumes: 0,
+            disk_image_excess: Some(2 * GB),
+            stale_caches: 0,
+        };
+        assert!(recommend(&remaining, "purge").is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! How old the space images and build cache take is. Cleanups with
+//! `--older-than` pick what to remove by age, so seeing sizes bucketed by
+//! age shows which cutoff reaches the bulk of it. Images are aged by when
+//! they were built, build cache by when a build last used it.
+
+use crate::resources::{BuildCacheRecord, Image};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Each bucket's label and the age it starts at, in days; the last one is
+/// open-ended.
+const BUCKETS: [(&str, u64); 4] = [("<1w", 0), ("1-4w", 7), ("1-3mo", 28), (">3mo", 90)];
+
+/// Resources whose age Docker didn't report.
+const UNKNOWN: &str = "unknown";
+
+/// What images and build cache of one age take.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgeBucket {
+    pub label: &'static str,
+    /// The youngest age in the bucket, in days; none for resources of
+    /// unknown age
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_days: Option<u64>,
+    pub images: u64,
+    pub image_count: usize,
+    pub build_cache: u64,
+    pub cache_count: usize,
+}
+
+impl AgeBucket {
+    fn new(label: &'static str, min_days: Option<u64>) -> Self {
+        Self {
+            label,
+            min_days,
+            images: 0,
+            image_count: 0,
+            build_cache: 0,
+            cache_count: 0,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.images + self.build_cache
+    }
+}
+
+/// Images and build cache by age, by `analyze ages`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgeReport {
+    /// Youngest first, then one for unknown ages if there are any
+    pub buckets: Vec<AgeBucket>,
+}
+
+impl AgeReport {
+    /// Bucket `images`, each counted once however many tags it has, and the
+    /// build `cache` by age at `now` (UNIX seconds).
+    pub fn new(images: &[Image], cache: &[BuildCacheRecord], now: u64) -> Self {
+        let mut buckets: Vec<AgeBucket> = BUCKETS
+            .iter()
+            .map(|(label, days)| AgeBucket::new(label, Some(*days)))
+            .collect();
+        let mut unknown = AgeBucket::new(UNKNOWN, None);
+
+        let mut seen = HashSet::new();
+        for image in images.iter().filter(|i| seen.insert(&i.id)) {
+            let bucket = match image.created() {
+                Some(created) => bucket_for(&mut buckets, now.saturating_sub(created)),
+                None => &mut unknown,
+            };
+            bucket.images += image.size_bytes();
+            bucket.image_count += 1;
+        }
+        for record in cache {
+            let bucket = match record.last_used {
+                Some(used) => bucket_for(&mut buckets, now.saturating_sub(used)),
+                None => &mut unknown,
+            };
+            bucket.build_cache += record.size;
+            bucket.cache_count += 1;
+        }
+
+        if unknown.image_count + unknown.cache_count > 0 {
+            buckets.push(unknown);
+        }
+        Self { buckets }
+    }
+
+    /// What resources at least `days` old take: the most a cleanup with
+    /// `--older-than {days}d` could free.
+    pub fn older_than(&self, days: u64) -> u64 {
+        self.buckets
+            .iter()
+            .filter(|b| b.min_days.is_some_and(|min| min >= days))
+            .map(AgeBucket::total)
+            .sum()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().map(AgeBucket::total).sum()
+    }
+}
+
+/// The bucket for something `age` seconds old.
+fn bucket_for(buckets: &mut [AgeBucket], age: u64) -> &mut AgeBucket {
+    let days = age / 86_400;
+    buckets
+        .iter_mut()
+        .rev()
+        .find(|b| b.min_days.is_some_and(|min| days >= min))
+        .expect("the first bucket starts at zero")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::format_timestamp;
+
+    const DAY: u64 = 86_400;
+    const NOW: u64 = 1_000 * DAY;
+
+    fn image(id: &str, size: &str, days_old: u64) -> Image {
+        Image {
+            id: id.to_string(),
+            repository: "app".to_string(),
+            tag: id.to_string(),
+            size: size.to_string(),
+            created_at: format!("{} +0000 UTC", format_timestamp(NOW - days_old * DAY)),
+            digest: String::new(),
+        }
+    }
+
+    fn cache(size: u64, days_old: Option<u64>) -> BuildCacheRecord {
+        BuildCacheRecord {
+            size,
+            last_used: days_old.map(|d| NOW - d * DAY),
+            in_use: Some(false),
+        }
+    }
+
+    #[test]
+    fn test_buckets_images_by_build_and_cache_by_last_use() {
+        let images = [
+            image("a", "100MB", 2),
+            // The same image under a second tag counts once
+            image("a", "100MB", 2),
+            image("b", "1GB", 7),
+            image("c", "2GB", 400),
+        ];
+        let cache = [cache(50, Some(0)), cache(30, Some(45)), cache(5, None)];
+        let report = AgeReport::new(&images, &cache, NOW);
+
+        let sizes: Vec<(&str, u64, usize, u64, usize)> = report
+            .buckets
+            .iter()
+            .map(|b| {
+                (
+                    b.label,
+                    b.images,
+                    b.image_count,
+                    b.build_cache,
+                    b.cache_count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                ("<1w", 100_000_000, 1, 50, 1),
+                ("1-4w", 1_000_000_000, 1, 0, 0),
+                ("1-3mo", 0, 0, 30, 1),
+                (">3mo", 2_000_000_000, 1, 0, 0),
+                ("unknown", 0, 0, 5, 1),
+            ]
+        );
+        assert_eq!(report.older_than(7), 3_000_000_030);
+        assert_eq!(report.older_than(90), 2_000_000_000);
+        assert_eq!(report.total(), 3_100_000_085);
+    }
+
+    #[test]
+    fn test_unknown_bucket_only_when_needed() {
+        let report = AgeReport::new(&[image("a", "1MB", 30)], &[], NOW);
+        assert_eq!(report.buckets.len(), BUCKETS.len());
+        assert_eq!(report.buckets[2].image_count, 1);
+    }
+}
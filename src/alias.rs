@@ -0,0 +1,201 @@
+//! Command shortcuts from the config's `[alias]`, like git's and cargo's:
+//! `weekly = "purge --older-than 7d --named-volumes"` makes `dockerase
+//! weekly` run that purge. An alias expands in place of the command, so
+//! flags before it still apply and arguments after it are appended.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+/// Aliases may refer to other aliases, up to this deep.
+const MAX_DEPTH: usize = 16;
+
+/// What an alias stands for: a command line, split like a shell would
+/// (quotes group words), or the words themselves.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Line(String),
+    Words(Vec<String>),
+}
+
+impl Alias {
+    fn words(&self) -> Result<Vec<String>, String> {
+        match self {
+            Alias::Line(line) => split_words(line),
+            Alias::Words(words) => Ok(words.clone()),
+        }
+    }
+}
+
+/// `args` (program name first) with the command replaced by what it stands
+/// for, if it is an alias. Built-in commands can't be redefined.
+pub fn expand(
+    mut args: Vec<OsString>,
+    aliases: &BTreeMap<String, Alias>,
+    cli: &clap::Command,
+) -> Result<Vec<OsString>, String> {
+    if let Some(name) = aliases.keys().find(|n| cli.find_subcommand(n).is_some()) {
+        return Err(format!(
+            "Alias '{}' in the config would replace the built-in command",
+            name
+        ));
+    }
+    let mut expanded = Vec::new();
+    while let Some(position) = command_position(&args, cli) {
+        let Some(name) = args[position].to_str().map(str::to_string) else {
+            break;
+        };
+        let Some(alias) = aliases.get(&name) else {
+            break;
+        };
+        if expanded.contains(&name) || expanded.len() == MAX_DEPTH {
+            return Err(format!("Alias '{}' refers back to itself", name));
+        }
+        let words = alias
+            .words()
+            .map_err(|e| format!("Alias '{}': {}", name, e))?;
+        args.splice(position..=position, words.into_iter().map(OsString::from));
+        expanded.push(name);
+    }
+    Ok(args)
+}
+
+/// Index of the first argument that isn't a global flag or its value.
+fn command_position(args: &[OsString], cli: &clap::Command) -> Option<usize> {
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].to_str()?;
+        if arg == "--" {
+            return None;
+        }
+        let takes_value = if let Some(long) = arg.strip_prefix("--") {
+            !long.contains('=')
+                && cli
+                    .get_arguments()
+                    .any(|a| a.get_long() == Some(long) && a.get_action().takes_values())
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+            // Only a value-taking flag at the end of a cluster takes the
+            // next argument; earlier in the cluster the rest is its value
+            shorts.chars().last().is_some_and(|last| {
+                !shorts[..shorts.len() - last.len_utf8()]
+                    .chars()
+                    .any(|c| takes_short_value(cli, c))
+                    && takes_short_value(cli, last)
+            })
+        } else {
+            return Some(i);
+        };
+        i += if takes_value { 2 } else { 1 };
+    }
+    None
+}
+
+fn takes_short_value(cli: &clap::Command, short: char) -> bool {
+    cli.get_arguments()
+        .any(|a| a.get_short() == Some(short) && a.get_action().takes_values())
+}
+
+/// Split `line` into words at whitespace, keeping quoted text together.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unclosed quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction, Command};
+
+    fn cli() -> Command {
+        Command::new("dockerase")
+            .arg(
+                Arg::new("force")
+                    .short('f')
+                    .long("force")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(Arg::new("host").short('H').long("host"))
+            .subcommand(Command::new("purge"))
+            .subcommand(Command::new("paths"))
+    }
+
+    fn aliases(entries: &[(&str, Alias)]) -> BTreeMap<String, Alias> {
+        entries
+            .iter()
+            .map(|(name, alias)| (name.to_string(), alias.clone()))
+            .collect()
+    }
+
+    fn expand_line(line: &str, aliases: &BTreeMap<String, Alias>) -> Result<String, String> {
+        let args = line.split(' ').map(OsString::from).collect();
+        let args = expand(args, aliases, &cli())?;
+        Ok(args
+            .iter()
+            .map(|a| a.to_str().unwrap())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    #[test]
+    fn test_alias_expands_in_place_of_the_command() {
+        let aliases = aliases(&[
+            (
+                "weekly",
+                Alias::Line("purge --older-than 7d --keep 'my app*'".to_string()),
+            ),
+            ("w", Alias::Words(vec!["weekly".to_string()])),
+        ]);
+        assert_eq!(
+            expand_line("dockerase -H ssh://box w --dry-run", &aliases).unwrap(),
+            "dockerase -H ssh://box purge --older-than 7d --keep my app* --dry-run"
+        );
+        // Global flag values and built-in commands aren't aliases
+        assert_eq!(
+            expand_line("dockerase --host weekly paths", &aliases).unwrap(),
+            "dockerase --host weekly paths"
+        );
+        assert_eq!(
+            expand_line("dockerase -fH weekly", &aliases).unwrap(),
+            "dockerase -fH weekly"
+        );
+        assert_eq!(
+            expand_line("dockerase -f weekly", &aliases).unwrap(),
+            "dockerase -f purge --older-than 7d --keep my app*"
+        );
+    }
+
+    #[test]
+    fn test_bad_aliases_are_errors() {
+        let shadowing = aliases(&[("purge", Alias::Line("paths".to_string()))]);
+        assert!(expand_line("dockerase paths", &shadowing).is_err());
+
+        let looping = aliases(&[
+            ("a", Alias::Line("b".to_string())),
+            ("b", Alias::Line("a --force".to_string())),
+        ]);
+        let err = expand_line("dockerase a", &looping).unwrap_err();
+        assert!(err.contains("'a'"), "{err}");
+
+        let unclosed = aliases(&[("x", Alias::Line("purge --keep 'web".to_string()))]);
+        assert!(expand_line("dockerase x", &unclosed).is_err());
+    }
+}
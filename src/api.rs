@@ -0,0 +1,231 @@
+//! Resources in the Engine API's JSON shape, converted into the types the
+//! CLI backend parses. Shared by the Engine API client and the Podman
+//! backend, whose `--format json` output follows the same layout (with a few
+//! differences handled here).
+
+use crate::resources::{BuildCacheRecord, Container, Image, Network, Volume};
+use crate::units::{format_timestamp, parse_docker_timestamp};
+use serde_json::Value;
+
+pub fn array(value: &Value) -> &[Value] {
+    value.as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+/// `key`, or Podman's lowercase spelling of it.
+fn field<'a>(v: &'a Value, key: &str) -> &'a Value {
+    match &v[key] {
+        Value::Null => &v[key.to_lowercase()],
+        value => value,
+    }
+}
+
+/// A creation time in the CLI's `CreatedAt` format, from a UNIX timestamp
+/// or an RFC 3339 string.
+fn created_at(created: &Value) -> String {
+    match created {
+        Value::Number(secs) => secs
+            .as_u64()
+            .map(|secs| format!("{} +0000 UTC", format_timestamp(secs)))
+            .unwrap_or_default(),
+        Value::String(s) => rfc3339_to_cli(s),
+        _ => String::new(),
+    }
+}
+
+/// An RFC 3339 time (`2024-01-05T10:30:00.123+01:00`) in the CLI's format.
+fn rfc3339_to_cli(s: &str) -> String {
+    let Some((date, rest)) = s.split_once('T') else {
+        return String::new();
+    };
+    let Some(time) = rest.get(..8) else {
+        return String::new();
+    };
+    let zone = rest[8..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset = if zone.is_empty() || zone == "Z" {
+        "+0000".to_string()
+    } else {
+        zone.replace(':', "")
+    };
+    format!("{} {} {}", date, time, offset)
+}
+
+pub fn image_from_api(v: &Value) -> Image {
+    let tag = array(&v["RepoTags"])
+        .iter()
+        .filter_map(Value::as_str)
+        .find(|t| *t != "<none>:<none>");
+    let (repository, tag) = match tag.and_then(|t| t.rsplit_once(':')) {
+        Some((repository, tag)) => (repository.to_string(), tag.to_string()),
+        None => ("<none>".to_string(), "<none>".to_string()),
+    };
+    // Only a digest in the tag's repository pulls this tag back, as the
+    // docker CLI's `--digests` column has it
+    let digest = array(&v["RepoDigests"])
+        .iter()
+        .filter_map(Value::as_str)
+        .filter_map(|d| d.split_once('@'))
+        .find(|(name, _)| *name == repository)
+        .map(|(_, digest)| digest.to_string())
+        .unwrap_or_default();
+    Image {
+        id: string(&v["Id"]),
+        repository,
+        tag,
+        size: format!("{}B", v["Size"].as_u64().unwrap_or(0)),
+        created_at: created_at(&v["Created"]),
+        digest,
+    }
+}
+
+pub fn container_from_api(v: &Value) -> Container {
+    let name = array(&v["Names"])
+        .first()
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .trim_start_matches('/')
+        .to_string();
+    Container {
+        id: string(&v["Id"]),
+        names: name,
+        image: string(&v["Image"]),
+        state: string(&v["State"]),
+        status: string(&v["Status"]),
+        // Podman nests it as `Size.rwSize`
+        size: v["SizeRw"]
+            .as_u64()
+            .or_else(|| v["Size"]["rwSize"].as_u64())
+            .map(|size| format!("{}B", size))
+            .unwrap_or_default(),
+        created_at: created_at(&v["Created"]),
+        labels: labels(&v["Labels"]),
+    }
+}
+
+/// A `BuildCache` entry of `/system/df`; `LastUsedAt` is null for records
+/// no build has reused.
+pub fn build_cache_from_api(v: &Value) -> BuildCacheRecord {
+    let time = |key: &str| parse_docker_timestamp(&created_at(&v[key]));
+    BuildCacheRecord {
+        size: v["Size"].as_u64().unwrap_or(0),
+        last_used: time("LastUsedAt").or_else(|| time("CreatedAt")),
+        in_use: v["InUse"].as_bool(),
+    }
+}
+
+pub fn volume_from_api(v: &Value) -> Volume {
+    Volume {
+        name: string(&v["Name"]),
+        driver: string(&v["Driver"]),
+        mountpoint: string(&v["Mountpoint"]),
+        labels: labels(&v["Labels"]),
+    }
+}
+
+/// A label map in the CLI's sorted `key=value,...` form.
+fn labels(v: &Value) -> String {
+    let mut labels: Vec<String> = v
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(key, value)| format!("{}={}", key, value.as_str().unwrap_or_default()))
+        .collect();
+    labels.sort();
+    labels.join(",")
+}
+
+pub fn network_from_api(v: &Value) -> Network {
+    Network {
+        id: string(field(v, "Id")),
+        name: string(field(v, "Name")),
+        driver: string(field(v, "Driver")),
+        scope: string(&v["Scope"]),
+        created_at: created_at(field(v, "Created")),
+        labels: labels(field(v, "Labels")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_converts_api_resources() {
+        let container = container_from_api(&json!({
+            "Id": "c0ffee", "Names": ["/db"], "Image": "postgres:16",
+            "State": "exited", "Status": "Exited (0) 3 days ago", "SizeRw": 2048,
+            "Created": 0
+        }));
+        assert_eq!(container.names, "db");
+        assert_eq!(container.size_bytes(), 2048);
+        assert_eq!(container.created_at, "1970-01-01 00:00:00 +0000 UTC");
+
+        let dangling = image_from_api(
+            &json!({"Id": "sha256:0123456789abcdef", "RepoTags": ["<none>:<none>"]}),
+        );
+        assert!(dangling.is_dangling());
+        assert_eq!(dangling.digest(), None);
+
+        let pulled = image_from_api(&json!({
+            "Id": "sha256:aaa", "RepoTags": ["postgres:16"],
+            "RepoDigests": ["postgres@sha256:feed"]
+        }));
+        assert_eq!(pulled.digest(), Some("sha256:feed"));
+
+        // Retagged from a pulled image, but never pushed under its own name
+        let retagged = image_from_api(&json!({
+            "Id": "sha256:aaa", "RepoTags": ["mydb:1"],
+            "RepoDigests": ["postgres@sha256:feed"]
+        }));
+        assert_eq!(retagged.digest(), None);
+
+        let volume = volume_from_api(&json!({
+            "Name": "4f4f", "Driver": "local",
+            "Labels": {"com.docker.volume.anonymous": ""}
+        }));
+        assert!(volume.is_anonymous());
+
+        let network = network_from_api(&json!({
+            "Id": "net1", "Name": "app-net", "Driver": "bridge", "Scope": "local",
+            "Created": "2024-01-05T10:30:00.123456+01:00"
+        }));
+        assert_eq!(network.created_at, "2024-01-05 10:30:00 +0100");
+
+        let cache = build_cache_from_api(&json!({
+            "Size": 4096, "CreatedAt": "1970-01-02T00:00:00Z", "LastUsedAt": null,
+            "InUse": true
+        }));
+        assert_eq!(
+            (cache.size, cache.last_used, cache.in_use),
+            (4096, Some(86_400), Some(true))
+        );
+    }
+
+    #[test]
+    fn test_converts_podman_resources() {
+        let container = container_from_api(&json!({
+            "Id": "c0ffee", "Names": ["db"], "Image": "docker.io/library/postgres:16",
+            "State": "exited", "Size": {"rootFsSize": 400000000, "rwSize": 4096},
+            "Created": "2024-01-05T10:30:00.5Z"
+        }));
+        assert_eq!(container.names, "db");
+        assert_eq!(container.size_bytes(), 4096);
+        assert_eq!(container.created_at, "2024-01-05 10:30:00 +0000");
+
+        // Untagged images have no RepoTags at all
+        let dangling = image_from_api(&json!({"Id": "0123456789ab", "RepoTags": null}));
+        assert!(dangling.is_dangling());
+
+        let network = network_from_api(&json!({
+            "name": "podman", "id": "2f25", "driver": "bridge",
+            "created": "2024-01-05T10:30:00Z", "labels": {"env": "dev"}
+        }));
+        assert_eq!(network.name, "podman");
+        assert_eq!(network.driver, "bridge");
+        assert_eq!(network.labels, "env=dev");
+    }
+}
@@ -0,0 +1,387 @@
+//! Build artifacts left in project directories: `node_modules`, Rust
+//! `target/`, `.venv` and the like. They can be rebuilt from the project's
+//! sources, so those nobody has touched in a while are safe to delete.
+//! Artifact directories are not searched for more artifacts, and symlinks
+//! are never followed. A `.dockeraseignore` in the scanned directory lists
+//! paths (gitignore syntax) never searched or offered.
+
+use crate::filters::glob_match;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Artifacts untouched for this long count as stale unless configured.
+pub const DEFAULT_OLDER_THAN: Duration = Duration::from_secs(60 * 24 * 60 * 60);
+
+/// Directories never searched: they hold no builds, only history.
+const SKIPPED_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+/// Lists what a scan of its directory leaves alone.
+pub const IGNORE_FILE: &str = ".dockeraseignore";
+
+/// One line of an ignore file.
+#[derive(Debug, Clone, PartialEq)]
+struct IgnoreRule {
+    /// Path segments, each matched with `glob_match`; `**` matches any
+    /// number of them
+    segments: Vec<String>,
+    /// Matched against the whole path from the scanned directory, rather
+    /// than against any directory's name
+    anchored: bool,
+    /// A `!` rule, taking back what earlier rules ignored
+    negated: bool,
+}
+
+/// The rules of an ignore file, in gitignore syntax: `*` and `?` match
+/// within a path segment, `**` across them, a pattern containing `/` is
+/// relative to the scanned directory, and the last matching rule decides.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ignore {
+    rules: Vec<IgnoreRule>,
+}
+
+impl Ignore {
+    /// The rules in `root`'s ignore file; none if it has no ignore file.
+    pub fn read(root: &Path) -> Result<Self, String> {
+        let path = root.join(IGNORE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                // Only directories are scanned, so a trailing `/` changes nothing
+                let line = line.trim_end_matches('/');
+                let anchored = line.contains('/');
+                let segments = line
+                    .trim_start_matches('/')
+                    .split('/')
+                    .map(str::to_string)
+                    .collect();
+                IgnoreRule {
+                    segments,
+                    anchored,
+                    negated,
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether the directory at `relative` (to the scanned directory) is
+    /// left alone.
+    pub fn ignores(&self, relative: &Path) -> bool {
+        let path: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let Some(name) = path.last() else {
+            return false;
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                if rule.anchored {
+                    segments_match(&rule.segments, &path)
+                } else {
+                    glob_match(&rule.segments[0], name)
+                }
+            })
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+fn segments_match(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| segments_match(rest, &path[skip..]))
+        }
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(name, path)| glob_match(first, name) && segments_match(rest, path)),
+    }
+}
+
+/// A directory name that marks a build artifact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub name: String,
+    pub kind: String,
+    /// Files one of which must sit next to the directory, for names as
+    /// common as `target` or `build` that are only artifacts in some
+    /// projects; none for names that always are
+    pub markers: Vec<String>,
+}
+
+impl Pattern {
+    fn new(name: &str, kind: &str, markers: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            markers: markers.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|n| n == self.name.as_str())
+            && (self.markers.is_empty()
+                || path
+                    .parent()
+                    .is_some_and(|parent| self.markers.iter().any(|m| parent.join(m).exists())))
+    }
+}
+
+/// The artifacts looked for out of the box.
+pub fn default_patterns() -> Vec<Pattern> {
+    vec![
+        Pattern::new("node_modules", "Node", &[]),
+        Pattern::new("target", "Rust", &["Cargo.toml"]),
+        Pattern::new(".venv", "Python", &[]),
+        // A checked-in `build/` has no build file next to it
+        Pattern::new(
+            "build",
+            "Build output",
+            &[
+                "build.gradle",
+                "build.gradle.kts",
+                "CMakeLists.txt",
+                "package.json",
+                "setup.py",
+                "pyproject.toml",
+            ],
+        ),
+        Pattern::new("Pods", "CocoaPods", &["Podfile"]),
+    ]
+}
+
+/// The config's `[scan]` section.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanSettings {
+    /// Directory names to look for besides the built-in ones
+    pub patterns: Vec<String>,
+    /// Artifacts untouched for this long count as stale
+    #[serde(deserialize_with = "crate::config::duration")]
+    pub older_than: Option<Duration>,
+}
+
+impl ScanSettings {
+    pub fn patterns(&self) -> Vec<Pattern> {
+        let mut patterns = default_patterns();
+        for name in &self.patterns {
+            if !patterns.iter().any(|p| &p.name == name) {
+                patterns.push(Pattern::new(name, name, &[]));
+            }
+        }
+        patterns
+    }
+}
+
+/// A build artifact directory with its total size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub kind: String,
+    pub size: u64,
+    /// When the most recently modified file inside was last changed
+    pub modified: Option<SystemTime>,
+}
+
+impl Artifact {
+    /// How long ago the artifact was last modified.
+    pub fn age(&self) -> Option<Duration> {
+        self.modified
+            .map(|m| SystemTime::now().duration_since(m).unwrap_or_default())
+    }
+
+    pub fn is_stale(&self, older_than: Duration) -> bool {
+        self.age().is_some_and(|age| age >= older_than)
+    }
+}
+
+/// Every artifact under `root` that its ignore file doesn't exclude,
+/// largest first.
+pub fn scan(root: &Path, patterns: &[Pattern]) -> Result<Vec<Artifact>, String> {
+    fs::read_dir(root).map_err(|e| format!("Failed to read {}: {}", root.display(), e))?;
+    let ignore = Ignore::read(root)?;
+    let mut artifacts = Vec::new();
+    // Directories wait as paths, not open handles, so a deep tree can't
+    // run out of file descriptors
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if !file_type.is_dir() {
+                continue;
+            }
+            let path = entry.path();
+            if ignore.ignores(path.strip_prefix(root).unwrap_or(&path)) {
+                continue;
+            }
+            if let Some(pattern) = patterns.iter().find(|p| p.matches(&path)) {
+                let (size, modified) = measure(&path);
+                artifacts.push(Artifact {
+                    path,
+                    kind: pattern.kind.clone(),
+                    size,
+                    modified,
+                });
+            } else if !SKIPPED_DIRS.iter().any(|d| entry.file_name() == *d) {
+                dirs.push(path);
+            }
+        }
+    }
+    artifacts.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    Ok(artifacts)
+}
+
+/// Total size of the files under `path` and the newest modification time
+/// among them (the directory's own, if it holds no files).
+fn measure(path: &Path) -> (u64, Option<SystemTime>) {
+    let mut size = 0;
+    let mut newest: Option<SystemTime> = None;
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                dirs.push(entry.path());
+                continue;
+            }
+            size += meta.len();
+            if let Ok(modified) = meta.modified() {
+                newest = Some(newest.map_or(modified, |n| n.max(modified)));
+            }
+        }
+    }
+    let newest = newest.or_else(|| fs::metadata(path).and_then(|m| m.modified()).ok());
+    (size, newest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn write(path: &Path, len: usize, age: Duration) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; len]).unwrap();
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn test_scan_finds_artifacts_with_size_and_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let day = Duration::from_secs(24 * 60 * 60);
+        write(&root.join("web/node_modules/a/index.js"), 300, 90 * day);
+        write(&root.join("web/node_modules/b/index.js"), 200, 100 * day);
+        write(&root.join("cli/Cargo.toml"), 10, day);
+        write(&root.join("cli/target/debug/cli"), 1_000, 2 * day);
+        // Not a Rust project, so not an artifact
+        write(&root.join("notes/target/todo.txt"), 50, 300 * day);
+        write(&root.join(".git/build/x"), 50, 300 * day);
+        // A `build/` counts only next to a build file
+        write(&root.join("docs/build/index.html"), 40, 300 * day);
+        write(&root.join("app/build.gradle"), 10, day);
+        write(&root.join("app/build/app.jar"), 70, 300 * day);
+
+        let artifacts = scan(root, &default_patterns()).unwrap();
+        let found: Vec<(PathBuf, &str, u64)> = artifacts
+            .iter()
+            .map(|a| {
+                (
+                    a.path.strip_prefix(root).unwrap().to_path_buf(),
+                    a.kind.as_str(),
+                    a.size,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (PathBuf::from("cli/target"), "Rust", 1_000),
+                (PathBuf::from("web/node_modules"), "Node", 500),
+                (PathBuf::from("app/build"), "Build output", 70),
+            ]
+        );
+
+        // The newest file inside decides the age
+        let stale: Vec<bool> = artifacts.iter().map(|a| a.is_stale(60 * day)).collect();
+        assert_eq!(stale, [false, true, true]);
+        let age = artifacts[1].age().unwrap();
+        assert!(age >= 90 * day && age < 91 * day, "{age:?}");
+    }
+
+    #[test]
+    fn test_ignore_file_excludes_directories() {
+        let ignore = Ignore::parse(
+            "# generated by the build\n\
+             vendor/\n\
+             /apps/*/build\n\
+             **/fixtures/**/node_modules\n\
+             legacy-*\n\
+             !legacy-web\n",
+        );
+        let ignores = |path: &str| ignore.ignores(Path::new(path));
+        assert!(ignores("vendor"));
+        assert!(ignores("libs/vendor"));
+        assert!(ignores("apps/shop/build"));
+        assert!(!ignores("libs/shop/build"));
+        assert!(!ignores("apps/shop/web/build"));
+        assert!(ignores("tests/fixtures/node_modules"));
+        assert!(ignores("a/fixtures/b/c/node_modules"));
+        assert!(!ignores("web/node_modules"));
+        assert!(ignores("legacy-api"));
+        assert!(!ignores("legacy-web"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let day = Duration::from_secs(24 * 60 * 60);
+        write(&root.join("web/node_modules/x.js"), 10, day);
+        write(&root.join("vendor/lib/node_modules/x.js"), 10, day);
+        fs::write(root.join(IGNORE_FILE), "vendor\n").unwrap();
+        let found: Vec<PathBuf> = scan(root, &default_patterns())
+            .unwrap()
+            .into_iter()
+            .map(|a| a.path)
+            .collect();
+        assert_eq!(found, [root.join("web/node_modules")]);
+    }
+
+    #[test]
+    fn test_configured_patterns_extend_the_defaults() {
+        let settings = ScanSettings {
+            patterns: vec!["dist".to_string(), "node_modules".to_string()],
+            older_than: None,
+        };
+        let patterns = settings.patterns();
+        assert_eq!(patterns.len(), default_patterns().len() + 1);
+        assert_eq!(patterns.last().unwrap(), &Pattern::new("dist", "dist", &[]));
+    }
+}
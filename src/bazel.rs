@@ -0,0 +1,147 @@
+//! Bazel's output bases, freed by Bazel itself. Each workspace built gets an
+//! output base under `_bazel_$USER` holding its build outputs and external
+//! repositories; they are often tens of gigabytes, contain read-only files
+//! and belong to a Bazel server that may still be running, so they are
+//! removed with `bazel clean --expunge` from their workspace rather than
+//! deleted file by file.
+
+use crate::paths::installed;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Marker Bazel writes into every output base, naming its workspace.
+const MARKER: &str = "DO_NOT_BUILD_HERE";
+
+/// The launchers tried, in order; bazelisk picks each workspace's version.
+const PROGRAMS: [&str; 2] = ["bazel", "bazelisk"];
+
+/// One workspace's build state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputBase {
+    pub path: PathBuf,
+    pub workspace: PathBuf,
+}
+
+/// Where Bazel keeps its per-user roots: `$TEST_TMPDIR` when set, as for
+/// Bazel itself, else `~/.cache/bazel` (following `$XDG_CACHE_HOME`) or, on
+/// macOS, `/private/var/tmp`. `cache` is the XDG cache directory.
+pub fn output_root(cache: &Path) -> PathBuf {
+    if let Some(dir) = env::var_os("TEST_TMPDIR").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/private/var/tmp")
+    } else {
+        cache.join("bazel")
+    }
+}
+
+/// The `_bazel_<user>` directories under `root`, for every user whose
+/// directory is readable.
+pub fn user_roots(root: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return vec![];
+    };
+    let mut roots: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("_bazel_"))
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    roots.sort();
+    roots
+}
+
+/// The output bases in a `_bazel_<user>` directory. The install bases and
+/// the repository cache sit next to them but have no marker.
+pub fn output_bases(user_root: &Path) -> Vec<OutputBase> {
+    let Ok(entries) = fs::read_dir(user_root) else {
+        return vec![];
+    };
+    let mut bases: Vec<OutputBase> = entries
+        .flatten()
+        .filter_map(|e| {
+            let marker = fs::read_to_string(e.path().join(MARKER)).ok()?;
+            Some(OutputBase {
+                path: e.path(),
+                workspace: parse_marker(&marker)?,
+            })
+        })
+        .collect();
+    bases.sort_by(|a, b| a.workspace.cmp(&b.workspace));
+    bases
+}
+
+/// The workspace named by an output base's marker file.
+fn parse_marker(contents: &str) -> Option<PathBuf> {
+    let workspace = PathBuf::from(contents.trim());
+    workspace.is_absolute().then_some(workspace)
+}
+
+/// The Bazel launcher on `PATH`, if any.
+pub fn program() -> Option<&'static str> {
+    PROGRAMS.into_iter().find(|program| installed(program))
+}
+
+/// Run `bazel clean --expunge` for `base` from its workspace. The output base
+/// is passed explicitly so a `.bazelrc` pointing elsewhere can't redirect the
+/// clean to another one.
+pub fn expunge(program: &str, base: &OutputBase) -> Result<(), String> {
+    let output = Command::new(program)
+        .arg(format!("--output_base={}", base.path.display()))
+        .args(["clean", "--expunge"])
+        .current_dir(&base.workspace)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} clean --expunge in {} failed: {}",
+            program,
+            base.workspace.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_bases_are_the_directories_with_a_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_root = dir.path().join("_bazel_dev");
+        for (hash, marker) in [
+            ("1111", Some("/home/dev/code/app\n")),
+            ("2222", Some("/home/dev/code/api")),
+            ("3333", Some("relative/path")),
+            ("install", None),
+            ("cache", None),
+        ] {
+            let base = user_root.join(hash);
+            fs::create_dir_all(&base).unwrap();
+            if let Some(marker) = marker {
+                fs::write(base.join(MARKER), marker).unwrap();
+            }
+        }
+        fs::create_dir_all(dir.path().join("unrelated")).unwrap();
+
+        assert_eq!(user_roots(dir.path()), vec![user_root.clone()]);
+        assert_eq!(
+            output_bases(&user_root),
+            vec![
+                OutputBase {
+                    path: user_root.join("2222"),
+                    workspace: PathBuf::from("/home/dev/code/api"),
+                },
+                OutputBase {
+                    path: user_root.join("1111"),
+                    workspace: PathBuf::from("/home/dev/code/app"),
+                },
+            ]
+        );
+    }
+}
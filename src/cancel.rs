@@ -0,0 +1,50 @@
+use crate::display::print_cancelled;
+use crate::report::CleanupReport;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Install a Ctrl-C (and SIGTERM) handler that requests cancellation instead
+/// of killing the process, so long operations can stop between items and
+/// leave a consistent partial state. A second signal exits immediately.
+pub fn install_handler() {
+    ctrlc::set_handler(|| {
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("\nCancelling after the current item... (press Ctrl-C again to force quit)");
+    })
+    .ok();
+}
+
+pub fn flag() -> &'static AtomicBool {
+    &CANCELLED
+}
+
+pub fn is_requested() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Fail with "Cancelled" once cancellation has been requested; checked
+/// between the steps of a cleanup.
+pub fn check() -> Result<(), String> {
+    if is_requested() {
+        return Err("Cancelled".to_string());
+    }
+    Ok(())
+}
+
+/// End a cleanup whose steps returned `result`. When it stopped because
+/// of Ctrl-C, what it didn't get to is marked as not removed and summed
+/// up, and the run goes on to report rather than failing; other errors
+/// pass through.
+pub fn settle(report: &mut CleanupReport, result: Result<(), String>) -> Result<(), String> {
+    match result {
+        Err(e) if is_requested() => {
+            report.mark_unreached(&e);
+            print_cancelled(&report.items);
+            Ok(())
+        }
+        result => result,
+    }
+}
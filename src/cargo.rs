@@ -0,0 +1,373 @@
+//! Rust projects that each build into their own `target/` compile the same
+//! dependencies over and over and keep a copy of every build. Pointing
+//! Cargo at one shared target directory (`CARGO_TARGET_DIR` or
+//! `build.target-dir`) builds each dependency once; sccache shares the
+//! compiling but not the copies. This works out what sharing would save
+//! for the targets `system scan` found, and which targets nothing builds
+//! into any more.
+
+use crate::artifacts::Artifact;
+use crate::paths::xdg_dir;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Fewer Rust targets than this aren't worth a word about sharing.
+pub const ADVISE_FROM: usize = 3;
+
+/// How Rust builds are shared between projects, if at all.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sharing {
+    /// The shared target directory and where it is set
+    pub target_dir: Option<(PathBuf, String)>,
+    /// sccache wraps rustc
+    pub sccache: bool,
+}
+
+impl Sharing {
+    /// Sharing as set up for the current user: the environment, then
+    /// Cargo's config in `$CARGO_HOME` (`~/.cargo`).
+    pub fn detect(home: &Path) -> Self {
+        let cargo_home = xdg_dir("CARGO_HOME", home, ".cargo");
+        let config = ["config.toml", "config"]
+            .iter()
+            .map(|name| cargo_home.join(name))
+            .find(|path| path.is_file());
+        let build = config
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| text.parse::<toml::Table>().ok())
+            .and_then(|table| table.get("build")?.as_table().cloned())
+            .unwrap_or_default();
+        Self::from_settings(
+            env::var_os("CARGO_TARGET_DIR"),
+            env::var_os("RUSTC_WRAPPER"),
+            &build,
+            config.as_deref(),
+        )
+    }
+
+    /// Sharing from the environment's values, which win, and the `[build]`
+    /// table of the Cargo config at `config`.
+    fn from_settings(
+        target_dir: Option<OsString>,
+        wrapper: Option<OsString>,
+        build: &toml::Table,
+        config: Option<&Path>,
+    ) -> Self {
+        let configured = |key: &str| build.get(key).and_then(|v| v.as_str());
+        let target_dir = match target_dir.filter(|d| !d.is_empty()) {
+            Some(dir) => Some((PathBuf::from(dir), "CARGO_TARGET_DIR".to_string())),
+            None => configured("target-dir").zip(config).map(|(dir, config)| {
+                // Relative paths in a config are relative to the directory
+                // holding its `.cargo` directory
+                let base = config.parent().and_then(Path::parent).unwrap_or(config);
+                (base.join(dir), config.display().to_string())
+            }),
+        };
+        let wrapper = wrapper
+            .map(|w| w.to_string_lossy().into_owned())
+            .or_else(|| configured("rustc-wrapper").map(str::to_string));
+        Self {
+            target_dir,
+            sccache: wrapper.is_some_and(|w| {
+                Path::new(&w)
+                    .file_stem()
+                    .is_some_and(|stem| stem == "sccache")
+            }),
+        }
+    }
+}
+
+/// Why nothing builds into a target any more.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redundant {
+    /// Builds go to the shared target directory
+    Shared,
+    /// The project is a member of the workspace rooted here, and builds
+    /// into the workspace's target
+    WorkspaceMember(PathBuf),
+}
+
+/// What to make of the Rust targets a scan found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetAdvice {
+    pub targets: usize,
+    pub size: u64,
+    /// Dependency builds found in more than one target, which a shared
+    /// target directory would hold once
+    pub duplicated: u64,
+    pub sharing: Sharing,
+    pub redundant: Vec<(PathBuf, Redundant)>,
+}
+
+impl TargetAdvice {
+    pub fn redundant_size(&self, artifacts: &[Artifact]) -> u64 {
+        artifacts
+            .iter()
+            .filter(|a| self.redundant.iter().any(|(path, _)| *path == a.path))
+            .map(|a| a.size)
+            .sum()
+    }
+}
+
+/// Advice on the Rust targets among `artifacts`, found under `root`; none
+/// when there are too few to matter and none is redundant.
+pub fn advise(artifacts: &[Artifact], root: &Path, sharing: Sharing) -> Option<TargetAdvice> {
+    let targets: Vec<&Artifact> = artifacts.iter().filter(|a| is_target(&a.path)).collect();
+    let shared = sharing
+        .target_dir
+        .as_ref()
+        .map(|(dir, _)| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()));
+    let redundant: Vec<(PathBuf, Redundant)> = targets
+        .iter()
+        .filter(|t| shared.as_ref() != Some(&t.path))
+        .filter_map(|t| {
+            let project = t.path.parent()?;
+            let reason = match workspace_root(project, root) {
+                Some(workspace) => Redundant::WorkspaceMember(workspace),
+                None if shared.is_some() => Redundant::Shared,
+                None => return None,
+            };
+            Some((t.path.clone(), reason))
+        })
+        .collect();
+    if targets.len() < ADVISE_FROM && redundant.is_empty() {
+        return None;
+    }
+    let paths: Vec<&Path> = targets.iter().map(|t| t.path.as_path()).collect();
+    Some(TargetAdvice {
+        targets: targets.len(),
+        size: targets.iter().map(|t| t.size).sum(),
+        duplicated: duplicated_size(&paths),
+        sharing,
+        redundant,
+    })
+}
+
+/// A Rust project's target directory.
+fn is_target(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n == "target")
+        && path
+            .parent()
+            .is_some_and(|project| project.join("Cargo.toml").is_file())
+}
+
+/// The workspace `project` is a member of, looking up to `root`: the
+/// nearest enclosing `Cargo.toml` with a `[workspace]` that doesn't
+/// exclude it. None when `project` is a workspace root itself.
+fn workspace_root(project: &Path, root: &Path) -> Option<PathBuf> {
+    let mut dir = project;
+    while dir.starts_with(root) {
+        let manifest = fs::read_to_string(dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|text| text.parse::<toml::Table>().ok());
+        if let Some(workspace) = manifest.as_ref().and_then(|m| m.get("workspace")) {
+            if dir == project {
+                return None;
+            }
+            let relative = project.strip_prefix(dir).ok()?;
+            let excluded = workspace
+                .get("exclude")
+                .and_then(|e| e.as_array())
+                .is_some_and(|exclude| {
+                    exclude
+                        .iter()
+                        .filter_map(|e| e.as_str())
+                        .any(|e| relative.starts_with(e.trim_end_matches('/')))
+                });
+            return (!excluded).then(|| dir.to_path_buf());
+        }
+        dir = dir.parent()?;
+    }
+    None
+}
+
+/// Bytes of dependency builds (`<target>/<profile>/deps`, with or without
+/// a target triple) found in more than one of `targets`, beyond the first
+/// copy. Cargo names each build after a hash of the crate, its version and
+/// features, so the same file name is the same build.
+pub fn duplicated_size(targets: &[&Path]) -> u64 {
+    let mut builds: HashMap<OsString, (u64, usize)> = HashMap::new();
+    for target in targets {
+        for deps in deps_dirs(target) {
+            let Ok(entries) = fs::read_dir(&deps) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if meta.is_file() {
+                    let build = builds.entry(entry.file_name()).or_insert((meta.len(), 0));
+                    build.1 += 1;
+                }
+            }
+        }
+    }
+    builds
+        .values()
+        .map(|(size, copies)| size * (*copies as u64 - 1))
+        .sum()
+}
+
+fn deps_dirs(target: &Path) -> Vec<PathBuf> {
+    let subdirs = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+                    .map(|e| e.path())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut deps = Vec::new();
+    for profile in subdirs(target) {
+        if profile.join("deps").is_dir() {
+            deps.push(profile.join("deps"));
+        } else {
+            // `target/<triple>/<profile>/deps`
+            deps.extend(
+                subdirs(&profile)
+                    .into_iter()
+                    .map(|p| p.join("deps"))
+                    .filter(|d| d.is_dir()),
+            );
+        }
+    }
+    deps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, len: usize) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; len]).unwrap();
+    }
+
+    fn artifact(path: PathBuf) -> Artifact {
+        Artifact {
+            path,
+            kind: "Rust".to_string(),
+            size: 100,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn test_sharing_reads_environment_then_cargo_config() {
+        let build: toml::Table = "target-dir = \"builds\"\nrustc-wrapper = \"/usr/bin/sccache\""
+            .parse()
+            .unwrap();
+        let config = Path::new("/home/dev/.cargo/config.toml");
+        let configured = Sharing::from_settings(None, None, &build, Some(config));
+        assert_eq!(
+            configured.target_dir,
+            Some((
+                PathBuf::from("/home/dev/builds"),
+                "/home/dev/.cargo/config.toml".to_string()
+            ))
+        );
+        assert!(configured.sccache);
+
+        let from_env = Sharing::from_settings(
+            Some("/tmp/cargo".into()),
+            Some("cachepot".into()),
+            &build,
+            Some(config),
+        );
+        assert_eq!(
+            from_env.target_dir,
+            Some((PathBuf::from("/tmp/cargo"), "CARGO_TARGET_DIR".to_string()))
+        );
+        assert!(!from_env.sccache);
+
+        let nothing = Sharing::from_settings(None, None, &toml::Table::new(), None);
+        assert_eq!(nothing, Sharing::default());
+    }
+
+    #[test]
+    fn test_duplicated_size_counts_builds_past_the_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a/target");
+        let b = dir.path().join("b/target");
+        let c = dir.path().join("c/target");
+        for target in [&a, &b] {
+            write(&target.join("debug/deps/libserde-1a2b.rlib"), 1_000);
+        }
+        write(
+            &c.join("x86_64-unknown-linux-gnu/release/deps/libserde-1a2b.rlib"),
+            1_000,
+        );
+        // Other features, other hash: a different build
+        write(&b.join("debug/deps/libserde-9f8e.rlib"), 1_000);
+        write(&a.join("debug/a"), 5_000);
+
+        assert_eq!(duplicated_size(&[&a, &b, &c]), 2_000);
+        assert_eq!(duplicated_size(&[&a]), 0);
+    }
+
+    #[test]
+    fn test_advise_finds_redundant_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for project in ["ws", "ws/core", "ws/tools", "app", "ws/nested"] {
+            fs::create_dir_all(root.join(project).join("target")).unwrap();
+            fs::write(root.join(project).join("Cargo.toml"), "[package]\n").unwrap();
+        }
+        fs::write(
+            root.join("ws/Cargo.toml"),
+            "[workspace]\nmembers = [\"core\"]\nexclude = [\"tools/\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("ws/nested/Cargo.toml"),
+            "[package]\n\n[workspace]\n",
+        )
+        .unwrap();
+        let artifacts: Vec<Artifact> = ["ws", "ws/core", "ws/tools", "app", "ws/nested"]
+            .iter()
+            .map(|p| artifact(root.join(p).join("target")))
+            .collect();
+
+        // The member builds into the workspace's target; the excluded
+        // project, the workspace of its own and the rest still use their own
+        let advice = advise(&artifacts, root, Sharing::default()).unwrap();
+        assert_eq!(advice.targets, 5);
+        assert_eq!(
+            advice.redundant,
+            [(
+                root.join("ws/core/target"),
+                Redundant::WorkspaceMember(root.join("ws"))
+            )]
+        );
+        assert_eq!(advice.redundant_size(&artifacts), 100);
+
+        // With a shared target every other one is left over, but not the
+        // shared one itself
+        let sharing = Sharing {
+            target_dir: Some((root.join("app/target"), "CARGO_TARGET_DIR".to_string())),
+            sccache: false,
+        };
+        let advice = advise(&artifacts, root, sharing).unwrap();
+        let redundant: Vec<&Path> = advice.redundant.iter().map(|(p, _)| p.as_path()).collect();
+        assert_eq!(
+            redundant,
+            [
+                root.join("ws/target"),
+                root.join("ws/core/target"),
+                root.join("ws/tools/target"),
+                root.join("ws/nested/target")
+            ]
+        );
+
+        // Two projects of their own: nothing to say
+        assert_eq!(advise(&artifacts[2..4], root, Sharing::default()), None);
+    }
+}
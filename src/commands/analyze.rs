@@ -0,0 +1,264 @@
+use crate::ages::{AgeBucket, AgeReport};
+use crate::display::{format_bytes, print_error, print_info, print_success, print_text, Styled};
+use crate::docker::Docker;
+use crate::history::now_secs;
+use crate::layers::LayerReport;
+use crate::report::Report;
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use dialoguer::console::Term;
+use std::collections::HashMap;
+
+/// Instructions are cut to this many characters in the tables.
+const CREATED_BY_WIDTH: usize = 60;
+
+/// Cells in the bar of the largest age bucket.
+const CHART_WIDTH: usize = 40;
+
+/// Cells the chart keeps however narrow the terminal.
+const MIN_CHART_WIDTH: usize = 10;
+
+/// The `--older-than` cutoffs `analyze ages` sizes up, in days, with how
+/// they read.
+const CUTOFFS: [(u64, &str); 3] = [(7, "1 week"), (28, "4 weeks"), (90, "3 months")];
+
+/// Show which layers images share, their largest unique layers and what
+/// removing each image would really free. Nothing is changed.
+pub fn images(top: usize) -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    // `docker images` lists an image once per tag
+    let mut images: Vec<(String, String)> = vec![];
+    for image in Docker::list_images(&[])? {
+        match images.iter_mut().find(|(id, _)| *id == image.id) {
+            Some((_, name)) => *name += &format!(", {}", image.display_name()),
+            None => images.push((image.id.clone(), image.display_name())),
+        }
+    }
+    let mut containers: HashMap<String, Vec<String>> = HashMap::new();
+    for container in Docker::list_containers(true, &[])? {
+        let image = images.iter().find(|(id, name)| {
+            name.split(", ").any(|n| n == container.image)
+                || id
+                    .trim_start_matches("sha256:")
+                    .starts_with(&container.image)
+        });
+        if let Some((id, _)) = image {
+            containers
+                .entry(id.clone())
+                .or_default()
+                .push(container.names.clone());
+        }
+    }
+
+    let report = LayerReport::new(&Docker::image_layers(&images)?, &containers, top);
+
+    print_text(&format!("{}", "Image Layers".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    if report.images.is_empty() {
+        print_success("No images.");
+        return Ok(Report::Layers(report));
+    }
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec![
+        "IMAGE",
+        "LAYERS",
+        "SIZE",
+        "SHARED",
+        "FREED IF REMOVED",
+    ]);
+    for image in &report.images {
+        let mut name = image.name.clone();
+        if !image.containers.is_empty() {
+            name += &format!("\n(used by {})", image.containers.join(", "));
+        }
+        table.add_row(vec![
+            name,
+            image.layers.to_string(),
+            format_bytes(image.size),
+            format_bytes(image.shared),
+            format_bytes(image.unique),
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+
+    if !report.shared_layers.is_empty() {
+        print_text(&format!("{}", "Shared layers".bold()));
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY);
+        table.set_header(vec!["LAYER", "SIZE", "IMAGES", "CREATED BY"]);
+        for layer in &report.shared_layers {
+            table.add_row(vec![
+                short_digest(&layer.digest),
+                format_bytes(layer.size),
+                layer.images.join("\n"),
+                truncate(&layer.created_by),
+            ]);
+        }
+        print_text(&format!("{table}"));
+        print_text("");
+    }
+
+    if !report.largest_unique_layers.is_empty() {
+        print_text(&format!("{}", "Largest unique layers".bold()));
+        let mut table = Table::new();
+        table.load_preset(UTF8_BORDERS_ONLY);
+        table.set_header(vec!["LAYER", "SIZE", "IMAGE", "CREATED BY"]);
+        for layer in &report.largest_unique_layers {
+            table.add_row(vec![
+                short_digest(&layer.digest),
+                format_bytes(layer.size),
+                layer.image.clone(),
+                truncate(&layer.created_by),
+            ]);
+        }
+        print_text(&format!("{table}"));
+        print_text("");
+    }
+
+    print_info(&format!(
+        "{} images take {} on disk; `docker images` adds up to {} because {} is shared",
+        report.images.len(),
+        format_bytes(report.disk_size).good().bold(),
+        format_bytes(report.listed_size),
+        format_bytes(report.shared_savings())
+    ));
+    print_text(&format!(
+        "{}",
+        "  Removing an image frees only its unique layers; shared ones stay until every image using them is gone"
+            .dimmed()
+    ));
+
+    Ok(Report::Layers(report))
+}
+
+/// Chart how much space images and build cache of each age take, and what
+/// each `--older-than` cutoff could reach. Nothing is changed.
+pub fn ages() -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    let report = AgeReport::new(
+        &Docker::list_images(&[])?,
+        &Docker::build_cache()?,
+        now_secs(),
+    );
+
+    print_text(&format!(
+        "{}",
+        "Images and Build Cache by Age".bold().cyan()
+    ));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let largest = report
+        .buckets
+        .iter()
+        .map(AgeBucket::total)
+        .max()
+        .unwrap_or(0);
+    if largest == 0 {
+        print_success("No images or build cache.");
+        return Ok(Report::Ages(report));
+    }
+
+    let counts: Vec<String> = report
+        .buckets
+        .iter()
+        .map(|b| format!("{} images, {} cache records", b.image_count, b.cache_count))
+        .collect();
+    // Label, size and counts around the bar
+    let reserved = 2 + 7 + 1 + 1 + 10 + 2 + counts.iter().map(String::len).max().unwrap_or(0);
+    let width = chart_width(reserved);
+    for (bucket, count) in report.buckets.iter().zip(&counts) {
+        let images = cells(bucket.images, largest, width);
+        let cache = cells(bucket.total(), largest, width)
+            .saturating_sub(images)
+            .max(usize::from(bucket.build_cache > 0));
+        print_text(&format!(
+            "  {:>7} {}{}{} {:>10}  {}",
+            bucket.label,
+            "█".repeat(images).info(),
+            "▒".repeat(cache).caution(),
+            " ".repeat(width.saturating_sub(images + cache)),
+            format_bytes(bucket.total()),
+            count.dimmed()
+        ));
+    }
+    print_text("");
+    print_text(&format!(
+        "  {} images (by when they were built)   {} build cache (by when a build last used it)",
+        "█".info(),
+        "▒".caution()
+    ));
+    print_text("");
+
+    for (days, age) in CUTOFFS {
+        let older = report.older_than(days);
+        if older == 0 {
+            continue;
+        }
+        print_info(&format!(
+            "Older than {}: {} of {}, the most `--older-than {}d` could free",
+            age,
+            format_bytes(older).good().bold(),
+            format_bytes(report.total()),
+            days
+        ));
+    }
+    print_text(&format!(
+        "{}",
+        "  Image sizes count layers shared with other images; `dockerase analyze images` shows what each would free"
+            .dimmed()
+    ));
+
+    Ok(Report::Ages(report))
+}
+
+/// Cells of a bar for `size`, scaled so `largest` fills the `width` of the
+/// chart; anything at all gets at least one.
+fn cells(size: u64, largest: u64, width: usize) -> usize {
+    if size == 0 {
+        return 0;
+    }
+    ((size as f64 / largest as f64 * width as f64).round() as usize).max(1)
+}
+
+/// Cells in the largest bar: `CHART_WIDTH`, or fewer to fit a terminal with
+/// `reserved` columns taken around the chart.
+fn chart_width(reserved: usize) -> usize {
+    match Term::stdout().size_checked() {
+        Some((_, columns)) => usize::from(columns)
+            .saturating_sub(reserved)
+            .clamp(MIN_CHART_WIDTH, CHART_WIDTH),
+        None => CHART_WIDTH,
+    }
+}
+
+/// `sha256:0123456789ab…` as `0123456789ab`.
+fn short_digest(digest: &str) -> String {
+    digest
+        .trim_start_matches("sha256:")
+        .chars()
+        .take(12)
+        .collect()
+}
+
+fn truncate(created_by: &str) -> String {
+    let created_by = created_by.trim_start_matches("/bin/sh -c ");
+    if created_by.chars().count() <= CREATED_BY_WIDTH {
+        return created_by.to_string();
+    }
+    let cut: String = created_by.chars().take(CREATED_BY_WIDTH - 1).collect();
+    cut + "…"
+}
@@ -0,0 +1,430 @@
+//! `dockerase bench`: time how fast this machine removes resources with
+//! each backend and strategy. Everything it creates is empty, named after
+//! the run and labelled `dockerase.bench=<run id>`, so it never touches
+//! anything else and is cleaned up however the run ends. Each strategy is
+//! checked to have removed everything, which makes a run an end-to-end
+//! test of the backends as well.
+
+use crate::display::{print_error, print_info, print_success, print_text, print_warning, Styled};
+use crate::docker::{self, BenchBackend, Docker, ResourceType};
+use crate::policy::{self, Category};
+use crate::read_only;
+use crate::report::{BenchChoice, BenchReport, BenchResult, Report};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::collections::HashSet;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Label on everything a run creates, set to the run ID.
+pub const BENCH_LABEL: &str = "dockerase.bench";
+
+/// Containers go before the image they were created from.
+const KINDS: [ResourceType; 3] = [
+    ResourceType::Container,
+    ResourceType::Volume,
+    ResourceType::Image,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+    /// Every resource in one call
+    Batched,
+    /// One call per resource, one after the other
+    Serial,
+    /// One call per resource, on several threads
+    Parallel,
+}
+
+impl Strategy {
+    fn as_str(self) -> &'static str {
+        match self {
+            Strategy::Batched => "batched",
+            Strategy::Serial => "serial",
+            Strategy::Parallel => "parallel",
+        }
+    }
+}
+
+/// What is worth timing on a backend. The Engine API removes one resource
+/// per request, so batching there is the same as going serially.
+fn strategies(backend: &BenchBackend) -> &'static [Strategy] {
+    match backend.name {
+        "api" => &[Strategy::Serial, Strategy::Parallel],
+        _ => &[Strategy::Batched, Strategy::Serial, Strategy::Parallel],
+    }
+}
+
+/// Create `count` disposable resources of each kind per strategy, time
+/// their removal with every backend and strategy, and report the fastest.
+pub fn run(count: usize, jobs: usize) -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+    read_only::check("run benchmarks")?;
+    for kind in KINDS {
+        policy::check(kind.into(), "run benchmarks")?;
+    }
+
+    let count = count.max(1);
+    let jobs = jobs.max(1);
+    let run_id = run_id();
+    let bench = Bench {
+        label: format!("{}={}", BENCH_LABEL, run_id),
+        prefix: format!("dockerase-bench-{}", run_id),
+        count,
+        jobs,
+    };
+    let backends = docker::bench_backends();
+
+    print_text(&format!("{}", "Removal Benchmark".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text(&format!(
+        "  {}",
+        format!(
+            "{} of each resource per strategy, labelled {}",
+            count, bench.label
+        )
+        .dimmed()
+    ));
+    print_text("");
+
+    let mut results = Vec::new();
+    let outcome = bench.run_all(&backends, &mut results);
+    let leftovers = bench.clean_up();
+    outcome?;
+
+    let report = BenchReport {
+        run_id,
+        count,
+        jobs,
+        fastest: fastest(&results),
+        results,
+        leftovers,
+    };
+    print_results(&report);
+    Ok(Report::Bench(report))
+}
+
+/// Short and unique enough to tell runs apart on one daemon.
+fn run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    format!("{:08x}", nanos ^ process::id().rotate_left(16))
+}
+
+struct Bench {
+    label: String,
+    prefix: String,
+    count: usize,
+    jobs: usize,
+}
+
+impl Bench {
+    /// The image every benchmark container is created from.
+    fn base_image(&self) -> String {
+        format!("{}:base", self.prefix)
+    }
+
+    fn run_all(
+        &self,
+        backends: &[BenchBackend],
+        results: &mut Vec<BenchResult>,
+    ) -> Result<(), String> {
+        Docker::import_empty_image(&self.base_image(), &self.label)
+            .map_err(|e| format!("Failed to create benchmark resources: {}", e.trim()))?;
+        let mut round = 0;
+        for kind in KINDS {
+            for backend in backends {
+                for &strategy in strategies(backend) {
+                    round += 1;
+                    print_info(&format!(
+                        "Removing {} {} with the {} backend, {}...",
+                        self.count,
+                        Category::from(kind).as_str(),
+                        backend.name,
+                        strategy.as_str()
+                    ));
+                    let names = self.create(kind, round)?;
+                    results.push(self.measure(backend, strategy, kind, &names));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a round's resources, named after the run and the round.
+    fn create(&self, kind: ResourceType, round: usize) -> Result<Vec<String>, String> {
+        (1..=self.count)
+            .map(|n| {
+                let name = match kind {
+                    ResourceType::Image => format!("{}-{}:{}", self.prefix, round, n),
+                    _ => format!("{}-{}-{}", self.prefix, round, n),
+                };
+                match kind {
+                    ResourceType::Container => {
+                        Docker::create_container(&self.base_image(), &name, &self.label)
+                    }
+                    ResourceType::Volume => Docker::create_volume(&name, &self.label),
+                    _ => Docker::import_empty_image(&name, &self.label),
+                }
+                .map(|_| name)
+                .map_err(|e| format!("Failed to create benchmark resources: {}", e.trim()))
+            })
+            .collect()
+    }
+
+    fn measure(
+        &self,
+        backend: &BenchBackend,
+        strategy: Strategy,
+        kind: ResourceType,
+        names: &[String],
+    ) -> BenchResult {
+        let ids: Vec<&str> = names.iter().map(String::as_str).collect();
+        let start = Instant::now();
+        let removed = match strategy {
+            Strategy::Batched => backend.remove(kind, &ids).map(drop),
+            Strategy::Serial => ids
+                .iter()
+                .try_for_each(|id| backend.remove(kind, &[id]).map(drop)),
+            Strategy::Parallel => self.remove_parallel(backend, kind, &ids),
+        };
+        let seconds = start.elapsed().as_secs_f64();
+
+        let error = removed.err().or_else(|| {
+            let remaining = self.remaining(kind).ok()?;
+            let left = names.iter().filter(|n| remaining.contains(*n)).count();
+            (left > 0).then(|| format!("{} of {} left behind", left, names.len()))
+        });
+        BenchResult {
+            kind: Category::from(kind).as_str(),
+            backend: backend.name,
+            strategy: strategy.as_str(),
+            seconds,
+            per_second: names.len() as f64 / seconds.max(f64::EPSILON),
+            error: error.map(|e| e.trim().to_string()),
+        }
+    }
+
+    /// One removal per resource on up to `jobs` threads; the first error
+    /// is reported, the other resources are still removed.
+    fn remove_parallel(
+        &self,
+        backend: &BenchBackend,
+        kind: ResourceType,
+        ids: &[&str],
+    ) -> Result<(), String> {
+        let next = AtomicUsize::new(0);
+        let error: Mutex<Option<String>> = Mutex::new(None);
+        thread::scope(|scope| {
+            for _ in 0..self.jobs.min(ids.len()) {
+                scope.spawn(|| {
+                    while let Some(id) = ids.get(next.fetch_add(1, Ordering::SeqCst)) {
+                        if let Err(e) = backend.remove(kind, &[id]) {
+                            if let Ok(mut error) = error.lock() {
+                                error.get_or_insert(e);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        match error.into_inner().unwrap_or_default() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Names of this run's resources of `kind` that still exist.
+    fn remaining(&self, kind: ResourceType) -> Result<HashSet<String>, String> {
+        let filters = [format!("label={}", self.label)];
+        Ok(match kind {
+            ResourceType::Container => Docker::list_containers(true, &filters)?
+                .into_iter()
+                .map(|c| c.names)
+                .collect(),
+            ResourceType::Volume => Docker::list_volumes(&filters)?
+                .into_iter()
+                .map(|v| v.name)
+                .collect(),
+            _ => Docker::list_images(&filters)?
+                .into_iter()
+                .map(|i| format!("{}:{}", i.repository, i.tag))
+                .collect(),
+        })
+    }
+
+    /// Remove whatever the run created and still exists, returning what
+    /// couldn't be. Runs however the benchmark ended.
+    fn clean_up(&self) -> Vec<String> {
+        let base_image = self.base_image();
+        let mut leftovers = Vec::new();
+        for kind in KINDS {
+            let Ok(remaining) = self.remaining(kind) else {
+                continue;
+            };
+            let mut names: Vec<&str> = remaining
+                .iter()
+                .map(String::as_str)
+                .filter(|n| n.starts_with(&self.prefix))
+                .collect();
+            if kind == ResourceType::Image {
+                names.push(&base_image);
+            }
+            names.sort_unstable();
+            names.dedup();
+            let removed = match kind {
+                ResourceType::Container => Docker::remove_containers(&names),
+                ResourceType::Volume => Docker::remove_volumes(&names),
+                _ => Docker::remove_images(&names),
+            };
+            if removed.is_err() {
+                leftovers.extend(names.iter().map(|n| n.to_string()));
+            }
+        }
+        leftovers
+    }
+}
+
+/// The backend and strategy with the least total time, among those that
+/// succeeded for every kind.
+fn fastest(results: &[BenchResult]) -> Option<BenchChoice> {
+    let mut choices: Vec<BenchChoice> = Vec::new();
+    let mut failed: HashSet<(&str, &str)> = HashSet::new();
+    for result in results {
+        if result.error.is_some() {
+            failed.insert((result.backend, result.strategy));
+            continue;
+        }
+        match choices
+            .iter_mut()
+            .find(|c| c.backend == result.backend && c.strategy == result.strategy)
+        {
+            Some(choice) => choice.seconds += result.seconds,
+            None => choices.push(BenchChoice {
+                backend: result.backend,
+                strategy: result.strategy,
+                seconds: result.seconds,
+            }),
+        }
+    }
+    choices
+        .into_iter()
+        .filter(|c| !failed.contains(&(c.backend, c.strategy)))
+        .min_by(|a, b| a.seconds.total_cmp(&b.seconds))
+}
+
+fn print_results(report: &BenchReport) {
+    print_text("");
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["KIND", "BACKEND", "STRATEGY", "TIME", "PER SECOND"]);
+    for result in &report.results {
+        let rate = match &result.error {
+            Some(e) => e.danger().to_string(),
+            None => format!("{:.1}", result.per_second),
+        };
+        let fastest_for_kind = report
+            .results
+            .iter()
+            .filter(|r| r.kind == result.kind && r.error.is_none())
+            .all(|r| r.seconds >= result.seconds);
+        let time = format!("{:.2}s", result.seconds);
+        table.add_row(vec![
+            result.kind.to_string(),
+            result.backend.to_string(),
+            result.strategy.to_string(),
+            if fastest_for_kind && result.error.is_none() {
+                time.good().to_string()
+            } else {
+                time
+            },
+            rate,
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+
+    if !report.leftovers.is_empty() {
+        print_warning(&format!(
+            "Could not clean up {} benchmark resource(s); remove them with `docker rm`, `docker volume rm` or `docker rmi`: {}",
+            report.leftovers.len(),
+            report.leftovers.join(", ")
+        ));
+    }
+    let failures = report.results.iter().filter(|r| r.error.is_some()).count();
+    if failures > 0 {
+        print_warning(&format!(
+            "{} of {} runs failed",
+            failures,
+            report.results.len()
+        ));
+    }
+    match &report.fastest {
+        Some(choice) => {
+            print_success(&format!(
+                "Fastest on this machine: the {} backend, {} ({:.2}s in all)",
+                choice.backend, choice.strategy, choice.seconds
+            ));
+            if choice.backend == "api" {
+                print_text(&format!(
+                    "{}",
+                    "  Set DOCKERASE_BACKEND=api to use it for every command".dimmed()
+                ));
+            }
+        }
+        None => print_warning("No strategy removed everything it was given"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(backend: &'static str, strategy: &'static str, seconds: f64) -> BenchResult {
+        BenchResult {
+            kind: "containers",
+            backend,
+            strategy,
+            seconds,
+            per_second: 10.0 / seconds,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_fastest_totals_every_kind_and_skips_failures() {
+        let mut results = vec![
+            result("cli", "batched", 1.0),
+            result("cli", "parallel", 0.5),
+            result("api", "parallel", 0.2),
+            BenchResult {
+                kind: "volumes",
+                ..result("cli", "batched", 1.0)
+            },
+            BenchResult {
+                kind: "volumes",
+                ..result("cli", "parallel", 2.0)
+            },
+        ];
+        let choice = fastest(&results).unwrap();
+        assert_eq!((choice.backend, choice.strategy), ("api", "parallel"));
+
+        results[2].error = Some("connection refused".to_string());
+        assert_eq!(
+            fastest(&results),
+            Some(BenchChoice {
+                backend: "cli",
+                strategy: "batched",
+                seconds: 2.0,
+            })
+        );
+    }
+}
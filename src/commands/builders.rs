@@ -0,0 +1,164 @@
+use crate::display::{format_bytes, print_error, print_info, print_success, print_text, Styled};
+use crate::docker::Docker;
+use crate::report::{BuilderListReport, BuilderSummary, Report};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::cmp::Reverse;
+
+/// The buildx builders with a cache of their own, largest cache first.
+/// Listing them is best-effort: without buildx there are none.
+pub fn summaries() -> Vec<BuilderSummary> {
+    let mut builders: Vec<BuilderSummary> = Docker::buildx_builders()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|b| BuilderSummary {
+            // Measuring a stopped builder would start it
+            cache_size: if b.is_running() {
+                Docker::builder_cache_size(&b.name).ok()
+            } else {
+                None
+            },
+            running: b.is_running(),
+            name: b.name,
+            driver: b.driver,
+        })
+        .collect();
+    builders.sort_by_key(|b| Reverse(b.cache_size));
+    builders
+}
+
+/// The named builders whose cache a cleanup prunes besides the daemon's.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub names: Vec<String>,
+    pub all: bool,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && !self.all
+    }
+
+    /// The selected ones among `builders`; every named builder must exist.
+    /// `all` takes only the local ones.
+    pub fn pick(&self, builders: Vec<BuilderSummary>) -> Result<Vec<BuilderSummary>, String> {
+        if let Some(missing) = self
+            .names
+            .iter()
+            .find(|n| !builders.iter().any(|b| &b.name == *n))
+        {
+            return Err(format!(
+                "No buildx builder named '{}' with a cache of its own (see `dockerase builders`)",
+                missing
+            ));
+        }
+        Ok(builders
+            .into_iter()
+            .filter(|b| self.names.contains(&b.name) || (self.all && b.is_local()))
+            .collect())
+    }
+}
+
+/// How a builder's cache is described in prompts and reports.
+pub fn describe(builder: &BuilderSummary) -> String {
+    match builder.cache_size {
+        Some(size) => format!("builder {} ({})", builder.name, format_bytes(size)),
+        None => format!("builder {} (not running)", builder.name),
+    }
+}
+
+/// Every buildx builder with a cache of its own and the cache's size.
+pub fn list() -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    let builders = summaries();
+    let total_size = builders.iter().filter_map(|b| b.cache_size).sum();
+    if builders.is_empty() {
+        print_success("No buildx builders besides the daemon's own.");
+        return Ok(Report::Builders(BuilderListReport {
+            builders,
+            total_size,
+        }));
+    }
+
+    print_text(&format!("{}", "Buildx Builders".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["BUILDER", "DRIVER", "STATUS", "CACHE"]);
+    for builder in &builders {
+        table.add_row(vec![
+            builder.name.clone(),
+            builder.driver.clone(),
+            if builder.running {
+                "running".good().to_string()
+            } else {
+                "stopped".caution().to_string()
+            },
+            builder.cache_size.map_or("-".to_string(), format_bytes),
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+    print_info(&format!(
+        "{} builders, {} of build cache",
+        builders.len(),
+        format_bytes(total_size)
+    ));
+    print_text(&format!(
+        "{}",
+        "  Run `dockerase purge --builder NAME`, or `--all-builders` for the docker-container ones, to prune their caches".dimmed()
+    ));
+
+    Ok(Report::Builders(BuilderListReport {
+        builders,
+        total_size,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(name: &str) -> BuilderSummary {
+        BuilderSummary {
+            name: name.to_string(),
+            driver: "docker-container".to_string(),
+            running: true,
+            cache_size: Some(1_000),
+        }
+    }
+
+    #[test]
+    fn test_selection_picks_named_or_all_builders() {
+        let builders = || vec![builder("ci"), builder("arm")];
+        let names = |picked: Vec<BuilderSummary>| -> Vec<String> {
+            picked.into_iter().map(|b| b.name).collect()
+        };
+
+        let named = Selection {
+            names: vec!["arm".to_string()],
+            all: false,
+        };
+        assert_eq!(names(named.pick(builders()).unwrap()), ["arm"]);
+
+        let all = Selection {
+            names: vec![],
+            all: true,
+        };
+        assert_eq!(names(all.pick(builders()).unwrap()), ["ci", "arm"]);
+        assert!(Selection::default().pick(builders()).unwrap().is_empty());
+
+        let missing = Selection {
+            names: vec!["gone".to_string()],
+            all: true,
+        };
+        let err = missing.pick(builders()).unwrap_err();
+        assert!(err.contains("'gone'"), "{err}");
+    }
+}
@@ -0,0 +1,143 @@
+use crate::commands::{builders, purge, system as system_cmd};
+use crate::config::Config;
+use crate::display::{format_bytes, print_error, print_info, print_text, Styled};
+use crate::docker::Docker;
+use crate::filters::Filters;
+use crate::report::{CheckReport, Report};
+use crate::system::{discover_caches, total_size, CacheInfo};
+use colored::Colorize;
+
+pub struct CheckOptions {
+    /// Most reclaimable space tolerated, in bytes
+    pub max_reclaimable: u64,
+    /// Count purgeable system caches too
+    pub system: bool,
+    /// Purge instead of failing when over the limit
+    pub auto: bool,
+    pub dry_run: bool,
+    pub named_volumes: bool,
+}
+
+/// Measure reclaimable space against a limit for unattended runs. Never
+/// prompts: over the limit it either fails or, with `auto`, purges.
+pub fn run(opts: &CheckOptions, filters: &Filters, config: &Config) -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    let (docker_reclaimable, system_reclaimable) = reclaimable(opts, config)?;
+    let mut report = CheckReport::new(opts.max_reclaimable, docker_reclaimable, system_reclaimable);
+
+    print_summary(&report);
+
+    if !report.exceeded {
+        print_text(&format!("{} Within the limit", "✓".good().bold()));
+        return Ok(Report::Check(report));
+    }
+
+    let over = format_bytes(report.total_reclaimable - report.threshold);
+    if !opts.auto {
+        print_text(&format!(
+            "{} Over the limit by {}; run {} or pass --auto",
+            "✗".danger().bold(),
+            over,
+            "dockerase purge".cyan().bold()
+        ));
+        return Ok(Report::Check(report));
+    }
+
+    print_info(&format!("Over the limit by {}; purging", over));
+    print_text("");
+    let purged = purge::run(
+        true,
+        opts.dry_run,
+        opts.named_volumes,
+        filters,
+        &config.protect,
+        &builders::Selection::default(),
+    )?;
+    report.add_cleanup(purged);
+
+    if opts.system {
+        print_text("");
+        let purged = system_cmd::purge(
+            &system_cmd::PurgeOptions {
+                force: true,
+                dry_run: opts.dry_run,
+                interactive: false,
+                jobs: system_cmd::default_jobs(),
+                detail: false,
+                top: 0,
+                older_than: None,
+                trash: config.defaults.trash,
+            },
+            config,
+        )?;
+        report.add_cleanup(purged);
+    }
+
+    // A dry run changed nothing; otherwise only what is left counts
+    if !opts.dry_run {
+        let (docker, system) = reclaimable(opts, config)?;
+        let after = docker + system.unwrap_or(0);
+        report.reclaimable_after = Some(after);
+        print_text("");
+        if report.unresolved() {
+            print_text(&format!(
+                "{} Still {} reclaimable after the cleanup, over the limit of {}",
+                "✗".danger().bold(),
+                format_bytes(after),
+                format_bytes(report.threshold)
+            ));
+        } else {
+            print_text(&format!(
+                "{} Within the limit after the cleanup ({} reclaimable)",
+                "✓".good().bold(),
+                format_bytes(after)
+            ));
+        }
+    }
+
+    Ok(Report::Check(report))
+}
+
+/// What Docker could reclaim and, when counted, the system caches.
+fn reclaimable(opts: &CheckOptions, config: &Config) -> Result<(u64, Option<u64>), String> {
+    let docker = Docker::get_disk_usage()?.total_reclaimable();
+    Ok((docker, opts.system.then(|| system_reclaimable(config))))
+}
+
+/// What `system purge` would free, leaving out protected caches.
+fn system_reclaimable(config: &Config) -> u64 {
+    let caches = discover_caches(config);
+    let refs: Vec<&CacheInfo> = caches
+        .iter()
+        .filter(|c| !config.protect.protects_cache(&c.name))
+        .collect();
+    total_size(&refs)
+}
+
+fn print_summary(report: &CheckReport) {
+    print_text(&format!("{}", "Reclaimable Space".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text(&format!(
+        "  {:<16} {:>10}",
+        "Docker",
+        format_bytes(report.docker_reclaimable)
+    ));
+    if let Some(size) = report.system_reclaimable {
+        print_text(&format!(
+            "  {:<16} {:>10}",
+            "System caches",
+            format_bytes(size)
+        ));
+    }
+    print_text(&format!(
+        "  {:<16} {:>10}  (limit {})",
+        "Total".bold(),
+        format_bytes(report.total_reclaimable),
+        format_bytes(report.threshold)
+    ));
+    print_text("");
+}
@@ -0,0 +1,266 @@
+use crate::cancel;
+use crate::config::Protected;
+use crate::display::{
+    confirm, format_bytes, print_dry_run_header, print_error, print_info, print_protected,
+    print_space_saved, print_success, print_text, print_warning, Styled,
+};
+use crate::docker::Docker;
+use crate::notes::Kind;
+use crate::policy::{self, Category};
+use crate::report::{CleanupReport, ComposeListReport, ComposeSummary, Report, ReportItem};
+use crate::resources::ComposeProject;
+use crate::undo::{self, Manifest};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::collections::HashMap;
+
+pub fn list() -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    let projects = Docker::compose_projects()?;
+    if projects.is_empty() {
+        print_success("No docker compose projects found.");
+        return Ok(Report::Compose(ComposeListReport { projects: vec![] }));
+    }
+
+    // Measuring volumes is slow and optional; sizes just come out smaller
+    let volume_sizes = Docker::volume_sizes().unwrap_or_default();
+    let summaries: Vec<ComposeSummary> = projects
+        .iter()
+        .map(|p| summarize(p, &volume_sizes))
+        .collect();
+
+    print_text(&format!("{}", "Docker Compose Projects".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec![
+        "PROJECT",
+        "CONTAINERS",
+        "IMAGES",
+        "VOLUMES",
+        "NETWORKS",
+        "SIZE",
+        "STATUS",
+    ]);
+    for summary in &summaries {
+        let status = if summary.running > 0 {
+            format!("{} running", summary.running).good().to_string()
+        } else {
+            "stopped".caution().to_string()
+        };
+        table.add_row(vec![
+            summary.name.clone(),
+            summary.containers.to_string(),
+            summary.images.to_string(),
+            summary.volumes.to_string(),
+            summary.networks.to_string(),
+            format_bytes(summary.size),
+            status,
+        ]);
+    }
+
+    print_text(&format!("{table}"));
+    print_text("");
+    print_text(&format!(
+        "Run {} to remove a stopped project",
+        "dockerase compose purge <project>".cyan().bold()
+    ));
+
+    Ok(Report::Compose(ComposeListReport {
+        projects: summaries,
+    }))
+}
+
+fn summarize(project: &ComposeProject, volume_sizes: &HashMap<String, u64>) -> ComposeSummary {
+    let volumes_size: u64 = project
+        .volumes
+        .iter()
+        .filter_map(|v| volume_sizes.get(&v.name))
+        .sum();
+    ComposeSummary {
+        name: project.name.clone(),
+        containers: project.containers.len(),
+        running: project.running(),
+        images: project.images.len(),
+        volumes: project.volumes.len(),
+        networks: project.networks.len(),
+        size: project.size_bytes() + volumes_size,
+    }
+}
+
+/// Remove every container, built image, volume and network of a stopped
+/// compose project.
+pub fn purge(
+    name: &str,
+    force: bool,
+    dry_run: bool,
+    protect: &Protected,
+) -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    let project = Docker::compose_projects()?
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("No docker compose project named '{}'", name))?;
+
+    // Only stale projects: a running one is better taken down by compose
+    let running = project.running();
+    if running > 0 {
+        return Err(format!(
+            "Project '{}' has {} running container{}; stop it first with `docker compose -p {} down`",
+            name,
+            running,
+            if running == 1 { "" } else { "s" },
+            name
+        ));
+    }
+
+    // A project goes as a whole, so the policy must allow all of it
+    for category in [
+        Category::Containers,
+        Category::Images,
+        Category::Volumes,
+        Category::Networks,
+    ] {
+        policy::check(category, "purge compose projects")?;
+    }
+
+    if dry_run {
+        print_dry_run_header();
+    }
+
+    let before = Docker::get_disk_usage()?;
+    let mut report = CleanupReport::new("compose purge", dry_run);
+
+    let (protected_images, images): (Vec<_>, Vec<_>) = project
+        .images
+        .iter()
+        .partition(|i| protect.protects_image(i));
+    let (protected_volumes, volumes): (Vec<_>, Vec<_>) = project
+        .volumes
+        .iter()
+        .partition(|v| protect.protects_volume(&v.name));
+    let protected_images: Vec<String> = protected_images.iter().map(|i| i.display_name()).collect();
+    let protected_volumes: Vec<String> = protected_volumes.iter().map(|v| v.name.clone()).collect();
+
+    for c in &project.containers {
+        report.items.push(ReportItem::planned(
+            "containers",
+            &c.names,
+            Some(c.size_bytes()),
+        ));
+    }
+    for i in &images {
+        report.items.push(ReportItem::planned(
+            "images",
+            &i.display_name(),
+            Some(i.size_bytes()),
+        ));
+    }
+    for name in &protected_images {
+        report.items.push(ReportItem::skipped(
+            "images",
+            name,
+            &protect.reason(Kind::Image, name),
+        ));
+    }
+    for v in &volumes {
+        report
+            .items
+            .push(ReportItem::planned("volumes", &v.name, None));
+    }
+    for name in &protected_volumes {
+        report.items.push(ReportItem::skipped(
+            "volumes",
+            name,
+            &protect.reason(Kind::Volume, name),
+        ));
+    }
+    for n in &project.networks {
+        report
+            .items
+            .push(ReportItem::planned("networks", &n.name, None));
+    }
+
+    print_text(&format!(
+        "Compose project {} will lose:",
+        project.name.bold()
+    ));
+    print_info(&format!("{} containers", project.containers.len()));
+    print_info(&format!("{} built images", images.len()));
+    print_info(&format!("{} volumes", volumes.len()));
+    print_info(&format!("{} networks", project.networks.len()));
+    print_protected("images", &protect.labels(Kind::Image, &protected_images));
+    print_protected("volumes", &protect.labels(Kind::Volume, &protected_volumes));
+    print_text("");
+
+    if dry_run {
+        print_warning("Dry run - no changes made");
+        return Ok(Report::Cleanup(report));
+    }
+
+    if !force {
+        let confirmed = confirm(
+            &format!("Remove compose project '{}'?", project.name),
+            false,
+        )?;
+
+        if !confirmed {
+            print_warning("Aborted");
+            return Ok(Report::Cleanup(report.aborted()));
+        }
+    }
+
+    print_text("");
+    let mut manifest = Manifest::new("compose purge");
+    manifest.add_containers(&project.containers);
+    manifest.add_images(images.iter().copied());
+    manifest.add_volumes(volumes.iter().map(|v| &v.name));
+    manifest.add_networks(project.networks.iter().map(|n| &n.name));
+    undo::record(manifest);
+
+    // Containers first, so their images, volumes and networks are free
+    let mut execute = || -> Result<(), String> {
+        let ids: Vec<&str> = project.containers.iter().map(|c| c.id.as_str()).collect();
+        Docker::remove_containers(&ids)?;
+        for c in &project.containers {
+            report.mark_removed("containers", &c.names);
+        }
+        cancel::check()?;
+        let ids: Vec<&str> = images.iter().map(|i| i.id.as_str()).collect();
+        Docker::remove_images(&ids)?;
+        for i in &images {
+            report.mark_removed("images", &i.display_name());
+        }
+        cancel::check()?;
+        let names: Vec<&str> = volumes.iter().map(|v| v.name.as_str()).collect();
+        Docker::remove_volumes(&names)?;
+        for name in names {
+            report.mark_removed("volumes", name);
+        }
+        cancel::check()?;
+        let ids: Vec<&str> = project.networks.iter().map(|n| n.id.as_str()).collect();
+        Docker::remove_networks(&ids)?;
+        print_success(&format!("Compose project '{}' removed", project.name));
+        Ok(())
+    };
+    let executed = execute();
+    cancel::settle(&mut report, executed)?;
+
+    let after = Docker::get_disk_usage()?;
+    print_space_saved(before.total_size(), after.total_size());
+
+    report.mark_all_removed();
+    report.measure_freed(&before, &after);
+
+    Ok(Report::Cleanup(report))
+}
@@ -0,0 +1,115 @@
+use crate::display::{format_bytes, print_success, print_text, Styled};
+use crate::docker::Docker;
+use crate::report::{ContextListReport, ContextSummary, Report};
+use crate::resources::DockerContext;
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for each context's daemon before calling it unreachable.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn run() -> Result<Report, String> {
+    let contexts = Docker::list_contexts()?;
+
+    if contexts.is_empty() {
+        print_success("No docker contexts found.");
+        return Ok(Report::Contexts(ContextListReport { contexts: vec![] }));
+    }
+
+    // Remote daemons can be slow; probe them all at once
+    let summaries: Vec<ContextSummary> = thread::scope(|scope| {
+        let handles: Vec<_> = contexts
+            .iter()
+            .map(|context| scope.spawn(move || probe(context)))
+            .collect();
+        handles
+            .into_iter()
+            .zip(&contexts)
+            .map(|(handle, context)| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| unreachable(context, "Probe failed".to_string()))
+            })
+            .collect()
+    });
+
+    print_text(&format!("{}", "Docker Contexts".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec![
+        "CONTEXT",
+        "ENDPOINT",
+        "STATUS",
+        "TOTAL",
+        "RECLAIMABLE",
+    ]);
+
+    for summary in &summaries {
+        let name = if summary.current {
+            format!("{} *", summary.name)
+        } else {
+            summary.name.clone()
+        };
+        let status = if summary.reachable {
+            "reachable".good().to_string()
+        } else {
+            "unreachable".danger().to_string()
+        };
+        let size = |bytes: Option<u64>| bytes.map(format_bytes).unwrap_or_else(|| "-".to_string());
+        table.add_row(vec![
+            name,
+            summary.endpoint.clone(),
+            status,
+            size(summary.total_size),
+            size(summary.total_reclaimable),
+        ]);
+    }
+
+    print_text(&format!("{table}"));
+    print_text(&format!("{}", "* current context".dimmed()));
+
+    for summary in summaries.iter().filter(|s| !s.reachable) {
+        if let Some(error) = &summary.error {
+            print_text(&format!(
+                "  {} {}",
+                format!("{}:", summary.name).caution(),
+                error.lines().next().unwrap_or_default().dimmed()
+            ));
+        }
+    }
+
+    Ok(Report::Contexts(ContextListReport {
+        contexts: summaries,
+    }))
+}
+
+fn probe(context: &DockerContext) -> ContextSummary {
+    match Docker::disk_usage_in_context(&context.name, PROBE_TIMEOUT) {
+        Ok(usage) => ContextSummary {
+            reachable: true,
+            total_size: Some(usage.total_size()),
+            total_reclaimable: Some(usage.total_reclaimable()),
+            error: None,
+            ..unreachable(context, String::new())
+        },
+        Err(e) => unreachable(context, e.trim().to_string()),
+    }
+}
+
+fn unreachable(context: &DockerContext, error: String) -> ContextSummary {
+    ContextSummary {
+        name: context.name.clone(),
+        description: context.description.clone(),
+        endpoint: context.endpoint.clone(),
+        current: context.current,
+        reachable: false,
+        total_size: None,
+        total_reclaimable: None,
+        error: Some(error),
+    }
+}
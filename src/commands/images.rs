@@ -0,0 +1,310 @@
+//! Tag retention: `dockerase images prune --keep-latest N` keeps the newest
+//! N tags of each repository and removes the rest, something docker's own
+//! prune filters can't express.
+
+use crate::cancel;
+use crate::config::{Protected, PROTECTED_NOTE};
+use crate::display::{
+    confirm, format_bytes, print_dry_run_header, print_error, print_info, print_space_saved,
+    print_success, print_text, print_warning, Styled,
+};
+use crate::docker::Docker;
+use crate::grace;
+use crate::notes::Kind;
+use crate::policy::{self, Category};
+use crate::report::{CleanupReport, Report, ReportItem};
+use crate::resources::{Container, Image, Provenance};
+use crate::undo::{self, Manifest};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Why a tag is kept or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Among the newest tags of its repository
+    Newest,
+    /// A container was created from it
+    InUse,
+    Protected,
+    Remove,
+}
+
+impl Decision {
+    fn note(self, keep_latest: usize) -> String {
+        match self {
+            Decision::Newest => format!("newest {}", keep_latest),
+            Decision::InUse => "used by a container".to_string(),
+            Decision::Protected => PROTECTED_NOTE.to_string(),
+            Decision::Remove => String::new(),
+        }
+    }
+}
+
+/// Decide for each tagged image whether it stays, repository by
+/// repository, newest first. Tags in use or protected are kept without
+/// counting towards `keep_latest`; untagged and held images aren't
+/// considered at all.
+pub fn plan<'a>(
+    images: &'a [Image],
+    containers: &[Container],
+    keep_latest: usize,
+    protect: &Protected,
+) -> Vec<(&'a Image, Decision)> {
+    let mut repositories: BTreeMap<&str, Vec<&Image>> = BTreeMap::new();
+    for image in images.iter().filter(|i| grace::can_hold(i)) {
+        repositories
+            .entry(image.repository.as_str())
+            .or_default()
+            .push(image);
+    }
+
+    let mut decisions = Vec::new();
+    for (_, mut tags) in repositories {
+        // An unknown creation time counts as newest, so it is never the
+        // reason a tag goes
+        tags.sort_by_key(|i| (Reverse(i.created().unwrap_or(u64::MAX)), i.tag.clone()));
+        let mut kept = 0;
+        for image in tags {
+            let decision = if protect.protects_image(image) {
+                Decision::Protected
+            } else if containers.iter().any(|c| c.uses(image)) {
+                Decision::InUse
+            } else if kept < keep_latest {
+                kept += 1;
+                Decision::Newest
+            } else {
+                Decision::Remove
+            };
+            decisions.push((image, decision));
+        }
+    }
+    decisions
+}
+
+pub fn prune(
+    keep_latest: usize,
+    force: bool,
+    dry_run: bool,
+    grace: Option<Duration>,
+    protect: &Protected,
+) -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+    policy::check(Category::Images, "prune images")?;
+
+    if dry_run {
+        print_dry_run_header();
+    }
+
+    let before = Docker::get_disk_usage()?;
+    let mut report = CleanupReport::new("images prune", dry_run);
+
+    let images = Docker::list_images(&[])?;
+    let containers = Docker::list_containers(true, &[])?;
+    let decisions = plan(&images, &containers, keep_latest, protect);
+    if decisions.is_empty() {
+        print_success("No tagged images found.");
+        return Ok(Report::Cleanup(report));
+    }
+
+    print_text(&format!(
+        "{}",
+        format!("Keeping the newest {} tags per repository", keep_latest)
+            .bold()
+            .cyan()
+    ));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["IMAGE", "CREATED", "SIZE", "SOURCE", "DECISION"]);
+    for (image, decision) in &decisions {
+        let name = image.display_name();
+        let created = image.created_at.split_whitespace().next().unwrap_or("-");
+        let note = match decision {
+            Decision::Protected => protect.reason(Kind::Image, &name),
+            d => d.note(keep_latest),
+        };
+        let verdict = match decision {
+            Decision::Remove => "REMOVE".danger().to_string(),
+            _ => format!("KEEP ({})", note).good().to_string(),
+        };
+        table.add_row(vec![
+            name.clone(),
+            created.to_string(),
+            format_bytes(image.size_bytes()),
+            match image.provenance() {
+                Provenance::Registry => Provenance::Registry.label().to_string(),
+                Provenance::Local => Provenance::Local.label().caution().to_string(),
+            },
+            verdict,
+        ]);
+
+        report.items.push(match decision {
+            Decision::Remove => ReportItem::planned("images", &name, Some(image.size_bytes())),
+            _ => ReportItem::skipped("images", &name, &note),
+        });
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+
+    let remove: Vec<&Image> = decisions
+        .iter()
+        .filter(|(_, d)| *d == Decision::Remove)
+        .map(|(i, _)| *i)
+        .collect();
+    if remove.is_empty() {
+        print_success("Every repository is within its retention limit.");
+        return Ok(Report::Cleanup(report));
+    }
+    print_info(&format!(
+        "{} tags to remove ({})",
+        remove.len(),
+        format_bytes(remove.iter().map(|i| i.size_bytes()).sum())
+    ));
+    let local = remove
+        .iter()
+        .filter(|i| i.provenance() == Provenance::Local)
+        .count();
+    if local > 0 {
+        print_warning(&format!(
+            "{} of them {} built locally and never pushed; they can't be pulled back",
+            local,
+            if local == 1 { "was" } else { "were" }
+        ));
+    }
+    print_text("");
+
+    if dry_run {
+        print_warning("Dry run - no changes made");
+        return Ok(Report::Cleanup(report));
+    }
+
+    if !force {
+        let confirmed = confirm(&format!("Remove {} image tags?", remove.len()), false)?;
+
+        if !confirmed {
+            print_warning("Aborted");
+            return Ok(Report::Cleanup(report.aborted()));
+        }
+    }
+
+    print_text("");
+    let mut manifest = Manifest::new("images prune");
+    manifest.add_images(remove.iter().copied());
+    undo::record(manifest);
+
+    // By name rather than ID: another tag of the same image may be kept
+    let removed = match grace {
+        Some(period) => grace::hold(&remove, period),
+        None => {
+            let names: Vec<String> = remove.iter().map(|i| i.display_name()).collect();
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            Docker::remove_images(&names).map_err(String::from)
+        }
+    };
+    match (&removed, grace) {
+        // Held, not removed: they are still there until the period is up
+        (Ok(_), Some(period)) => {
+            for image in &remove {
+                report.mark_skipped("images", &image.display_name(), &grace::held_note(period));
+            }
+        }
+        (Ok(_), None) => print_success(&format!("Removed {} image tags", remove.len())),
+        (Err(_), _) => {}
+    }
+    cancel::settle(&mut report, removed.map(drop))?;
+
+    let after = Docker::get_disk_usage()?;
+    print_space_saved(before.total_size(), after.total_size());
+
+    report.mark_all_removed();
+    report.measure_freed(&before, &after);
+
+    Ok(Report::Cleanup(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(id: &str, name: &str, created_at: &str) -> Image {
+        let (repository, tag) = name.split_once(':').unwrap_or((name, ""));
+        Image {
+            id: id.to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            size: "100MB".to_string(),
+            created_at: created_at.to_string(),
+            digest: String::new(),
+        }
+    }
+
+    fn decisions(
+        images: &[Image],
+        containers: &[Container],
+        protect: &Protected,
+    ) -> Vec<(String, Decision)> {
+        plan(images, containers, 2, protect)
+            .into_iter()
+            .map(|(i, d)| (i.display_name(), d))
+            .collect()
+    }
+
+    #[test]
+    fn test_keeps_the_newest_tags_per_repository() {
+        let images = [
+            image("a1", "app:1", "2024-01-01 00:00:00 +0000 UTC"),
+            image("a3", "app:3", "2024-03-01 00:00:00 +0000 UTC"),
+            image("a2", "app:2", "2024-02-01 00:00:00 +0000 UTC"),
+            image("d1", "db:16", "2023-01-01 00:00:00 +0000 UTC"),
+            image("n1", "<none>:<none>", "2020-01-01 00:00:00 +0000 UTC"),
+            image("h1", "dockerase-grace:h1", "2020-01-01 00:00:00 +0000 UTC"),
+        ];
+        assert_eq!(
+            decisions(&images, &[], &Protected::default()),
+            [
+                ("app:3".to_string(), Decision::Newest),
+                ("app:2".to_string(), Decision::Newest),
+                ("app:1".to_string(), Decision::Remove),
+                ("db:16".to_string(), Decision::Newest),
+            ]
+        );
+        assert_eq!(images[0].created(), Some(1_704_067_200));
+    }
+
+    #[test]
+    fn test_used_protected_and_undated_tags_are_kept() {
+        let images = [
+            image("a1", "app:1", "2024-01-01 00:00:00 +0000 UTC"),
+            image("a2", "app:2", "2024-02-01 00:00:00 +0000 UTC"),
+            image("a3", "app:3", "2024-03-01 00:00:00 +0000 UTC"),
+            image("a4", "app:4", "2024-04-01 00:00:00 +0000 UTC"),
+            image("a5", "app:5", ""),
+        ];
+        let container: Container = serde_json::from_str(
+            r#"{"ID":"c1","Names":"web","Image":"app:1","State":"exited","Status":""}"#,
+        )
+        .unwrap();
+        let protect = Protected {
+            images: vec!["app:4".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            decisions(&images, &[container], &protect),
+            [
+                ("app:5".to_string(), Decision::Newest),
+                ("app:4".to_string(), Decision::Protected),
+                ("app:3".to_string(), Decision::Newest),
+                ("app:2".to_string(), Decision::Remove),
+                ("app:1".to_string(), Decision::InUse),
+            ]
+        );
+    }
+}
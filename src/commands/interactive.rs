@@ -0,0 +1,126 @@
+use crate::display::{format_bytes, print_text, select};
+use crate::docker::Docker;
+use crate::resources::DiskUsage;
+use colored::Colorize;
+use std::io::{self, IsTerminal};
+
+/// What `dockerase interactive` can run; each maps to a regular command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Usage,
+    Purge,
+    Select,
+    SystemCaches,
+    Nuclear,
+}
+
+impl Action {
+    const ALL: [Action; 5] = [
+        Action::Usage,
+        Action::Purge,
+        Action::Select,
+        Action::SystemCaches,
+        Action::Nuclear,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Action::Usage => "View disk usage",
+            Action::Purge => "Purge unused resources (safe)",
+            Action::Select => "Select resources to remove",
+            Action::SystemCaches => "Clean system caches",
+            Action::Nuclear => "Nuclear: remove everything",
+        }
+    }
+
+    /// The equivalent command, so users learn it along the way.
+    fn command(self) -> &'static str {
+        match self {
+            Action::Usage => "dockerase",
+            Action::Purge => "dockerase purge",
+            Action::Select => "dockerase select",
+            Action::SystemCaches => "dockerase system select",
+            Action::Nuclear => "dockerase --nuclear",
+        }
+    }
+
+    /// What the action would touch, from the current usage when Docker is
+    /// reachable.
+    fn preview(self, usage: Option<&DiskUsage>) -> String {
+        let Some(usage) = usage else {
+            return match self {
+                Action::SystemCaches => "npm, pip, Cargo, Gradle and more".to_string(),
+                _ => "Docker is not available".to_string(),
+            };
+        };
+        match self {
+            Action::Usage => format!("{} used by Docker", format_bytes(usage.total_size())),
+            Action::Purge => format!(
+                "{} reclaimable: dangling images, stopped containers, orphaned volumes",
+                format_bytes(usage.total_reclaimable())
+            ),
+            Action::Select => format!(
+                "Pick from {} images, {} containers and {} volumes",
+                usage.images_count, usage.containers_count, usage.volumes_count
+            ),
+            Action::SystemCaches => "npm, pip, Cargo, Gradle and more".to_string(),
+            Action::Nuclear => format!(
+                "Deletes ALL {} of Docker data, volumes included",
+                format_bytes(usage.total_size())
+            ),
+        }
+    }
+}
+
+/// Offer every action in one prompt. Returns `None` when the user backs out.
+pub fn choose() -> Result<Option<Action>, String> {
+    if !io::stdin().is_terminal() {
+        return Err(
+            "`dockerase interactive` needs a terminal; run a command directly (see --help)"
+                .to_string(),
+        );
+    }
+
+    let usage = if Docker::is_available() {
+        Docker::get_disk_usage().ok()
+    } else {
+        None
+    };
+
+    let items: Vec<String> = Action::ALL
+        .iter()
+        .map(|a| format!("{:<32} {}", a.title(), a.preview(usage.as_ref()).dimmed()))
+        .collect();
+
+    let choice = select("What would you like to do? (Esc to quit)", &items, 0)?;
+
+    let action = choice.map(|i| Action::ALL[i]);
+    if let Some(action) = action {
+        print_text(&format!("{}", format!("$ {}", action.command()).dimmed()));
+        print_text("");
+    }
+    Ok(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_previews_without_docker() {
+        assert_eq!(Action::Purge.preview(None), "Docker is not available");
+        // System caches don't need the daemon
+        assert!(Action::SystemCaches.preview(None).contains("npm"));
+    }
+
+    #[test]
+    fn test_previews_show_usage() {
+        let usage = DiskUsage {
+            images_reclaimable: 2_000_000_000,
+            images_count: 4,
+            ..Default::default()
+        };
+        assert!(Action::Purge.preview(Some(&usage)).starts_with("2.0 GB"));
+        assert!(Action::Select.preview(Some(&usage)).contains("4 images"));
+    }
+}
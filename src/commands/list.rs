@@ -1,18 +1,72 @@
-use crate::display::{print_disk_usage, print_error, print_footer, print_header};
+use crate::config::Config;
+use crate::display::{
+    lists_resources, print_disk_usage, print_error, print_footer, print_forecast, print_header,
+    print_health, print_success, print_text, print_unused_volumes,
+};
 use crate::docker::Docker;
+use crate::health::HealthScore;
+use crate::history;
+use crate::influx;
+use crate::report::{Report, ResourceListing, UsageReport};
+use crate::resources::DiskUsage;
+use crate::system::{discover_quick_caches, DiskSpace};
 
-pub fn run() -> Result<(), String> {
+pub fn run(config: &Config) -> Result<Report, String> {
     if !Docker::is_available() {
         print_error("Docker is not available. Is Docker running?");
         return Err("Docker not available".to_string());
     }
 
     print_header();
-    println!();
+    print_text("");
 
     let usage = Docker::get_disk_usage()?;
     print_disk_usage(&usage);
+    // Listings are extra detail; the overview stands without them
+    let unused_volumes = Docker::unused_volumes(&[]).unwrap_or_default();
+    print_unused_volumes(&unused_volumes);
+    let resources = if lists_resources() {
+        ResourceListing::new(
+            &Docker::list_images(&[]).unwrap_or_default(),
+            &Docker::list_containers(true, &[]).unwrap_or_default(),
+            &Docker::list_volumes(&[]).unwrap_or_default(),
+            &unused_volumes,
+            &config.protect.notes,
+        )
+    } else {
+        ResourceListing::default()
+    };
+    let disk = Docker::host_disk_space();
+    let forecast = forecast_days_until_full(&usage, disk);
+    if let Some(days) = forecast {
+        print_forecast(days);
+    }
+    let health = HealthScore::compute(&usage, &discover_quick_caches(config), disk);
+    print_health(&health);
     print_footer();
 
-    Ok(())
+    Ok(Report::Usage(Box::new(
+        UsageReport::new(usage, unused_volumes, forecast, health).with_resources(resources),
+    )))
+}
+
+/// The overview, optionally pushed to InfluxDB as line protocol.
+pub fn usage(config: &Config, push: Option<&str>) -> Result<Report, String> {
+    let report = run(config)?;
+    if let Some(url) = push {
+        influx::push(url, &report.to_influx()?)?;
+        print_success(&format!("Pushed usage metrics to {}", url));
+    }
+    Ok(report)
+}
+
+/// Record the current usage and estimate how long until the disk backing
+/// Docker is full at the recent growth rate.
+fn forecast_days_until_full(usage: &DiskUsage, disk: Option<DiskSpace>) -> Option<f64> {
+    // History is best-effort; a read-only data dir shouldn't break the overview
+    history::record_snapshot(usage).ok();
+
+    let snapshots = history::load_snapshots();
+    let rate = history::growth_rate_per_day(&snapshots, history::now_secs())?;
+    history::days_until_full(disk?.available, rate)
 }
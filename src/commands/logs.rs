@@ -0,0 +1,252 @@
+use crate::display::{
+    format_bytes, multi_select, print_dry_run_header, print_error, print_info, print_success,
+    print_text, print_warning,
+};
+use crate::docker::Docker;
+use crate::read_only;
+use crate::report::{CleanupReport, ItemStatus, Report, ReportItem};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::cmp::Reverse;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Logs smaller than this are left alone unless containers are named.
+pub const DEFAULT_MIN_SIZE: u64 = 100_000_000;
+
+/// A container's json-file log on this machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerLog {
+    pub container: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// The log of every container whose file is readable here. Logs inside a
+/// VM (Docker Desktop) or on a remote daemon are left out.
+pub fn container_logs() -> Result<Vec<ContainerLog>, String> {
+    Ok(Docker::container_log_paths()?
+        .into_iter()
+        .filter_map(|(container, path)| {
+            let size = fs::metadata(&path).ok()?.len();
+            Some(ContainerLog {
+                container,
+                path,
+                size,
+            })
+        })
+        .collect())
+}
+
+/// Empty a container log in place; the daemon keeps writing to the same
+/// file, so this needs no space and no restart.
+pub fn truncate(path: &Path) -> Result<(), String> {
+    read_only::check("truncate container logs")?;
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map(drop)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The logs to offer: those of the named `containers` (every one must
+/// have a log), else all logs of at least `min_size`, largest first.
+fn candidates(
+    logs: Vec<ContainerLog>,
+    containers: &[String],
+    min_size: Option<u64>,
+) -> Result<Vec<ContainerLog>, String> {
+    if let Some(missing) = containers
+        .iter()
+        .find(|name| !logs.iter().any(|l| &l.container == *name))
+    {
+        return Err(format!(
+            "No readable json-file log for container '{}'",
+            missing
+        ));
+    }
+    let min_size = match (min_size, containers.is_empty()) {
+        (Some(size), _) => size,
+        (None, true) => DEFAULT_MIN_SIZE,
+        (None, false) => 0,
+    };
+    let mut logs: Vec<ContainerLog> = logs
+        .into_iter()
+        .filter(|l| containers.is_empty() || containers.contains(&l.container))
+        .filter(|l| l.size > 0 && l.size >= min_size)
+        .collect();
+    logs.sort_by_key(|l| Reverse(l.size));
+    Ok(logs)
+}
+
+/// Truncate container logs that have grown large, or those of the named
+/// `containers`. Without `force` each log can be picked or left out.
+pub fn prune(
+    containers: &[String],
+    min_size: Option<u64>,
+    force: bool,
+    dry_run: bool,
+) -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+    if !Docker::is_local() {
+        return Err("Container logs can only be truncated on a local daemon".to_string());
+    }
+
+    if dry_run {
+        print_dry_run_header();
+    }
+
+    let mut report = CleanupReport::new("logs prune", dry_run);
+    let logs = candidates(container_logs()?, containers, min_size)?;
+    if logs.is_empty() {
+        if containers.is_empty() {
+            print_success(&format!(
+                "No container logs of {} or more found.",
+                format_bytes(min_size.unwrap_or(DEFAULT_MIN_SIZE))
+            ));
+        } else {
+            print_success("Those container logs are already small.");
+        }
+        return Ok(Report::Cleanup(report));
+    }
+
+    print_text(&format!("{}", "Container Logs".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["CONTAINER", "LOG SIZE", "PATH"]);
+    for log in &logs {
+        table.add_row(vec![
+            log.container.clone(),
+            format_bytes(log.size),
+            log.path.display().to_string(),
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+
+    let total: u64 = logs.iter().map(|l| l.size).sum();
+    print_info(&format!(
+        "{} log{} to truncate ({})",
+        logs.len(),
+        if logs.len() == 1 { "" } else { "s" },
+        format_bytes(total)
+    ));
+    print_text("");
+
+    if dry_run {
+        for log in &logs {
+            report.items.push(ReportItem::planned(
+                "container_logs",
+                &log.container,
+                Some(log.size),
+            ));
+        }
+        print_warning("Dry run - no changes made");
+        return Ok(Report::Cleanup(report));
+    }
+
+    let chosen: Vec<usize> = if force {
+        (0..logs.len()).collect()
+    } else {
+        let labels: Vec<String> = logs
+            .iter()
+            .map(|l| format!("{} ({})", l.container, format_bytes(l.size)))
+            .collect();
+        let defaults = vec![true; logs.len()];
+        multi_select(Some("Select logs to truncate"), &labels, &defaults)?
+    };
+
+    let mut failed = 0;
+    for (i, log) in logs.iter().enumerate() {
+        let mut item = ReportItem::planned("container_logs", &log.container, Some(log.size));
+        if !chosen.contains(&i) {
+            item.status = ItemStatus::Skipped;
+            item.note = Some("Not selected".to_string());
+        } else {
+            match truncate(&log.path) {
+                Ok(()) => {
+                    item.status = ItemStatus::Removed;
+                    report.space_freed += log.size;
+                }
+                Err(e) => {
+                    failed += 1;
+                    item.status = ItemStatus::Failed;
+                    item.error = Some(e);
+                }
+            }
+        }
+        report.items.push(item);
+    }
+
+    if failed == 0 {
+        print_success(&format!(
+            "Truncated {} log{}",
+            chosen.len(),
+            if chosen.len() == 1 { "" } else { "s" }
+        ));
+    } else {
+        print_warning(&format!(
+            "{failed} log{} could not be truncated (try again with sudo)",
+            if failed == 1 { "" } else { "s" }
+        ));
+    }
+    print_info(&format!(
+        "Space freed: {}",
+        format_bytes(report.space_freed)
+    ));
+
+    Ok(Report::Cleanup(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(container: &str, size: u64) -> ContainerLog {
+        ContainerLog {
+            container: container.to_string(),
+            path: PathBuf::from(format!("/logs/{container}-json.log")),
+            size,
+        }
+    }
+
+    fn names(logs: &[ContainerLog]) -> Vec<&str> {
+        logs.iter().map(|l| l.container.as_str()).collect()
+    }
+
+    #[test]
+    fn test_candidates_are_large_logs_largest_first() {
+        let logs = || {
+            vec![
+                log("db", 200_000_000),
+                log("web", 5_000),
+                log("job", 900_000_000),
+                log("idle", 0),
+            ]
+        };
+
+        let picked = candidates(logs(), &[], None).unwrap();
+        assert_eq!(names(&picked), ["job", "db"]);
+
+        let picked = candidates(logs(), &[], Some(1_000)).unwrap();
+        assert_eq!(names(&picked), ["job", "db", "web"]);
+    }
+
+    #[test]
+    fn test_named_containers_ignore_the_default_threshold() {
+        let logs = || vec![log("db", 200_000_000), log("web", 5_000)];
+
+        let picked = candidates(logs(), &["web".to_string()], None).unwrap();
+        assert_eq!(names(&picked), ["web"]);
+
+        let err = candidates(logs(), &["cache".to_string()], None).unwrap_err();
+        assert!(err.contains("'cache'"), "{err}");
+    }
+}
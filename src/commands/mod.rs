@@ -1,5 +1,23 @@
+pub mod analyze;
+pub mod bench;
+pub mod builders;
+pub mod check;
+pub mod compose;
+pub mod contexts;
+pub mod images;
+pub mod interactive;
 pub mod list;
+pub mod logs;
+pub mod notes;
 pub mod nuclear;
+pub mod paths;
 pub mod purge;
+pub mod quota;
+pub mod reclaim;
+pub mod rescue;
 pub mod select;
+pub mod stats;
 pub mod system;
+pub mod undo;
+pub mod volumes;
+pub mod watch;
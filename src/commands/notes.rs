@@ -0,0 +1,99 @@
+use crate::display::{print_info, print_success, print_text, Styled};
+use crate::notes::{self, Kind, Note};
+use crate::report::{NotesReport, Report};
+use crate::units::format_timestamp;
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+
+/// Attach `text` to a resource, show its note, or with `remove` drop it.
+/// Without a resource, list every note.
+pub fn run(
+    resource: Option<(Kind, String)>,
+    text: Option<String>,
+    remove: bool,
+) -> Result<Report, String> {
+    let Some((kind, name)) = resource else {
+        return list();
+    };
+    let label = format!("{} {}", kind.label(), name);
+    let notes = if remove {
+        let note = notes::remove(kind, &name)?.ok_or(format!("{} has no note", label))?;
+        print_success(&format!(
+            "Removed the note on {}; cleanups may remove it again",
+            label
+        ));
+        vec![note]
+    } else if let Some(text) = text {
+        let note = notes::set(kind, &name, &text)?;
+        print_success(&format!(
+            "Noted {}; cleanups will leave it alone",
+            label.bold()
+        ));
+        vec![note]
+    } else {
+        let note = notes::find(&notes::list()?, kind, &name).cloned();
+        match &note {
+            Some(note) => print_note(note),
+            None => print_info(&format!("{} has no note", label)),
+        }
+        note.into_iter().collect()
+    };
+    Ok(Report::Notes(NotesReport { notes }))
+}
+
+/// Every note, by kind and name.
+fn list() -> Result<Report, String> {
+    let notes = notes::list()?;
+    if notes.is_empty() {
+        print_success("No notes yet.");
+        print_text(&format!(
+            "{}",
+            "  Run `dockerase note volume <name> \"why it stays\"` to add one".dimmed()
+        ));
+        return Ok(Report::Notes(NotesReport { notes }));
+    }
+
+    print_text(&format!("{}", "Resource Notes".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["KIND", "NAME", "NOTE", "NOTED (UTC)"]);
+    for note in &notes {
+        table.add_row(vec![
+            note.kind.label().to_string(),
+            note.name.clone(),
+            note.text.clone(),
+            noted_on(note).to_string(),
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+    print_info(&format!(
+        "{} noted resources are protected from every cleanup",
+        notes.len()
+    ));
+
+    Ok(Report::Notes(NotesReport { notes }))
+}
+
+fn print_note(note: &Note) {
+    print_text(&format!(
+        "{} {} {}",
+        note.kind.label(),
+        note.name.bold(),
+        format!("(noted {})", noted_on(note)).dimmed()
+    ));
+    print_text(&format!("  {}", note.text.good()));
+}
+
+/// The day a note was written.
+fn noted_on(note: &Note) -> String {
+    let timestamp = format_timestamp(note.noted_at);
+    timestamp
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
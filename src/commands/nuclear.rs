@@ -1,12 +1,28 @@
+use crate::active::{self, ActiveItem};
+use crate::cancel;
+use crate::commands::builders;
+use crate::config::Protected;
 use crate::display::{
-    format_bytes, print_dry_run_header, print_error, print_info, print_nuclear_warning,
-    print_space_saved, print_success, print_warning,
+    confirm, format_bytes, print_dry_run_header, print_error, print_info, print_nuclear_warning,
+    print_protected, print_space_saved, print_success, print_text, print_warning, Styled,
 };
-use crate::docker::Docker;
+use crate::docker::{Docker, RemoveError};
+use crate::failures;
+use crate::filters::Filters;
+use crate::history;
+use crate::notes::Kind;
+use crate::policy::{self, Category, POLICY_NOTE};
+use crate::report::{BuilderSummary, CleanupReport, Report, ReportItem};
+use crate::resources::{Container, Image, Network, Volume};
+use crate::undo::{self, Manifest};
 use colored::Colorize;
-use dialoguer::Confirm;
 
-pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
+pub fn run(
+    force: bool,
+    dry_run: bool,
+    filters: &Filters,
+    protect: &Protected,
+) -> Result<Report, String> {
     if !Docker::is_available() {
         print_error("Docker is not available. Is Docker running?");
         return Err("Docker not available".to_string());
@@ -19,100 +35,482 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
     print_nuclear_warning();
 
     let before = Docker::get_disk_usage()?;
-    let containers = Docker::list_containers(true)?;
-    let images = Docker::list_images()?;
-    let volumes = Docker::list_volumes()?;
-    let networks = Docker::list_networks()?;
-    let custom_networks: Vec<_> = networks.iter().filter(|n| !n.is_default()).collect();
-
-    println!("This will remove:");
-    print_info(&format!("{} containers", containers.len()));
-    print_info(&format!("{} images", images.len()));
-    print_info(&format!("{} volumes", volumes.len()));
-    print_info(&format!("{} custom networks", custom_networks.len()));
-    print_info("All build cache");
-    println!();
-    println!(
-        "Total space to free: {}",
-        format_bytes(before.total_size()).green().bold()
+    let now = history::now_secs();
+    let labels = filters.label_args();
+    // Categories the policy disables are left alone entirely
+    let disabled: Vec<Category> = [
+        Category::Containers,
+        Category::Images,
+        Category::Volumes,
+        Category::Networks,
+        Category::BuildCache,
+    ]
+    .into_iter()
+    .filter(|&c| !policy::allows(c))
+    .collect();
+    let mut containers: Vec<_> = Docker::list_containers(true, &labels)?
+        .into_iter()
+        .filter(|c| filters.allows(&c.names, &c.created_at, now))
+        .filter(|_| policy::allows(Category::Containers))
+        .collect();
+    let (protected_images, mut images): (Vec<_>, Vec<_>) = Docker::list_images(&labels)?
+        .into_iter()
+        .filter(|i| filters.allows(&i.display_name(), &i.created_at, now))
+        .filter(|_| policy::allows(Category::Images))
+        .partition(|i| protect.protects_image(i));
+    // `docker volume ls` has no creation time, so an age limit spares them all
+    let (protected_volumes, mut volumes): (Vec<_>, Vec<_>) =
+        if filters.older_than.is_some() || !policy::allows(Category::Volumes) {
+            (vec![], vec![])
+        } else {
+            Docker::list_volumes(&labels)?
+                .into_iter()
+                .filter(|v| !filters.is_kept(&v.name))
+                .partition(|v| protect.protects_volume(&v.name))
+        };
+    let protected_images: Vec<String> = protected_images.iter().map(|i| i.display_name()).collect();
+    let protected_volumes: Vec<String> = protected_volumes.into_iter().map(|v| v.name).collect();
+    let networks = Docker::list_networks(&labels)?;
+    let mut custom_networks: Vec<_> = networks
+        .iter()
+        .filter(|n| !n.is_default() && filters.allows(&n.name, &n.created_at, now))
+        .filter(|_| policy::allows(Category::Networks))
+        .collect();
+    // Build cache records carry no labels
+    let clear_build_cache = filters.labels.is_empty() && policy::allows(Category::BuildCache);
+    // Remote and cloud builders may be shared, and a stopped one would be
+    // started to prune it
+    let buildx = if clear_build_cache {
+        builders::summaries()
+            .into_iter()
+            .filter(|b| b.is_local() && b.running)
+            .collect()
+    } else {
+        vec![]
+    };
+
+    // What something live depends on stays unless --include-active
+    let active = Docker::active_resources(&images)?;
+    let in_use: Vec<ActiveItem> = containers
+        .iter()
+        .filter_map(|c| ActiveItem::new("containers", &c.names, active.container(c)))
+        .chain(
+            images
+                .iter()
+                .filter_map(|i| ActiveItem::new("images", &i.display_name(), active.image(i))),
+        )
+        .chain(
+            volumes
+                .iter()
+                .filter_map(|v| ActiveItem::new("volumes", &v.name, active.volume(v))),
+        )
+        .chain(
+            custom_networks
+                .iter()
+                .filter_map(|n| ActiveItem::new("networks", &n.name, active.network(n))),
+        )
+        .collect();
+    let spare_active = !active::include(&in_use, force, dry_run)?;
+    if spare_active {
+        containers.retain(|c| active.container(c).is_none());
+        images.retain(|i| active.image(i).is_none());
+        volumes.retain(|v| active.volume(v).is_none());
+        custom_networks.retain(|n| active.network(n).is_none());
+    }
+
+    let mut report = CleanupReport::new("nuclear", dry_run);
+    if spare_active {
+        active::skip(&mut report, &in_use);
+    }
+    for c in &containers {
+        report
+            .items
+            .push(ReportItem::planned("containers", &c.names, None));
+    }
+    for i in &images {
+        report
+            .items
+            .push(ReportItem::planned("images", &i.display_name(), None));
+    }
+    for v in &volumes {
+        report
+            .items
+            .push(ReportItem::planned("volumes", &v.name, None));
+    }
+    for n in &custom_networks {
+        report
+            .items
+            .push(ReportItem::planned("networks", &n.name, None));
+    }
+    for name in &protected_images {
+        report.items.push(ReportItem::skipped(
+            "images",
+            name,
+            &protect.reason(Kind::Image, name),
+        ));
+    }
+    for name in &protected_volumes {
+        report.items.push(ReportItem::skipped(
+            "volumes",
+            name,
+            &protect.reason(Kind::Volume, name),
+        ));
+    }
+    for category in &disabled {
+        report.items.push(ReportItem::skipped(
+            category.as_str(),
+            &format!("all {}", category.as_str().replace('_', " ")),
+            POLICY_NOTE,
+        ));
+    }
+    if filters.older_than.is_some() && policy::allows(Category::Volumes) {
+        report.items.push(ReportItem::skipped(
+            "volumes",
+            "all volumes",
+            "docker can't filter volumes by age",
+        ));
+    }
+    let build_cache_item = match filters.older_than {
+        Some(_) => "old build cache",
+        None => "all build cache",
+    };
+    if clear_build_cache && filters.older_than.is_some() {
+        report
+            .items
+            .push(ReportItem::planned("build_cache", build_cache_item, None));
+    } else if clear_build_cache {
+        report.items.push(ReportItem::planned(
+            "build_cache",
+            build_cache_item,
+            Some(before.build_cache_size),
+        ));
+    }
+    for builder in &buildx {
+        report.items.push(ReportItem::planned(
+            "build_cache",
+            &format!("builder {}", builder.name),
+            builder.cache_size,
+        ));
+    }
+    if !clear_build_cache && policy::allows(Category::BuildCache) {
+        report.items.push(ReportItem::skipped(
+            "build_cache",
+            "all build cache",
+            "build cache has no labels",
+        ));
+    }
+
+    if !filters.is_empty() {
+        print_info(&format!("Filters: {}", filters.describe()));
+        print_text("");
+    }
+    print_protected("images", &protect.labels(Kind::Image, &protected_images));
+    print_protected("volumes", &protect.labels(Kind::Volume, &protected_volumes));
+    if !protected_images.is_empty() || !protected_volumes.is_empty() {
+        print_text("");
+    }
+    if !disabled.is_empty() {
+        let names: Vec<&str> = disabled.iter().map(|c| c.as_str()).collect();
+        print_warning(&format!(
+            "Leaving {} alone: disabled by policy",
+            names.join(", ")
+        ));
+        print_text("");
+    }
+    let until: Vec<String> = filters.until_arg().into_iter().collect();
+    let operations = plan(
+        &buildx,
+        &containers,
+        &images,
+        &volumes,
+        &custom_networks,
+        clear_build_cache.then_some(build_cache_item),
     );
-    println!();
+    if dry_run {
+        print_text("Would run, in order:");
+        let lines: Vec<String> = operations.iter().flat_map(|op| op.lines(&until)).collect();
+        let width = lines.len().to_string().len();
+        for (n, line) in lines.iter().enumerate() {
+            print_text(&format!("  {:>width$}. {}", n + 1, line));
+        }
+    } else {
+        print_text("This will remove:");
+        print_info(&format!("{} containers", containers.len()));
+        print_info(&format!("{} images", images.len()));
+        print_info(&format!("{} volumes", volumes.len()));
+        print_info(&format!("{} custom networks", custom_networks.len()));
+        if clear_build_cache {
+            print_info("All build cache");
+        }
+        if !buildx.is_empty() {
+            print_info(&format!(
+                "The build cache of {} buildx builder(s)",
+                buildx.len()
+            ));
+        }
+    }
+    print_text("");
+    // With filters only part of it goes, and docker can't say how much
+    let everything = filters.is_empty()
+        && (in_use.is_empty() || !spare_active)
+        && protected_images.is_empty()
+        && protected_volumes.is_empty()
+        && disabled.is_empty();
+    if everything {
+        print_text(&format!(
+            "Total space to free: {}",
+            format_bytes(before.total_size()).good().bold()
+        ));
+        print_text("");
+    }
 
     if dry_run {
         print_warning("Dry run - no changes made");
-        return Ok(());
+        return Ok(Report::Cleanup(report));
     }
 
     if !force {
-        println!(
+        print_text(&format!(
             "{}",
             "Type 'yes' to confirm complete Docker cleanup:"
-                .red()
+                .danger()
                 .bold()
-        );
-        let confirm = Confirm::new()
-            .with_prompt("Are you absolutely sure?")
-            .default(false)
-            .interact()
-            .map_err(|e| e.to_string())?;
-
-        if !confirm {
+        ));
+        let confirmed = confirm("Are you absolutely sure?", false)?;
+
+        if !confirmed {
             print_warning("Aborted - no changes made");
-            return Ok(());
+            return Ok(Report::Cleanup(report.aborted()));
         }
     }
 
-    println!();
+    print_text("");
 
-    // Stop running containers first
-    let running: Vec<_> = containers.iter().filter(|c| c.is_running()).collect();
-    if !running.is_empty() {
-        print_info(&format!("Stopping {} running containers...", running.len()));
-        Docker::stop_all_containers()?;
-        print_success("Containers stopped");
-    }
+    let mut manifest = Manifest::new("nuclear");
+    manifest.add_containers(&containers);
+    manifest.add_images(&images);
+    manifest.add_volumes(volumes.iter().map(|v| &v.name));
+    manifest.add_networks(custom_networks.iter().map(|n| &n.name));
+    undo::record(manifest);
+
+    let mut execute = || -> Result<(), String> {
+        for op in &operations {
+            cancel::check()?;
+            print_info(&op.progress());
+            if op.record(&mut report, op.run(&until))? {
+                print_success(&op.done());
+            }
+        }
+        Ok(())
+    };
+    let executed = execute();
+    failures::settle(&mut report, executed)?;
+
+    let after = Docker::get_disk_usage()?;
+    print_space_saved(before.total_size(), after.total_size());
 
-    // Remove all containers
-    if !containers.is_empty() {
-        print_info(&format!("Removing {} containers...", containers.len()));
-        Docker::remove_all_containers()?;
-        print_success("Containers removed");
+    if !cancel::is_requested() && !report.has_failures() {
+        print_text("");
+        print_success(if everything {
+            "Nuclear cleanup complete. Docker is now empty."
+        } else {
+            "Nuclear cleanup complete."
+        });
     }
 
-    // Remove all images
-    if !images.is_empty() {
-        print_info(&format!("Removing {} images...", images.len()));
-        Docker::remove_all_images()?;
-        print_success("Images removed");
+    report.mark_all_removed();
+    report.measure_freed(&before, &after);
+
+    Ok(Report::Cleanup(report))
+}
+
+/// A resource a step acts on.
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    id: String,
+    name: String,
+}
+
+impl Target {
+    fn new(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+        }
     }
+}
 
-    // Remove all volumes
-    if !volumes.is_empty() {
-        print_info(&format!("Removing {} volumes...", volumes.len()));
-        Docker::remove_all_volumes()?;
-        print_success("Volumes removed");
+/// One step of a nuclear cleanup. The dry run lists the same steps,
+/// resource by resource, that execution then runs.
+#[derive(Debug, Clone, PartialEq)]
+enum Operation {
+    /// Clear the cache of the buildx builder with this name
+    PruneBuilder(String),
+    StopContainers(Vec<Target>),
+    RemoveContainers(Vec<Target>),
+    RemoveImages(Vec<Target>),
+    RemoveVolumes(Vec<Target>),
+    RemoveNetworks(Vec<Target>),
+    /// Prune the build cache; the name is its report item's
+    PruneBuildCache(&'static str),
+}
+
+/// Order the cleanup so nothing is removed while something still depends
+/// on it: builders first, as pruning goes through the builder's container
+/// and it has to still be there; running containers are stopped and
+/// removed before the images, volumes and networks they use; the build
+/// cache last. Steps with nothing to do are left out.
+fn plan(
+    builders: &[BuilderSummary],
+    containers: &[Container],
+    images: &[Image],
+    volumes: &[Volume],
+    networks: &[&Network],
+    build_cache: Option<&'static str>,
+) -> Vec<Operation> {
+    let running: Vec<Target> = containers
+        .iter()
+        .filter(|c| c.is_running())
+        .map(|c| Target::new(&c.id, &c.names))
+        .collect();
+    let steps = [
+        Operation::StopContainers(running),
+        Operation::RemoveContainers(
+            containers
+                .iter()
+                .map(|c| Target::new(&c.id, &c.names))
+                .collect(),
+        ),
+        Operation::RemoveImages(
+            images
+                .iter()
+                .map(|i| Target::new(&i.reference(), &i.display_name()))
+                .collect(),
+        ),
+        Operation::RemoveVolumes(
+            volumes
+                .iter()
+                .map(|v| Target::new(&v.name, &v.name))
+                .collect(),
+        ),
+        Operation::RemoveNetworks(
+            networks
+                .iter()
+                .map(|n| Target::new(&n.id, &n.name))
+                .collect(),
+        ),
+    ];
+
+    let mut operations: Vec<Operation> = builders
+        .iter()
+        .map(|b| Operation::PruneBuilder(b.name.clone()))
+        .collect();
+    operations.extend(steps.into_iter().filter(|op| !op.targets().is_empty()));
+    operations.extend(build_cache.map(Operation::PruneBuildCache));
+    operations
+}
+
+impl Operation {
+    fn targets(&self) -> &[Target] {
+        match self {
+            Operation::StopContainers(t)
+            | Operation::RemoveContainers(t)
+            | Operation::RemoveImages(t)
+            | Operation::RemoveVolumes(t)
+            | Operation::RemoveNetworks(t) => t,
+            Operation::PruneBuilder(_) | Operation::PruneBuildCache(_) => &[],
+        }
     }
 
-    // Remove custom networks
-    if !custom_networks.is_empty() {
-        print_info(&format!(
-            "Removing {} custom networks...",
-            custom_networks.len()
-        ));
-        Docker::remove_custom_networks()?;
-        print_success("Networks removed");
+    /// The docker commands this step comes down to, one per resource, with
+    /// resources by name.
+    fn lines(&self, until: &[String]) -> Vec<String> {
+        let filters: String = until.iter().map(|f| format!(" --filter {}", f)).collect();
+        let each = |verb: &str| -> Vec<String> {
+            self.targets()
+                .iter()
+                .map(|t| format!("{} {}", verb, t.name))
+                .collect()
+        };
+        match self {
+            Operation::PruneBuilder(name) => {
+                vec![format!("buildx prune --builder {} --all{}", name, filters)]
+            }
+            Operation::StopContainers(_) => each("stop"),
+            Operation::RemoveContainers(_) => each("rm -f"),
+            Operation::RemoveImages(_) => each("rmi -f"),
+            Operation::RemoveVolumes(_) => each("volume rm -f"),
+            Operation::RemoveNetworks(_) => each("network rm"),
+            Operation::PruneBuildCache(_) => vec![format!("builder prune --all{}", filters)],
+        }
     }
 
-    // Clear all build cache
-    print_info("Clearing all build cache...");
-    Docker::prune_build_cache(true)?;
-    print_success("Build cache cleared");
+    fn progress(&self) -> String {
+        let count = self.targets().len();
+        match self {
+            Operation::PruneBuilder(name) => {
+                format!("Clearing the build cache of builder {}...", name)
+            }
+            Operation::StopContainers(_) => format!("Stopping {} running containers...", count),
+            Operation::RemoveContainers(_) => format!("Removing {} containers...", count),
+            Operation::RemoveImages(_) => format!("Removing {} images...", count),
+            Operation::RemoveVolumes(_) => format!("Removing {} volumes...", count),
+            Operation::RemoveNetworks(_) => format!("Removing {} custom networks...", count),
+            Operation::PruneBuildCache(_) => "Clearing all build cache...".to_string(),
+        }
+    }
 
-    let after = Docker::get_disk_usage()?;
-    print_space_saved(before.total_size(), after.total_size());
+    fn done(&self) -> String {
+        match self {
+            Operation::PruneBuilder(name) => format!("Build cache of builder {} cleared", name),
+            Operation::StopContainers(_) => "Containers stopped".to_string(),
+            Operation::RemoveContainers(_) => "Containers removed".to_string(),
+            Operation::RemoveImages(_) => "Images removed".to_string(),
+            Operation::RemoveVolumes(_) => "Volumes removed".to_string(),
+            Operation::RemoveNetworks(_) => "Networks removed".to_string(),
+            Operation::PruneBuildCache(_) => "Build cache cleared".to_string(),
+        }
+    }
 
-    println!();
-    print_success("Nuclear cleanup complete. Docker is now empty.");
+    fn run(&self, until: &[String]) -> Result<String, RemoveError> {
+        let ids: Vec<&str> = self.targets().iter().map(|t| t.id.as_str()).collect();
+        match self {
+            Operation::PruneBuilder(name) => Ok(Docker::prune_builder(name, true, until)?),
+            Operation::StopContainers(_) => Ok(Docker::stop_containers(&ids)?),
+            Operation::RemoveContainers(_) => Docker::remove_containers(&ids),
+            Operation::RemoveImages(_) => Docker::remove_images(&ids),
+            Operation::RemoveVolumes(_) => Docker::remove_volumes(&ids),
+            Operation::RemoveNetworks(_) => Docker::remove_networks(&ids),
+            Operation::PruneBuildCache(_) => Ok(Docker::prune_build_cache(true, until)?),
+        }
+    }
 
-    Ok(())
+    /// Mark what this step removed in `report`, and what it couldn't.
+    /// Returns whether everything went, or an error to stop the run.
+    fn record(
+        &self,
+        report: &mut CleanupReport,
+        result: Result<String, RemoveError>,
+    ) -> Result<bool, String> {
+        let category = match self {
+            Operation::PruneBuilder(name) => {
+                return failures::step(report, "build_cache", &format!("builder {}", name), result)
+            }
+            Operation::PruneBuildCache(item) => {
+                return failures::step(report, "build_cache", item, result)
+            }
+            // `rm -f` takes whatever couldn't be stopped
+            Operation::StopContainers(_) => return Ok(result.is_ok()),
+            Operation::RemoveContainers(_) => "containers",
+            Operation::RemoveImages(_) => "images",
+            Operation::RemoveVolumes(_) => "volumes",
+            Operation::RemoveNetworks(_) => "networks",
+        };
+        let targets: Vec<(&str, &str)> = self
+            .targets()
+            .iter()
+            .map(|t| (t.id.as_str(), t.name.as_str()))
+            .collect();
+        failures::removed(report, category, &targets, result)
+    }
 }
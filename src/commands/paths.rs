@@ -0,0 +1,58 @@
+use crate::display::{print_text, Styled};
+use crate::paths;
+use crate::report::{PathEntry, PathsReport, Report};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::path::PathBuf;
+
+/// Show where dockerase reads its config and keeps its state.
+pub fn run() -> Result<Report, String> {
+    let entries: Vec<PathEntry> = [
+        ("config", paths::config_file()),
+        ("system config", paths::system_config_file()),
+        ("state", paths::state_dir()),
+        ("usage history", paths::usage_history()),
+        ("undo history", paths::undo_history()),
+        ("cleanup stats", paths::cleanup_stats()),
+        ("watch log", paths::watch_log()),
+        ("image holds", paths::image_holds()),
+        ("resource notes", paths::resource_notes()),
+        ("cache", paths::cache_dir()),
+    ]
+    .into_iter()
+    .map(|(name, path)| entry(name, path))
+    .collect();
+
+    print_text(&format!("{}", "dockerase Paths".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["WHAT", "PATH", "STATUS"]);
+    for entry in &entries {
+        table.add_row(vec![
+            entry.name.clone(),
+            entry.path.clone(),
+            if entry.exists {
+                "exists".good().to_string()
+            } else {
+                "not created yet".dimmed().to_string()
+            },
+        ]);
+    }
+    print_text(&format!("{table}"));
+
+    Ok(Report::Paths(PathsReport { paths: entries }))
+}
+
+fn entry(name: &str, path: Option<PathBuf>) -> PathEntry {
+    let exists = path.as_ref().is_some_and(|p| p.exists());
+    PathEntry {
+        name: name.to_string(),
+        path: path
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "unknown (no home directory)".to_string()),
+        exists,
+    }
+}
@@ -1,11 +1,46 @@
+use crate::cancel;
+use crate::commands::{builders, rescue};
+use crate::config::Protected;
 use crate::display::{
-    format_bytes, print_dry_run_header, print_error, print_info, print_space_saved, print_success,
-    print_warning,
+    confirm, format_bytes, print_dry_run_header, print_error, print_info, print_protected,
+    print_space_saved, print_success, print_text, print_warning,
 };
-use crate::docker::Docker;
-use dialoguer::Confirm;
+use crate::docker::{is_no_space_error, Docker, RemoveError};
+use crate::failures;
+use crate::filters::Filters;
+use crate::grace::{self, Fate, Hold};
+use crate::history;
+use crate::notes;
+use crate::policy::{self, POLICY_NOTE};
+use crate::report::{BuilderSummary, CleanupReport, Report, ReportItem};
+use crate::resources::DiskUsage;
+use crate::undo::{self, Manifest};
+use colored::Colorize;
 
-pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
+/// `builders` are the buildx builders whose own caches are pruned too.
+pub fn run(
+    force: bool,
+    dry_run: bool,
+    named_volumes: bool,
+    filters: &Filters,
+    protect: &Protected,
+    builders: &builders::Selection,
+) -> Result<Report, String> {
+    let result = cleanup(force, dry_run, named_volumes, filters, protect, builders);
+    if result.as_ref().is_err_and(|e| is_no_space_error(e)) {
+        rescue::print_hint();
+    }
+    result
+}
+
+fn cleanup(
+    force: bool,
+    dry_run: bool,
+    named_volumes: bool,
+    filters: &Filters,
+    protect: &Protected,
+    builders: &builders::Selection,
+) -> Result<Report, String> {
     if !Docker::is_available() {
         print_error("Docker is not available. Is Docker running?");
         return Err("Docker not available".to_string());
@@ -15,22 +50,33 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
         print_dry_run_header();
     }
 
+    let mut report = CleanupReport::new("purge", dry_run);
+
     let before = Docker::get_disk_usage()?;
     let reclaimable = before.total_reclaimable();
+    let holds = due_holds(filters)?;
+    let (builders, builders_skipped) = builder_steps(builders, filters)?;
 
-    if reclaimable == 0 {
+    if reclaimable == 0 && holds.is_empty() && builders.is_empty() {
         print_success("Nothing to clean up. Docker is already tidy!");
-        return Ok(());
+        return Ok(Report::Cleanup(report));
+    }
+
+    if !filters.is_empty() {
+        print_info(&format!("Filters: {}", filters.describe()));
+        print_text("");
     }
 
-    println!("Found {} of reclaimable space:", format_bytes(reclaimable));
-    println!();
+    print_text(&format!(
+        "Found {} of reclaimable space:",
+        format_bytes(reclaimable)
+    ));
+    print_text("");
 
     let unused_images = before.images_count.saturating_sub(before.images_active);
     let stopped_containers = before
         .containers_count
         .saturating_sub(before.containers_active);
-    let unused_volumes = before.volumes_count.saturating_sub(before.volumes_active);
 
     if unused_images > 0 {
         print_info(&format!(
@@ -46,11 +92,25 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
             format_bytes(before.containers_reclaimable)
         ));
     }
-    if unused_volumes > 0 {
+    // Only orphans by default: a named volume that isn't mounted right now
+    // may still hold data someone means to reattach
+    let unused_volumes = Docker::unused_volumes(&filters.label_args())?;
+    let mut volumes = unused_volumes.orphaned.clone();
+    if named_volumes {
+        volumes.extend(unused_volumes.unmounted.iter().cloned());
+    }
+    let (protected, volumes): (Vec<String>, Vec<String>) = volumes
+        .into_iter()
+        .partition(|v| protect.protects_volume(v));
+    if !volumes.is_empty() {
         print_info(&format!(
-            "{} unused volumes ({})",
-            unused_volumes,
-            format_bytes(before.volumes_reclaimable)
+            "{} {}",
+            volumes.len(),
+            if named_volumes {
+                "unused volumes"
+            } else {
+                "orphaned anonymous volumes"
+            }
         ));
     }
     if before.build_cache_reclaimable > 0 {
@@ -59,50 +119,422 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
             format_bytes(before.build_cache_reclaimable)
         ));
     }
+    for builder in &builders {
+        print_info(&format!("Build cache of {}", builders::describe(builder)));
+    }
+    let expired: Vec<&Hold> = holds
+        .iter()
+        .filter(|(_, fate)| *fate == Fate::Delete)
+        .map(|(hold, _)| hold)
+        .collect();
+    if !expired.is_empty() {
+        print_info(&format!(
+            "{} held images past their grace period",
+            expired.len()
+        ));
+    }
+    for hold in &expired {
+        report
+            .items
+            .push(ReportItem::planned("held_images", &hold.image.name(), None));
+    }
+
+    if !named_volumes && !unused_volumes.unmounted.is_empty() {
+        print_text(&format!(
+            "    {}",
+            format!(
+                "Keeping {} named volume(s) not mounted by any container (--named-volumes removes them)",
+                unused_volumes.unmounted.len()
+            )
+            .dimmed()
+        ));
+    }
 
-    println!();
+    print_protected("volumes", &protect.labels(notes::Kind::Volume, &protected));
+    for name in &protected {
+        report.items.push(ReportItem::skipped(
+            "volumes",
+            name,
+            &protect.reason(notes::Kind::Volume, name),
+        ));
+    }
+
+    let steps = plan(filters, &volumes)?;
+    for (kind, step) in &steps {
+        match step {
+            Step::Prune(_) => report.items.push(ReportItem::planned(
+                kind.category(),
+                kind.description(),
+                kind.reclaimable(&before),
+            )),
+            Step::Remove(resources) => {
+                for (_, name) in resources {
+                    report
+                        .items
+                        .push(ReportItem::planned(kind.category(), name, None));
+                }
+            }
+            Step::Skip(reason) => {
+                report.items.push(ReportItem::skipped(
+                    kind.category(),
+                    kind.description(),
+                    reason,
+                ));
+                print_warning(&format!("Skipping {}: {}", kind.description(), reason));
+            }
+        }
+    }
+
+    for builder in &builders {
+        report.items.push(ReportItem::planned(
+            "build_cache",
+            &format!("builder {}", builder.name),
+            builder.cache_size,
+        ));
+    }
+    if let Some(reason) = builders_skipped {
+        report.items.push(ReportItem::skipped(
+            "build_cache",
+            "buildx builders",
+            reason,
+        ));
+        print_warning(&format!("Skipping buildx builders: {}", reason));
+    }
+
+    print_text("");
 
     if dry_run {
         print_warning("Dry run - no changes made");
-        return Ok(());
+        return Ok(Report::Cleanup(report));
     }
 
     if !force {
-        let confirm = Confirm::new()
-            .with_prompt("Proceed with cleanup?")
-            .default(false)
-            .interact()
-            .map_err(|e| e.to_string())?;
+        let confirmed = confirm("Proceed with cleanup?", false)?;
 
-        if !confirm {
+        if !confirmed {
             print_warning("Aborted");
-            return Ok(());
+            return Ok(Report::Cleanup(report.aborted()));
         }
     }
 
-    println!();
-    print_info("Removing stopped containers...");
-    Docker::prune_containers()?;
-    print_success("Containers cleaned");
+    print_text("");
+    let mut manifest = manifest(&steps, filters)?;
+    manifest
+        .images
+        .extend(expired.iter().map(|h| h.image.clone()));
+    undo::record(manifest);
+    let until: Vec<String> = filters.until_arg().into_iter().collect();
+    let mut execute = || -> Result<(), String> {
+        for (kind, step) in &steps {
+            cancel::check()?;
+            let done = match step {
+                Step::Prune(args) => {
+                    print_info(&format!("Removing {}...", kind.description()));
+                    failures::step(
+                        &mut report,
+                        kind.category(),
+                        kind.description(),
+                        kind.prune(args),
+                    )?
+                }
+                Step::Remove(resources) if !resources.is_empty() => {
+                    print_info(&format!(
+                        "Removing {} {}...",
+                        resources.len(),
+                        kind.category()
+                    ));
+                    let targets: Vec<(&str, &str)> = resources
+                        .iter()
+                        .map(|(id, name)| (id.as_str(), name.as_str()))
+                        .collect();
+                    let ids: Vec<&str> = targets.iter().map(|(id, _)| *id).collect();
+                    failures::removed(&mut report, kind.category(), &targets, kind.remove(&ids))?
+                }
+                Step::Remove(_) | Step::Skip(_) => continue,
+            };
+            if done {
+                print_success(kind.done());
+            }
+        }
+        for builder in &builders {
+            cancel::check()?;
+            print_info(&format!(
+                "Pruning the build cache of builder {}...",
+                builder.name
+            ));
+            let pruned = Docker::prune_builder(&builder.name, false, &until);
+            let name = format!("builder {}", builder.name);
+            if failures::step(&mut report, "build_cache", &name, pruned)? {
+                print_success("Build cache cleared");
+            }
+        }
+        if !holds.is_empty() {
+            cancel::check()?;
+            if !expired.is_empty() {
+                print_info("Deleting held images past their grace period...");
+            }
+            grace::settle(&holds)?;
+            if !expired.is_empty() {
+                print_success("Held images deleted");
+            }
+        }
+        Ok(())
+    };
+    let executed = execute();
+    failures::settle(&mut report, executed)?;
 
-    print_info("Removing dangling images...");
-    Docker::prune_images(false)?;
-    print_success("Images cleaned");
+    let after = Docker::get_disk_usage()?;
+    print_space_saved(before.total_size(), after.total_size());
 
-    print_info("Removing unused volumes...");
-    Docker::prune_volumes()?;
-    print_success("Volumes cleaned");
+    report.mark_all_removed();
+    report.measure_freed(&before, &after);
 
-    print_info("Removing unused networks...");
-    Docker::prune_networks()?;
-    print_success("Networks cleaned");
+    Ok(Report::Cleanup(report))
+}
 
-    print_info("Clearing build cache...");
-    Docker::prune_build_cache(false)?;
-    print_success("Build cache cleared");
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Containers,
+    Images,
+    Volumes,
+    Networks,
+    BuildCache,
+}
 
-    let after = Docker::get_disk_usage()?;
-    print_space_saved(before.total_size(), after.total_size());
+/// How one kind of resource is cleaned up under the active filters.
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// Prune with these `--filter` values
+    Prune(Vec<String>),
+    /// Remove exactly these `(id, name)` resources, picked client-side
+    /// because `--keep` can't be expressed as a prune filter
+    Remove(Vec<(String, String)>),
+    /// Left alone because docker can't apply the filters to it
+    Skip(&'static str),
+}
+
+impl Kind {
+    const ALL: [Kind; 5] = [
+        Kind::Containers,
+        Kind::Images,
+        Kind::Volumes,
+        Kind::Networks,
+        Kind::BuildCache,
+    ];
+
+    fn policy_category(self) -> policy::Category {
+        match self {
+            Kind::Containers => policy::Category::Containers,
+            Kind::Images => policy::Category::Images,
+            Kind::Volumes => policy::Category::Volumes,
+            Kind::Networks => policy::Category::Networks,
+            Kind::BuildCache => policy::Category::BuildCache,
+        }
+    }
+
+    fn category(self) -> &'static str {
+        match self {
+            Kind::Containers => "containers",
+            Kind::Images => "images",
+            Kind::Volumes => "volumes",
+            Kind::Networks => "networks",
+            Kind::BuildCache => "build_cache",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Kind::Containers => "stopped containers",
+            Kind::Images => "dangling images",
+            Kind::Volumes => "unused volumes",
+            Kind::Networks => "unused networks",
+            Kind::BuildCache => "build cache",
+        }
+    }
+
+    fn done(self) -> &'static str {
+        match self {
+            Kind::Containers => "Containers cleaned",
+            Kind::Images => "Images cleaned",
+            Kind::Volumes => "Volumes cleaned",
+            Kind::Networks => "Networks cleaned",
+            Kind::BuildCache => "Build cache cleared",
+        }
+    }
+
+    fn reclaimable(self, usage: &DiskUsage) -> Option<u64> {
+        match self {
+            Kind::Containers => Some(usage.containers_reclaimable),
+            Kind::Images => Some(usage.images_reclaimable),
+            Kind::Volumes => Some(usage.volumes_reclaimable),
+            Kind::Networks => None,
+            Kind::BuildCache => Some(usage.build_cache_reclaimable),
+        }
+    }
+
+    /// `volumes` are the unused volumes this run may remove.
+    fn step(self, filters: &Filters, volumes: &[String]) -> Result<Step, String> {
+        if !policy::allows(self.policy_category()) {
+            return Ok(Step::Skip(POLICY_NOTE));
+        }
+        if self == Kind::Volumes {
+            if filters.older_than.is_some() {
+                return Ok(Step::Skip("docker can't filter volumes by age"));
+            }
+            return Ok(Step::Remove(
+                volumes
+                    .iter()
+                    .filter(|v| !filters.is_kept(v))
+                    .map(|v| (v.clone(), v.clone()))
+                    .collect(),
+            ));
+        }
+        if self == Kind::BuildCache && !filters.labels.is_empty() {
+            return Ok(Step::Skip("build cache has no labels"));
+        }
+        let named = matches!(self, Kind::Containers | Kind::Networks);
+        if named && !filters.keep.is_empty() {
+            return Ok(Step::Remove(self.candidates(filters)?));
+        }
+        let mut args = filters.label_args();
+        args.extend(filters.until_arg());
+        Ok(Step::Prune(args))
+    }
+
+    fn prune(self, filters: &[String]) -> Result<String, String> {
+        match self {
+            Kind::Containers => Docker::prune_containers(filters),
+            Kind::Images => Docker::prune_images(false, filters),
+            Kind::Volumes => Docker::prune_volumes(filters),
+            Kind::Networks => Docker::prune_networks(filters),
+            Kind::BuildCache => Docker::prune_build_cache(false, filters),
+        }
+    }
+
+    fn remove(self, ids: &[&str]) -> Result<String, RemoveError> {
+        match self {
+            Kind::Containers => Docker::remove_containers(ids),
+            Kind::Volumes => Docker::remove_volumes(ids),
+            Kind::Networks => Docker::remove_networks(ids),
+            // Dangling images and build cache have no names to keep
+            Kind::Images | Kind::BuildCache => Ok(String::new()),
+        }
+    }
+
+    /// Containers or networks a prune would remove, minus those matching `--keep`.
+    fn candidates(self, filters: &Filters) -> Result<Vec<(String, String)>, String> {
+        let now = history::now_secs();
+        let labels = filters.label_args();
+        Ok(match self {
+            Kind::Containers => Docker::list_containers(true, &labels)?
+                .into_iter()
+                .filter(|c| !c.is_running() && filters.allows(&c.names, &c.created_at, now))
+                .map(|c| (c.id, c.names))
+                .collect(),
+            Kind::Networks => {
+                let mut args = labels;
+                args.push("dangling=true".to_string());
+                Docker::list_networks(&args)?
+                    .into_iter()
+                    .filter(|n| !n.is_default() && filters.allows(&n.name, &n.created_at, now))
+                    .map(|n| (n.id, n.name))
+                    .collect()
+            }
+            Kind::Images | Kind::Volumes | Kind::BuildCache => vec![],
+        })
+    }
+}
+
+/// What the steps are about to remove. Prunes are resolved by listing what
+/// they would take; build cache has nothing worth recording.
+fn manifest(steps: &[(Kind, Step)], filters: &Filters) -> Result<Manifest, String> {
+    let mut manifest = Manifest::new("purge");
+    let now = history::now_secs();
+    let labels = filters.label_args();
+    for (kind, step) in steps {
+        match (kind, step) {
+            (Kind::Containers, Step::Prune(_)) => {
+                let containers = Docker::list_containers(true, &labels)?;
+                manifest.add_containers(
+                    containers.iter().filter(|c| {
+                        !c.is_running() && filters.allows(&c.names, &c.created_at, now)
+                    }),
+                );
+            }
+            (Kind::Containers, Step::Remove(resources)) => {
+                let containers = Docker::list_containers(true, &labels)?;
+                manifest.add_containers(
+                    containers
+                        .iter()
+                        .filter(|c| resources.iter().any(|(id, _)| *id == c.id)),
+                );
+            }
+            (Kind::Images, Step::Prune(_)) => {
+                let images = Docker::list_images(&labels)?;
+                manifest.add_images(images.iter().filter(|i| {
+                    i.is_dangling() && filters.allows(&i.display_name(), &i.created_at, now)
+                }));
+            }
+            (Kind::Networks, Step::Prune(_)) => {
+                let networks = Kind::Networks.candidates(filters)?;
+                manifest.add_networks(networks.iter().map(|(_, name)| name));
+            }
+            (Kind::Volumes, Step::Remove(resources)) => {
+                manifest.add_volumes(resources.iter().map(|(_, name)| name));
+            }
+            (Kind::Networks, Step::Remove(resources)) => {
+                manifest.add_networks(resources.iter().map(|(_, name)| name));
+            }
+            _ => {}
+        }
+    }
+    Ok(manifest)
+}
+
+/// The selected builders to prune, or why none are: like the daemon's
+/// build cache, theirs carries no labels.
+fn builder_steps(
+    selection: &builders::Selection,
+    filters: &Filters,
+) -> Result<(Vec<BuilderSummary>, Option<&'static str>), String> {
+    if selection.is_empty() {
+        return Ok((vec![], None));
+    }
+    let builders = selection.pick(builders::summaries())?;
+    if builders.is_empty() {
+        Ok((builders, None))
+    } else if !policy::allows(policy::Category::BuildCache) {
+        Ok((vec![], Some(POLICY_NOTE)))
+    } else if !filters.labels.is_empty() {
+        Ok((vec![], Some("build cache has no labels")))
+    } else {
+        Ok((builders, None))
+    }
+}
+
+/// Images held by `select` that this run deletes, releases (tagged again
+/// since) or forgets (already gone). Label filters can't be checked against
+/// held images, so none are due then.
+fn due_holds(filters: &Filters) -> Result<Vec<(Hold, Fate)>, String> {
+    if !filters.labels.is_empty()
+        || grace::list().is_empty()
+        || !policy::allows(policy::Category::Images)
+    {
+        return Ok(vec![]);
+    }
+    let images = Docker::list_images(&[])?;
+    let containers = Docker::list_containers(true, &[])?;
+    Ok(grace::review(&images, &containers, history::now_secs())
+        .into_iter()
+        .filter(|(hold, fate)| *fate != Fate::Keep && !filters.is_kept(&hold.image.name()))
+        .collect())
+}
 
-    Ok(())
+/// What to do for each kind of resource. Volumes are always removed by
+/// name; the rest are pruned unless `--keep` needs them listed one by one.
+fn plan(filters: &Filters, volumes: &[String]) -> Result<Vec<(Kind, Step)>, String> {
+    Kind::ALL
+        .into_iter()
+        .map(|kind| Ok((kind, kind.step(filters, volumes)?)))
+        .collect()
 }
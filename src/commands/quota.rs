@@ -0,0 +1,72 @@
+use crate::commands::{builders, purge};
+use crate::config::Config;
+use crate::display::{
+    confirm, format_bytes, print_info, print_quota_nag, print_text, print_warning, Styled,
+};
+use crate::docker::Docker;
+use crate::filters::Filters;
+use crate::quota;
+use crate::report::{QuotaReport, Report};
+use colored::Colorize;
+use std::io::{self, IsTerminal};
+
+/// Measure Docker's usage against `[quota]`. With `nag` (as from the shell
+/// hook) nothing is printed unless over the quota, and then, on a terminal,
+/// one keystroke starts `dockerase purge`.
+pub fn run(nag: bool, config: &Config) -> Result<Report, String> {
+    let usage = match Docker::get_disk_usage() {
+        Ok(usage) => usage,
+        // A new shell has nothing to say when docker isn't running
+        Err(_) if nag => return Ok(Report::Quota(QuotaReport::default())),
+        Err(e) => return Err(e),
+    };
+    let Some(status) = quota::status(&config.quota, &usage) else {
+        if !nag {
+            print_warning("No quota set; add max_usage under [quota] in the config");
+        }
+        return Ok(Report::Quota(QuotaReport {
+            used: usage.total_size(),
+            ..Default::default()
+        }));
+    };
+    let mut report = QuotaReport {
+        max_usage: Some(status.max_usage),
+        used: status.used,
+        exceeded: status.exceeded(),
+        ..Default::default()
+    };
+
+    if !report.exceeded {
+        if !nag {
+            print_text(&format!(
+                "{} Docker is using {} of its {} quota",
+                "✓".good().bold(),
+                format_bytes(status.used),
+                format_bytes(status.max_usage)
+            ));
+        }
+        return Ok(Report::Quota(report));
+    }
+
+    report.offenders = quota::find_offenders(&usage);
+    print_quota_nag(&status, &report.offenders);
+    if nag
+        && io::stdin().is_terminal()
+        && io::stdout().is_terminal()
+        && confirm("Clean up now?", false)?
+    {
+        print_info(&format!("$ {}", "dockerase purge".dimmed()));
+        print_text("");
+        if let Report::Cleanup(cleanup) = purge::run(
+            false,
+            config.defaults.dry_run,
+            config.defaults.named_volumes,
+            &Filters::default(),
+            &config.protect,
+            &builders::Selection::default(),
+        )? {
+            report.cleanup = Some(cleanup);
+        }
+    }
+    Ok(Report::Quota(report))
+}
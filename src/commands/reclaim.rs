@@ -0,0 +1,216 @@
+use crate::display::{
+    confirm, format_bytes, print_dry_run_header, print_info, print_success, print_text,
+    print_warning, Styled,
+};
+use crate::docker::{Docker, NOT_DESKTOP};
+use crate::read_only;
+use crate::report::{CleanupReport, ItemStatus, Report, ReportItem};
+use crate::system::get_home_dir;
+use colored::Colorize;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Where Docker Desktop keeps its VM disk image, relative to the home
+/// directory: macOS first, then Linux.
+const DISK_IMAGES: [&str; 2] = [
+    "Library/Containers/com.docker.docker/Data/vms/0/data/Docker.raw",
+    ".docker/desktop/vms/0/data/Docker.raw",
+];
+
+/// Docker Desktop's VM disk image. It is a sparse file: `apparent` is the
+/// most it may grow to, `allocated` what it takes on the host disk. Space
+/// freed inside the VM stays allocated until the VM trims its disk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskImage {
+    pub path: PathBuf,
+    pub apparent: u64,
+    pub allocated: u64,
+}
+
+impl DiskImage {
+    /// Host disk space beyond what `in_use` (Docker's own data) needs.
+    pub fn excess(&self, in_use: u64) -> u64 {
+        self.allocated.saturating_sub(in_use)
+    }
+}
+
+/// The Docker Desktop disk image on this machine, if there is one.
+pub fn find() -> Option<DiskImage> {
+    let home = get_home_dir()?;
+    DISK_IMAGES.iter().find_map(|relative| {
+        let path = home.join(relative);
+        let metadata = fs::metadata(&path).ok()?;
+        Some(DiskImage {
+            allocated: allocated(&metadata),
+            apparent: metadata.len(),
+            path,
+        })
+    })
+}
+
+#[cfg(unix)]
+fn allocated(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Return space freed inside Docker Desktop's VM to the host: trim the VM
+/// disk so the sparse disk image shrinks, and with `restart` restart
+/// Docker Desktop too, which compacts the image further.
+pub fn run(force: bool, dry_run: bool, restart: bool) -> Result<Report, String> {
+    let image = find().ok_or(
+        "No Docker Desktop disk image found; only Docker Desktop on macOS and Linux keeps one",
+    )?;
+
+    if dry_run {
+        print_dry_run_header();
+    }
+
+    let in_use = if Docker::is_available() {
+        Docker::get_disk_usage().ok().map(|u| u.total_size())
+    } else {
+        None
+    };
+
+    print_text(&format!("{}", "Docker Desktop Disk Image".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text(&format!("  {}", image.path.display().to_string().dimmed()));
+    print_text(&format!(
+        "  Apparent size:  {}",
+        format_bytes(image.apparent)
+    ));
+    print_text(&format!(
+        "  On disk:        {}",
+        format_bytes(image.allocated).caution().bold()
+    ));
+    if let Some(in_use) = in_use {
+        print_text(&format!("  Docker data:    {}", format_bytes(in_use)));
+        print_text(&format!(
+            "  Reclaimable:    up to {}",
+            format_bytes(image.excess(in_use)).good().bold()
+        ));
+    }
+    print_text("");
+
+    let mut report = CleanupReport::new("reclaim", dry_run);
+    let mut item = ReportItem::planned(
+        "disk_image",
+        &image.path.display().to_string(),
+        in_use.map(|in_use| image.excess(in_use)),
+    );
+
+    if dry_run {
+        print_info("Would trim the VM disk so the disk image shrinks");
+        if restart {
+            print_info("Would restart Docker Desktop");
+        }
+        print_warning("Dry run - no changes made");
+        report.items.push(item);
+        return Ok(Report::Cleanup(report));
+    }
+
+    if in_use.is_none() {
+        return Err("Docker Desktop must be running to trim its disk".to_string());
+    }
+    // A disk image on this machine says nothing about which daemon the
+    // active context reaches
+    if !Docker::is_desktop() {
+        return Err(NOT_DESKTOP.to_string());
+    }
+
+    if !force {
+        let prompt = if restart {
+            "Trim the VM disk (from a privileged alpine container) and restart Docker Desktop?"
+        } else {
+            "Trim the VM disk (from a privileged alpine container)?"
+        };
+        let confirmed = confirm(prompt, false)?;
+
+        if !confirmed {
+            print_warning("Aborted");
+            report.items.push(item);
+            return Ok(Report::Cleanup(report.aborted()));
+        }
+    }
+
+    print_info("Trimming the VM disk...");
+    let result = Docker::trim_vm_disk().and_then(|_| {
+        if restart {
+            print_info("Restarting Docker Desktop...");
+            restart_desktop()
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(e) = result {
+        item.status = ItemStatus::Failed;
+        item.error = Some(e.trim().to_string());
+        report.items.push(item);
+        return Ok(Report::Cleanup(report));
+    }
+
+    let after = find().map_or(image.allocated, |i| i.allocated);
+    report.space_freed = image.allocated.saturating_sub(after);
+    item.status = ItemStatus::Removed;
+    report.items.push(item);
+    print_success(&format!(
+        "Disk image now takes {} ({} returned to the host)",
+        format_bytes(after),
+        format_bytes(report.space_freed)
+    ));
+    if restart {
+        print_text(&format!(
+            "{}",
+            "  Docker Desktop may keep compacting the image for a while after it starts".dimmed()
+        ));
+    }
+
+    Ok(Report::Cleanup(report))
+}
+
+/// Quit and relaunch Docker Desktop; it compacts its disk image on start.
+fn restart_desktop() -> Result<(), String> {
+    read_only::check("restart Docker Desktop")?;
+    let steps: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[
+            ("osascript", &["-e", "quit app \"Docker\""]),
+            ("open", &["-a", "Docker"]),
+        ]
+    } else if cfg!(target_os = "linux") {
+        &[("systemctl", &["--user", "restart", "docker-desktop"])]
+    } else {
+        return Err("Restart Docker Desktop from its menu to compact the disk image".to_string());
+    };
+    for (program, args) in steps {
+        let status = Command::new(program)
+            .args(*args)
+            .status()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        if !status.success() {
+            return Err(format!("`{} {}` failed", program, args.join(" ")));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excess_over_docker_data() {
+        let image = DiskImage {
+            path: PathBuf::from("Docker.raw"),
+            apparent: 64_000_000_000,
+            allocated: 30_000_000_000,
+        };
+        assert_eq!(image.excess(12_000_000_000), 18_000_000_000);
+        assert_eq!(image.excess(40_000_000_000), 0);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::commands::logs::{self, ContainerLog};
+use crate::display::{
+    confirm, format_bytes, print_dry_run_header, print_error, print_info, print_success,
+    print_text, print_warning, Styled,
+};
+use crate::docker::Docker;
+use crate::report::{CleanupReport, ItemStatus, Report, ReportItem};
+use colored::Colorize;
+
+/// Point at rescue mode after a cleanup failed for lack of space. Rescue
+/// prunes all build cache and truncates logs regardless of filters and
+/// `[protect]`, so it only ever runs when asked for.
+pub fn print_hint() {
+    print_warning("Docker is out of disk space, so regular prunes can't run");
+    print_text(&format!(
+        "{}",
+        "  Run `dockerase rescue` to free space step by step".dimmed()
+    ));
+}
+
+/// Recovery flow for a daemon whose disk is full. Regular prunes often fail
+/// in this state because the daemon needs scratch space to run them, so this
+/// frees space in the order most likely to work: build cache first, then
+/// container logs truncated in place on the host, then stopped containers
+/// and dangling images. Steps are independent; one failing doesn't stop the
+/// rest.
+pub fn run(force: bool, dry_run: bool) -> Result<Report, String> {
+    if dry_run {
+        print_dry_run_header();
+    }
+    print_text(&format!(
+        "{}",
+        "Rescue mode: Docker is out of disk space".danger().bold()
+    ));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut report = CleanupReport::new("rescue", dry_run);
+    let mut rescue = Rescue {
+        force,
+        dry_run,
+        report: &mut report,
+    };
+
+    rescue.step(
+        ReportItem::planned("build_cache", "all build cache", None),
+        "Clear the build cache",
+        || Docker::prune_build_cache(true, &[]).map(drop),
+    )?;
+
+    // Listing can fail on a full daemon too; logs are then left to the user
+    let logs: Vec<ContainerLog> = logs::container_logs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|l| l.size > 0)
+        .collect();
+    rescue.truncate_logs(&logs)?;
+
+    rescue.step(
+        ReportItem::planned("containers", "stopped containers", None),
+        "Remove stopped containers",
+        || Docker::prune_containers(&[]).map(drop),
+    )?;
+    rescue.step(
+        ReportItem::planned("images", "dangling images", None),
+        "Remove dangling images",
+        || Docker::prune_images(false, &[]).map(drop),
+    )?;
+
+    print_recovery_guidance();
+
+    Ok(Report::Cleanup(report))
+}
+
+struct Rescue<'a> {
+    force: bool,
+    dry_run: bool,
+    report: &'a mut CleanupReport,
+}
+
+impl Rescue<'_> {
+    /// Ask before each step unless forced; a declined step is skipped.
+    fn confirm(&self, prompt: &str) -> Result<bool, String> {
+        if self.force {
+            return Ok(true);
+        }
+        confirm(prompt, true)
+    }
+
+    fn step(
+        &mut self,
+        mut item: ReportItem,
+        prompt: &str,
+        action: impl FnOnce() -> Result<(), String>,
+    ) -> Result<(), String> {
+        if self.dry_run {
+            print_info(prompt);
+        } else if !self.confirm(&format!("{prompt}?"))? {
+            item.status = ItemStatus::Skipped;
+            item.note = Some("Declined".to_string());
+        } else {
+            match action() {
+                Ok(()) => {
+                    print_success(&format!("{prompt}: done"));
+                    item.status = ItemStatus::Removed;
+                }
+                Err(e) => {
+                    print_error(&format!("{prompt}: {}", e.trim()));
+                    item.status = ItemStatus::Failed;
+                    item.error = Some(e.trim().to_string());
+                }
+            }
+        }
+        self.report.items.push(item);
+        Ok(())
+    }
+
+    /// Empty container logs in place (see `logs::truncate`).
+    fn truncate_logs(&mut self, logs: &[ContainerLog]) -> Result<(), String> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+
+        let total: u64 = logs.iter().map(|l| l.size).sum();
+        let prompt = format!(
+            "Truncate {} container log{} ({})",
+            logs.len(),
+            if logs.len() == 1 { "" } else { "s" },
+            format_bytes(total)
+        );
+        let proceed = self.dry_run || self.confirm(&format!("{prompt}?"))?;
+        if self.dry_run {
+            print_info(&prompt);
+        }
+
+        for log in logs {
+            let mut item = ReportItem::planned("container_logs", &log.container, Some(log.size));
+            if self.dry_run {
+                print_text(&format!(
+                    "    {:>10}  {}",
+                    format_bytes(log.size),
+                    log.path.display().to_string().dimmed()
+                ));
+            } else if !proceed {
+                item.status = ItemStatus::Skipped;
+                item.note = Some("Declined".to_string());
+            } else {
+                match logs::truncate(&log.path) {
+                    Ok(()) => {
+                        item.status = ItemStatus::Removed;
+                        self.report.space_freed += log.size;
+                    }
+                    Err(e) => {
+                        item.status = ItemStatus::Failed;
+                        item.error = Some(e);
+                    }
+                }
+            }
+            self.report.items.push(item);
+        }
+
+        if proceed && !self.dry_run {
+            let failed = self
+                .report
+                .items
+                .iter()
+                .filter(|i| i.category == "container_logs" && i.status == ItemStatus::Failed)
+                .count();
+            if failed == 0 {
+                print_success(&format!("{prompt}: done"));
+            } else {
+                print_warning(&format!(
+                    "{failed} log{} could not be truncated (try again with sudo)",
+                    if failed == 1 { "" } else { "s" }
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn print_recovery_guidance() {
+    print_text("");
+    print_text(&format!("{}", "If the daemon is still stuck:".bold()));
+    print_text("  • Docker Desktop: restart it, or raise the disk limit in Settings > Resources");
+    print_text(
+        "  • Linux: free space on the disk holding /var/lib/docker, then `sudo systemctl restart docker`",
+    );
+    print_text(&format!(
+        "  • Once Docker responds again, run {} for a regular cleanup",
+        "dockerase purge".cyan().bold()
+    ));
+}
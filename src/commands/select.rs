@@ -1,23 +1,66 @@
+use crate::active::{self, ActiveItem};
+use crate::cancel;
+use crate::commands::{builders, rescue, volumes};
+use crate::config::Protected;
 use crate::display::{
-    format_bytes, print_error, print_info, print_space_saved, print_success, print_warning,
+    format_bytes, multi_select, page, print_error, print_info, print_protected, print_space_saved,
+    print_success, print_text, print_warning, select, Styled,
 };
-use crate::docker::Docker;
+use crate::docker::{is_no_space_error, Docker, RemoveError, RemoveFailure};
+use crate::failures;
+use crate::grace;
+use crate::notes::Kind;
+use crate::policy;
+use crate::report::{CleanupReport, ItemStatus, Report, ReportItem};
+use crate::resources::{Container, Image, Provenance, UnusedVolumes, Volume};
+use crate::system::CacheEntry;
+use crate::undo::{self, Manifest};
 use colored::Colorize;
-use dialoguer::MultiSelect;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
+/// Files listed per container when explaining its size.
+const TOP_CHANGES: usize = 5;
+
+/// With a `grace` period, tagged images are held (see `grace`) rather than
+/// deleted.
+pub fn run(
+    force: bool,
+    dry_run: bool,
+    cves: bool,
+    grace: Option<Duration>,
+    protect: &Protected,
+) -> Result<Report, String> {
+    let result = cleanup(force, dry_run, cves, grace, protect);
+    if result.as_ref().is_err_and(|e| is_no_space_error(e)) {
+        rescue::print_hint();
+    }
+    result
+}
+
+fn cleanup(
+    force: bool,
+    dry_run: bool,
+    cves: bool,
+    grace: Option<Duration>,
+    protect: &Protected,
+) -> Result<Report, String> {
     if !Docker::is_available() {
         print_error("Docker is not available. Is Docker running?");
         return Err("Docker not available".to_string());
     }
 
     let before = Docker::get_disk_usage()?;
+    let mut report = CleanupReport::new("select", dry_run);
 
     // Gather all purgeable items
     let mut items: Vec<PurgeItem> = Vec::new();
 
     // Stopped containers
-    let containers = Docker::list_containers(true)?;
+    let containers = Docker::list_containers(true, &[])?;
     let stopped: Vec<_> = containers.iter().filter(|c| !c.is_running()).collect();
     if !stopped.is_empty() {
         items.push(PurgeItem {
@@ -26,8 +69,31 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
         });
     }
 
+    // Protected images and volumes are never offered
+    let (protected_images, images): (Vec<_>, Vec<_>) = Docker::list_images(&[])?
+        .into_iter()
+        .partition(|i| protect.protects_image(i));
+    let (protected_volumes, mut volumes): (Vec<_>, Vec<_>) = Docker::list_volumes(&[])?
+        .into_iter()
+        .partition(|v| protect.protects_volume(&v.name));
+    let protected_images: Vec<String> = protected_images.iter().map(|i| i.display_name()).collect();
+    let protected_volumes: Vec<String> = protected_volumes.into_iter().map(|v| v.name).collect();
+    for name in &protected_images {
+        report.items.push(ReportItem::skipped(
+            "images",
+            name,
+            &protect.reason(Kind::Image, name),
+        ));
+    }
+    for name in &protected_volumes {
+        report.items.push(ReportItem::skipped(
+            "volumes",
+            name,
+            &protect.reason(Kind::Volume, name),
+        ));
+    }
+
     // Dangling images
-    let images = Docker::list_images()?;
     let dangling_count = before.images_count.saturating_sub(before.images_active);
     if dangling_count > 0 || before.images_reclaimable > 0 {
         items.push(PurgeItem {
@@ -42,26 +108,82 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
 
     // All images (for more aggressive cleanup)
     if !images.is_empty() {
+        let (scope, size) = if protected_images.is_empty() {
+            ("ALL", before.images_size)
+        } else {
+            (
+                "ALL unprotected",
+                images.iter().map(Image::size_bytes).sum(),
+            )
+        };
+        let mut label = format!(
+            "{} images ({} images, {})",
+            scope,
+            images.len(),
+            format_bytes(size)
+        );
+        let local = images
+            .iter()
+            .filter(|i| !i.is_dangling() && i.provenance() == Provenance::Local)
+            .count();
+        if local > 0 {
+            label += &format!(" - {} built locally, can't be pulled back", local)
+                .caution()
+                .to_string();
+        }
+        items.push(PurgeItem {
+            label,
+            category: Category::AllImages,
+        });
+    }
+
+    // Images with critical vulnerabilities that nothing running depends on
+    let cve_counts = if cves {
+        scan_images(&images)
+    } else {
+        HashMap::new()
+    };
+    let vulnerable = vulnerable_images(&images, &containers, &cve_counts);
+    if !vulnerable.is_empty() {
+        let size: u64 = vulnerable.iter().map(|i| i.size_bytes()).sum();
         items.push(PurgeItem {
             label: format!(
-                "ALL images ({} images, {})",
-                images.len(),
-                format_bytes(before.images_size)
+                "Images with critical CVEs and no running containers ({} images, {})",
+                vulnerable.len(),
+                format_bytes(size)
             ),
-            category: Category::AllImages,
+            category: Category::VulnerableImages,
         });
     }
 
-    // Unused volumes
-    let volumes = Docker::list_volumes()?;
-    let unused_volumes = before.volumes_count.saturating_sub(before.volumes_active);
-    if unused_volumes > 0 || before.volumes_reclaimable > 0 {
+    // Anonymous volumes whose container is gone: nothing can use them again
+    let mut volume_use = Docker::unused_volumes(&[])?;
+    volume_use.orphaned.retain(|v| !protect.protects_volume(v));
+    volume_use.unmounted.retain(|v| !protect.protects_volume(v));
+    if !volume_use.orphaned.is_empty() {
         items.push(PurgeItem {
             label: format!(
-                "Unused volumes ({} volumes, {})",
-                unused_volumes,
-                format_bytes(before.volumes_reclaimable)
+                "Orphaned anonymous volumes ({} volumes)",
+                volume_use.orphaned.len()
             ),
+            category: Category::OrphanedVolumes,
+        });
+    }
+
+    // Every volume no container mounts, named or anonymous. `volume prune`
+    // would only take the anonymous ones, so they are removed by name.
+    let unused_volumes = volume_use.orphaned.len() + volume_use.unmounted.len();
+    if unused_volumes > 0 {
+        items.push(PurgeItem {
+            label: if protected_volumes.is_empty() {
+                format!(
+                    "Unused volumes ({} volumes, {})",
+                    unused_volumes,
+                    format_bytes(before.volumes_reclaimable)
+                )
+            } else {
+                format!("Unused unprotected volumes ({} volumes)", unused_volumes)
+            },
             category: Category::Volumes,
         });
     }
@@ -69,17 +191,21 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
     // All volumes
     if !volumes.is_empty() {
         items.push(PurgeItem {
-            label: format!(
-                "ALL volumes ({} volumes, {})",
-                volumes.len(),
-                format_bytes(before.volumes_size)
-            ),
+            label: if protected_volumes.is_empty() {
+                format!(
+                    "ALL volumes ({} volumes, {})",
+                    volumes.len(),
+                    format_bytes(before.volumes_size)
+                )
+            } else {
+                format!("ALL unprotected volumes ({} volumes)", volumes.len())
+            },
             category: Category::AllVolumes,
         });
     }
 
     // Unused networks
-    let networks = Docker::list_networks()?;
+    let networks = Docker::list_networks(&[])?;
     let custom_networks: Vec<_> = networks.iter().filter(|n| !n.is_default()).collect();
     if !custom_networks.is_empty() {
         items.push(PurgeItem {
@@ -96,14 +222,62 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
         });
     }
 
+    // Local buildx builders with caches of their own; --force doesn't start
+    // stopped ones
+    let buildx: Vec<_> = builders::summaries()
+        .into_iter()
+        .filter(|b| b.is_local() && (b.running || !force))
+        .collect();
+    for (i, builder) in buildx.iter().enumerate() {
+        items.push(PurgeItem {
+            label: format!("Build cache of {}", builders::describe(builder)),
+            category: Category::Builder(i),
+        });
+    }
+
+    // Individual resources, chosen in a second step. Not offered with
+    // --force, which selects every category without asking.
+    if !force {
+        if !stopped.is_empty() {
+            items.push(PurgeItem {
+                label: "Choose individual stopped containers...".to_string(),
+                category: Category::PickContainers,
+            });
+        }
+        if !images.is_empty() {
+            items.push(PurgeItem {
+                label: "Choose individual images...".to_string(),
+                category: Category::PickImages,
+            });
+        }
+        if !volumes.is_empty() {
+            items.push(PurgeItem {
+                label: "Choose individual volumes...".to_string(),
+                category: Category::PickVolumes,
+            });
+        }
+    }
+
+    // Nothing is offered from categories the policy disables
+    items.retain(|item| policy::allows(item.category.resources()));
+
     if items.is_empty() {
         print_success("Nothing to clean up. Docker is already tidy!");
-        return Ok(());
+        return Ok(Report::Cleanup(report));
+    }
+
+    print_protected("images", &protect.labels(Kind::Image, &protected_images));
+    print_protected("volumes", &protect.labels(Kind::Volume, &protected_volumes));
+    if !protected_images.is_empty() || !protected_volumes.is_empty() {
+        print_text("");
     }
 
-    println!("{}", "Select items to purge:".bold());
-    println!("{}", "(Use space to select, enter to confirm)".dimmed());
-    println!();
+    print_text(&format!("{}", "Select items to purge:".bold()));
+    print_text(&format!(
+        "{}",
+        "(Use space to select, enter to confirm)".dimmed()
+    ));
+    print_text("");
 
     let labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
 
@@ -111,93 +285,887 @@ pub fn run(force: bool, dry_run: bool) -> Result<(), String> {
         // If force, select all by default
         (0..items.len()).collect()
     } else {
-        MultiSelect::new()
-            .items(&labels)
-            .interact()
-            .map_err(|e| e.to_string())?
+        multi_select(None, &labels, &[])?
     };
 
-    if selections.is_empty() {
+    let selected_categories: Vec<Category> =
+        selections.iter().map(|&i| items[i].category).collect();
+
+    let mut picked: Vec<Resource> = Vec::new();
+    for category in &selected_categories {
+        let (title, resources) = match category {
+            Category::PickContainers => ("Select containers to remove:", container_resources()?),
+            Category::PickImages => (
+                "Select images to remove:",
+                image_resources(&images, &cve_counts),
+            ),
+            Category::PickVolumes => (
+                "Select volumes to remove:",
+                volume_resources(&volumes, &volume_use),
+            ),
+            _ => continue,
+        };
+        picked.extend(pick(title, resources)?);
+    }
+
+    // What something live depends on stays unless --include-active
+    let active = Docker::active_resources(&images)?;
+    let active_reason = |resource: &Resource| match resource.kind {
+        ResourceKind::Container => containers
+            .iter()
+            .find(|c| c.id == resource.id)
+            .and_then(|c| active.container(c)),
+        ResourceKind::Image => images
+            .iter()
+            .find(|i| i.reference() == resource.id)
+            .and_then(|i| active.image(i)),
+        ResourceKind::Volume => volumes
+            .iter()
+            .find(|v| v.name == resource.id)
+            .and_then(|v| active.volume(v)),
+    };
+    let all_volumes = selected_categories.contains(&Category::AllVolumes);
+    let mut in_use: Vec<ActiveItem> = volumes
+        .iter()
+        .filter(|_| all_volumes)
+        .filter_map(|v| ActiveItem::new("volume", &v.name, active.volume(v)))
+        .collect();
+    for resource in &picked {
+        if let Some(item) = ActiveItem::new(
+            resource.kind.category(),
+            &resource.name,
+            active_reason(resource),
+        ) {
+            if !in_use.contains(&item) {
+                in_use.push(item);
+            }
+        }
+    }
+    if !active::include(&in_use, force, dry_run)? {
+        picked.retain(|r| active_reason(r).is_none());
+        volumes.retain(|v| active.volume(v).is_none());
+        active::skip(&mut report, &in_use);
+    }
+
+    let broad: Vec<usize> = selections
+        .iter()
+        .copied()
+        .filter(|&i| !items[i].category.is_picker())
+        .collect();
+    if broad.is_empty() && picked.is_empty() {
         print_warning("Nothing selected. Aborting.");
-        return Ok(());
+        return Ok(Report::Cleanup(report.aborted()));
     }
 
-    println!();
-    println!("{}", "Selected for removal:".bold());
-    for &idx in &selections {
+    print_text("");
+    print_text(&format!("{}", "Selected for removal:".bold()));
+    for &idx in &broad {
         print_info(&items[idx].label);
+        report.items.push(ReportItem::planned(
+            items[idx].category.as_str(),
+            &items[idx].label,
+            None,
+        ));
+    }
+
+    // Picked resources remember their report item so execution can update it
+    let mut to_remove: Vec<(usize, &Resource)> = Vec::new();
+    for resource in &picked {
+        let mut item = ReportItem::planned(
+            resource.kind.category(),
+            &resource.name,
+            Some(resource.size),
+        );
+        let covered = resource.kind.covered_by(&selected_categories).or_else(|| {
+            let (in_group, group) = match resource.kind {
+                ResourceKind::Image => (
+                    vulnerable.iter().any(|i| i.reference() == resource.id),
+                    Category::VulnerableImages,
+                ),
+                ResourceKind::Volume => (
+                    volume_use.orphaned.contains(&resource.id),
+                    Category::OrphanedVolumes,
+                ),
+                ResourceKind::Container => (false, Category::Containers),
+            };
+            (in_group && selected_categories.contains(&group)).then_some(group)
+        });
+        match covered {
+            Some(broad) => {
+                print_info(&format!(
+                    "{} {}",
+                    resource.name,
+                    format!("(included in {})", broad.as_str()).dimmed()
+                ));
+                item.status = ItemStatus::Skipped;
+                item.note = Some(format!("Included in {}", broad.as_str()));
+            }
+            None => {
+                print_info(&format!(
+                    "{} ({})",
+                    resource.name,
+                    format_bytes(resource.size)
+                ));
+                if resource.kind == ResourceKind::Container {
+                    item.details = explain_container_size(&resource.id);
+                }
+                to_remove.push((report.items.len(), resource));
+            }
+        }
+        report.items.push(item);
     }
-    println!();
+    print_text("");
 
     if dry_run {
         print_warning("Dry run - no changes made");
-        return Ok(());
+        return Ok(Report::Cleanup(report));
     }
 
-    // Execute selected purges
-    let selected_categories: Vec<Category> =
-        selections.iter().map(|&i| items[i].category).collect();
-
-    // Check for conflicts (can't do both dangling and all images)
+    // Execute selected purges. Check for conflicts (can't do both dangling and all images)
     let has_all_images = selected_categories.contains(&Category::AllImages);
     let has_all_volumes = selected_categories.contains(&Category::AllVolumes);
 
+    // What each selected category is about to take, for `dockerase restore`
+    let mut manifest = Manifest::new("select");
     if selected_categories.contains(&Category::Containers) {
-        print_info("Removing stopped containers...");
-        Docker::prune_containers()?;
-        print_success("Containers removed");
+        manifest.add_containers(stopped.iter().copied());
     }
-
     if has_all_images {
-        print_info("Removing ALL images...");
-        Docker::prune_images(true)?;
-        print_success("All images removed");
+        manifest.add_images(
+            images
+                .iter()
+                .filter(|i| !containers.iter().any(|c| c.uses(i))),
+        );
     } else if selected_categories.contains(&Category::Images) {
-        print_info("Removing dangling images...");
-        Docker::prune_images(false)?;
-        print_success("Dangling images removed");
+        manifest.add_images(images.iter().filter(|i| i.is_dangling()));
+    }
+    if selected_categories.contains(&Category::VulnerableImages) {
+        manifest.add_images(vulnerable.iter().copied());
     }
-
     if has_all_volumes {
-        print_info("Removing ALL volumes...");
-        Docker::remove_all_volumes()?;
-        print_success("All volumes removed");
+        manifest.add_volumes(volumes.iter().map(|v| &v.name));
     } else if selected_categories.contains(&Category::Volumes) {
-        print_info("Removing unused volumes...");
-        Docker::prune_volumes()?;
-        print_success("Unused volumes removed");
+        manifest.add_volumes(volume_use.orphaned.iter().chain(&volume_use.unmounted));
+    } else if selected_categories.contains(&Category::OrphanedVolumes) {
+        manifest.add_volumes(&volume_use.orphaned);
     }
-
     if selected_categories.contains(&Category::Networks) {
-        print_info("Removing custom networks...");
-        Docker::prune_networks()?;
-        print_success("Networks removed");
+        manifest.add_networks(custom_networks.iter().map(|n| &n.name));
     }
-
-    if selected_categories.contains(&Category::BuildCache) {
-        print_info("Clearing build cache...");
-        Docker::prune_build_cache(true)?;
-        print_success("Build cache cleared");
+    for (_, resource) in &to_remove {
+        match resource.kind {
+            ResourceKind::Container => {
+                manifest.add_containers(containers.iter().filter(|c| c.id == resource.id))
+            }
+            ResourceKind::Image => {
+                manifest.add_images(images.iter().filter(|i| i.reference() == resource.id))
+            }
+            ResourceKind::Volume => manifest.add_volumes([&resource.id]),
+        }
     }
+    undo::record(manifest);
+
+    let execute = || -> Result<(), String> {
+        if selected_categories.contains(&Category::Containers) {
+            cancel::check()?;
+            print_info("Removing stopped containers...");
+            let pruned = Docker::prune_containers(&[]);
+            if settle_step(&mut report, &items, Category::Containers, pruned)? {
+                print_success("Containers removed");
+            }
+        }
+
+        if has_all_images {
+            cancel::check()?;
+            print_info("Removing ALL images...");
+            let removed = if protected_images.is_empty() && grace.is_none() {
+                Docker::prune_images(true, &[]).map_err(RemoveError::from)
+            } else {
+                // What `image prune -a` would take, minus the protected images
+                let unused: Vec<&Image> = images
+                    .iter()
+                    .filter(|i| !containers.iter().any(|c| c.uses(i)))
+                    .collect();
+                remove_images(&unused, grace)
+            };
+            if settle_step(&mut report, &items, Category::AllImages, removed)? {
+                print_success("All images removed");
+            }
+        } else if selected_categories.contains(&Category::Images) {
+            cancel::check()?;
+            print_info("Removing dangling images...");
+            let pruned = Docker::prune_images(false, &[]);
+            if settle_step(&mut report, &items, Category::Images, pruned)? {
+                print_success("Dangling images removed");
+            }
+        }
+
+        if selected_categories.contains(&Category::VulnerableImages) && !has_all_images {
+            cancel::check()?;
+            print_info("Removing images with critical CVEs...");
+            let removed = remove_images(&vulnerable, grace);
+            if settle_step(&mut report, &items, Category::VulnerableImages, removed)? {
+                print_success("Vulnerable images removed");
+            }
+        }
+
+        if has_all_volumes {
+            cancel::check()?;
+            print_info("Removing ALL volumes...");
+            let names: Vec<&str> = volumes.iter().map(|v| v.name.as_str()).collect();
+            let removed = Docker::remove_volumes(&names);
+            if settle_step(&mut report, &items, Category::AllVolumes, removed)? {
+                print_success("All volumes removed");
+            }
+        } else if selected_categories.contains(&Category::Volumes) {
+            cancel::check()?;
+            print_info("Removing unused volumes...");
+            let names: Vec<&str> = volume_use
+                .orphaned
+                .iter()
+                .chain(&volume_use.unmounted)
+                .map(String::as_str)
+                .collect();
+            let removed = Docker::remove_volumes(&names);
+            if settle_step(&mut report, &items, Category::Volumes, removed)? {
+                print_success("Unused volumes removed");
+            }
+        } else if selected_categories.contains(&Category::OrphanedVolumes) {
+            cancel::check()?;
+            print_info("Removing orphaned volumes...");
+            let names: Vec<&str> = volume_use.orphaned.iter().map(String::as_str).collect();
+            let removed = Docker::remove_volumes(&names);
+            if settle_step(&mut report, &items, Category::OrphanedVolumes, removed)? {
+                print_success("Orphaned volumes removed");
+            }
+        }
+
+        if selected_categories.contains(&Category::Networks) {
+            cancel::check()?;
+            print_info("Removing custom networks...");
+            let pruned = Docker::prune_networks(&[]);
+            if settle_step(&mut report, &items, Category::Networks, pruned)? {
+                print_success("Networks removed");
+            }
+        }
+
+        if selected_categories.contains(&Category::BuildCache) {
+            cancel::check()?;
+            print_info("Clearing build cache...");
+            let pruned = Docker::prune_build_cache(true, &[]);
+            if settle_step(&mut report, &items, Category::BuildCache, pruned)? {
+                print_success("Build cache cleared");
+            }
+        }
+        for category in &selected_categories {
+            if let Category::Builder(i) = *category {
+                cancel::check()?;
+                print_info(&format!(
+                    "Clearing the build cache of builder {}...",
+                    buildx[i].name
+                ));
+                let pruned = Docker::prune_builder(&buildx[i].name, true, &[]);
+                if settle_step(&mut report, &items, *category, pruned)? {
+                    print_success("Build cache cleared");
+                }
+            }
+        }
+
+        // Containers first, so images they used can be removed afterwards.
+        // Each pick is tried even when an earlier one failed; the run stops
+        // (unless continuing) once all have been.
+        let mut stop = false;
+        for (idx, resource) in to_remove {
+            cancel::check()?;
+            let held = images
+                .iter()
+                .find(|i| resource.kind == ResourceKind::Image && i.reference() == resource.id)
+                .filter(|i| grace::can_hold(i));
+            let category = report.items[idx].category.clone();
+            let holding = held.zip(grace);
+            let result = match holding {
+                Some((image, period)) => grace::hold(&[image], period).inspect(|_| {
+                    report.mark_skipped(&category, &resource.name, &grace::held_note(period))
+                }),
+                None => resource.kind.remove(&resource.id),
+            };
+            let result =
+                result.map_err(|e| RemoveError::Failed(vec![RemoveFailure::new(&resource.id, &e)]));
+            let target = [(resource.id.as_str(), resource.name.as_str())];
+            match failures::record(&mut report, &category, &target, result)? {
+                None if holding.is_none() => print_success(&format!("Removed {}", resource.name)),
+                None => {}
+                Some(stops) => stop |= stops,
+            }
+        }
+        failures::stop_unless_continuing(stop)
+    };
+    let executed = execute();
+    failures::settle(&mut report, executed)?;
 
     let after = Docker::get_disk_usage()?;
     print_space_saved(before.total_size(), after.total_size());
 
-    Ok(())
+    report.mark_all_removed();
+    report.measure_freed(&before, &after);
+
+    Ok(Report::Cleanup(report))
+}
+
+/// Force-remove `images`, tagged ones by tag so a protected tag of the same
+/// image stays; with a `grace` period the tagged ones are held instead.
+fn remove_images(images: &[&Image], grace: Option<Duration>) -> Result<String, RemoveError> {
+    let (held, removed): (Vec<&Image>, Vec<&Image>) = match grace {
+        Some(_) => images.iter().partition(|i| grace::can_hold(i)),
+        None => (vec![], images.to_vec()),
+    };
+    if let Some(period) = grace {
+        grace::hold(&held, period)?;
+    }
+    let references: Vec<String> = removed.iter().map(|i| i.reference()).collect();
+    let references: Vec<&str> = references.iter().map(String::as_str).collect();
+    Docker::remove_images(&references)
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Category {
     Containers,
     Images,
     AllImages,
+    VulnerableImages,
+    OrphanedVolumes,
     Volumes,
     AllVolumes,
     Networks,
     BuildCache,
+    /// The cache of one of the buildx builders, by index
+    Builder(usize),
+    PickContainers,
+    PickImages,
+    PickVolumes,
+}
+
+impl Category {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Category::Containers => "containers",
+            Category::Images => "images",
+            Category::AllImages => "all_images",
+            Category::VulnerableImages => "vulnerable_images",
+            Category::OrphanedVolumes => "orphaned_volumes",
+            Category::Volumes => "volumes",
+            Category::AllVolumes => "all_volumes",
+            Category::Networks => "networks",
+            Category::BuildCache | Category::Builder(_) => "build_cache",
+            Category::PickContainers => "containers",
+            Category::PickImages => "images",
+            Category::PickVolumes => "volumes",
+        }
+    }
+
+    /// The kind of resource this removes, as the policy names it.
+    fn resources(&self) -> policy::Category {
+        match self {
+            Category::Containers | Category::PickContainers => policy::Category::Containers,
+            Category::Images
+            | Category::AllImages
+            | Category::VulnerableImages
+            | Category::PickImages => policy::Category::Images,
+            Category::OrphanedVolumes
+            | Category::Volumes
+            | Category::AllVolumes
+            | Category::PickVolumes => policy::Category::Volumes,
+            Category::Networks => policy::Category::Networks,
+            Category::BuildCache | Category::Builder(_) => policy::Category::BuildCache,
+        }
+    }
+
+    /// Opens a second-level picker instead of removing anything itself.
+    fn is_picker(&self) -> bool {
+        matches!(
+            self,
+            Category::PickContainers | Category::PickImages | Category::PickVolumes
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ResourceKind {
+    Container,
+    Image,
+    Volume,
+}
+
+impl ResourceKind {
+    fn category(&self) -> &'static str {
+        match self {
+            ResourceKind::Container => "container",
+            ResourceKind::Image => "image",
+            ResourceKind::Volume => "volume",
+        }
+    }
+
+    /// The selected broad category that already removes this kind of
+    /// resource, making an individual removal redundant.
+    fn covered_by(&self, selected: &[Category]) -> Option<Category> {
+        let broad = match self {
+            ResourceKind::Container => Category::Containers,
+            ResourceKind::Image => Category::AllImages,
+            ResourceKind::Volume => Category::AllVolumes,
+        };
+        selected.contains(&broad).then_some(broad)
+    }
+
+    fn remove(&self, id: &str) -> Result<String, String> {
+        match self {
+            ResourceKind::Container => Docker::remove_container(id),
+            ResourceKind::Image => Docker::remove_image(id),
+            ResourceKind::Volume => Docker::remove_volume(id),
+        }
+    }
+
+    /// What can be looked at before deciding to remove one.
+    fn views(&self) -> &'static [View] {
+        match self {
+            ResourceKind::Container => &[View::Inspect, View::Logs],
+            ResourceKind::Image => &[View::Inspect, View::History],
+            ResourceKind::Volume => &[View::Inspect],
+        }
+    }
+}
+
+/// Details of a resource shown in the pager from a picker.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum View {
+    Inspect,
+    Logs,
+    History,
+}
+
+impl View {
+    fn title(&self) -> &'static str {
+        match self {
+            View::Inspect => "docker inspect",
+            View::Logs => "Last 200 log lines",
+            View::History => "Image history (layers)",
+        }
+    }
+
+    fn show(&self, resource: &Resource) -> Result<String, String> {
+        match self {
+            View::Inspect => Docker::inspect(&resource.id, resource.kind == ResourceKind::Volume),
+            View::Logs => Docker::container_logs_tail(&resource.id, 200),
+            View::History => Docker::image_history(&resource.id),
+        }
+    }
+}
+
+/// A single container, image or volume offered in a second-level picker.
+struct Resource {
+    kind: ResourceKind,
+    /// What the docker CLI removes it by: container ID, image tag (or ID when
+    /// untagged) or volume name
+    id: String,
+    name: String,
+    size: u64,
+    /// Shown after the name in the picker, e.g. a CVE count
+    note: Option<String>,
+    /// Can't be had back once removed, such as an image built here and
+    /// never pushed; listed after the rest
+    irreplaceable: bool,
+}
+
+fn container_resources() -> Result<Vec<Resource>, String> {
+    Ok(Docker::list_containers_with_size()?
+        .into_iter()
+        .filter(|c| !c.is_running())
+        .map(|c| Resource {
+            kind: ResourceKind::Container,
+            size: c.size_bytes(),
+            id: c.id,
+            name: c.names,
+            note: None,
+            irreplaceable: false,
+        })
+        .collect())
+}
+
+fn image_resources(images: &[Image], cve_counts: &HashMap<String, usize>) -> Vec<Resource> {
+    images
+        .iter()
+        .map(|i| Resource {
+            kind: ResourceKind::Image,
+            id: i.reference(),
+            name: i.display_name(),
+            size: i.size_bytes(),
+            note: Some(image_note(i, cve_counts.get(&i.id).copied().unwrap_or(0))),
+            irreplaceable: i.provenance() == Provenance::Local,
+        })
+        .collect()
+}
+
+fn volume_resources(volumes: &[Volume], unused: &UnusedVolumes) -> Vec<Resource> {
+    volumes::summaries(volumes, unused)
+        .into_iter()
+        .map(|v| Resource {
+            kind: ResourceKind::Volume,
+            id: v.name.clone(),
+            size: v.size.unwrap_or(0),
+            note: Some(
+                format!(
+                    "[{}, created {}, used by {}]",
+                    v.state,
+                    volumes::created_date(&v),
+                    volumes::users(&v)
+                )
+                .dimmed()
+                .to_string(),
+            ),
+            irreplaceable: false,
+            name: v.name,
+        })
+        .collect()
+}
+
+/// Where an image came from and, when scanned, its critical CVEs.
+fn image_note(image: &Image, critical_cves: usize) -> String {
+    let mut note = match image.provenance() {
+        Provenance::Registry => format!("[{}]", Provenance::Registry.label())
+            .dimmed()
+            .to_string(),
+        Provenance::Local => "[built locally, can't be pulled back]"
+            .caution()
+            .to_string(),
+    };
+    if critical_cves > 0 {
+        note = format!("{} {}", critical_cves_label(critical_cves).danger(), note);
+    }
+    note
+}
+
+/// Let the user check individual resources, largest first, with those that
+/// can't be had back last.
+fn pick(title: &str, mut resources: Vec<Resource>) -> Result<Vec<Resource>, String> {
+    resources.sort_by_key(|r| (r.irreplaceable, Reverse(r.size)));
+    let labels: Vec<String> = resources
+        .iter()
+        .map(|r| match &r.note {
+            Some(note) => format!("{} ({}) {}", r.name, format_bytes(r.size), note),
+            None => format!("{} ({})", r.name, format_bytes(r.size)),
+        })
+        .collect();
+
+    print_text("");
+    print_text(&format!("{}", title.bold()));
+    look_closer(&resources, &labels)?;
+    let chosen = multi_select(None, &labels, &[])?;
+
+    let mut resources: Vec<Option<Resource>> = resources.into_iter().map(Some).collect();
+    Ok(chosen
+        .into_iter()
+        .filter_map(|i| resources[i].take())
+        .collect())
+}
+
+/// Until Esc, let the user open the details of any resource in a pager,
+/// to check what it is before choosing what to remove.
+fn look_closer(resources: &[Resource], labels: &[String]) -> Result<(), String> {
+    let mut highlighted = 0;
+    loop {
+        let Some(i) = select(
+            "Enter: inspect an item first · Esc: choose what to remove",
+            labels,
+            highlighted,
+        )?
+        else {
+            return Ok(());
+        };
+        highlighted = i;
+        let resource = &resources[i];
+        let views = resource.kind.views();
+        let view = match views {
+            [only] => Some(*only),
+            _ => {
+                let titles: Vec<&str> = views.iter().map(View::title).collect();
+                select(&format!("Show for {}", resource.name), &titles, 0)?.map(|v| views[v])
+            }
+        };
+        if let Some(view) = view {
+            match view.show(resource) {
+                Ok(details) => page(&details),
+                Err(e) => print_error(e.trim()),
+            }
+        }
+    }
+}
+
+/// Show what fills a container's writable layer, so the cause can be fixed
+/// rather than the container just recreated and grown again. Returns the
+/// largest changed files for the report.
+fn explain_container_size(id: &str) -> Vec<CacheEntry> {
+    let Ok(paths) = Docker::container_changes(id) else {
+        return vec![];
+    };
+    let Some(layer) = Docker::container_layer_dir(id) else {
+        // The layer lives in a VM (Docker Desktop): name files, not sizes
+        if !paths.is_empty() {
+            print_text(&format!(
+                "    {}",
+                format!(
+                    "{} changed paths, e.g. {}",
+                    paths.len(),
+                    paths.last().unwrap().display()
+                )
+                .dimmed()
+            ));
+        }
+        return vec![];
+    };
+
+    let entries = largest_changes(&layer, paths, TOP_CHANGES);
+    for entry in &entries {
+        print_text(&format!(
+            "    {:>10}  {}",
+            format_bytes(entry.size),
+            entry.path.display().to_string().dimmed()
+        ));
+    }
+    if let Some(hint) = root_cause_hint(&entries) {
+        print_text(&format!("    {} {}", "→".info(), hint));
+    }
+    entries
+}
+
+/// The `top` largest files among `paths`, sized inside the layer directory.
+fn largest_changes(layer: &Path, paths: Vec<PathBuf>, top: usize) -> Vec<CacheEntry> {
+    let mut entries: Vec<CacheEntry> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let meta = fs::symlink_metadata(layer.join(path.strip_prefix("/").ok()?)).ok()?;
+            meta.is_file().then_some(CacheEntry {
+                size: meta.len(),
+                path,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|e| Reverse(e.size));
+    entries.truncate(top);
+    entries
+}
+
+/// A fix for the usual reasons a container grows, judged by its largest file.
+fn root_cause_hint(entries: &[CacheEntry]) -> Option<&'static str> {
+    let path = &entries.first()?.path;
+    if path.extension().is_some_and(|ext| ext == "log") || path.starts_with("/var/log") {
+        Some("Logs written inside the container: log to stdout or mount a volume for them")
+    } else if path.starts_with("/tmp") || path.starts_with("/var/tmp") {
+        Some("Temporary files pile up in the layer: mount a tmpfs at that path")
+    } else if path.starts_with("/root/.cache") || path.starts_with("/var/cache") {
+        Some("A cache fills the layer: mount a volume for it or clear it in the image build")
+    } else {
+        None
+    }
+}
+
+/// Critical CVE counts by image ID, from `docker scout`. Dangling images are
+/// left out: the dangling images option already removes them.
+fn scan_images(images: &[Image]) -> HashMap<String, usize> {
+    if !Docker::scout_available() {
+        print_warning("docker scout is not installed; skipping the vulnerability scan");
+        print_text("");
+        return HashMap::new();
+    }
+
+    let tagged: Vec<&Image> = images.iter().filter(|i| !i.is_dangling()).collect();
+    print_info(&format!(
+        "Scanning {} images for critical vulnerabilities...",
+        tagged.len()
+    ));
+    let mut counts = HashMap::new();
+    for image in tagged {
+        let name = image.display_name();
+        match Docker::critical_cves(&name) {
+            Ok(count) => {
+                if count > 0 {
+                    print_warning(&format!("{}: {}", name, critical_cves_label(count)));
+                }
+                counts.insert(image.id.clone(), count);
+            }
+            Err(e) => print_warning(&format!("Could not scan {}: {}", name, e.trim())),
+        }
+    }
+    print_text("");
+    counts
+}
+
+/// Images with at least one critical CVE that no running container uses.
+fn vulnerable_images<'a>(
+    images: &'a [Image],
+    containers: &[Container],
+    cve_counts: &HashMap<String, usize>,
+) -> Vec<&'a Image> {
+    images
+        .iter()
+        .filter(|i| cve_counts.get(&i.id).is_some_and(|&n| n > 0))
+        .filter(|i| !containers.iter().any(|c| c.is_running() && c.uses(i)))
+        .collect()
+}
+
+fn critical_cves_label(count: usize) -> String {
+    format!(
+        "{} critical CVE{}",
+        count,
+        if count == 1 { "" } else { "s" }
+    )
 }
 
 struct PurgeItem {
     label: String,
     category: Category,
 }
+
+/// Record how the step for what was selected as `category` went. Returns
+/// whether everything went, or an error to stop the run.
+fn settle_step(
+    report: &mut CleanupReport,
+    items: &[PurgeItem],
+    category: Category,
+    result: Result<String, impl Into<RemoveError>>,
+) -> Result<bool, String> {
+    let label = items
+        .iter()
+        .find(|i| i.category == category)
+        .map_or("", |i| i.label.as_str());
+    failures::step(report, category.as_str(), label, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broad_selection_covers_individual_resources() {
+        let selected = [Category::Containers, Category::Images];
+        assert_eq!(
+            ResourceKind::Container.covered_by(&selected),
+            Some(Category::Containers)
+        );
+        // Dangling-only pruning doesn't cover a tagged image
+        assert_eq!(ResourceKind::Image.covered_by(&selected), None);
+        assert_eq!(
+            ResourceKind::Image.covered_by(&[Category::AllImages]),
+            Some(Category::AllImages)
+        );
+        assert_eq!(ResourceKind::Volume.covered_by(&selected), None);
+    }
+
+    fn image(id: &str, repository: &str) -> Image {
+        Image {
+            id: id.to_string(),
+            repository: repository.to_string(),
+            tag: "latest".to_string(),
+            size: "10MB".to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        }
+    }
+
+    fn container(image: &str, state: &str) -> Container {
+        Container {
+            id: "c0ffee".to_string(),
+            names: "app".to_string(),
+            image: image.to_string(),
+            state: state.to_string(),
+            status: String::new(),
+            size: String::new(),
+            created_at: String::new(),
+            labels: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_vulnerable_images_excludes_running_and_clean_images() {
+        let images = [
+            image("sha256:aaa", "postgres"),
+            image("sha256:bbb", "alpine"),
+            image("sha256:ccc", "nginx"),
+        ];
+        let containers = [
+            container("postgres", "running"),
+            container("alpine", "exited"),
+        ];
+        let counts = HashMap::from([
+            ("sha256:aaa".to_string(), 3),
+            ("sha256:bbb".to_string(), 1),
+            ("sha256:ccc".to_string(), 0),
+        ]);
+        let names: Vec<String> = vulnerable_images(&images, &containers, &counts)
+            .iter()
+            .map(|i| i.display_name())
+            .collect();
+        // A stopped container doesn't protect its image
+        assert_eq!(names, vec!["alpine:latest"]);
+    }
+
+    #[test]
+    fn test_largest_changes_sizes_files_in_layer() {
+        let layer = tempfile::tempdir().unwrap();
+        fs::create_dir_all(layer.path().join("var/log")).unwrap();
+        fs::write(layer.path().join("var/log/app.log"), vec![0u8; 300]).unwrap();
+        fs::write(layer.path().join("var/log/small.log"), vec![0u8; 10]).unwrap();
+
+        let paths = [
+            "/var",
+            "/var/log",
+            "/var/log/small.log",
+            "/var/log/app.log",
+            "/gone",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        let entries = largest_changes(layer.path(), paths, 5);
+        let sizes: Vec<u64> = entries.iter().map(|e| e.size).collect();
+        // Directories and paths missing from the layer are left out
+        assert_eq!(sizes, vec![300, 10]);
+        assert_eq!(entries[0].path, Path::new("/var/log/app.log"));
+        assert!(root_cause_hint(&entries).unwrap().starts_with("Logs"));
+    }
+
+    #[test]
+    fn test_root_cause_hint() {
+        let hint = |path: &str| {
+            root_cause_hint(&[CacheEntry {
+                path: path.into(),
+                size: 1,
+            }])
+        };
+        assert!(hint("/tmp/upload-1").unwrap().contains("tmpfs"));
+        assert!(hint("/var/cache/apt/pkgcache.bin")
+            .unwrap()
+            .contains("cache"));
+        assert_eq!(hint("/app/data.db"), None);
+        assert_eq!(root_cause_hint(&[]), None);
+    }
+
+    #[test]
+    fn test_picker_categories() {
+        assert!(Category::PickImages.is_picker());
+        assert!(!Category::AllImages.is_picker());
+    }
+
+    #[test]
+    fn test_views_fit_the_resource_kind() {
+        for kind in [
+            ResourceKind::Container,
+            ResourceKind::Image,
+            ResourceKind::Volume,
+        ] {
+            assert_eq!(kind.views()[0], View::Inspect);
+        }
+        // Only containers have logs, and only images layers
+        assert!(ResourceKind::Container.views().contains(&View::Logs));
+        assert!(!ResourceKind::Image.views().contains(&View::Logs));
+        assert!(ResourceKind::Image.views().contains(&View::History));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::display::{format_bytes, print_success, print_text, Styled};
+use crate::report::Report;
+use crate::stats::{self, StatsReport};
+use crate::units::format_timestamp;
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+
+/// Show how much past cleanups freed: in total, per category and per month.
+pub fn run() -> Result<Report, String> {
+    let summary = StatsReport::new(&stats::load());
+    if summary.runs == 0 {
+        print_success("No cleanups recorded yet.");
+        return Ok(Report::Stats(summary));
+    }
+
+    print_text(&format!("{}", "Cleanup Stats".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut headline = format!(
+        "Freed {} over {} {}",
+        format_bytes(summary.space_freed).good().bold(),
+        summary.runs,
+        if summary.runs == 1 { "run" } else { "runs" }
+    );
+    if let Some(first) = summary.first_run {
+        headline += &format!(" since {}", &format_timestamp(first)[..10]);
+    }
+    if let Some(offender) = summary.biggest_offender() {
+        headline += &format!(
+            ", biggest offender: {} ({})",
+            label(&offender.category).bold(),
+            format_bytes(offender.space_freed)
+        );
+    }
+    print_text(&headline);
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["CATEGORY", "FREED", "SHARE", "RUNS"]);
+    for category in &summary.categories {
+        table.add_row(vec![
+            label(&category.category),
+            format_bytes(category.space_freed),
+            share(category.space_freed, summary.space_freed),
+            category.runs.to_string(),
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+
+    let mut timeline = Table::new();
+    timeline.load_preset(UTF8_BORDERS_ONLY);
+    timeline.set_header(vec!["MONTH", "RUNS", "FREED"]);
+    for period in &summary.timeline {
+        timeline.add_row(vec![
+            period.month.clone(),
+            period.runs.to_string(),
+            format_bytes(period.space_freed),
+        ]);
+    }
+    print_text(&format!("{timeline}"));
+
+    Ok(Report::Stats(summary))
+}
+
+/// `build_cache` as `build cache`.
+fn label(category: &str) -> String {
+    category.replace('_', " ")
+}
+
+fn share(size: u64, total: u64) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{:.0}%", size as f64 * 100.0 / total as f64)
+}
@@ -1,74 +1,241 @@
-use crate::display::{format_bytes, print_error, print_info, print_success, print_warning};
-use crate::system::{discover_caches, purge_cache, CacheInfo};
+use crate::artifacts::{self, Artifact};
+use crate::cancel;
+use crate::cargo::{self, Redundant, TargetAdvice};
+use crate::config::Config;
+use crate::display::{
+    confirm, format_bytes, multi_select, print_cancelled, print_dry_run_header, print_error,
+    print_info, print_protected, print_success, print_text, print_warning, ProgressBar, Styled,
+};
+use crate::notes::Kind;
+use crate::policy;
+use crate::report::{CacheListReport, CleanupReport, ItemStatus, Report, ReportItem};
+use crate::system::{
+    containing_cache, discover_caches, get_home_dir, largest_entries, outermost,
+    purge_caches_parallel, total_size, CacheInfo, PurgeGuard,
+};
+use crate::trash::Trash;
+use crate::undo::{self, Manifest};
+use crate::units::format_age;
 use colored::Colorize;
 use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
-use dialoguer::MultiSelect;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 
-pub fn list() -> Result<(), String> {
-    let caches = discover_caches();
+pub fn list(config: &Config) -> Result<Report, String> {
+    let caches = discover_caches(config);
 
     if caches.is_empty() {
         print_success("No purgeable caches found. System is clean!");
-        return Ok(());
+        return Ok(Report::Caches(CacheListReport::new(caches)));
     }
 
-    println!("{}", "System Caches".bold().cyan());
-    println!("{}", "═".repeat(50).dimmed());
-    println!();
+    print_text(&format!("{}", "System Caches".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
 
     let mut table = Table::new();
     table.load_preset(UTF8_BORDERS_ONLY);
     table.set_header(vec!["CACHE", "SIZE", "PATH"]);
 
-    let mut total_size = 0u64;
-
-    for cache in &caches {
-        total_size += cache.size;
+    for cache in caches.iter().filter(|c| c.is_purgeable()) {
+        let name = config.protect.label(Kind::Cache, &cache.name);
+        let name = match &cache.nested_in {
+            Some(parent) => format!("{} (inside {})", name, parent),
+            None => name,
+        };
         table.add_row(vec![
-            cache.name.clone(),
+            name,
             format_bytes(cache.size),
             cache.path.display().to_string(),
         ]);
     }
 
-    println!("{table}");
-    println!();
-    println!(
+    let refs: Vec<&CacheInfo> = caches.iter().collect();
+    let total_size = total_size(&refs);
+
+    print_text(&format!("{table}"));
+    print_text("");
+    print_text(&format!(
         "{} {}",
         "Total Purgeable:".bold(),
-        format_bytes(total_size).green().bold()
-    );
-    println!();
-    println!("{}", "─".repeat(50).dimmed());
-    println!(
+        format_bytes(total_size).good().bold()
+    ));
+    print_report_only(&caches);
+    print_text("");
+    print_text(&format!("{}", "─".repeat(50).dimmed()));
+    print_text(&format!(
         "Run {} to interactively select caches to purge",
         "dockerase system select".cyan().bold()
-    );
-    println!(
+    ));
+    print_text(&format!(
         "Run {} to purge all caches",
         "dockerase system purge".cyan().bold()
-    );
+    ));
+
+    Ok(Report::Caches(CacheListReport::new(caches)))
+}
+
+/// Data dockerase measures but leaves to the owning app, so users can see
+/// where their disk went.
+fn print_report_only(caches: &[CacheInfo]) {
+    let report_only: Vec<&CacheInfo> = caches.iter().filter(|c| !c.is_purgeable()).collect();
+    if report_only.is_empty() {
+        return;
+    }
+
+    print_text("");
+    print_text(&format!("{}", "Managed by apps (not purged):".bold()));
+    for cache in report_only {
+        print_text(&format!(
+            "  {:<22} {:>10}  {}",
+            cache.name,
+            format_bytes(cache.size),
+            cache.report_only.as_deref().unwrap_or_default().dimmed()
+        ));
+    }
+}
+
+/// Upper bound on parallel cache purges; beyond this the disk, not the CPU,
+/// is the bottleneck.
+const MAX_DEFAULT_JOBS: usize = 4;
+
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_DEFAULT_JOBS)
+}
 
-    Ok(())
+pub struct PurgeOptions {
+    pub force: bool,
+    pub dry_run: bool,
+    pub interactive: bool,
+    pub jobs: usize,
+    /// List the `top` largest entries of each selected cache
+    pub detail: bool,
+    pub top: usize,
+    /// Only delete entries not used for this long, keeping the rest
+    pub older_than: Option<Duration>,
+    /// Move files to the trash instead of deleting them
+    pub trash: bool,
 }
 
-pub fn purge(force: bool, dry_run: bool, interactive: bool) -> Result<(), String> {
-    let caches = discover_caches();
+pub fn purge(opts: &PurgeOptions, config: &Config) -> Result<Report, String> {
+    let PurgeOptions {
+        force,
+        dry_run,
+        interactive,
+        jobs,
+        detail,
+        top,
+        older_than,
+        trash,
+    } = *opts;
+    policy::check(policy::Category::SystemCaches, "purge system caches")?;
+    let trash = if trash { Some(Trash::open()?) } else { None };
+    let (protected, caches): (Vec<CacheInfo>, Vec<CacheInfo>) = discover_caches(config)
+        .into_iter()
+        .filter(CacheInfo::is_purgeable)
+        .partition(|c| config.protect.protects_cache(&c.name));
+    // Plugins and tools free their caches in place, and most only whole
+    let (in_place, caches): (Vec<CacheInfo>, Vec<CacheInfo>) = caches.into_iter().partition(|c| {
+        (older_than.is_some() && !c.can_trim()) || (trash.is_some() && c.is_freed_in_place())
+    });
+
+    let caches: Vec<CacheInfo> = match older_than {
+        Some(age) => caches.into_iter().map(|c| c.older_than(age)).collect(),
+        None => caches,
+    };
+    // Going through the trash means not emptying it
+    let (trash_caches, caches): (Vec<CacheInfo>, Vec<CacheInfo>) = match &trash {
+        Some(trash) => caches
+            .into_iter()
+            .partition(|c| trash.overlaps(&c.real_path)),
+        None => (vec![], caches),
+    };
+    let caches: Vec<CacheInfo> = caches
+        .into_iter()
+        .map(|mut c| {
+            c.trash = trash.clone();
+            c
+        })
+        .collect();
+    // Purging a cache that holds a protected one would take it along
+    let (holding, caches): (Vec<CacheInfo>, Vec<CacheInfo>) = caches.into_iter().partition(|c| {
+        protected
+            .iter()
+            .any(|p| p.real_path != c.real_path && p.real_path.starts_with(&c.real_path))
+    });
+    let command = if interactive {
+        "system select"
+    } else {
+        "system purge"
+    };
+    let mut report = CleanupReport::new(command, dry_run);
+
+    let names: Vec<String> = protected
+        .iter()
+        .chain(&holding)
+        .map(|c| c.name.clone())
+        .collect();
+    print_protected("caches", &config.protect.labels(Kind::Cache, &names));
+    for cache in &protected {
+        report.items.push(ReportItem::skipped(
+            "cache",
+            &cache.name,
+            &config.protect.reason(Kind::Cache, &cache.name),
+        ));
+    }
+    for cache in &holding {
+        report.items.push(ReportItem::skipped(
+            "cache",
+            &cache.name,
+            "contains a protected cache",
+        ));
+    }
+    for cache in &in_place {
+        let note = if trash.is_some() && cache.is_freed_in_place() {
+            "its plugin or tool frees it in place, not to the trash"
+        } else {
+            "its plugin or tool can't trim by age"
+        };
+        report
+            .items
+            .push(ReportItem::skipped("cache", &cache.name, note));
+    }
+    for cache in &trash_caches {
+        report.items.push(ReportItem::skipped(
+            "cache",
+            &cache.name,
+            "the trash is kept with --trash",
+        ));
+    }
 
     if caches.is_empty() {
         print_success("No purgeable caches found. System is clean!");
-        return Ok(());
+        return Ok(Report::Cleanup(report));
     }
 
     if dry_run {
-        println!("{}", "[DRY RUN] No changes will be made".yellow().bold());
-        println!();
+        print_dry_run_header();
+    }
+    if let Some(age) = older_than {
+        print_info(&format!(
+            "Only entries unused for {}+ are removed; sizes count just those",
+            format_age(age)
+        ));
     }
 
     let selections: Vec<usize> = if interactive {
-        println!("{}", "Select caches to purge:".bold());
-        println!("{}", "(Use space to select, enter to confirm)".dimmed());
-        println!();
+        print_text(&format!("{}", "Select caches to purge:".bold()));
+        print_text(&format!(
+            "{}",
+            "(Use space to select, enter to confirm)".dimmed()
+        ));
+        print_text("");
 
         let labels: Vec<String> = caches
             .iter()
@@ -80,29 +247,25 @@ pub fn purge(force: bool, dry_run: bool, interactive: bool) -> Result<(), String
         if force {
             (0..caches.len()).collect()
         } else {
-            MultiSelect::new()
-                .items(&label_refs)
-                .interact()
-                .map_err(|e| e.to_string())?
+            multi_select(None, &label_refs, &[])?
         }
     } else {
         // Non-interactive: select all
         if !force && !dry_run {
-            use dialoguer::Confirm;
-            let total: u64 = caches.iter().map(|c| c.size).sum();
-            let confirm = Confirm::new()
-                .with_prompt(format!(
+            let refs: Vec<&CacheInfo> = caches.iter().collect();
+            let total = total_size(&refs);
+            let confirmed = confirm(
+                &format!(
                     "Purge all {} caches ({})? This cannot be undone",
                     caches.len(),
                     format_bytes(total)
-                ))
-                .default(false)
-                .interact()
-                .map_err(|e| e.to_string())?;
+                ),
+                false,
+            )?;
 
-            if !confirm {
+            if !confirmed {
                 print_warning("Aborted");
-                return Ok(());
+                return Ok(Report::Cleanup(report.aborted()));
             }
         }
         (0..caches.len()).collect()
@@ -110,51 +273,499 @@ pub fn purge(force: bool, dry_run: bool, interactive: bool) -> Result<(), String
 
     if selections.is_empty() {
         print_warning("Nothing selected. Aborting.");
-        return Ok(());
+        return Ok(Report::Cleanup(report.aborted()));
     }
 
-    println!();
-    println!("{}", "Selected for removal:".bold());
-    let selected_caches: Vec<&CacheInfo> = selections.iter().map(|&i| &caches[i]).collect();
+    print_text("");
+    print_text(&format!("{}", "Selected for removal:".bold()));
+    let selected: Vec<&CacheInfo> = selections.iter().map(|&i| &caches[i]).collect();
+    let selected_caches = outermost(&selected);
+
+    for cache in &selected {
+        if let Some(parent) = containing_cache(cache, &selected) {
+            let note = format!("Included in {}", parent.name);
+            print_info(&format!(
+                "{} ({}) - {}",
+                cache.name,
+                format_bytes(cache.size),
+                note.dimmed()
+            ));
+            let mut item = ReportItem::planned("cache", &cache.name, Some(cache.size));
+            item.status = ItemStatus::Skipped;
+            item.note = Some(note);
+            report.items.push(item);
+            continue;
+        }
 
-    for cache in &selected_caches {
         print_info(&format!(
             "{} ({}) - {}",
             cache.name,
             format_bytes(cache.size),
             cache.description
         ));
+        let mut item = ReportItem::planned("cache", &cache.name, Some(cache.size));
+        if detail {
+            item.details = largest_entries(&cache.real_path, top);
+            for entry in &item.details {
+                print_text(&format!(
+                    "    {:>10}  {}",
+                    format_bytes(entry.size),
+                    entry.path.display().to_string().dimmed()
+                ));
+            }
+        }
+        report.items.push(item);
     }
-    println!();
+    print_text("");
 
     if dry_run {
         print_warning("Dry run - no changes made");
-        return Ok(());
+        return Ok(Report::Cleanup(report));
     }
 
     let mut total_freed = 0u64;
+    let mut total_trashed = 0u64;
 
-    for cache in selected_caches {
-        print_info(&format!("Removing {}...", cache.name));
-        match purge_cache(cache) {
-            Ok(size) => {
-                total_freed += size;
-                print_success(&format!("{} cleared", cache.name));
+    print_info(&format!(
+        "{} {} caches ({} at a time)...",
+        if trash.is_some() {
+            "Moving to the trash:"
+        } else {
+            "Removing"
+        },
+        selected_caches.len(),
+        jobs.max(1)
+    ));
+    let guard = PurgeGuard::from_config(config)?;
+    let mut manifest = Manifest::new(command);
+    for cache in &selected_caches {
+        manifest.add_cache(&cache.path);
+    }
+    undo::record(manifest);
+    // One bar for all caches, advanced by what each worker has freed so far
+    let bar = ProgressBar::bytes(
+        "Clearing caches",
+        selected_caches.iter().map(|c| c.size).sum(),
+    );
+    let freed: Mutex<HashMap<PathBuf, u64>> = Mutex::new(HashMap::new());
+    let results = purge_caches_parallel(
+        &selected_caches,
+        jobs,
+        &guard,
+        cancel::flag(),
+        |cache, progress| {
+            if let Ok(mut freed) = freed.lock() {
+                freed.insert(cache.path.clone(), progress.bytes_freed);
+                bar.set(freed.values().sum());
+            }
+        },
+        |cache, result| match result {
+            Ok(p) if p.cancelled => print_warning(&format!(
+                "{} cancelled after freeing {}",
+                cache.name,
+                format_bytes(p.bytes_freed)
+            )),
+            Ok(p) if !p.skipped.is_empty() => print_warning(&format!(
+                "{} cleared, {} in-use or protected item{} skipped",
+                cache.name,
+                p.skipped.len(),
+                if p.skipped.len() == 1 { "" } else { "s" }
+            )),
+            Ok(p) if p.kept > 0 => print_success(&format!(
+                "{} trimmed, {} {}, {} recently used item{} kept",
+                cache.name,
+                format_bytes(p.bytes_freed),
+                if cache.trash.is_some() {
+                    "moved to the trash"
+                } else {
+                    "freed"
+                },
+                p.kept,
+                if p.kept == 1 { "" } else { "s" }
+            )),
+            Ok(_) if cache.trash.is_some() => {
+                print_success(&format!("{} moved to the trash", cache.name))
+            }
+            Ok(_) => print_success(&format!("{} cleared", cache.name)),
+            Err(e) => print_error(&format!("Failed to clear {}: {}", cache.name, e)),
+        },
+    );
+    drop(bar);
+
+    let planned = report
+        .items
+        .iter_mut()
+        .filter(|i| i.status == ItemStatus::Planned);
+    for ((result, item), cache) in results.into_iter().zip(planned).zip(&selected_caches) {
+        match result {
+            Ok(progress) => {
+                // Files in the trash still take up space until it is emptied
+                if cache.trash.is_some() {
+                    total_trashed += progress.bytes_freed;
+                } else {
+                    total_freed += progress.bytes_freed;
+                }
+                if progress.cancelled {
+                    item.status = ItemStatus::Failed;
+                    item.error = Some("Cancelled".to_string());
+                } else {
+                    item.status = ItemStatus::Removed;
+                }
+                let moved = match cache.trash {
+                    Some(_) => "moved to the trash",
+                    None => "freed",
+                };
+                if progress.kept > 0 {
+                    item.note = Some(format!(
+                        "{} {}, {} recently used items kept",
+                        format_bytes(progress.bytes_freed),
+                        moved,
+                        progress.kept
+                    ));
+                } else if cache.trash.is_some() {
+                    item.note = Some(moved.to_string());
+                }
+                item.skipped = progress.skipped;
             }
             Err(e) => {
-                print_error(&format!("Failed to clear {}: {}", cache.name, e));
+                item.status = ItemStatus::Failed;
+                item.error = Some(e);
             }
         }
     }
 
+    print_skipped_summary(&report.items);
+    if cancel::is_requested() {
+        print_cancelled(&report.items);
+    }
+
     if total_freed > 0 {
-        println!();
-        println!(
+        print_text("");
+        print_text(&format!(
             "{} {}",
             "Space freed:".bold(),
-            format_bytes(total_freed).green().bold()
+            format_bytes(total_freed).good().bold()
+        ));
+    }
+    if total_trashed > 0 {
+        print_text("");
+        print_text(&format!(
+            "{} {} {}",
+            "Moved to the trash:".bold(),
+            format_bytes(total_trashed).good().bold(),
+            "(empty the trash to free the space)".dimmed()
+        ));
+    }
+
+    report.space_freed = total_freed;
+
+    Ok(Report::Cleanup(report))
+}
+
+pub struct ScanOptions {
+    pub force: bool,
+    pub dry_run: bool,
+    /// Artifacts untouched for this long are offered for deletion
+    pub older_than: Option<Duration>,
+    /// Also take Rust targets nothing builds into any more
+    pub redundant_targets: bool,
+}
+
+/// Find build artifacts under `root` and delete the stale ones the user
+/// picks. With `--force` or `--dry-run` every stale artifact is taken, and
+/// with `--redundant-targets` the Rust targets a shared target directory
+/// or workspace has replaced.
+pub fn scan(root: &Path, opts: &ScanOptions, config: &Config) -> Result<Report, String> {
+    policy::check(policy::Category::SystemCaches, "delete build artifacts")?;
+    let older_than = opts
+        .older_than
+        .or(config.scan.older_than)
+        .unwrap_or(artifacts::DEFAULT_OLDER_THAN);
+    let root = fs::canonicalize(root).map_err(|e| format!("{}: {}", root.display(), e))?;
+    let found = artifacts::scan(&root, &config.scan.patterns())?;
+    let mut report = CleanupReport::new("system scan", opts.dry_run);
+
+    if found.is_empty() {
+        print_success(&format!("No build artifacts under {}.", root.display()));
+        return Ok(Report::Cleanup(report));
+    }
+
+    let relative = |a: &Artifact| {
+        a.path
+            .strip_prefix(&root)
+            .unwrap_or(&a.path)
+            .display()
+            .to_string()
+    };
+    let age = |a: &Artifact| a.age().map_or("-".to_string(), format_age);
+
+    print_text(&format!("{}", "Build Artifacts".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["PATH", "KIND", "SIZE", "AGE"]);
+    for artifact in &found {
+        let age = if artifact.is_stale(older_than) {
+            age(artifact).caution().to_string()
+        } else {
+            age(artifact)
+        };
+        table.add_row(vec![
+            relative(artifact),
+            artifact.kind.clone(),
+            format_bytes(artifact.size),
+            age,
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+
+    let stale: Vec<bool> = found.iter().map(|a| a.is_stale(older_than)).collect();
+    let stale_size: u64 = found
+        .iter()
+        .zip(&stale)
+        .filter(|(_, s)| **s)
+        .map(|(a, _)| a.size)
+        .sum();
+    print_info(&format!(
+        "{} artifacts, {}; {} of it untouched for {}+",
+        found.len(),
+        format_bytes(found.iter().map(|a| a.size).sum()),
+        format_bytes(stale_size),
+        format_age(older_than)
+    ));
+    print_text("");
+
+    let rust =
+        get_home_dir().and_then(|home| cargo::advise(&found, &root, cargo::Sharing::detect(&home)));
+    let redundant: Vec<Option<&Redundant>> = found
+        .iter()
+        .map(|a| {
+            let rust = rust.as_ref()?;
+            rust.redundant
+                .iter()
+                .find(|(path, _)| *path == a.path)
+                .map(|(_, why)| why)
+        })
+        .collect();
+    if let Some(rust) = &rust {
+        print_target_advice(rust, &found, &relative);
+    }
+    // Asked rather than assumed: the shared setup may be newer than some
+    // projects' last build
+    let take_redundant = opts.redundant_targets
+        || (!opts.force
+            && !opts.dry_run
+            && redundant.iter().any(Option::is_some)
+            && confirm(
+                "Preselect the Rust targets nothing builds into any more?",
+                true,
+            )?);
+    let offered: Vec<bool> = stale
+        .iter()
+        .zip(&redundant)
+        .map(|(stale, redundant)| *stale || (take_redundant && redundant.is_some()))
+        .collect();
+
+    if opts.dry_run {
+        print_dry_run_header();
+    }
+
+    let selections: Vec<usize> = if opts.force || opts.dry_run {
+        (0..found.len()).filter(|&i| offered[i]).collect()
+    } else {
+        print_text(&format!("{}", "Select artifacts to delete:".bold()));
+        print_text(&format!(
+            "{}",
+            if take_redundant {
+                "(Stale ones and left-over Rust targets are preselected. Use space to select, enter to confirm)"
+            } else {
+                "(Stale ones are preselected. Use space to select, enter to confirm)"
+            }
+            .dimmed()
+        ));
+        print_text("");
+
+        let labels: Vec<String> = found
+            .iter()
+            .map(|a| format!("{} ({}, {})", relative(a), format_bytes(a.size), age(a)))
+            .collect();
+        multi_select(None, &labels, &offered)?
+    };
+
+    if selections.is_empty() && (opts.force || opts.dry_run) {
+        print_success(&format!(
+            "Nothing untouched for {}+, nothing to delete.",
+            format_age(older_than)
+        ));
+        return Ok(Report::Cleanup(report));
+    }
+    if selections.is_empty() {
+        print_warning("Nothing selected. Aborting.");
+        return Ok(Report::Cleanup(report.aborted()));
+    }
+
+    print_text(&format!("{}", "Selected for removal:".bold()));
+    let selected: Vec<&Artifact> = selections.iter().map(|&i| &found[i]).collect();
+    for &i in &selections {
+        let artifact = &found[i];
+        print_info(&format!(
+            "{} ({}) - {}",
+            relative(artifact),
+            format_bytes(artifact.size),
+            artifact.kind
+        ));
+        let mut item = ReportItem::planned(
+            "artifact",
+            &artifact.path.display().to_string(),
+            Some(artifact.size),
         );
+        item.note = redundant[i].map(|why| redundant_note(why, rust.as_ref()));
+        report.items.push(item);
+    }
+    print_text("");
+
+    if opts.dry_run {
+        print_warning("Dry run - no changes made");
+        return Ok(Report::Cleanup(report));
     }
 
-    Ok(())
+    let guard = PurgeGuard::from_config(config)?;
+    let mut manifest = Manifest::new("system scan");
+    for artifact in &selected {
+        manifest.add_cache(&artifact.path);
+    }
+    undo::record(manifest);
+
+    for (artifact, item) in selected.iter().zip(report.items.iter_mut()) {
+        if cancel::is_requested() {
+            break;
+        }
+        let result = guard
+            .check(&artifact.path)
+            .and_then(|()| fs::remove_dir_all(&artifact.path).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => {
+                print_success(&format!("Deleted {}", relative(artifact)));
+                item.status = ItemStatus::Removed;
+                report.space_freed += artifact.size;
+            }
+            Err(e) => {
+                print_error(&format!("Failed to delete {}: {}", relative(artifact), e));
+                item.status = ItemStatus::Failed;
+                item.error = Some(e);
+            }
+        }
+    }
+    cancel::settle(&mut report, cancel::check())?;
+
+    if report.space_freed > 0 {
+        print_text("");
+        print_text(&format!(
+            "{} {}",
+            "Space freed:".bold(),
+            format_bytes(report.space_freed).good().bold()
+        ));
+    }
+
+    Ok(Report::Cleanup(report))
+}
+
+/// How many skipped paths to list per cache before summarizing the rest.
+const MAX_SKIPPED_SHOWN: usize = 5;
+
+/// Why a Rust target is left over, for reports.
+fn redundant_note(why: &Redundant, rust: Option<&TargetAdvice>) -> String {
+    match (why, rust.and_then(|r| r.sharing.target_dir.as_ref())) {
+        (Redundant::WorkspaceMember(workspace), _) => {
+            format!("builds go to the workspace at {}", workspace.display())
+        }
+        (Redundant::Shared, Some((dir, _))) => format!("builds go to {}", dir.display()),
+        (Redundant::Shared, None) => "builds go to the shared target dir".to_string(),
+    }
+}
+
+/// What sharing one target directory would save across the Rust projects
+/// found, and which of their targets are left over already.
+fn print_target_advice(
+    advice: &TargetAdvice,
+    artifacts: &[Artifact],
+    relative: &dyn Fn(&Artifact) -> String,
+) {
+    if advice.targets >= cargo::ADVISE_FROM {
+        print_info(&format!(
+            "{} Rust targets hold {}; about {} of it is the same dependencies built more than once",
+            advice.targets,
+            format_bytes(advice.size),
+            format_bytes(advice.duplicated)
+        ));
+        match &advice.sharing.target_dir {
+            Some((dir, source)) => print_text(&format!(
+                "  {}",
+                format!("Builds go to {} (set by {})", dir.display(), source).dimmed()
+            )),
+            None => {
+                print_text(&format!(
+                    "  {}",
+                    "Set build.target-dir in ~/.cargo/config.toml (or CARGO_TARGET_DIR) to build each dependency once"
+                        .dimmed()
+                ));
+                if advice.sharing.sccache {
+                    print_text(&format!(
+                        "  {}",
+                        "sccache already shares the compiling, but each target keeps its own copy"
+                            .dimmed()
+                    ));
+                }
+            }
+        }
+    }
+    if !advice.redundant.is_empty() {
+        print_info(&format!(
+            "{} Rust targets ({}) are left over: nothing builds into them any more",
+            advice.redundant.len(),
+            format_bytes(advice.redundant_size(artifacts))
+        ));
+        for artifact in artifacts {
+            if let Some((_, why)) = advice.redundant.iter().find(|(p, _)| *p == artifact.path) {
+                print_text(&format!(
+                    "  {} {}",
+                    relative(artifact),
+                    format!("({})", redundant_note(why, Some(advice))).dimmed()
+                ));
+            }
+        }
+    }
+    print_text("");
+}
+
+fn print_skipped_summary(items: &[ReportItem]) {
+    let with_skips: Vec<&ReportItem> = items.iter().filter(|i| !i.skipped.is_empty()).collect();
+    if with_skips.is_empty() {
+        return;
+    }
+
+    print_text("");
+    print_text(&format!(
+        "{}",
+        "Skipped items (in use or permission denied):".bold()
+    ));
+    for item in with_skips {
+        print_text(&format!("  {} ({})", item.name, item.skipped.len()));
+        for entry in item.skipped.iter().take(MAX_SKIPPED_SHOWN) {
+            print_text(&format!(
+                "    {} {}",
+                entry.path.display(),
+                format!("- {}", entry.reason).dimmed()
+            ));
+        }
+        if item.skipped.len() > MAX_SKIPPED_SHOWN {
+            print_text(&format!(
+                "    {}",
+                format!("... and {} more", item.skipped.len() - MAX_SKIPPED_SHOWN).dimmed()
+            ));
+        }
+    }
 }
@@ -0,0 +1,196 @@
+use crate::display::{
+    confirm, print_dry_run_header, print_error, print_info, print_success, print_text,
+    print_warning,
+};
+use crate::docker::Docker;
+use crate::report::{CleanupReport, HistoryReport, ItemStatus, Report, ReportItem};
+use crate::undo::{self, Manifest};
+use crate::units::format_timestamp;
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::path::Path;
+
+/// Report note on removed resources that can't be brought back.
+const NOT_RESTORABLE: &str = "not restorable; recorded for reference";
+
+/// List the undo manifests saved by past cleanups, newest first.
+pub fn history() -> Result<Report, String> {
+    let manifests = undo::list();
+    if manifests.is_empty() {
+        print_success("No cleanups recorded yet.");
+        return Ok(Report::History(HistoryReport { manifests }));
+    }
+
+    print_text(&format!("{}", "Cleanup History".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec![
+        "ID",
+        "DATE (UTC)",
+        "COMMAND",
+        "IMAGES",
+        "CONTAINERS",
+        "VOLUMES",
+        "NETWORKS",
+        "CACHES",
+    ]);
+    for manifest in &manifests {
+        let restorable = manifest.restorable_images().count();
+        table.add_row(vec![
+            manifest.id.clone(),
+            format_timestamp(manifest.timestamp),
+            manifest.command.clone(),
+            format!("{} ({} restorable)", manifest.images.len(), restorable),
+            manifest.containers.len().to_string(),
+            manifest.volumes.len().to_string(),
+            manifest.networks.len().to_string(),
+            manifest.caches.len().to_string(),
+        ]);
+    }
+
+    print_text(&format!("{table}"));
+    print_text("");
+    print_text(&format!(
+        "Run {} to pull back the tagged images of a cleanup",
+        "dockerase restore <id>".cyan().bold()
+    ));
+
+    Ok(Report::History(HistoryReport { manifests }))
+}
+
+/// Pull back the tagged images a cleanup removed. Containers, volume data,
+/// networks and caches are gone for good and only listed.
+pub fn restore(id: &str, force: bool, dry_run: bool) -> Result<Report, String> {
+    let manifest = undo::load(id)?;
+
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    if dry_run {
+        print_dry_run_header();
+    }
+
+    let mut report = CleanupReport::new("restore", dry_run);
+    // Images pulled back since, or never removed, are left alone
+    let present: Vec<String> = Docker::list_images(&[])?
+        .iter()
+        .map(|i| i.display_name())
+        .collect();
+    let mut to_pull = Vec::new();
+    for image in &manifest.images {
+        if !image.is_restorable() {
+            report
+                .items
+                .push(ReportItem::skipped("images", &image.id, NOT_RESTORABLE));
+        } else if present.contains(&image.name()) {
+            report.items.push(ReportItem::skipped(
+                "images",
+                &image.name(),
+                "already present",
+            ));
+        } else {
+            to_pull.push((report.items.len(), image));
+            report
+                .items
+                .push(ReportItem::planned("images", &image.name(), None));
+        }
+    }
+    skip_unrestorable(&manifest, &mut report);
+
+    print_text(&format!(
+        "Cleanup {} ({}, {} UTC):",
+        manifest.id.bold(),
+        manifest.command,
+        format_timestamp(manifest.timestamp)
+    ));
+    print_info(&format!("{} images to pull", to_pull.len()));
+    let lost = manifest.containers.len()
+        + manifest.volumes.len()
+        + manifest.networks.len()
+        + manifest.caches.len();
+    if lost > 0 {
+        print_info(&format!(
+            "{} containers, volumes, networks and caches can't be restored",
+            lost
+        ));
+    }
+    print_text("");
+
+    if to_pull.is_empty() {
+        print_success("Nothing to restore.");
+        return Ok(Report::Cleanup(report));
+    }
+
+    if dry_run {
+        print_warning("Dry run - no changes made");
+        return Ok(Report::Cleanup(report));
+    }
+
+    if !force {
+        let confirmed = confirm(&format!("Pull {} images?", to_pull.len()), false)?;
+
+        if !confirmed {
+            print_warning("Aborted");
+            return Ok(Report::Cleanup(report.aborted()));
+        }
+    }
+
+    for (idx, image) in to_pull {
+        let item = &mut report.items[idx];
+        print_info(&format!("Pulling {}...", image.pull_reference()));
+        match Docker::restore_image(image) {
+            Ok(_) => {
+                print_success(&format!("Restored {}", image.name()));
+                item.status = ItemStatus::Restored;
+            }
+            Err(e) => {
+                print_error(&format!("Failed to restore {}: {}", image.name(), e.trim()));
+                item.status = ItemStatus::Failed;
+                item.error = Some(e.trim().to_string());
+            }
+        }
+    }
+
+    Ok(Report::Cleanup(report))
+}
+
+/// Write the tagged images a cleanup removed to `path`, ready to pull back
+/// on this or another machine.
+pub fn export(id: &str, path: &Path) -> Result<Report, String> {
+    let manifest = undo::load(id)?;
+    let count = undo::write_export(&manifest, path)?;
+    print_success(&format!(
+        "Wrote {} tagged images from cleanup {} to {}",
+        count,
+        manifest.id,
+        path.display()
+    ));
+    Ok(Report::History(HistoryReport {
+        manifests: vec![manifest],
+    }))
+}
+
+fn skip_unrestorable(manifest: &Manifest, report: &mut CleanupReport) {
+    let items = manifest
+        .containers
+        .iter()
+        .map(|c| ("containers", c.name.clone()))
+        .chain(manifest.volumes.iter().map(|v| ("volumes", v.clone())))
+        .chain(manifest.networks.iter().map(|n| ("networks", n.clone())))
+        .chain(
+            manifest
+                .caches
+                .iter()
+                .map(|p| ("cache", p.display().to_string())),
+        );
+    for (category, name) in items {
+        report
+            .items
+            .push(ReportItem::skipped(category, &name, NOT_RESTORABLE));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::dedup;
+use crate::display::{
+    format_bytes, print_error, print_info, print_success, print_text, print_warning, Styled,
+};
+use crate::docker::Docker;
+use crate::notes::{self, Kind};
+use crate::report::{DuplicatesReport, Report, VolumeDetails, VolumeListReport};
+use crate::resources::{UnusedVolumes, Volume};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use std::cmp::Reverse;
+use std::path::PathBuf;
+
+/// Size, creation date and users of each volume. These take extra calls to
+/// the daemon and only inform a decision, so whatever fails is left out.
+pub fn summaries(volumes: &[Volume], unused: &UnusedVolumes) -> Vec<VolumeDetails> {
+    let sizes = Docker::volume_sizes().unwrap_or_default();
+    let names: Vec<&str> = volumes.iter().map(|v| v.name.as_str()).collect();
+    let mut created = Docker::volume_created(&names).unwrap_or_default();
+    let users = Docker::volume_users().unwrap_or_default();
+    let notes = notes::list().unwrap_or_default();
+    volumes
+        .iter()
+        .map(|v| VolumeDetails {
+            name: v.name.clone(),
+            size: sizes.get(&v.name).copied(),
+            created_at: created.remove(&v.name),
+            used_by: users.get(&v.name).cloned().unwrap_or_default(),
+            compose_project: v.compose_project().map(str::to_string),
+            state: unused.describe(&v.name),
+            note: notes::find(&notes, Kind::Volume, &v.name).map(|n| n.text.clone()),
+        })
+        .collect()
+}
+
+/// The containers using a volume or, when there are none, the best hint
+/// of what did.
+pub fn users(volume: &VolumeDetails) -> String {
+    if !volume.used_by.is_empty() {
+        volume.used_by.join(", ")
+    } else if let Some(project) = &volume.compose_project {
+        format!("compose project {}", project)
+    } else if volume.state == "orphaned" {
+        "a removed container".to_string()
+    } else {
+        "-".to_string()
+    }
+}
+
+/// The day a volume was created, if known.
+pub fn created_date(volume: &VolumeDetails) -> &str {
+    volume
+        .created_at
+        .as_deref()
+        .and_then(|c| c.split(['T', ' ']).next())
+        .unwrap_or("-")
+}
+
+/// Every volume with its size, creation date and the containers using it,
+/// largest first.
+pub fn list() -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    let volumes = Docker::list_volumes(&[])?;
+    if volumes.is_empty() {
+        print_success("No volumes found.");
+        return Ok(Report::Volumes(VolumeListReport {
+            volumes: vec![],
+            total_size: 0,
+        }));
+    }
+    let unused = Docker::unused_volumes(&[])?;
+    let mut volumes = summaries(&volumes, &unused);
+    volumes.sort_by_key(|v| Reverse(v.size));
+    let total_size = volumes.iter().filter_map(|v| v.size).sum();
+
+    print_text(&format!("{}", "Docker Volumes".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    let noted = volumes.iter().any(|v| v.note.is_some());
+    let mut header = vec!["VOLUME", "SIZE", "CREATED", "USED BY", "STATE"];
+    if noted {
+        header.push("NOTE");
+    }
+    table.set_header(header);
+    for volume in &volumes {
+        let state = match volume.state {
+            "in use" => volume.state.good().to_string(),
+            "orphaned" => volume.state.danger().to_string(),
+            _ => volume.state.caution().to_string(),
+        };
+        let mut row = vec![
+            volume.name.clone(),
+            volume.size.map_or("-".to_string(), format_bytes),
+            created_date(volume).to_string(),
+            users(volume),
+            state,
+        ];
+        if noted {
+            row.push(volume.note.clone().unwrap_or_else(|| "-".to_string()));
+        }
+        table.add_row(row);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+    print_info(&format!(
+        "{} volumes, {}",
+        volumes.len(),
+        format_bytes(total_size)
+    ));
+    print_text(&format!(
+        "{}",
+        "  Run `dockerase select` and choose individual volumes to remove some".dimmed()
+    ));
+
+    Ok(Report::Volumes(VolumeListReport {
+        volumes,
+        total_size,
+    }))
+}
+
+/// Report files that more than one volume holds, such as the same
+/// dependencies installed into several. Nothing is changed.
+pub fn duplicates(names: &[String], min_file_size: u64) -> Result<Report, String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+    if !Docker::is_local() {
+        return Err("Volumes can only be compared on a local daemon".to_string());
+    }
+
+    let volumes: Vec<(String, PathBuf)> = Docker::list_volumes(&[])?
+        .into_iter()
+        .filter(|v| names.is_empty() || names.contains(&v.name))
+        .map(|v| (v.name, PathBuf::from(v.mountpoint)))
+        .collect();
+    if let Some(missing) = names.iter().find(|n| !volumes.iter().any(|(v, _)| v == *n)) {
+        return Err(format!("No volume named '{}'", missing));
+    }
+
+    let scan = dedup::scan(&volumes, min_file_size);
+    let report = DuplicatesReport {
+        volumes_scanned: volumes.len() - scan.unreadable.len(),
+        files_scanned: scan.files,
+        total_duplicated: scan.groups.iter().map(|g| g.duplicated).sum(),
+        groups: scan.groups,
+        unreadable: scan.unreadable,
+    };
+
+    print_text(&format!("{}", "Duplicated Volume Data".bold().cyan()));
+    print_text(&format!("{}", "═".repeat(50).dimmed()));
+    print_text("");
+
+    if !report.unreadable.is_empty() {
+        print_warning(&format!(
+            "Could not read {} volume(s): {} (try again with sudo; Docker Desktop keeps volumes inside its VM)",
+            report.unreadable.len(),
+            report.unreadable.join(", ")
+        ));
+    }
+    if report.groups.is_empty() {
+        print_success(&format!(
+            "No duplicated files across {} volumes.",
+            report.volumes_scanned
+        ));
+        return Ok(Report::Duplicates(report));
+    }
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec!["VOLUMES", "FILES", "DUPLICATED", "LARGEST FILE"]);
+    for group in &report.groups {
+        table.add_row(vec![
+            group.volumes.join("\n"),
+            group.files.to_string(),
+            format_bytes(group.duplicated),
+            group.example.display().to_string(),
+        ]);
+    }
+    print_text(&format!("{table}"));
+    print_text("");
+    print_info(&format!(
+        "{} duplicated across {} volumes ({} files compared by size and sampled content)",
+        format_bytes(report.total_duplicated),
+        report.volumes_scanned,
+        report.files_scanned
+    ));
+    print_text(&format!(
+        "{}",
+        "  Consider sharing one volume, or building the data into an image layer".dimmed()
+    ));
+
+    Ok(Report::Duplicates(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(state: &'static str) -> VolumeDetails {
+        VolumeDetails {
+            name: "data".to_string(),
+            size: Some(1_000),
+            created_at: Some("2024-03-01T09:30:00Z".to_string()),
+            used_by: vec![],
+            compose_project: None,
+            state,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_users_fall_back_to_the_best_hint() {
+        let mut used = volume("in use");
+        used.used_by = vec!["db".to_string(), "backup".to_string()];
+        assert_eq!(users(&used), "db, backup");
+
+        let mut compose = volume("not mounted");
+        compose.compose_project = Some("shop".to_string());
+        assert_eq!(users(&compose), "compose project shop");
+
+        assert_eq!(users(&volume("orphaned")), "a removed container");
+        assert_eq!(users(&volume("not mounted")), "-");
+        assert_eq!(created_date(&used), "2024-03-01");
+    }
+}
@@ -0,0 +1,343 @@
+use crate::cancel;
+use crate::commands::{builders, purge};
+use crate::config::Protected;
+use crate::display::{
+    format_bytes, print_error, print_info, print_success, print_text, print_warning, Styled,
+};
+use crate::docker::Docker;
+use crate::filters::Filters;
+use crate::history::now_secs;
+use crate::notify::{self, Notification};
+use crate::paths;
+use crate::report::Report;
+use crate::schedule::{Schedule, DEFAULT_MAX_LOAD};
+use crate::stats;
+use crate::units::format_timestamp;
+use colored::Colorize;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct WatchOptions {
+    pub interval: Duration,
+    pub disk_above: u8,
+    pub reclaimable_above: Option<u64>,
+    pub webhook: Option<String>,
+    pub once: bool,
+    /// Run the safe purge whenever a threshold is crossed, instead of only
+    /// notifying
+    pub purge: bool,
+    pub dry_run: bool,
+    pub named_volumes: bool,
+    /// When the purge may run; it waits for a later check otherwise
+    pub schedule: Schedule,
+}
+
+/// One check, as appended to the watch log.
+#[derive(Debug, Serialize)]
+struct WatchRun {
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disk_percent: Option<u8>,
+    reclaimable: u64,
+    breaches: Vec<String>,
+    /// Space the purge freed, when one ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    space_freed: Option<u64>,
+    /// Why the purge waits for a later check, when it does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    postponed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub fn run(opts: &WatchOptions, filters: &Filters, protect: &Protected) -> Result<(), String> {
+    if !Docker::is_available() {
+        print_error("Docker is not available. Is Docker running?");
+        return Err("Docker not available".to_string());
+    }
+
+    if opts.purge {
+        print_info(&format!(
+            "Watching every {}s; purging unused resources when a threshold is crossed",
+            opts.interval.as_secs()
+        ));
+        if opts.schedule.is_restricted() {
+            print_info(&format!("Purging only {}", describe(&opts.schedule)));
+        }
+    } else {
+        print_info(&format!(
+            "Watching every {}s (advisory mode: nothing will be deleted)",
+            opts.interval.as_secs()
+        ));
+    }
+
+    // Only notify when a threshold is first crossed, not on every check
+    let mut alerted = false;
+
+    loop {
+        match check(opts) {
+            Ok(mut run) => {
+                alerted = respond(opts, filters, protect, &mut run, alerted);
+                log_run(&run);
+            }
+            Err(e) if opts.once => return Err(e),
+            // A daemon restart shouldn't end a long-running watch
+            Err(e) => {
+                print_warning(&format!("Check failed: {}", e.trim()));
+                log_run(&failed_run(e));
+            }
+        }
+
+        if opts.once || wait(opts.interval) {
+            break;
+        }
+    }
+
+    if cancel::flag().load(Ordering::SeqCst) {
+        print_info("Stopped watching");
+    }
+    Ok(())
+}
+
+/// Print the outcome of a check, purging and notifying as configured.
+/// Returns whether a notification has gone out for the current breach.
+fn respond(
+    opts: &WatchOptions,
+    filters: &Filters,
+    protect: &Protected,
+    run: &mut WatchRun,
+    alerted: bool,
+) -> bool {
+    let stamp = format_timestamp(run.timestamp).dimmed();
+    if run.breaches.is_empty() {
+        print_text(&format!(
+            "{} {} usage within thresholds",
+            stamp,
+            "✓".good().bold()
+        ));
+        return false;
+    }
+
+    for breach in &run.breaches {
+        print_text(&format!("{} {} {}", stamp, "⚠".caution().bold(), breach));
+    }
+    if opts.purge {
+        match opts.schedule.blocker() {
+            Some(reason) => {
+                print_text(&format!(
+                    "{} {} Purge postponed: {}",
+                    stamp,
+                    "⏸".info(),
+                    reason
+                ));
+                run.postponed = Some(reason);
+            }
+            None => auto_purge(opts, filters, protect, run),
+        }
+    }
+    if !alerted {
+        send_advisory(opts, run);
+    }
+    true
+}
+
+fn check(opts: &WatchOptions) -> Result<WatchRun, String> {
+    let usage = Docker::get_disk_usage()?;
+    let disk_percent = Docker::host_disk_space().map(|s| s.used_percent());
+    let reclaimable = usage.total_reclaimable();
+    Ok(WatchRun {
+        timestamp: now_secs(),
+        disk_percent,
+        reclaimable,
+        breaches: evaluate_thresholds(disk_percent, reclaimable, opts),
+        space_freed: None,
+        postponed: None,
+        error: None,
+    })
+}
+
+fn failed_run(error: String) -> WatchRun {
+    WatchRun {
+        timestamp: now_secs(),
+        disk_percent: None,
+        reclaimable: 0,
+        breaches: Vec::new(),
+        space_freed: None,
+        postponed: None,
+        error: Some(error.trim().to_string()),
+    }
+}
+
+/// When a scheduled purge may run, as in `within Mon-Fri 19:00-07:00 and
+/// when idle (...)`.
+fn describe(schedule: &Schedule) -> String {
+    let mut when = vec![];
+    if !schedule.windows.is_empty() {
+        when.push(format!("within {}", schedule.describe_windows()));
+    }
+    if schedule.when_idle {
+        when.push(format!(
+            "when idle (no build running, on AC power, CPU load at most {}%)",
+            schedule.max_load.unwrap_or(DEFAULT_MAX_LOAD)
+        ));
+    }
+    when.join(" and ")
+}
+
+/// Run `purge --force` with the configured protections, recording what it
+/// freed or why it failed.
+fn auto_purge(opts: &WatchOptions, filters: &Filters, protect: &Protected, run: &mut WatchRun) {
+    match purge::run(
+        true,
+        opts.dry_run,
+        opts.named_volumes,
+        filters,
+        protect,
+        &builders::Selection::default(),
+    ) {
+        Ok(Report::Cleanup(report)) => {
+            stats::record(&report).ok();
+            run.space_freed = Some(report.space_freed);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            print_warning(&format!("Purge failed: {}", e.trim()));
+            run.error = Some(e.trim().to_string());
+        }
+    }
+}
+
+/// Sleep for `interval`, waking early when Ctrl-C or SIGTERM asks to stop.
+/// Returns whether to stop.
+fn wait(interval: Duration) -> bool {
+    let cancelled = cancel::flag();
+    let deadline = Instant::now() + interval;
+    while !cancelled.load(Ordering::SeqCst) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        thread::sleep(left.min(Duration::from_secs(1)));
+    }
+    true
+}
+
+/// Append the run to the watch log. Best effort: a log that can't be
+/// written is reported but doesn't stop watching.
+fn log_run(run: &WatchRun) {
+    if let Err(e) = append_log(run) {
+        print_warning(&format!("Could not write watch log: {}", e));
+    }
+}
+
+fn append_log(run: &WatchRun) -> Result<(), String> {
+    let path = paths::watch_log().ok_or("Could not determine state directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let line = serde_json::to_string(run).map_err(|e| e.to_string())?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut f| writeln!(f, "{}", line))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn evaluate_thresholds(
+    disk_percent: Option<u8>,
+    reclaimable: u64,
+    opts: &WatchOptions,
+) -> Vec<String> {
+    let mut breaches = Vec::new();
+
+    if let Some(percent) = disk_percent {
+        if percent > opts.disk_above {
+            breaches.push(format!(
+                "Disk is {}% full (threshold {}%)",
+                percent, opts.disk_above
+            ));
+        }
+    }
+
+    if let Some(limit) = opts.reclaimable_above {
+        if reclaimable > limit {
+            breaches.push(format!(
+                "Docker has {} reclaimable (threshold {})",
+                format_bytes(reclaimable),
+                format_bytes(limit)
+            ));
+        }
+    }
+
+    breaches
+}
+
+fn send_advisory(opts: &WatchOptions, run: &WatchRun) {
+    let next_step = match run.space_freed {
+        Some(freed) if !opts.dry_run => {
+            format!("Ran `dockerase purge`, freeing {}.", format_bytes(freed))
+        }
+        _ => match &run.postponed {
+            Some(reason) => format!("The purge waits: {}.", reason),
+            None => "Run `dockerase purge` to clean up.".to_string(),
+        },
+    };
+    let notification = Notification {
+        title: "dockerase: cleanup suggested".to_string(),
+        message: format!("{}. {}", run.breaches.join("; "), next_step),
+    };
+
+    notify::desktop(&notification);
+
+    if let Some(url) = &opts.webhook {
+        match notify::webhook(url, &notification) {
+            Ok(()) => print_success("Webhook notified"),
+            Err(e) => print_warning(&e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(disk_above: u8, reclaimable_above: Option<u64>) -> WatchOptions {
+        WatchOptions {
+            interval: Duration::from_secs(60),
+            disk_above,
+            reclaimable_above,
+            webhook: None,
+            once: true,
+            purge: false,
+            dry_run: false,
+            named_volumes: false,
+            schedule: Schedule::default(),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_thresholds_within_limits() {
+        let breaches = evaluate_thresholds(Some(50), 1_000, &opts(85, Some(10_000)));
+        assert!(breaches.is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_thresholds_disk_breach() {
+        let breaches = evaluate_thresholds(Some(90), 0, &opts(85, None));
+        assert_eq!(breaches.len(), 1);
+        assert!(breaches[0].contains("90%"));
+    }
+
+    #[test]
+    fn test_evaluate_thresholds_reclaimable_breach() {
+        let breaches = evaluate_thresholds(None, 20_000, &opts(85, Some(10_000)));
+        assert_eq!(breaches.len(), 1);
+        assert!(breaches[0].contains("reclaimable"));
+    }
+}
@@ -0,0 +1,769 @@
+use crate::alias::Alias;
+use crate::artifacts::ScanSettings;
+use crate::display::{parse_color, Palette, SizeFormat, Styles};
+use crate::docker;
+use crate::filters::glob_match;
+use crate::notes::{self, Kind, Note};
+use crate::paths;
+use crate::policy::Policy;
+use crate::quota::Quota;
+use crate::resources::Image;
+use crate::schedule::Schedule;
+use crate::units::{parse_byte_size, parse_duration};
+use colored::Color;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// What to leave behind once a cache directory has been emptied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AfterPurge {
+    /// Remove the directory and recreate it empty with its original
+    /// permissions and ownership (some tools expect it to exist)
+    Recreate,
+    /// Remove the directory and leave it absent
+    Absent,
+    /// Only remove the contents, never the directory itself
+    KeepDir,
+}
+
+/// Per-cache settings, keyed by cache name in `[caches."<name>"]`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheSettings {
+    pub after_purge: Option<AfterPurge>,
+    /// Permissions for the recreated directory, e.g. `0o755`
+    pub mode: Option<u32>,
+    /// Owner for the recreated directory
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+/// A user-defined cache from `[[custom_caches]]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomCache {
+    pub name: String,
+    /// Absolute path, or relative to the home directory with a `~/` prefix
+    pub path: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Report note on resources left alone because of `[protect]`.
+pub const PROTECTED_NOTE: &str = "protected by config";
+
+/// Resources no command may remove, from `[protect]`. Patterns support `*`
+/// and `?` wildcards.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Protected {
+    /// Image patterns, matched against `repository:tag` and the repository
+    /// alone (e.g. `postgres` or `myorg/*`)
+    pub images: Vec<String>,
+    /// Volume name patterns
+    pub volumes: Vec<String>,
+    /// System cache names, as listed by `dockerase system`
+    pub caches: Vec<String>,
+    /// Resources with a note from `dockerase note`, protected too
+    #[serde(skip)]
+    pub notes: Vec<Note>,
+}
+
+impl Protected {
+    pub fn protects_image(&self, image: &Image) -> bool {
+        let name = image.display_name();
+        self.images
+            .iter()
+            .any(|p| glob_match(p, &name) || glob_match(p, &image.repository))
+            || self.note(Kind::Image, &name).is_some()
+    }
+
+    pub fn protects_volume(&self, name: &str) -> bool {
+        self.volumes.iter().any(|p| glob_match(p, name)) || self.note(Kind::Volume, name).is_some()
+    }
+
+    pub fn protects_cache(&self, name: &str) -> bool {
+        self.caches.iter().any(|p| glob_match(p, name)) || self.note(Kind::Cache, name).is_some()
+    }
+
+    /// The note on the resource `name`. Images are looked up as
+    /// `repository:tag`, then by repository.
+    fn note(&self, kind: Kind, name: &str) -> Option<&Note> {
+        match kind {
+            Kind::Image => notes::find_image(&self.notes, name),
+            _ => notes::find(&self.notes, kind, name),
+        }
+    }
+
+    /// Why the protected resource `name` is left alone, for reports: its
+    /// note, if it has one.
+    pub fn reason(&self, kind: Kind, name: &str) -> String {
+        match self.note(kind, name) {
+            Some(note) => format!("noted: {}", note.text),
+            None => PROTECTED_NOTE.to_string(),
+        }
+    }
+
+    /// `name` as listed, with its note if it has one.
+    pub fn label(&self, kind: Kind, name: &str) -> String {
+        match self.note(kind, name) {
+            Some(note) => format!("{} (\"{}\")", name, note.text),
+            None => name.to_string(),
+        }
+    }
+
+    pub fn labels(&self, kind: Kind, names: &[String]) -> Vec<String> {
+        names.iter().map(|name| self.label(kind, name)).collect()
+    }
+}
+
+/// Flags applied to every run unless overridden on the command line, from
+/// `[defaults]`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    /// Preview only; `--no-dry-run` makes changes anyway
+    pub dry_run: bool,
+    /// `purge --named-volumes`
+    pub named_volumes: bool,
+    /// `--older-than` for `purge` and `--nuclear`, e.g. `"7d"`
+    #[serde(deserialize_with = "duration")]
+    pub older_than: Option<Duration>,
+    /// `system purge --trash`; `--permanent` deletes anyway
+    pub trash: bool,
+}
+
+/// A duration such as `"7d"`, as the command line's `--older-than` takes.
+pub fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    parse_duration(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// A size such as `"50GB"`, as the command line's `--max-reclaimable` takes.
+pub fn byte_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    parse_byte_size(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Output colors and sizes, from `[display]`. Each meaning can be given its
+/// own color on top of the palette: a name such as `"bright blue"`, or
+/// `"#rrggbb"`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplaySettings {
+    pub palette: Palette,
+    pub sizes: SizeFormat,
+    #[serde(deserialize_with = "color")]
+    pub good: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub caution: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub danger: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub info: Option<Color>,
+}
+
+impl DisplaySettings {
+    /// The colors to use; `palette` (from `--palette`) replaces the
+    /// configured palette but not the per-meaning colors.
+    pub fn styles(&self, palette: Option<Palette>) -> Styles {
+        let preset = Styles::preset(palette.unwrap_or(self.palette));
+        Styles {
+            good: self.good.or(preset.good),
+            caution: self.caution.or(preset.caution),
+            danger: self.danger.or(preset.danger),
+            info: self.info.or(preset.info),
+        }
+    }
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    parse_color(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// `[defaults]` inside a host overlay; only the flags given are replaced.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultsOverlay {
+    pub dry_run: Option<bool>,
+    pub named_volumes: Option<bool>,
+    #[serde(deserialize_with = "duration")]
+    pub older_than: Option<Duration>,
+    pub trash: Option<bool>,
+}
+
+/// Settings for the machines matching a `[hosts."<pattern>"]` key, checked
+/// against the hostname and the current docker context name.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HostOverlay {
+    pub read_only: Option<bool>,
+    pub production: Option<bool>,
+    /// Added to the top-level `[protect]` lists
+    pub protect: Protected,
+    pub defaults: DefaultsOverlay,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub caches: BTreeMap<String, CacheSettings>,
+    pub custom_caches: Vec<CustomCache>,
+    /// Directories outside the home directory that caches may live in
+    pub allowed_paths: Vec<String>,
+    /// Also offer caches that need more care, such as logs and the macOS
+    /// per-user temp directories, which are only trimmed of old files
+    pub include_advanced: bool,
+    /// Never delete anything, whatever the command line says; see
+    /// `DOCKERASE_READ_ONLY`
+    pub read_only: bool,
+    /// Destructive commands need the host's name typed (or passed with
+    /// `--confirm-production`) before they run
+    pub production: bool,
+    /// Hold tagged images `select` and `images prune` remove for this long
+    /// before deleting them, e.g. `"7d"`; see `grace`
+    #[serde(deserialize_with = "duration")]
+    pub image_grace_period: Option<Duration>,
+    /// Tags per repository `images prune` keeps unless given `--keep-latest`
+    pub image_keep_latest: Option<usize>,
+    pub protect: Protected,
+    pub defaults: Defaults,
+    pub display: DisplaySettings,
+    /// Limits on what may be removed at all; see `policy`
+    pub policy: Policy,
+    /// Per-machine overrides, applied in key order on top of the above
+    pub hosts: BTreeMap<String, HostOverlay>,
+    /// Command shortcuts; see `alias`
+    pub alias: BTreeMap<String, Alias>,
+    /// What `system scan` looks for; see `artifacts`
+    pub scan: ScanSettings,
+    /// Usage to nag about; see `quota`
+    pub quota: Quota,
+    /// When `watch --purge` may clean up; see `schedule`
+    pub watch: Schedule,
+}
+
+impl Config {
+    pub fn cache_settings(&self, name: &str) -> Option<&CacheSettings> {
+        self.caches.get(name)
+    }
+
+    /// Apply every `[hosts]` overlay whose pattern matches one of `names`.
+    fn apply_host_overlays(&mut self, names: &[String]) {
+        let hosts = std::mem::take(&mut self.hosts);
+        for (pattern, overlay) in &hosts {
+            if !names.iter().any(|n| glob_match(pattern, n)) {
+                continue;
+            }
+            if let Some(read_only) = overlay.read_only {
+                self.read_only = read_only;
+            }
+            if let Some(production) = overlay.production {
+                self.production = production;
+            }
+            let protect = &overlay.protect;
+            self.protect.images.extend_from_slice(&protect.images);
+            self.protect.volumes.extend_from_slice(&protect.volumes);
+            self.protect.caches.extend_from_slice(&protect.caches);
+            let defaults = &overlay.defaults;
+            if let Some(dry_run) = defaults.dry_run {
+                self.defaults.dry_run = dry_run;
+            }
+            if let Some(named_volumes) = defaults.named_volumes {
+                self.defaults.named_volumes = named_volumes;
+            }
+            if defaults.older_than.is_some() {
+                self.defaults.older_than = defaults.older_than;
+            }
+            if let Some(trash) = defaults.trash {
+                self.defaults.trash = trash;
+            }
+        }
+        self.hosts = hosts;
+    }
+
+    /// Re-apply what the system config locks down, in case the user config
+    /// or a host overlay loosened it: read-only and production mode stay
+    /// on, protected resources stay protected and the policy only gets
+    /// stricter.
+    fn apply_system_locks(&mut self, system: &Config) {
+        self.read_only |= system.read_only;
+        self.production |= system.production;
+        let add = |list: &mut Vec<String>, locked: &[String]| {
+            for pattern in locked {
+                if !list.contains(pattern) {
+                    list.push(pattern.clone());
+                }
+            }
+        };
+        add(&mut self.protect.images, &system.protect.images);
+        add(&mut self.protect.volumes, &system.protect.volumes);
+        add(&mut self.protect.caches, &system.protect.caches);
+        self.policy.tighten(&system.policy);
+    }
+}
+
+/// The names host overlays are matched against: this machine's hostname,
+/// the current docker context and the machine a `--host` or `DOCKER_HOST`
+/// daemon is on.
+fn host_names() -> Vec<String> {
+    [
+        hostname(),
+        Some(docker::current_context()),
+        docker::target_machine(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+pub fn hostname() -> Option<String> {
+    if let Ok(name) = env::var("COMPUTERNAME") {
+        return Some(name);
+    }
+    let output = Command::new("hostname").output().ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !name.is_empty()).then_some(name)
+}
+
+/// Expand a leading `~/` to the home directory.
+pub fn expand_home(path: &str, home: &Path) -> PathBuf {
+    if path == "~" {
+        home.to_path_buf()
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        home.join(rest)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+/// Load the system config (see [`paths::system_config_file`]) with the user
+/// config merged over it. A missing file yields the defaults; a malformed
+/// one is an error so typos don't silently change what gets deleted. The
+/// same goes for the notes, which protect what they are on.
+pub fn load() -> Result<Config, String> {
+    let system = read(paths::system_config_file())?;
+    let user = read(paths::config_file())?;
+    let mut table = system.clone().unwrap_or_default();
+    merge(&mut table, user.unwrap_or_default());
+    let mut config = from_table(table)?;
+    if !config.hosts.is_empty() {
+        config.apply_host_overlays(&host_names());
+    }
+    if let Some(system) = system {
+        config.apply_system_locks(&from_table(system)?);
+    }
+    config.protect.notes = notes::list()?;
+    Ok(config)
+}
+
+/// `[alias]` from both config files. Aliases are expanded before the
+/// command line is parsed, so before host overlays could be matched.
+pub fn aliases() -> Result<BTreeMap<String, Alias>, String> {
+    let mut table = read(paths::system_config_file())?.unwrap_or_default();
+    merge(&mut table, read(paths::config_file())?.unwrap_or_default());
+    Ok(from_table(table)?.alias)
+}
+
+/// The contents of the config file at `path`, checked against the schema on
+/// its own so an error names the file it is in.
+fn read(path: Option<PathBuf>) -> Result<Option<toml::Table>, String> {
+    let path = match path {
+        Some(p) if p.exists() => p,
+        _ => return Ok(None),
+    };
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let invalid = |e: String| format!("Invalid config {}: {}", path.display(), e);
+    parse(&content).map_err(invalid)?;
+    content
+        .parse::<toml::Table>()
+        .map(Some)
+        .map_err(|e| invalid(e.to_string()))
+}
+
+/// Merge `overlay` into `base`: tables are merged key by key, any other
+/// value in `overlay` replaces the one in `base`.
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn from_table(table: toml::Table) -> Result<Config, String> {
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| e.to_string())
+}
+
+fn parse(content: &str) -> Result<Config, String> {
+    toml::from_str(content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::Category;
+
+    #[test]
+    fn test_parse_empty_config() {
+        let config = parse("").unwrap();
+        assert!(config.caches.is_empty());
+        assert!(!config.include_advanced);
+    }
+
+    #[test]
+    fn test_parse_cache_settings() {
+        let config = parse(
+            r#"
+            [caches.pnpm]
+            after_purge = "absent"
+
+            [caches."Cargo Registry"]
+            after_purge = "recreate"
+            mode = 0o700
+            uid = 501
+            gid = 20
+            "#,
+        )
+        .unwrap();
+
+        let pnpm = config.cache_settings("pnpm").unwrap();
+        assert_eq!(pnpm.after_purge, Some(AfterPurge::Absent));
+
+        let cargo = config.cache_settings("Cargo Registry").unwrap();
+        assert_eq!(cargo.after_purge, Some(AfterPurge::Recreate));
+        assert_eq!(cargo.mode, Some(0o700));
+        assert_eq!(cargo.uid, Some(501));
+        assert!(config.cache_settings("npm").is_none());
+    }
+
+    #[test]
+    fn test_parse_custom_caches() {
+        let config = parse(
+            r#"
+            allowed_paths = ["/opt/ci-cache"]
+
+            [[custom_caches]]
+            name = "Bazel"
+            path = "~/.cache/bazel"
+
+            [[custom_caches]]
+            name = "CI artifacts"
+            path = "/opt/ci-cache/artifacts"
+            description = "Old CI build outputs"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.allowed_paths, vec!["/opt/ci-cache"]);
+        assert_eq!(config.custom_caches.len(), 2);
+        assert_eq!(config.custom_caches[0].name, "Bazel");
+        assert!(config.custom_caches[0].description.is_none());
+        assert_eq!(
+            config.custom_caches[1].description.as_deref(),
+            Some("Old CI build outputs")
+        );
+    }
+
+    #[test]
+    fn test_parse_protect_and_defaults() {
+        let config = parse(
+            r#"
+            image_grace_period = "2w"
+            image_keep_latest = 3
+
+            [protect]
+            images = ["postgres", "myorg/*"]
+            volumes = ["pgdata", "prod-*"]
+            caches = ["Homebrew"]
+
+            [defaults]
+            dry_run = true
+            older_than = "7d"
+            "#,
+        )
+        .unwrap();
+
+        let image = |repository: &str, tag: &str| Image {
+            id: "abc".to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            size: "1MB".to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        };
+        let protect = &config.protect;
+        assert!(protect.protects_image(&image("postgres", "16")));
+        assert!(protect.protects_image(&image("myorg/api", "latest")));
+        assert!(!protect.protects_image(&image("redis", "7")));
+        assert!(!protect.protects_image(&image("<none>", "<none>")));
+        assert!(protect.protects_volume("prod-db"));
+        assert!(!protect.protects_volume("scratch"));
+        assert!(protect.protects_cache("Homebrew"));
+        assert!(!protect.protects_cache("npm"));
+
+        assert!(config.defaults.dry_run);
+        assert!(!config.defaults.named_volumes);
+        assert_eq!(
+            config.defaults.older_than,
+            Some(Duration::from_secs(7 * 86_400))
+        );
+        assert_eq!(
+            config.image_grace_period,
+            Some(Duration::from_secs(14 * 86_400))
+        );
+        assert_eq!(config.image_keep_latest, Some(3));
+    }
+
+    #[test]
+    fn test_noted_resources_are_protected_with_their_note() {
+        let note = |kind, name: &str| Note {
+            kind,
+            name: name.to_string(),
+            text: format!("keep {name}"),
+            noted_at: 0,
+        };
+        let protect = Protected {
+            notes: vec![note(Kind::Image, "postgres"), note(Kind::Volume, "pgdata")],
+            ..Default::default()
+        };
+        let image = |repository: &str, tag: &str| Image {
+            id: "abc".to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            size: "1MB".to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        };
+        assert!(protect.protects_image(&image("postgres", "16")));
+        assert!(!protect.protects_image(&image("redis", "7")));
+        assert!(protect.protects_volume("pgdata"));
+        assert!(!protect.protects_cache("pgdata"));
+
+        assert_eq!(
+            protect.reason(Kind::Image, "postgres:16"),
+            "noted: keep postgres"
+        );
+        assert_eq!(protect.reason(Kind::Volume, "prod-db"), PROTECTED_NOTE);
+        assert_eq!(
+            protect.labels(Kind::Volume, &["pgdata".to_string(), "other".to_string()]),
+            vec!["pgdata (\"keep pgdata\")", "other"]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_default_age() {
+        assert!(parse("[defaults]\nolder_than = \"soon\"").is_err());
+        assert!(parse("[protect]\ncontainers = []").is_err());
+    }
+
+    #[test]
+    fn test_host_overlays() {
+        let mut config = parse(
+            r#"
+            [protect]
+            volumes = ["pgdata"]
+
+            [defaults]
+            dry_run = true
+            older_than = "7d"
+
+            [hosts."ci-*"]
+            read_only = false
+            protect.volumes = ["runner-cache"]
+            defaults.dry_run = false
+
+            [hosts.laptop]
+            read_only = true
+            protect.volumes = ["scratch"]
+            "#,
+        )
+        .unwrap();
+
+        config.apply_host_overlays(&["ci-42".to_string(), "default".to_string()]);
+        assert!(!config.read_only);
+        assert_eq!(config.protect.volumes, vec!["pgdata", "runner-cache"]);
+        assert!(!config.defaults.dry_run);
+        // Flags the overlay leaves out keep the top-level value
+        assert_eq!(
+            config.defaults.older_than,
+            Some(Duration::from_secs(7 * 86_400))
+        );
+    }
+
+    #[test]
+    fn test_host_overlays_match_context_name() {
+        let mut config = parse(
+            "[hosts.remote-builder]
+read_only = true",
+        )
+        .unwrap();
+        config.apply_host_overlays(&["workstation".to_string(), "default".to_string()]);
+        assert!(!config.read_only);
+        config.apply_host_overlays(&["workstation".to_string(), "remote-builder".to_string()]);
+        assert!(config.read_only);
+        assert!(!config.production);
+        assert!(parse(
+            "[hosts.ci]
+nuclear = true"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_system_config_sits_under_the_user_config() {
+        let system: toml::Table = r#"
+            production = true
+            image_keep_latest = 5
+
+            [protect]
+            volumes = ["pgdata"]
+
+            [defaults]
+            dry_run = true
+            older_than = "7d"
+
+            [policy]
+            disable_nuclear = true
+            disabled = ["volumes"]
+            "#
+        .parse()
+        .unwrap();
+        let user: toml::Table = r#"
+            production = false
+
+            [protect]
+            volumes = ["scratch"]
+
+            [defaults]
+            older_than = "1d"
+
+            [policy]
+            disabled = ["build_cache"]
+            "#
+        .parse()
+        .unwrap();
+
+        let mut table = system.clone();
+        merge(&mut table, user);
+        let mut config = from_table(table).unwrap();
+        // The user's settings win, key by key
+        assert!(config.defaults.dry_run);
+        assert_eq!(
+            config.defaults.older_than,
+            Some(Duration::from_secs(86_400))
+        );
+        assert_eq!(config.image_keep_latest, Some(5));
+
+        // Except where the system config locks things down
+        config.apply_system_locks(&from_table(system).unwrap());
+        assert!(config.production);
+        assert_eq!(config.protect.volumes, vec!["scratch", "pgdata"]);
+        assert!(config.policy.disable_nuclear);
+        assert_eq!(
+            config.policy.disabled,
+            vec![Category::Volumes, Category::BuildCache]
+        );
+        assert!(parse("[policy]\ndisabled = [\"everything\"]").is_err());
+    }
+
+    #[test]
+    fn test_parse_display_settings() {
+        let config = parse(
+            r##"
+            [display]
+            palette = "color-blind"
+            danger = "#ff00ff"
+            "##,
+        )
+        .unwrap();
+
+        let styles = config.display.styles(None);
+        assert_eq!(
+            styles.danger,
+            Some(Color::TrueColor {
+                r: 255,
+                g: 0,
+                b: 255
+            })
+        );
+        assert_eq!(styles.info, Some(Color::Cyan));
+        // --palette swaps the preset but keeps the configured colors
+        let styles = config.display.styles(Some(Palette::Monochrome));
+        assert_eq!(styles.good, None);
+        assert!(styles.danger.is_some());
+
+        assert!(parse("[display]\ngood = \"chartreuse\"").is_err());
+        assert!(parse("[display]\npalette = \"neon\"").is_err());
+
+        let config = parse("[display]\nsizes = \"docker\"").unwrap();
+        assert_eq!(config.display.sizes, SizeFormat::Docker);
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = Path::new("/home/dev");
+        assert_eq!(expand_home("~", home), PathBuf::from("/home/dev"));
+        assert_eq!(
+            expand_home("~/.cache/bazel", home),
+            PathBuf::from("/home/dev/.cache/bazel")
+        );
+        assert_eq!(expand_home("/opt/cache", home), PathBuf::from("/opt/cache"));
+        assert_eq!(expand_home("~other/x", home), PathBuf::from("~other/x"));
+    }
+
+    #[test]
+    fn test_parse_quota() {
+        assert_eq!(parse("").unwrap().quota.max_usage, None);
+        let config = parse("[quota]\nmax_usage = \"50GB\"").unwrap();
+        assert_eq!(config.quota.max_usage, Some(50_000_000_000));
+        assert!(parse("[quota]\nmax_usage = \"lots\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_watch_schedule() {
+        let config =
+            parse("[watch]\nwindows = [\"Mon-Fri 19:00-07:00\", \"Sat-Sun\"]\nwhen_idle = true")
+                .unwrap();
+        assert_eq!(config.watch.windows.len(), 2);
+        assert!(config.watch.when_idle);
+        assert_eq!(
+            config.watch.describe_windows(),
+            "Mon-Fri 19:00-07:00 or Sat-Sun"
+        );
+        assert!(parse("[watch]\nwindows = [\"evenings\"]").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_values() {
+        assert!(parse("[caches.npm]\nafter_purge = \"explode\"").is_err());
+        assert!(parse("[caches.npm]\nrecreate = true").is_err());
+        assert!(parse("unknown_key = 1").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Files duplicated between volumes, such as the same `node_modules` baked
+//! into several of them. Files are matched by size first, then by a hash
+//! of their first and last 64 KiB, so a scan reads little of each volume;
+//! matches are likely, not certain, duplicates. This only reports them:
+//! consolidating volumes is up to their owner.
+
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Bytes hashed from each end of a file.
+const SAMPLE: u64 = 64 * 1024;
+
+/// Content found in each of a set of volumes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateGroup {
+    pub volumes: Vec<String>,
+    /// Distinct files present in every one of `volumes`
+    pub files: usize,
+    /// Bytes beyond the first copy of each file
+    pub duplicated: u64,
+    /// The largest shared file, relative to the volume
+    pub example: PathBuf,
+}
+
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// Largest duplication first
+    pub groups: Vec<DuplicateGroup>,
+    pub files: usize,
+    /// Volumes whose directory couldn't be read
+    pub unreadable: Vec<String>,
+}
+
+struct Entry {
+    volume: usize,
+    path: PathBuf,
+    size: u64,
+}
+
+/// Scan the `(name, directory)` of each volume for files of at least
+/// `min_file_size` that more than one volume holds.
+pub fn scan(volumes: &[(String, PathBuf)], min_file_size: u64) -> Scan {
+    let mut scan = Scan::default();
+    let mut by_size: HashMap<u64, Vec<Entry>> = HashMap::new();
+    for (volume, (name, root)) in volumes.iter().enumerate() {
+        let Ok(files) = walk(root) else {
+            scan.unreadable.push(name.clone());
+            continue;
+        };
+        for (path, size) in files.into_iter().filter(|(_, s)| *s >= min_file_size) {
+            scan.files += 1;
+            by_size
+                .entry(size)
+                .or_default()
+                .push(Entry { volume, path, size });
+        }
+    }
+
+    // Only sizes seen in two volumes can be shared, so only those are read
+    let mut by_content: HashMap<(u64, u64), Vec<Entry>> = HashMap::new();
+    for entries in by_size.into_values().filter(|e| spans_volumes(e)) {
+        for entry in entries {
+            let root = &volumes[entry.volume].1;
+            if let Ok(hash) = sample_hash(&root.join(&entry.path), entry.size) {
+                by_content
+                    .entry((entry.size, hash))
+                    .or_default()
+                    .push(entry);
+            }
+        }
+    }
+
+    // Grouped by the set of volumes holding them, with the largest file
+    let mut groups: BTreeMap<Vec<usize>, (DuplicateGroup, u64)> = BTreeMap::new();
+    for entries in by_content.into_values().filter(|e| spans_volumes(e)) {
+        let mut holders: Vec<usize> = entries.iter().map(|e| e.volume).collect();
+        holders.sort_unstable();
+        holders.dedup();
+        let size = entries[0].size;
+        let copies = holders.len() as u64 - 1;
+        let (group, largest) = groups.entry(holders).or_insert_with_key(|holders| {
+            let group = DuplicateGroup {
+                volumes: holders.iter().map(|&v| volumes[v].0.clone()).collect(),
+                files: 0,
+                duplicated: 0,
+                example: PathBuf::new(),
+            };
+            (group, 0)
+        });
+        if size > *largest {
+            *largest = size;
+            group.example = entries[0].path.clone();
+        }
+        group.files += 1;
+        group.duplicated += size * copies;
+    }
+
+    scan.groups = groups.into_values().map(|(group, _)| group).collect();
+    scan.groups.sort_by_key(|g| Reverse(g.duplicated));
+    scan
+}
+
+fn spans_volumes(entries: &[Entry]) -> bool {
+    entries.iter().any(|e| e.volume != entries[0].volume)
+}
+
+/// Every regular file under `root` with its size, relative to `root`.
+/// Symlinks aren't followed, and unreadable subdirectories are skipped.
+fn walk(root: &Path) -> std::io::Result<Vec<(PathBuf, u64)>> {
+    fs::read_dir(root)?;
+    let mut files = Vec::new();
+    // Directories wait as paths, not open handles, so a deep tree can't
+    // run out of file descriptors
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            if metadata.is_dir() {
+                dirs.push(path);
+            } else if metadata.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                files.push((relative, metadata.len()));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Hash of the first and last [`SAMPLE`] bytes of a `size`-byte file.
+fn sample_hash(path: &Path, size: u64) -> std::io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = Vec::new();
+    (&mut file).take(SAMPLE).read_to_end(&mut buf)?;
+    if size > SAMPLE {
+        file.seek(SeekFrom::Start(size.saturating_sub(SAMPLE).max(SAMPLE)))?;
+        file.take(SAMPLE).read_to_end(&mut buf)?;
+    }
+    hasher.write(&buf);
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(root: &Path, path: &str, contents: &[u8]) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_finds_files_shared_between_volumes() {
+        let dir = tempdir().unwrap();
+        let volumes: Vec<(String, PathBuf)> = ["web", "worker", "db"]
+            .iter()
+            .map(|name| (name.to_string(), dir.path().join(name)))
+            .collect();
+        let big = vec![7u8; 200_000];
+        for (_, root) in &volumes[..2] {
+            write(root, "node_modules/lib/big.js", &big);
+            write(root, "node_modules/lib/small.js", &[1; 2048]);
+            write(root, "tiny.txt", b"same");
+        }
+        // Same size, different content
+        write(&volumes[1].1, "other.bin", &[2; 4096]);
+        write(&volumes[2].1, "data.bin", &[3; 4096]);
+
+        let scan = scan(&volumes, 1000);
+        assert_eq!(scan.files, 6);
+        assert_eq!(
+            scan.groups,
+            [DuplicateGroup {
+                volumes: vec!["web".to_string(), "worker".to_string()],
+                files: 2,
+                duplicated: 202_048,
+                example: PathBuf::from("node_modules/lib/big.js"),
+            }]
+        );
+        assert!(scan.unreadable.is_empty());
+    }
+
+    #[test]
+    fn test_samples_both_ends_of_large_files() {
+        let dir = tempdir().unwrap();
+        let mut a = vec![0u8; 300_000];
+        let mut b = a.clone();
+        a[299_999] = 1;
+        b[299_999] = 2;
+        write(dir.path(), "a", &a);
+        write(dir.path(), "b", &b);
+        assert_ne!(
+            sample_hash(&dir.path().join("a"), 300_000).unwrap(),
+            sample_hash(&dir.path().join("b"), 300_000).unwrap()
+        );
+
+        let missing = vec![("gone".to_string(), dir.path().join("missing"))];
+        assert_eq!(scan(&missing, 0).unreadable, ["gone"]);
+    }
+}
@@ -1,18 +1,329 @@
-use crate::resources::DiskUsage;
+use crate::active::ActiveItem;
+use crate::advice::Advice;
+use crate::failures;
+use crate::health::HealthScore;
+use crate::quota::{Offender, Status};
+use crate::read_only;
+use crate::render::{self, Level};
+use crate::report::{ItemStatus, ReportItem};
+use crate::resources::{DiskUsage, UnusedVolumes};
+use crate::transcript;
+use crate::units::format_docker_size;
 use bytesize::ByteSize;
-use colored::Colorize;
-use comfy_table::{presets::UTF8_BORDERS_ONLY, Table};
+use clap::ValueEnum;
+use colored::{Color, ColoredString, Colorize};
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Cell, CellAlignment, ContentArrangement, Table};
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+use serde::Deserialize;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// `println!` that also adds the line to the transcript (see `transcript`).
+macro_rules! say {
+    () => {
+        say!("")
+    };
+    ($($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        println!("{}", line);
+        transcript::record(&line);
+    }};
+}
+
+/// Whether human-oriented output should be printed. False in JSON, YAML and
+/// quiet modes; errors are always printed to stderr regardless.
+pub fn is_text() -> bool {
+    render::current().shows_text()
+}
+
+/// Whether the report lists every resource; only JSON and YAML do.
+pub fn lists_resources() -> bool {
+    render::current().lists_resources()
+}
+
+/// Color presets for what output means: good, caution, danger and info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    /// Green, yellow, red and blue
+    #[default]
+    Default,
+    /// Sky blue, yellow and vermillion (Okabe-Ito), which stay apart with
+    /// red-green color blindness
+    ColorBlind,
+    /// No colors; danger is underlined instead
+    Monochrome,
+}
+
+/// The color for each meaning; `None` leaves the text uncolored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Styles {
+    pub good: Option<Color>,
+    pub caution: Option<Color>,
+    pub danger: Option<Color>,
+    pub info: Option<Color>,
+}
+
+impl Styles {
+    pub fn preset(palette: Palette) -> Self {
+        match palette {
+            Palette::Default => Self {
+                good: Some(Color::Green),
+                caution: Some(Color::Yellow),
+                danger: Some(Color::Red),
+                info: Some(Color::Blue),
+            },
+            Palette::ColorBlind => Self {
+                good: Some(Color::TrueColor {
+                    r: 86,
+                    g: 180,
+                    b: 233,
+                }),
+                caution: Some(Color::TrueColor {
+                    r: 240,
+                    g: 228,
+                    b: 66,
+                }),
+                danger: Some(Color::TrueColor {
+                    r: 213,
+                    g: 94,
+                    b: 0,
+                }),
+                info: Some(Color::Cyan),
+            },
+            Palette::Monochrome => Self {
+                good: None,
+                caution: None,
+                danger: None,
+                info: None,
+            },
+        }
+    }
+}
+
+static STYLES: OnceLock<Styles> = OnceLock::new();
+
+/// Set the global colors. Output before this uses the default palette.
+pub fn configure_styles(styles: Styles) {
+    STYLES.set(styles).ok();
+}
+
+fn styles() -> &'static Styles {
+    STYLES.get_or_init(|| Styles::preset(Palette::Default))
+}
+
+/// A color name as `colored` spells it (`"red"`, `"bright blue"`) or
+/// `#rrggbb`.
+pub fn parse_color(spec: &str) -> Result<Color, String> {
+    let invalid = || format!("invalid color '{}'", spec);
+    if let Some(hex) = spec.strip_prefix('#') {
+        if hex.len() != 6 {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        return Ok(Color::TrueColor {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    spec.parse().map_err(|_| invalid())
+}
+
+/// Color text by what it means rather than by hue, so the palette can be
+/// swapped for one that doesn't rely on telling red from green.
+pub trait Styled {
+    fn good(&self) -> ColoredString;
+    fn caution(&self) -> ColoredString;
+    fn danger(&self) -> ColoredString;
+    fn info(&self) -> ColoredString;
+}
+
+impl Styled for str {
+    fn good(&self) -> ColoredString {
+        paint(self, styles().good)
+    }
+
+    fn caution(&self) -> ColoredString {
+        paint(self, styles().caution)
+    }
+
+    fn danger(&self) -> ColoredString {
+        match styles().danger {
+            Some(color) => self.color(color),
+            // Without color, danger still has to stand out
+            None => self.underline(),
+        }
+    }
+
+    fn info(&self) -> ColoredString {
+        paint(self, styles().info)
+    }
+}
+
+fn paint(text: &str, color: Option<Color>) -> ColoredString {
+    match color {
+        Some(color) => text.color(color),
+        None => text.normal(),
+    }
+}
+
+/// Print a line of human-oriented output (suppressed in JSON/YAML/quiet modes).
+pub fn print_text(line: &str) {
+    if is_text() {
+        clear_progress();
+        say!("{}", line);
+    }
+}
+
+/// Show long output in `$PAGER` (else `less -R`) when on a terminal, or
+/// just print it.
+pub fn page(text: &str) {
+    if !is_text() {
+        return;
+    }
+    if io::stdout().is_terminal() && show_in_pager(text).is_ok() {
+        transcript::record(text.trim_end());
+        return;
+    }
+    say!("{}", text.trim_end());
+}
+
+fn show_in_pager(text: &str) -> io::Result<()> {
+    let default = if cfg!(windows) { "more" } else { "less -R" };
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| default.to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or(default);
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user may quit before reading everything
+        stdin.write_all(text.as_bytes()).ok();
+    }
+    child.wait().map(drop)
+}
+
+/// Print structured output (JSON, YAML, ...) exactly as rendered.
+pub fn print_output(output: &str) {
+    print!("{}", output);
+    transcript::record(output.trim_end());
+}
+
+/// Ask a yes/no question. Like every prompt here, the question and the
+/// answer go to the transcript too.
+pub fn confirm(prompt: &str, default: bool) -> Result<bool, String> {
+    let answer = Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()
+        .map_err(|e| e.to_string())?;
+    transcript::record(&format!("{} {}", prompt, if answer { "yes" } else { "no" }));
+    Ok(answer)
+}
+
+/// Let the user tick any of `items`, `defaults` ticked to begin with.
+/// Returns the indices ticked.
+pub fn multi_select<T: ToString>(
+    prompt: Option<&str>,
+    items: &[T],
+    defaults: &[bool],
+) -> Result<Vec<usize>, String> {
+    let mut menu = MultiSelect::new();
+    if let Some(prompt) = prompt {
+        menu = menu.with_prompt(prompt);
+    }
+    let chosen = menu
+        .items(items)
+        .defaults(defaults)
+        .interact()
+        .map_err(|e| e.to_string())?;
+    if let Some(prompt) = prompt {
+        transcript::record(prompt);
+    }
+    for (i, item) in items.iter().enumerate() {
+        let mark = if chosen.contains(&i) { "[x]" } else { "[ ]" };
+        transcript::record(&format!("  {} {}", mark, item.to_string()));
+    }
+    Ok(chosen)
+}
+
+/// Let the user pick one of `items`, or none with Esc.
+pub fn select<T: ToString>(
+    prompt: &str,
+    items: &[T],
+    default: usize,
+) -> Result<Option<usize>, String> {
+    let choice = Select::new()
+        .with_prompt(prompt)
+        .items(items)
+        .default(default)
+        .interact_opt()
+        .map_err(|e| e.to_string())?;
+    let answer = choice.map_or_else(|| "(none)".to_string(), |i| items[i].to_string());
+    transcript::record(&format!("{} {}", prompt, answer));
+    Ok(choice)
+}
+
+/// Ask for a line of text, which may be empty.
+pub fn input(prompt: &str) -> Result<String, String> {
+    let answer = Input::<String>::new()
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .interact_text()
+        .map_err(|e| e.to_string())?;
+    transcript::record(&format!("{}: {}", prompt, answer));
+    Ok(answer)
+}
+
+/// How sizes are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SizeFormat {
+    /// One decimal and a space, e.g. `1.2 GB`
+    #[default]
+    Default,
+    /// Exactly as `docker system df` prints them, e.g. `1.235GB`, so they
+    /// match it side by side
+    Docker,
+}
+
+static SIZE_FORMAT: OnceLock<SizeFormat> = OnceLock::new();
+
+/// Set how sizes are printed. Output before this uses the default format.
+pub fn configure_size_format(format: SizeFormat) {
+    SIZE_FORMAT.set(format).ok();
+}
 
 pub fn format_bytes(bytes: u64) -> String {
-    ByteSize::b(bytes).to_string()
+    match SIZE_FORMAT.get().copied().unwrap_or_default() {
+        SizeFormat::Default => ByteSize::b(bytes).to_string(),
+        SizeFormat::Docker => format_docker_size(bytes),
+    }
 }
 
 pub fn print_header() {
-    println!("{}", "Docker Space Usage".bold().cyan());
-    println!("{}", "═".repeat(50).dimmed());
+    if !is_text() {
+        return;
+    }
+
+    say!("{}", "Docker Space Usage".bold().cyan());
+    say!("{}", "═".repeat(50).dimmed());
 }
 
 pub fn print_disk_usage(usage: &DiskUsage) {
+    if !is_text() {
+        return;
+    }
+
     let mut table = Table::new();
     table.load_preset(UTF8_BORDERS_ONLY);
     table.set_header(vec!["TYPE", "TOTAL", "RECLAIMABLE"]);
@@ -55,116 +366,554 @@ pub fn print_disk_usage(usage: &DiskUsage) {
         format_bytes(usage.build_cache_reclaimable),
     ]);
 
-    println!("{table}");
-    println!();
-    println!(
+    say!("{table}");
+    say!();
+    say!(
         "{} {}",
         "Total Reclaimable:".bold(),
-        format_bytes(usage.total_reclaimable()).green().bold()
+        format_bytes(usage.total_reclaimable()).good().bold()
+    );
+}
+
+pub fn print_unused_volumes(volumes: &UnusedVolumes) {
+    if !is_text() || (volumes.orphaned.is_empty() && volumes.unmounted.is_empty()) {
+        return;
+    }
+
+    say!();
+    if !volumes.orphaned.is_empty() {
+        say!(
+            "{} {} orphaned anonymous volume{} (container removed, safe to delete)",
+            "→".info().bold(),
+            volumes.orphaned.len(),
+            if volumes.orphaned.len() == 1 { "" } else { "s" }
+        );
+    }
+    if !volumes.unmounted.is_empty() {
+        say!(
+            "{} {} named volume{} not mounted by any container: {}",
+            "→".info().bold(),
+            volumes.unmounted.len(),
+            if volumes.unmounted.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+            volumes.unmounted.join(", ").dimmed()
+        );
+    }
+}
+
+/// Resources of one `kind` left alone because the config protects them.
+pub fn print_protected(kind: &str, names: &[String]) {
+    if !is_text() || names.is_empty() {
+        return;
+    }
+
+    say!(
+        "{} Skipping protected {}: {}",
+        "⚠".caution().bold(),
+        kind,
+        names.join(", ").dimmed()
+    );
+}
+
+/// Resources in active use that a cleanup was about to remove, with why.
+pub fn print_active(items: &[ActiveItem]) {
+    if !is_text() || items.is_empty() {
+        return;
+    }
+
+    say!(
+        "{} {} in active use:",
+        "⚠".caution().bold(),
+        if items.len() == 1 {
+            "1 resource is".to_string()
+        } else {
+            format!("{} resources are", items.len())
+        }
+    );
+    for item in items {
+        say!(
+            "  {} {} {}",
+            item.category.trim_end_matches('s'),
+            item.name.bold(),
+            format!("({})", item.reason).dimmed()
+        );
+    }
+}
+
+pub fn print_forecast(days: f64) {
+    if !is_text() {
+        return;
+    }
+
+    let days = days.round().max(1.0) as u64;
+    let message = format!(
+        "At this rate, Docker will fill your disk in ~{} day{}",
+        days,
+        if days == 1 { "" } else { "s" }
+    );
+    say!();
+    if days <= 7 {
+        say!("{} {}", "⚠".danger().bold(), message.danger().bold());
+    } else if days <= 30 {
+        say!("{} {}", "⚠".caution().bold(), message.caution());
+    } else {
+        say!("{} {}", "→".info().bold(), message.dimmed());
+    }
+}
+
+/// The soft-quota nag: how far over Docker is, its largest resources and
+/// the command that cleans up.
+pub fn print_quota_nag(status: &Status, offenders: &[Offender]) {
+    if !is_text() {
+        return;
+    }
+    say!(
+        "{} {}",
+        "⚠".caution().bold(),
+        format!(
+            "Docker is using {}, over its {} quota",
+            format_bytes(status.used),
+            format_bytes(status.max_usage)
+        )
+        .caution()
+    );
+    for offender in offenders {
+        say!(
+            "    {:<12} {:<32} {:>10}",
+            offender.kind.dimmed(),
+            offender.name,
+            format_bytes(offender.size)
+        );
+    }
+    say!(
+        "  {} Run {} to clean up safely",
+        "→".info().bold(),
+        "dockerase purge".cyan().bold()
     );
+    say!();
+}
+
+/// Width of the health gauge in characters.
+const GAUGE_WIDTH: usize = 20;
+
+pub fn print_health(health: &HealthScore) {
+    if !is_text() {
+        return;
+    }
+
+    let filled = usize::from(health.score) * GAUGE_WIDTH / 100;
+    let gauge = format!("{}{}", "█".repeat(filled), "░".repeat(GAUGE_WIDTH - filled));
+    let (gauge, score) = match health.score {
+        75.. => (gauge.good(), health.score.to_string().good().bold()),
+        50..=74 => (gauge.caution(), health.score.to_string().caution().bold()),
+        _ => (gauge.danger(), health.score.to_string().danger().bold()),
+    };
+    say!();
+    say!("{} {} {}/100", "Disk health:".bold(), gauge, score);
 }
 
 pub fn print_footer() {
-    println!();
-    println!("{}", "─".repeat(50).dimmed());
-    println!("Run {} to clean up safely", "dockerase purge".cyan().bold());
-    println!(
+    if !is_text() {
+        return;
+    }
+
+    say!();
+    say!("{}", "─".repeat(50).dimmed());
+    say!("Run {} to clean up safely", "dockerase purge".cyan().bold());
+    say!(
         "Run {} to remove everything",
-        "dockerase --nuclear".red().bold()
+        "dockerase --nuclear".danger().bold()
     );
 }
 
+/// What is still worth cleaning after a run, with the command for each.
+pub fn print_advice(advice: &[Advice]) {
+    if !is_text() || advice.is_empty() {
+        return;
+    }
+
+    say!();
+    say!("{}", "Next steps:".bold());
+    for step in advice {
+        say!("  {} - run {}", step.message, step.command.cyan().bold());
+    }
+}
+
 pub fn print_success(message: &str) {
-    println!("{} {}", "✓".green().bold(), message);
+    print_message(Level::Success, message);
 }
 
 pub fn print_warning(message: &str) {
-    println!("{} {}", "⚠".yellow().bold(), message);
+    print_message(Level::Warning, message);
 }
 
 pub fn print_error(message: &str) {
-    eprintln!("{} {}", "✗".red().bold(), message);
+    print_message(Level::Error, message);
 }
 
 pub fn print_info(message: &str) {
-    println!("{} {}", "→".blue().bold(), message);
+    print_message(Level::Info, message);
+}
+
+fn print_message(level: Level, message: &str) {
+    clear_progress();
+    match render::current().message(level, message) {
+        Some(line) if level == Level::Error => {
+            eprintln!("{}", line);
+            transcript::record(&line);
+        }
+        Some(line) => say!("{}", line),
+        None => {}
+    }
+}
+
+/// What a progress bar counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressUnit {
+    Items,
+    Bytes,
+}
+
+/// A bar is redrawn at most this often.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+const BAR_WIDTH: usize = 30;
+
+/// Whether a bar is on screen; printing a line clears it first, and the
+/// next update draws it again below.
+static BAR_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// A progress bar redrawn in place on stderr while something long runs,
+/// like removing hundreds of images or a cache of many gigabytes. It is
+/// only drawn for text output on a terminal, and can be advanced from
+/// several threads. Dropping it clears it.
+pub struct ProgressBar {
+    label: String,
+    total: u64,
+    unit: ProgressUnit,
+    visible: bool,
+    state: Mutex<(u64, Option<Instant>)>,
+}
+
+impl ProgressBar {
+    pub fn items(label: &str, total: usize) -> Self {
+        Self::new(label, total as u64, ProgressUnit::Items)
+    }
+
+    pub fn bytes(label: &str, total: u64) -> Self {
+        Self::new(label, total, ProgressUnit::Bytes)
+    }
+
+    fn new(label: &str, total: u64, unit: ProgressUnit) -> Self {
+        Self {
+            label: label.to_string(),
+            total,
+            unit,
+            visible: is_text() && io::stderr().is_terminal(),
+            state: Mutex::new((0, None)),
+        }
+    }
+
+    pub fn inc(&self, n: u64) {
+        self.update(|done| *done += n);
+    }
+
+    pub fn set(&self, done: u64) {
+        self.update(|done_so_far| *done_so_far = done);
+    }
+
+    fn update(&self, change: impl FnOnce(&mut u64)) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        change(&mut state.0);
+        let due = state.1.is_none_or(|t| t.elapsed() >= REDRAW_INTERVAL);
+        if self.visible && (due || state.0 >= self.total) {
+            state.1 = Some(Instant::now());
+            eprint!("\r\x1b[2K{}", self.line(state.0));
+            io::stderr().flush().ok();
+            BAR_SHOWN.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn line(&self, done: u64) -> String {
+        let done = done.min(self.total);
+        let filled = (done as f64 / self.total.max(1) as f64 * BAR_WIDTH as f64) as usize;
+        let count = match self.unit {
+            ProgressUnit::Items => format!("{}/{}", done, self.total),
+            ProgressUnit::Bytes => format!("{}/{}", format_bytes(done), format_bytes(self.total)),
+        };
+        format!(
+            "  {} [{}{}] {}",
+            self.label,
+            "█".repeat(filled),
+            "░".repeat(BAR_WIDTH - filled),
+            count
+        )
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        if self.visible {
+            clear_progress();
+        }
+    }
+}
+
+fn clear_progress() {
+    if BAR_SHOWN.swap(false, Ordering::SeqCst) {
+        eprint!("\r\x1b[2K");
+        io::stderr().flush().ok();
+    }
+}
+
+/// What a cleanup couldn't remove and why, under a count of how it went.
+/// Prints nothing when everything went.
+pub fn print_failures(items: &[ReportItem]) {
+    let in_use = |i: &&ReportItem| {
+        i.status == ItemStatus::Skipped
+            && i.note
+                .as_deref()
+                .is_some_and(|n| n.starts_with(failures::IN_USE_NOTE))
+    };
+    let unattempted = |i: &&ReportItem| {
+        i.status == ItemStatus::Failed && i.error.as_deref() == Some(failures::NOT_ATTEMPTED)
+    };
+    let failed: Vec<&ReportItem> = items
+        .iter()
+        .filter(|i| (i.status == ItemStatus::Failed && !unattempted(i)) || in_use(i))
+        .collect();
+    if failed.is_empty() || !is_text() {
+        return;
+    }
+    let mut counts = vec![
+        format!(
+            "{} removed",
+            items
+                .iter()
+                .filter(|i| i.status == ItemStatus::Removed)
+                .count()
+        ),
+        format!(
+            "{} failed",
+            failed
+                .iter()
+                .filter(|i| i.status == ItemStatus::Failed)
+                .count()
+        ),
+    ];
+    for (count, what) in [
+        (items.iter().filter(in_use).count(), "still in use"),
+        (items.iter().filter(unattempted).count(), "not attempted"),
+    ] {
+        if count > 0 {
+            counts.push(format!("{} {}", count, what));
+        }
+    }
+    say!();
+    print_warning(&counts.join(", "));
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_content_arrangement(ContentArrangement::Dynamic);
+    table.set_header(vec!["CATEGORY", "NAME", "RESULT", "REASON"]);
+    for item in failed {
+        let (result, reason) = match item.status {
+            ItemStatus::Failed => ("failed".danger().to_string(), item.error.as_deref()),
+            _ => ("in use".caution().to_string(), item.note.as_deref()),
+        };
+        let reason = reason.unwrap_or_default();
+        table.add_row(vec![
+            item.category.replace('_', " "),
+            item.name.clone(),
+            result,
+            reason
+                .strip_prefix(&format!("{}: ", failures::IN_USE_NOTE))
+                .unwrap_or(reason)
+                .to_string(),
+        ]);
+    }
+    say!("{table}");
+}
+
+/// What a cancelled cleanup did and didn't get to.
+pub fn print_cancelled(items: &[ReportItem]) {
+    if !is_text() {
+        return;
+    }
+    let removed = items
+        .iter()
+        .filter(|i| i.status == ItemStatus::Removed)
+        .count();
+    let left: Vec<&str> = items
+        .iter()
+        .filter(|i| i.status == ItemStatus::Failed)
+        .map(|i| i.name.as_str())
+        .collect();
+    say!();
+    print_warning(&format!(
+        "Cancelled: {} removed, {} not removed",
+        removed,
+        left.len()
+    ));
+    const SHOWN: usize = 10;
+    for name in left.iter().take(SHOWN) {
+        say!("  {} {}", "not removed:".dimmed(), name);
+    }
+    if left.len() > SHOWN {
+        say!(
+            "  {}",
+            format!("... and {} more", left.len() - SHOWN).dimmed()
+        );
+    }
 }
 
 pub fn print_space_saved(before: u64, after: u64) {
+    if !is_text() {
+        return;
+    }
+
     let saved = before.saturating_sub(after);
     if saved > 0 {
-        println!();
-        println!(
+        say!();
+        say!(
             "{} {} {}",
             "Space freed:".bold(),
-            format_bytes(saved).green().bold(),
+            format_bytes(saved).good().bold(),
             format!("({} → {})", format_bytes(before), format_bytes(after)).dimmed()
         );
     }
 }
 
+/// `UTF8_BORDERS_ONLY` drawn with double lines.
+const DOUBLE_BORDERS_ONLY: &str = "║║══╠══╣     ══╔╗╚╝";
+
+/// What `--nuclear` deletes, one line per resource kind.
+const NUCLEAR_TARGETS: &[&str] = &[
+    "This will PERMANENTLY DELETE:",
+    "• ALL containers (running and stopped)",
+    "• ALL images",
+    "• ALL volumes (including data!)",
+    "• ALL custom networks",
+    "• ALL build cache",
+];
+
+/// The nuclear warning panel, wrapped to fit `width` columns (the terminal
+/// width when `None`). Uses the text-style `⚠`: the emoji form has no
+/// agreed width and would push the right border out of line.
+fn nuclear_warning_box(width: Option<u16>) -> Vec<String> {
+    let mut table = Table::new();
+    table
+        .load_preset(DOUBLE_BORDERS_ONLY)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            Cell::new("⚠  NUCLEAR MODE WARNING  ⚠").set_alignment(CellAlignment::Center)
+        ]);
+    for line in NUCLEAR_TARGETS {
+        table.add_row(vec![*line]);
+    }
+    if let Some(width) = width {
+        table.set_width(width);
+    }
+    table.lines().collect()
+}
+
 pub fn print_nuclear_warning() {
-    println!();
-    println!(
-        "{}",
-        "╔══════════════════════════════════════════════════════╗"
-            .red()
-            .bold()
-    );
-    println!(
-        "{}",
-        "║            ⚠️  NUCLEAR MODE WARNING ⚠️                 ║"
-            .red()
-            .bold()
-    );
-    println!(
-        "{}",
-        "╠══════════════════════════════════════════════════════╣"
-            .red()
-            .bold()
-    );
-    println!(
-        "{}",
-        "║  This will PERMANENTLY DELETE:                       ║".red()
-    );
-    println!(
-        "{}",
-        "║  • ALL containers (running and stopped)              ║".red()
-    );
-    println!(
-        "{}",
-        "║  • ALL images                                        ║".red()
-    );
-    println!(
-        "{}",
-        "║  • ALL volumes (including data!)                     ║".red()
-    );
-    println!(
-        "{}",
-        "║  • ALL custom networks                               ║".red()
-    );
-    println!(
-        "{}",
-        "║  • ALL build cache                                   ║".red()
-    );
-    println!(
-        "{}",
-        "╚══════════════════════════════════════════════════════╝"
-            .red()
-            .bold()
-    );
-    println!();
+    if !is_text() {
+        return;
+    }
+
+    say!();
+    let lines = nuclear_warning_box(None);
+    // Borders and title up to the header separator are bold
+    let header = lines.iter().position(|l| l.starts_with('╠')).unwrap_or(0);
+    for (i, line) in lines.iter().enumerate() {
+        if i <= header {
+            say!("{}", line.danger().bold());
+        } else {
+            say!("{}", line.danger());
+        }
+    }
+    say!();
 }
 
 pub fn print_dry_run_header() {
-    println!("{}", "[DRY RUN] No changes will be made".yellow().bold());
-    println!();
+    if !is_text() {
+        return;
+    }
+
+    if read_only::is_enabled() {
+        say!(
+            "{}",
+            "[READ-ONLY] No changes will be made (DOCKERASE_READ_ONLY or read_only in config)"
+                .caution()
+                .bold()
+        );
+    } else {
+        say!("{}", "[DRY RUN] No changes will be made".caution().bold());
+    }
+    say!();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_progress_bar_line_fills_with_progress() {
+        let images = ProgressBar::items("Removing images", 120);
+        assert_eq!(
+            images.line(60),
+            format!(
+                "  Removing images [{}{}] 60/120",
+                "█".repeat(15),
+                "░".repeat(15)
+            )
+        );
+        let cache = ProgressBar::bytes("Clearing caches", 40_000_000_000);
+        assert!(cache
+            .line(50_000_000_000)
+            .ends_with(&format!("{}] 40.0 GB/40.0 GB", "█".repeat(BAR_WIDTH))));
+    }
+
+    #[test]
+    fn test_nuclear_warning_box_is_aligned_at_any_width() {
+        for width in [None, Some(30), Some(80)] {
+            let lines = nuclear_warning_box(width);
+            // Every character in the box is one column wide
+            let widths: Vec<usize> = lines.iter().map(|l| l.chars().count()).collect();
+            assert!(widths.iter().all(|w| *w == widths[0]), "{lines:#?}");
+            if let Some(width) = width {
+                assert!(widths[0] <= width as usize);
+            }
+            assert!(lines.iter().all(|l| l.starts_with(['╔', '║', '╠', '╚'])));
+        }
+        // Narrow terminals wrap the text instead of cutting it
+        let narrow = nuclear_warning_box(Some(30)).join("\n");
+        assert!(narrow.contains("PERMANENTLY"));
+        assert!(nuclear_warning_box(Some(30)).len() > nuclear_warning_box(Some(80)).len());
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("bright blue"), Ok(Color::BrightBlue));
+        assert_eq!(
+            parse_color("#0072b2"),
+            Ok(Color::TrueColor {
+                r: 0,
+                g: 0x72,
+                b: 0xb2
+            })
+        );
+        assert!(parse_color("#0072").is_err());
+        assert!(parse_color("#zz72b2").is_err());
+        assert!(parse_color("chartreuse").is_err());
+    }
+
     #[test]
     fn test_format_bytes_zero() {
         assert_eq!(format_bytes(0), "0 B");
@@ -1,15 +1,304 @@
-use crate::resources::{Container, DiskUsage, Image, Network, Volume};
-use std::process::Command;
+use crate::cancel;
+use crate::display::ProgressBar;
+use crate::history::now_secs;
+use crate::layers::{self, ImageLayers};
+use crate::paths::installed;
+use crate::policy;
+use crate::read_only;
+use crate::resources::{
+    ActiveResources, BuildCacheRecord, Builder, ComposeProject, Container, DiskUsage,
+    DockerContext, Image, Network, UnusedVolumes, Volume, COMPOSE_PROJECT_LABEL,
+};
+use crate::system::{disk_space, get_home_dir, DiskSpace};
+use crate::undo::ImageRecord;
+use crate::units::parse_docker_age;
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub struct Docker;
 
+/// The daemon to talk to instead of the docker CLI's current one, from the
+/// global `--context` and `--host` flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Context(String),
+    Host(String),
+}
+
+static TARGET: OnceLock<Option<Target>> = OnceLock::new();
+
+/// The container engine whose CLI dockerase runs, from the global
+/// `--runtime` flag or detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Runtime {
+    Docker,
+    Podman,
+}
+
+impl Runtime {
+    pub fn binary(self) -> &'static str {
+        match self {
+            Runtime::Docker => "docker",
+            Runtime::Podman => "podman",
+        }
+    }
+
+    /// The global flags pointing the CLI at `target`; Podman calls contexts
+    /// connections.
+    fn target_args(self, target: &Target) -> [&str; 2] {
+        match (self, target) {
+            (Runtime::Docker, Target::Context(name)) => ["--context", name],
+            (Runtime::Docker, Target::Host(host)) => ["--host", host],
+            (Runtime::Podman, Target::Context(name)) => ["--connection", name],
+            (Runtime::Podman, Target::Host(host)) => ["--url", host],
+        }
+    }
+}
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Use `runtime`, or detect one when not given. Must be called before the
+/// first docker call.
+pub fn configure_runtime(runtime: Option<Runtime>) {
+    RUNTIME.set(runtime.unwrap_or_else(detect_runtime)).ok();
+}
+
+pub fn runtime() -> Runtime {
+    *RUNTIME.get_or_init(detect_runtime)
+}
+
+/// Docker, unless only Podman is installed. A substitute binary from
+/// `DOCKERASE_DOCKER` counts as docker.
+fn detect_runtime() -> Runtime {
+    if env::var_os("DOCKERASE_DOCKER").is_none() && !installed("docker") && installed("podman") {
+        Runtime::Podman
+    } else {
+        Runtime::Docker
+    }
+}
+
+/// Point every docker call at `target`. Must be called before the first one.
+pub fn configure_target(target: Option<Target>) {
+    TARGET.set(target).ok();
+}
+
+fn target() -> Option<&'static Target> {
+    TARGET.get().and_then(Option::as_ref)
+}
+
+/// The context commands run against: `--context`, else `$DOCKER_CONTEXT`,
+/// else `currentContext` from the docker CLI config
+/// (`$DOCKER_CONFIG/config.json` or `~/.docker/config.json`), else
+/// `default`. Read directly so it never runs docker.
+pub fn current_context() -> String {
+    if let Some(Target::Context(name)) = target() {
+        return name.clone();
+    }
+    if runtime() == Runtime::Podman {
+        return env::var("CONTAINER_CONNECTION").unwrap_or_else(|_| "default".to_string());
+    }
+    if let Ok(context) = env::var("DOCKER_CONTEXT") {
+        return context;
+    }
+    env::var_os("DOCKER_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".docker")))
+        .and_then(|dir| fs::read_to_string(dir.join("config.json")).ok())
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .and_then(|v| v["currentContext"].as_str().map(str::to_string))
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// The daemon in use when it isn't the local default: the `--context` or
+/// `--host` target, else `$DOCKER_HOST` (`$CONTAINER_HOST` for Podman), else
+/// a non-default current context.
+pub fn explicit_target() -> Option<Target> {
+    if let Some(target) = target() {
+        return Some(target.clone());
+    }
+    let host_var = match runtime() {
+        Runtime::Docker => "DOCKER_HOST",
+        Runtime::Podman => "CONTAINER_HOST",
+    };
+    if let Ok(host) = env::var(host_var) {
+        return Some(Target::Host(host));
+    }
+    let context = current_context();
+    (context != "default").then_some(Target::Context(context))
+}
+
+/// The explicit target, e.g. `docker context 'build-1'`, so nobody cleans
+/// up the wrong machine by accident.
+pub fn describe_target() -> Option<String> {
+    let runtime = runtime();
+    explicit_target().map(|target| match target {
+        Target::Context(name) if runtime == Runtime::Podman => {
+            format!("podman connection '{}'", name)
+        }
+        Target::Context(name) => format!("docker context '{}'", name),
+        Target::Host(host) => format!("{} host {}", runtime.binary(), host),
+    })
+}
+
+impl Target {
+    pub fn name(&self) -> &str {
+        match self {
+            Target::Context(name) | Target::Host(name) => name,
+        }
+    }
+}
+
+/// The machine the `--host` or `$DOCKER_HOST` target is on, if there is a
+/// remote one.
+pub fn target_machine() -> Option<String> {
+    match explicit_target()? {
+        Target::Host(url) => host_machine(&url).map(str::to_string),
+        Target::Context(_) => None,
+    }
+}
+
+/// The machine a daemon URL points at, e.g. `prod-db` for
+/// `ssh://deploy@prod-db:22` or `tcp://prod-db:2376`; `None` for a socket
+/// on this one.
+fn host_machine(url: &str) -> Option<&str> {
+    if is_local_endpoint(url) {
+        return None;
+    }
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+/// Whether an endpoint is a socket or named pipe on this machine.
+fn is_local_endpoint(endpoint: &str) -> bool {
+    endpoint.starts_with("unix://") || endpoint.starts_with("npipe://")
+}
+
+/// The runtime's CLI to run. `DOCKERASE_DOCKER` substitutes a compatible
+/// binary, which is how the integration tests inject a fake backend.
+fn docker_bin() -> OsString {
+    env::var_os("DOCKERASE_DOCKER").unwrap_or_else(|| OsString::from(runtime().binary()))
+}
+
 impl Docker {
-    fn run_command(args: &[&str]) -> Result<String, String> {
-        let output = Command::new("docker")
-            .args(args)
+    /// A docker invocation against `context`, else the `--context` or
+    /// `--host` target.
+    fn command(context: Option<&str>, args: &[&str]) -> Command {
+        let mut cmd = Command::new(docker_bin());
+        let context = context.map(|c| Target::Context(c.to_string()));
+        if let Some(target) = context.as_ref().or(target()) {
+            cmd.args(runtime().target_args(target));
+        }
+        cmd.args(args);
+        cmd
+    }
+
+    /// Whether the daemon runs on this machine, so the paths it reports
+    /// (data root, log files, container layers) are local ones. Remote
+    /// targets and contexts are never touched through the local filesystem.
+    pub fn is_local() -> bool {
+        static LOCAL: OnceLock<bool> = OnceLock::new();
+        *LOCAL.get_or_init(|| match target() {
+            None => true,
+            Some(Target::Host(host)) => is_local_endpoint(host),
+            Some(Target::Context(name)) => Self::list_contexts()
+                .unwrap_or_default()
+                .iter()
+                .any(|c| &c.name == name && is_local_endpoint(&c.endpoint)),
+        })
+    }
+
+    pub fn run_command(args: &[&str]) -> Result<String, String> {
+        let output = Self::command(None, args)
             .output()
             .map_err(|e| format!("Failed to execute docker: {}", e))?;
+        Self::command_result(output)
+    }
+
+    /// Run a command and `read` its output as it arrives, instead of
+    /// buffering it whole: a listing on a host with tens of thousands of
+    /// containers is parsed as it streams in. Callers still get the parsed
+    /// listing whole once the command is done.
+    pub fn stream_command<T>(
+        args: &[&str],
+        read: impl FnOnce(&mut dyn BufRead) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut child = Self::command(None, args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute docker: {}", e))?;
+
+        // Drained alongside stdout, so a chatty stderr can't block docker
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let errors = thread::spawn(move || {
+            let mut errors = String::new();
+            stderr.read_to_string(&mut errors).ok();
+            errors
+        });
+        let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let result = read(&mut stdout);
+        // Whatever `read` left unread, so docker isn't stopped by a full pipe
+        io::copy(&mut stdout, &mut io::sink()).ok();
+        drop(stdout);
+
+        let status = child
+            .wait()
+            .map_err(|e| format!("Failed to wait for docker: {}", e))?;
+        let errors = errors.join().unwrap_or_default();
+        if !status.success() && !errors.is_empty() {
+            return Err(errors);
+        }
+        result
+    }
+
+    /// Run against a specific context, giving up if it doesn't answer within
+    /// `timeout` (e.g. an SSH host that is offline).
+    fn run_in_context(context: &str, args: &[&str], timeout: Duration) -> Result<String, String> {
+        let mut child = Self::command(Some(context), args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute docker: {}", e))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match child.try_wait() {
+                Ok(Some(_)) => break,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+                Ok(None) => {
+                    child.kill().ok();
+                    child.wait().ok();
+                    return Err(format!("No response within {}s", timeout.as_secs()));
+                }
+                Err(e) => return Err(format!("Failed to wait for docker: {}", e)),
+            }
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to read docker output: {}", e))?;
+        Self::command_result(output)
+    }
 
+    fn command_result(output: Output) -> Result<String, String> {
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).to_string())
         } else {
@@ -23,212 +312,1110 @@ impl Docker {
     }
 
     pub fn is_available() -> bool {
-        Command::new("docker")
-            .arg("version")
-            .output()
-            .map(|o| o.status.success())
-            .unwrap_or(false)
+        backend().is_available()
     }
 
     pub fn get_disk_usage() -> Result<DiskUsage, String> {
-        let output = Self::run_command(&["system", "df", "--format", "{{json .}}"])?;
-        let mut usage = DiskUsage::default();
+        backend().disk_usage()
+    }
 
-        for line in output.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) {
-                let type_name = entry["Type"].as_str().unwrap_or("");
-                let size = parse_size(entry["Size"].as_str().unwrap_or("0"));
-                let reclaimable_str = entry["Reclaimable"].as_str().unwrap_or("0");
-                let reclaimable = parse_reclaimable(reclaimable_str);
-                let count = entry["TotalCount"].as_i64().unwrap_or(0) as usize;
-                let active = entry["Active"].as_i64().unwrap_or(0) as usize;
-
-                match type_name {
-                    "Images" => {
-                        usage.images_size = size;
-                        usage.images_reclaimable = reclaimable;
-                        usage.images_count = count;
-                        usage.images_active = active;
-                    }
-                    "Containers" => {
-                        usage.containers_size = size;
-                        usage.containers_reclaimable = reclaimable;
-                        usage.containers_count = count;
-                        usage.containers_active = active;
-                    }
-                    "Local Volumes" => {
-                        usage.volumes_size = size;
-                        usage.volumes_reclaimable = reclaimable;
-                        usage.volumes_count = count;
-                        usage.volumes_active = active;
-                    }
-                    "Build Cache" => {
-                        usage.build_cache_size = size;
-                        usage.build_cache_reclaimable = reclaimable;
-                        usage.build_cache_count = count;
-                        usage.build_cache_active = active;
-                    }
-                    _ => {}
-                }
+    /// Disk usage of the daemon behind `context`; an error means the context
+    /// is unreachable.
+    pub fn disk_usage_in_context(context: &str, timeout: Duration) -> Result<DiskUsage, String> {
+        let output = Self::run_in_context(
+            context,
+            &["system", "df", "--format", "{{json .}}"],
+            timeout,
+        )?;
+        Ok(parse_disk_usage(&output))
+    }
+
+    /// Contexts known to the docker CLI (`docker context ls`). The one
+    /// picked with `--context` is marked current.
+    pub fn list_contexts() -> Result<Vec<DockerContext>, String> {
+        if runtime() == Runtime::Podman {
+            return Err("Podman has no contexts; see `podman system connection list`".to_string());
+        }
+        let output = Self::run_command(&["context", "ls", "--format", "{{json .}}"])?;
+        let mut contexts: Vec<DockerContext> = output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str::<DockerContext>(line).ok())
+            .collect();
+        if let Some(Target::Context(name)) = target() {
+            for context in &mut contexts {
+                context.current = &context.name == name;
             }
         }
-        Ok(usage)
+        Ok(contexts)
+    }
+
+    /// Docker's data root as reported by the daemon (e.g. `/var/lib/docker`).
+    pub fn root_dir() -> Result<String, String> {
+        backend().root_dir()
+    }
+
+    /// Whether the daemon is Docker Desktop's VM, rather than a native
+    /// engine or a remote host.
+    pub fn is_desktop() -> bool {
+        backend()
+            .operating_system()
+            .is_ok_and(|os| os == "Docker Desktop")
+    }
+
+    /// Free space on the disk backing Docker. On Docker Desktop the data root
+    /// lives inside the VM, so this falls back to the home volume where the VM
+    /// disk image is stored.
+    pub fn host_disk_space() -> Option<DiskSpace> {
+        if !Self::is_local() {
+            return None;
+        }
+        let root = Self::root_dir()
+            .ok()
+            .map(PathBuf::from)
+            .filter(|p| p.exists())
+            .or_else(get_home_dir)?;
+        disk_space(&root)
+    }
+
+    pub fn list_images(filters: &[String]) -> Result<Vec<Image>, String> {
+        backend().list_images(filters)
+    }
+
+    pub fn list_containers(all: bool, filters: &[String]) -> Result<Vec<Container>, String> {
+        backend().list_containers(all, filters)
+    }
+
+    /// All containers including their writable layer size (slower, as the
+    /// daemon has to measure each one).
+    pub fn list_containers_with_size() -> Result<Vec<Container>, String> {
+        backend().list_containers_with_size()
+    }
+
+    /// Size of each volume by name.
+    pub fn volume_sizes() -> Result<HashMap<String, u64>, String> {
+        backend().volume_sizes()
+    }
+
+    pub fn list_volumes(filters: &[String]) -> Result<Vec<Volume>, String> {
+        backend().list_volumes(filters)
+    }
+
+    /// Every build cache record with its size and when it was last used.
+    pub fn build_cache() -> Result<Vec<BuildCacheRecord>, String> {
+        backend().build_cache()
+    }
+
+    /// Volumes no container references, split into orphans and named ones.
+    pub fn unused_volumes(filters: &[String]) -> Result<UnusedVolumes, String> {
+        let mut filters = filters.to_vec();
+        filters.push("dangling=true".to_string());
+        Ok(UnusedVolumes::classify(Self::list_volumes(&filters)?))
+    }
+
+    pub fn list_networks(filters: &[String]) -> Result<Vec<Network>, String> {
+        backend().list_networks(filters)
+    }
+
+    /// Every compose project with its containers (and their sizes),
+    /// volumes, networks and the images compose built for it.
+    pub fn compose_projects() -> Result<Vec<ComposeProject>, String> {
+        let label = [format!("label={}", COMPOSE_PROJECT_LABEL)];
+        let containers = Self::list_containers_with_size()?;
+        let mut projects = ComposeProject::group(
+            containers,
+            Self::list_volumes(&label)?,
+            Self::list_networks(&label)?,
+        );
+        for project in &mut projects {
+            let filter = format!("label={}={}", COMPOSE_PROJECT_LABEL, project.name);
+            project.images = Self::list_images(&[filter])?;
+        }
+        Ok(projects)
+    }
+
+    pub fn prune_containers(filters: &[String]) -> Result<String, String> {
+        removing(ResourceType::Container, "prune containers")?.prune(
+            ResourceType::Container,
+            false,
+            filters,
+        )
     }
 
-    pub fn list_images() -> Result<Vec<Image>, String> {
-        let output = Self::run_command(&["images", "--format", "{{json .}}"])?;
+    pub fn prune_images(all: bool, filters: &[String]) -> Result<String, String> {
+        removing(ResourceType::Image, "prune images")?.prune(ResourceType::Image, all, filters)
+    }
+
+    pub fn prune_volumes(filters: &[String]) -> Result<String, String> {
+        removing(ResourceType::Volume, "prune volumes")?.prune(ResourceType::Volume, false, filters)
+    }
 
-        let mut images = Vec::new();
-        for line in output.lines() {
-            if line.trim().is_empty() {
-                continue;
+    pub fn prune_networks(filters: &[String]) -> Result<String, String> {
+        removing(ResourceType::Network, "prune networks")?.prune(
+            ResourceType::Network,
+            false,
+            filters,
+        )
+    }
+
+    pub fn prune_build_cache(all: bool, filters: &[String]) -> Result<String, String> {
+        removing(ResourceType::BuildCache, "prune build cache")?.prune(
+            ResourceType::BuildCache,
+            all,
+            filters,
+        )
+    }
+
+    pub fn stop_containers(ids: &[&str]) -> Result<String, String> {
+        if ids.is_empty() {
+            return Ok(String::new());
+        }
+        mutating("stop containers")?.stop_containers(ids)
+    }
+
+    pub fn remove_containers(ids: &[&str]) -> Result<String, RemoveError> {
+        Self::force_remove(ResourceType::Container, ids)
+    }
+
+    pub fn remove_images(ids: &[&str]) -> Result<String, RemoveError> {
+        Self::force_remove(ResourceType::Image, ids)
+    }
+
+    pub fn remove_volumes(names: &[&str]) -> Result<String, RemoveError> {
+        Self::force_remove(ResourceType::Volume, names)
+    }
+
+    pub fn remove_networks(ids: &[&str]) -> Result<String, RemoveError> {
+        Self::force_remove(ResourceType::Network, ids)
+    }
+
+    /// Remove `ids` even if in use; a no-op when there is nothing to remove.
+    /// They go `REMOVE_BATCH` at a time, each one removed advancing a
+    /// progress bar, and a Ctrl-C stops the removal after the current batch
+    /// with an error that says how many were removed.
+    ///
+    /// One resource docker refuses to remove fails its whole batch, so a
+    /// failed batch is gone through again one resource at a time. Those that
+    /// still fail as in use are tried once more at the end, when what
+    /// depended on them (a child image, say) may be gone; those and any
+    /// other failures are returned with docker's reasons.
+    fn force_remove(kind: ResourceType, ids: &[&str]) -> Result<String, RemoveError> {
+        if ids.is_empty() {
+            return Ok(String::new());
+        }
+        let backend = removing(kind, "remove resources")?;
+        let what = policy::Category::from(kind).as_str();
+        let bar = ProgressBar::items(&format!("Removing {}", what), ids.len());
+        let remove_one = |id: &str| -> Option<RemoveFailure> {
+            match backend.remove(kind, &[id], true) {
+                Ok(_) => None,
+                // Removed along with the rest of its batch after all
+                Err(e) if is_not_found_error(&e) => None,
+                Err(e) => Some(RemoveFailure::new(id, &e)),
             }
-            if let Ok(img) = serde_json::from_str::<Image>(line) {
-                images.push(img);
+        };
+        let mut output = String::new();
+        let mut failures = Vec::new();
+        // What actually went, leaving out what failed
+        let mut removed = 0;
+        for batch in ids.chunks(REMOVE_BATCH) {
+            if cancel::is_requested() {
+                return Err(RemoveError::Other(format!(
+                    "Cancelled after removing {} of {} {}",
+                    removed,
+                    ids.len(),
+                    what
+                )));
             }
+            let failed = failures.len();
+            match backend.remove(kind, batch, true) {
+                Ok(out) => output.push_str(&out),
+                Err(e) if is_no_space_error(&e) => return Err(RemoveError::Other(e)),
+                Err(_) => failures.extend(batch.iter().filter_map(|id| remove_one(id))),
+            }
+            let went = batch.len() - (failures.len() - failed);
+            removed += went;
+            bar.inc(went as u64);
+        }
+        let failures: Vec<RemoveFailure> = failures
+            .into_iter()
+            .filter_map(|f| {
+                if !f.in_use() {
+                    return Some(f);
+                }
+                let failure = remove_one(&f.id);
+                if failure.is_none() {
+                    bar.inc(1);
+                }
+                failure
+            })
+            .collect();
+        if failures.is_empty() {
+            Ok(output)
+        } else {
+            Err(RemoveError::Failed(failures))
         }
-        Ok(images)
     }
 
-    pub fn list_containers(all: bool) -> Result<Vec<Container>, String> {
-        let mut args = vec!["ps", "--format", "{{json .}}"];
-        if all {
-            args.insert(1, "-a");
+    pub fn remove_container(id: &str) -> Result<String, String> {
+        removing(ResourceType::Container, "remove a container")?.remove(
+            ResourceType::Container,
+            &[id],
+            false,
+        )
+    }
+
+    pub fn remove_image(id: &str) -> Result<String, String> {
+        removing(ResourceType::Image, "remove an image")?.remove(ResourceType::Image, &[id], false)
+    }
+
+    pub fn remove_volume(name: &str) -> Result<String, String> {
+        removing(ResourceType::Volume, "remove a volume")?.remove(
+            ResourceType::Volume,
+            &[name],
+            false,
+        )
+    }
+
+    pub fn tag_image(source: &str, target: &str) -> Result<String, String> {
+        mutating("tag images")?.tag(source, target)
+    }
+
+    /// Pull `image`'s digest (or, without one, its tag) and tag the result
+    /// with its old name.
+    pub fn restore_image(image: &ImageRecord) -> Result<String, String> {
+        let backend = mutating("pull images")?;
+        let reference = image.pull_reference();
+        let output = backend.pull(&reference)?;
+        if reference != image.name() {
+            backend.tag(&reference, &image.name())?;
+        }
+        Ok(output)
+    }
+
+    /// Paths added or changed in a container's writable layer, from
+    /// `docker diff`. Deletions are left out as they take no space.
+    pub fn container_changes(id: &str) -> Result<Vec<PathBuf>, String> {
+        let output = Self::run_command(&["diff", id])?;
+        Ok(parse_diff(&output))
+    }
+
+    /// Host directory holding a container's writable layer (overlay2's
+    /// `UpperDir`). Not reachable on Docker Desktop, where it's inside the VM.
+    pub fn container_layer_dir(id: &str) -> Option<PathBuf> {
+        if !Self::is_local() {
+            return None;
+        }
+        let output =
+            Self::run_command(&["inspect", "--format", "{{.GraphDriver.Data.UpperDir}}", id])
+                .ok()?;
+        let dir = PathBuf::from(output.trim());
+        (dir.is_absolute() && dir.is_dir()).then_some(dir)
+    }
+
+    /// Discard the VM disk's free blocks, so Docker Desktop can shrink its
+    /// disk image on the host. Runs `fstrim` in the VM's mount namespace
+    /// from a throwaway privileged container, so it refuses any daemon but
+    /// Docker Desktop's.
+    pub fn trim_vm_disk() -> Result<String, String> {
+        read_only::check("compact the Docker Desktop disk")?;
+        if !Self::is_desktop() {
+            return Err(NOT_DESKTOP.to_string());
+        }
+        Self::run_command(&[
+            "run",
+            "--rm",
+            "--privileged",
+            "--pid=host",
+            "alpine",
+            "nsenter",
+            "-t",
+            "1",
+            "-m",
+            "--",
+            "fstrim",
+            "-av",
+        ])
+    }
+
+    /// `docker inspect` output for a container or image, or `docker volume
+    /// inspect` with `volume`.
+    pub fn inspect(id: &str, volume: bool) -> Result<String, String> {
+        if volume {
+            Self::run_command(&["volume", "inspect", id])
+        } else {
+            Self::run_command(&["inspect", id])
+        }
+    }
+
+    /// The last `lines` lines a container logged, stdout and stderr together.
+    pub fn container_logs_tail(id: &str, lines: usize) -> Result<String, String> {
+        let lines = lines.to_string();
+        let output = Self::command(None, &["logs", "--tail", &lines, id])
+            .output()
+            .map_err(|e| format!("Failed to execute docker: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).to_string());
         }
+        let mut logs = String::from_utf8_lossy(&output.stdout).to_string();
+        logs.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(logs)
+    }
+
+    /// The layers of an image and the instructions that created them.
+    pub fn image_history(id: &str) -> Result<String, String> {
+        Self::run_command(&["history", "--no-trunc", id])
+    }
 
+    /// The layers of each of the `(id, name)` images, base first, with
+    /// their sizes. Takes one `docker history` per image.
+    pub fn image_layers(images: &[(String, String)]) -> Result<Vec<ImageLayers>, String> {
+        if images.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut args = vec![
+            "image",
+            "inspect",
+            "--format",
+            "{{.Id}}\t{{json .RootFS.Layers}}",
+        ];
+        args.extend(images.iter().map(|(id, _)| id.as_str()));
         let output = Self::run_command(&args)?;
-        let mut containers = Vec::new();
-        for line in output.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(c) = serde_json::from_str::<Container>(line) {
-                containers.push(c);
-            }
+        let mut digests: HashMap<&str, Vec<String>> = output
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(id, layers)| (id.trim(), serde_json::from_str(layers).unwrap_or_default()))
+            .collect();
+
+        let mut result = vec![];
+        for (id, name) in images {
+            let history = Self::run_command(&[
+                "history",
+                "--no-trunc",
+                "--human=false",
+                "--format",
+                "{{.Size}}\t{{.CreatedBy}}",
+                id,
+            ])?;
+            result.push(ImageLayers {
+                id: id.clone(),
+                name: name.clone(),
+                layers: layers::assign(
+                    digests.remove(id.as_str()).unwrap_or_default(),
+                    &layers::parse_history(&history),
+                ),
+            });
         }
-        Ok(containers)
+        Ok(result)
     }
 
-    pub fn list_volumes() -> Result<Vec<Volume>, String> {
-        let output = Self::run_command(&["volume", "ls", "--format", "{{json .}}"])?;
-        let mut volumes = Vec::new();
-        for line in output.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(v) = serde_json::from_str::<Volume>(line) {
-                volumes.push(v);
-            }
+    /// Whether the `docker scout` CLI plugin is installed.
+    pub fn scout_available() -> bool {
+        Self::run_command(&["scout", "version"]).is_ok()
+    }
+
+    /// Number of distinct critical vulnerabilities `docker scout` reports
+    /// for a local image (e.g. `alpine:3.19`).
+    pub fn critical_cves(image: &str) -> Result<usize, String> {
+        let reference = format!("local://{}", image);
+        let output = Self::run_command(&[
+            "scout",
+            "cves",
+            "--only-severity",
+            "critical",
+            "--format",
+            "sarif",
+            &reference,
+        ])?;
+        parse_cve_count(&output)
+    }
+
+    /// Each container's name and the host path of its json-file log. On
+    /// Docker Desktop the paths are inside the VM and won't exist locally.
+    pub fn container_log_paths() -> Result<Vec<(String, PathBuf)>, String> {
+        if !Self::is_local() {
+            return Ok(vec![]);
         }
-        Ok(volumes)
+        let containers = Self::list_containers(true, &[])?;
+        if containers.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut args = vec!["inspect", "--format", "{{.Name}}\t{{.LogPath}}"];
+        args.extend(containers.iter().map(|c| c.id.as_str()));
+        let output = Self::run_command(&args)?;
+        Ok(parse_log_paths(&output))
     }
 
-    pub fn list_networks() -> Result<Vec<Network>, String> {
-        let output = Self::run_command(&["network", "ls", "--format", "{{json .}}"])?;
-        let mut networks = Vec::new();
-        for line in output.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(n) = serde_json::from_str::<Network>(line) {
-                networks.push(n);
+    /// When each of the `names` volumes was created, as the daemon reports
+    /// it (RFC 3339). A volume removed since it was listed fails the call
+    /// for all of them, so then each is inspected on its own and the gone
+    /// ones are left out.
+    pub fn volume_created(names: &[&str]) -> Result<HashMap<String, String>, String> {
+        let inspect = |names: &[&str]| -> Result<HashMap<String, String>, String> {
+            if names.is_empty() {
+                return Ok(HashMap::new());
             }
+            let mut args = vec!["volume", "inspect", "--format", "{{.Name}}\t{{.CreatedAt}}"];
+            args.extend(names);
+            let output = Self::run_command(&args)?;
+            Ok(output
+                .lines()
+                .filter_map(|line| line.split_once('\t'))
+                .map(|(name, created)| (name.to_string(), created.trim().to_string()))
+                .filter(|(_, created)| !created.is_empty())
+                .collect())
+        };
+        match inspect(names) {
+            Err(_) if names.len() > 1 => Ok(names
+                .iter()
+                .filter_map(|name| inspect(&[name]).ok())
+                .flatten()
+                .collect()),
+            result => result,
+        }
+    }
+
+    /// The containers, running or stopped, that mount each volume. Docker
+    /// forgets a volume's containers once they are removed.
+    pub fn volume_users() -> Result<HashMap<String, Vec<String>>, String> {
+        let output = Self::run_command(&[
+            "ps",
+            "-a",
+            "--no-trunc",
+            "--format",
+            "{{.Names}}\t{{.Mounts}}",
+        ])?;
+        Ok(parse_volume_users(&output))
+    }
+
+    /// What of the daemon is in active use, checked before a sweeping
+    /// cleanup; `images` are the ones it may remove.
+    pub fn active_resources(images: &[Image]) -> Result<ActiveResources, String> {
+        let containers = Self::list_containers(true, &[])?;
+        if containers.is_empty() {
+            return Ok(ActiveResources::default());
+        }
+        let mut args = vec![
+            "inspect",
+            "--format",
+            "{{.Name}}\t{{.HostConfig.RestartPolicy.Name}}",
+        ];
+        args.extend(containers.iter().map(|c| c.id.as_str()));
+        let policies: HashMap<String, String> = Self::run_command(&args)?
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(name, policy)| {
+                (
+                    name.trim_start_matches('/').to_string(),
+                    policy.trim().to_string(),
+                )
+            })
+            .collect();
+        Ok(ActiveResources::new(
+            &containers,
+            images,
+            &Self::volume_users()?,
+            &policies,
+        ))
+    }
+
+    /// The buildx builders that keep a build cache of their own; the others
+    /// share the daemon's. Podman has no buildx.
+    pub fn buildx_builders() -> Result<Vec<Builder>, String> {
+        if runtime() == Runtime::Podman {
+            return Ok(vec![]);
         }
-        Ok(networks)
+        let output = Self::run_command(&["buildx", "ls", "--format", "json"])?;
+        Ok(output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Builder>(line).ok())
+            .filter(Builder::has_own_cache)
+            .collect())
     }
 
-    pub fn prune_containers() -> Result<String, String> {
-        Self::run_command(&["container", "prune", "-f"])
+    /// Size of a builder's cache, from `docker buildx du`.
+    pub fn builder_cache_size(name: &str) -> Result<u64, String> {
+        let output = Self::run_command(&["buildx", "du", "--builder", name])?;
+        parse_buildx_total(&output).ok_or_else(|| format!("No cache size for builder '{}'", name))
     }
 
-    pub fn prune_images(all: bool) -> Result<String, String> {
+    /// Whether a build holds any of a builder's cache records, from
+    /// `docker buildx du`.
+    pub fn builder_in_use(name: &str) -> Result<bool, String> {
+        let output = Self::run_command(&["buildx", "du", "--builder", name])?;
+        Ok(parse_buildx_in_use(&output))
+    }
+
+    /// Prune a buildx builder's cache; `all` takes what is still in use too.
+    pub fn prune_builder(name: &str, all: bool, filters: &[String]) -> Result<String, String> {
+        removing(ResourceType::BuildCache, "prune build cache")?;
+        let mut args = vec!["buildx", "prune", "--builder", name, "-f"];
         if all {
-            Self::run_command(&["image", "prune", "-af"])
-        } else {
-            Self::run_command(&["image", "prune", "-f"])
+            args.push("--all");
         }
+        Self::run_command(&with_filters(&args, filters))
     }
 
-    pub fn prune_volumes() -> Result<String, String> {
-        Self::run_command(&["volume", "prune", "-f"])
+    /// Import an empty filesystem as the image `tag`, labelled `label`.
+    /// Nothing is pulled, and every import is an image of its own.
+    pub fn import_empty_image(tag: &str, label: &str) -> Result<String, String> {
+        read_only::check("create images")?;
+        let change = format!("LABEL {}", label);
+        Self::run_with_input(&["import", "--change", &change, "-", tag], &EMPTY_TAR)
     }
 
-    pub fn prune_networks() -> Result<String, String> {
-        Self::run_command(&["network", "prune", "-f"])
+    /// Create, without starting, a container of `image` named `name`.
+    pub fn create_container(image: &str, name: &str, label: &str) -> Result<String, String> {
+        read_only::check("create containers")?;
+        Self::run_command(&["create", "--label", label, "--name", name, image, "true"])
     }
 
-    pub fn prune_build_cache(all: bool) -> Result<String, String> {
+    pub fn create_volume(name: &str, label: &str) -> Result<String, String> {
+        read_only::check("create volumes")?;
+        Self::run_command(&["volume", "create", "--label", label, name])
+    }
+
+    fn run_with_input(args: &[&str], input: &[u8]) -> Result<String, String> {
+        let mut child = Self::command(None, args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute docker: {}", e))?;
+        // A command that stops reading early says why in its exit status
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(input).ok();
+        drop(stdin);
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to wait for docker: {}", e))?;
+        Self::command_result(output)
+    }
+}
+
+/// Why the VM disk isn't trimmed through the active daemon.
+pub const NOT_DESKTOP: &str = "The active Docker daemon isn't Docker Desktop's; switch to it (e.g. `docker context use desktop-linux`) to trim its disk";
+
+/// A tar archive with no entries: two zeroed blocks.
+const EMPTY_TAR: [u8; 1024] = [0; 1024];
+
+/// Kinds of resource the backends prune and remove.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceType {
+    Container,
+    Image,
+    Volume,
+    Network,
+    BuildCache,
+}
+
+/// How dockerase reaches the daemon for the core listing and cleanup
+/// operations: by running the docker or podman CLI, or through the Engine API
+/// socket. Contexts, buildx, `docker scout`, `docker diff`, log paths, the
+/// details shown by `select` and the resources `bench` creates always use
+/// the CLI.
+pub trait DockerBackend: Send + Sync {
+    fn is_available(&self) -> bool;
+    fn disk_usage(&self) -> Result<DiskUsage, String>;
+    fn root_dir(&self) -> Result<String, String>;
+    /// What the daemon runs on, e.g. `Docker Desktop` or `Ubuntu 24.04 LTS`.
+    fn operating_system(&self) -> Result<String, String>;
+    fn list_images(&self, filters: &[String]) -> Result<Vec<Image>, String>;
+    fn list_containers(&self, all: bool, filters: &[String]) -> Result<Vec<Container>, String>;
+    fn list_containers_with_size(&self) -> Result<Vec<Container>, String>;
+    fn list_volumes(&self, filters: &[String]) -> Result<Vec<Volume>, String>;
+    fn volume_sizes(&self) -> Result<HashMap<String, u64>, String>;
+    fn build_cache(&self) -> Result<Vec<BuildCacheRecord>, String>;
+    fn list_networks(&self, filters: &[String]) -> Result<Vec<Network>, String>;
+    /// Prune unused resources; `all` also takes tagged images and all build cache.
+    fn prune(&self, kind: ResourceType, all: bool, filters: &[String]) -> Result<String, String>;
+    fn remove(&self, kind: ResourceType, ids: &[&str], force: bool) -> Result<String, String>;
+    fn stop_containers(&self, ids: &[&str]) -> Result<String, String>;
+    /// Pull an image by `name:tag` or `name@digest`.
+    fn pull(&self, reference: &str) -> Result<String, String>;
+    fn tag(&self, source: &str, target: &str) -> Result<String, String>;
+}
+
+/// Resources removed per call by batch removals, so hundreds of them show
+/// progress and can be cancelled part way.
+const REMOVE_BATCH: usize = 20;
+
+static BACKEND: OnceLock<Box<dyn DockerBackend>> = OnceLock::new();
+
+fn backend() -> &'static dyn DockerBackend {
+    BACKEND.get_or_init(select_backend).as_ref()
+}
+
+/// The backend for an operation that changes the daemon, unless read-only
+/// mode forbids it.
+fn mutating(action: &str) -> Result<&'static dyn DockerBackend, String> {
+    read_only::check(action)?;
+    Ok(backend())
+}
+
+/// [`mutating`], for removing resources of `kind`, unless the policy also
+/// forbids that.
+fn removing(kind: ResourceType, action: &str) -> Result<&'static dyn DockerBackend, String> {
+    policy::check(kind.into(), action)?;
+    mutating(action)
+}
+
+/// `DOCKERASE_BACKEND=api` talks to the Engine API socket and `cli` runs the
+/// runtime's CLI. Otherwise the CLI is used when installed, and for docker
+/// the socket when it isn't.
+fn select_backend() -> Box<dyn DockerBackend> {
+    #[cfg(unix)]
+    {
+        let requested = env::var("DOCKERASE_BACKEND").unwrap_or_default();
+        let use_api = match requested.as_str() {
+            "api" => true,
+            "cli" => false,
+            _ => {
+                runtime() == Runtime::Docker
+                    && env::var_os("DOCKERASE_DOCKER").is_none()
+                    && !installed("docker")
+            }
+        };
+        if use_api {
+            let api = match target() {
+                Some(Target::Host(host)) => crate::engine::EngineApi::from_host(host),
+                // Only the CLI can resolve contexts
+                Some(Target::Context(_)) => None,
+                None => crate::engine::EngineApi::from_env(),
+            };
+            if let Some(api) = api {
+                return Box::new(api);
+            }
+        }
+    }
+    match runtime() {
+        Runtime::Docker => Box::new(Cli),
+        Runtime::Podman => Box::new(crate::podman::Podman),
+    }
+}
+
+/// A backend `dockerase bench` times directly, whichever one this run
+/// selected.
+pub struct BenchBackend {
+    pub name: &'static str,
+    backend: Box<dyn DockerBackend>,
+}
+
+impl BenchBackend {
+    /// Force-remove `ids` in one call, after the checks any removal gets.
+    pub fn remove(&self, kind: ResourceType, ids: &[&str]) -> Result<String, String> {
+        policy::check(kind.into(), "remove benchmark resources")?;
+        read_only::check("remove benchmark resources")?;
+        self.backend.remove(kind, ids, true)
+    }
+}
+
+/// The backends worth comparing: the runtime's CLI and, for docker, the
+/// Engine API socket when it answers for the same daemon as the CLI.
+pub fn bench_backends() -> Vec<BenchBackend> {
+    let cli: Box<dyn DockerBackend> = match runtime() {
+        Runtime::Docker => Box::new(Cli),
+        Runtime::Podman => Box::new(crate::podman::Podman),
+    };
+    let mut backends = vec![BenchBackend {
+        name: "cli",
+        backend: cli,
+    }];
+    backends.extend(bench_api());
+    backends
+}
+
+#[cfg(unix)]
+fn bench_api() -> Option<BenchBackend> {
+    // A substitute CLI may not be talking to the socket's daemon
+    if runtime() != Runtime::Docker || env::var_os("DOCKERASE_DOCKER").is_some() {
+        return None;
+    }
+    let api = match target() {
+        Some(Target::Host(host)) => crate::engine::EngineApi::from_host(host),
+        Some(Target::Context(_)) => None,
+        None => crate::engine::EngineApi::from_env(),
+    }?;
+    api.is_available().then(|| BenchBackend {
+        name: "api",
+        backend: Box::new(api),
+    })
+}
+
+#[cfg(not(unix))]
+fn bench_api() -> Option<BenchBackend> {
+    None
+}
+
+/// Runs the docker CLI and parses its `--format '{{json .}}'` output.
+struct Cli;
+
+impl Cli {
+    /// One JSON object per line, each parsed as soon as it is read.
+    fn list<T: DeserializeOwned>(args: &[&str], filters: &[String]) -> Result<Vec<T>, String> {
+        Docker::stream_command(&with_filters(args, filters), |output| {
+            let mut items = Vec::new();
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                let read = output
+                    .read_until(b'\n', &mut line)
+                    .map_err(|e| format!("Failed to read docker output: {}", e))?;
+                if read == 0 {
+                    return Ok(items);
+                }
+                if let Ok(item) = serde_json::from_slice::<T>(line.trim_ascii()) {
+                    items.push(item);
+                }
+            }
+        })
+    }
+}
+
+impl DockerBackend for Cli {
+    fn is_available(&self) -> bool {
+        Docker::command(None, &["version"])
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    fn disk_usage(&self) -> Result<DiskUsage, String> {
+        let output = Docker::run_command(&["system", "df", "--format", "{{json .}}"])?;
+        Ok(parse_disk_usage(&output))
+    }
+
+    fn root_dir(&self) -> Result<String, String> {
+        let output = Docker::run_command(&["info", "--format", "{{.DockerRootDir}}"])?;
+        Ok(output.trim().to_string())
+    }
+
+    fn operating_system(&self) -> Result<String, String> {
+        let output = Docker::run_command(&["info", "--format", "{{.OperatingSystem}}"])?;
+        Ok(output.trim().to_string())
+    }
+
+    fn list_images(&self, filters: &[String]) -> Result<Vec<Image>, String> {
+        Self::list(&["images", "--digests", "--format", "{{json .}}"], filters)
+    }
+
+    fn list_containers(&self, all: bool, filters: &[String]) -> Result<Vec<Container>, String> {
+        let mut args = vec!["ps", "--format", "{{json .}}"];
         if all {
-            Self::run_command(&["builder", "prune", "-af"])
-        } else {
-            Self::run_command(&["builder", "prune", "-f"])
+            args.insert(1, "-a");
         }
+        Self::list(&args, filters)
     }
 
-    pub fn stop_all_containers() -> Result<String, String> {
-        let containers = Self::list_containers(false)?;
-        if containers.is_empty() {
-            return Ok(String::new());
+    fn list_containers_with_size(&self) -> Result<Vec<Container>, String> {
+        Self::list(&["ps", "-a", "--size", "--format", "{{json .}}"], &[])
+    }
+
+    fn list_volumes(&self, filters: &[String]) -> Result<Vec<Volume>, String> {
+        Self::list(&["volume", "ls", "--format", "{{json .}}"], filters)
+    }
+
+    fn volume_sizes(&self) -> Result<HashMap<String, u64>, String> {
+        let output = Docker::run_command(&["system", "df", "-v", "--format", "{{json .}}"])?;
+        Ok(parse_volume_sizes(&output))
+    }
+
+    fn build_cache(&self) -> Result<Vec<BuildCacheRecord>, String> {
+        let output = Docker::run_command(&["system", "df", "-v", "--format", "{{json .}}"])?;
+        Ok(parse_build_cache(&output, now_secs()))
+    }
+
+    fn list_networks(&self, filters: &[String]) -> Result<Vec<Network>, String> {
+        Self::list(&["network", "ls", "--format", "{{json .}}"], filters)
+    }
+
+    fn prune(&self, kind: ResourceType, all: bool, filters: &[String]) -> Result<String, String> {
+        let flags = if all { "-af" } else { "-f" };
+        let args = match kind {
+            ResourceType::Container => ["container", "prune", "-f"],
+            ResourceType::Image => ["image", "prune", flags],
+            ResourceType::Volume => ["volume", "prune", "-f"],
+            ResourceType::Network => ["network", "prune", "-f"],
+            ResourceType::BuildCache => ["builder", "prune", flags],
+        };
+        Docker::run_command(&with_filters(&args, filters))
+    }
+
+    fn remove(&self, kind: ResourceType, ids: &[&str], force: bool) -> Result<String, String> {
+        let mut args = match kind {
+            ResourceType::Container => vec!["rm"],
+            ResourceType::Image => vec!["rmi"],
+            ResourceType::Volume => vec!["volume", "rm"],
+            ResourceType::Network => vec!["network", "rm"],
+            ResourceType::BuildCache => return Err("Build cache can only be pruned".to_string()),
+        };
+        // `network rm` has no force flag
+        if force && kind != ResourceType::Network {
+            args.push("-f");
         }
-        let ids: Vec<&str> = containers.iter().map(|c| c.id.as_str()).collect();
+        args.extend(ids);
+        Docker::run_command(&args)
+    }
+
+    fn stop_containers(&self, ids: &[&str]) -> Result<String, String> {
         let mut args = vec!["stop"];
         args.extend(ids);
-        Self::run_command(&args)
+        Docker::run_command(&args)
     }
 
-    pub fn remove_all_containers() -> Result<String, String> {
-        let containers = Self::list_containers(true)?;
-        if containers.is_empty() {
-            return Ok(String::new());
+    fn pull(&self, reference: &str) -> Result<String, String> {
+        Docker::run_command(&["pull", "--quiet", reference])
+    }
+
+    fn tag(&self, source: &str, target: &str) -> Result<String, String> {
+        Docker::run_command(&["tag", source, target])
+    }
+}
+
+/// `args` followed by a `--filter` for each of `filters`.
+pub fn with_filters<'a>(args: &[&'a str], filters: &'a [String]) -> Vec<&'a str> {
+    let mut args = args.to_vec();
+    for filter in filters {
+        args.extend(["--filter", filter.as_str()]);
+    }
+    args
+}
+
+/// A resource docker wouldn't remove, with its reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoveFailure {
+    pub id: String,
+    pub reason: String,
+}
+
+impl RemoveFailure {
+    pub fn new(id: &str, error: &str) -> Self {
+        let reason = error.trim();
+        Self {
+            id: id.to_string(),
+            reason: reason
+                .strip_prefix("Error response from daemon: ")
+                .unwrap_or(reason)
+                .to_string(),
         }
-        let ids: Vec<&str> = containers.iter().map(|c| c.id.as_str()).collect();
-        let mut args = vec!["rm", "-f"];
-        args.extend(ids);
-        Self::run_command(&args)
     }
 
-    pub fn remove_all_images() -> Result<String, String> {
-        let images = Self::list_images()?;
-        if images.is_empty() {
-            return Ok(String::new());
+    /// Still used by a container, another image or a network endpoint, so
+    /// removing it again won't help until that is gone.
+    pub fn in_use(&self) -> bool {
+        let reason = self.reason.to_lowercase();
+        [
+            "in use",
+            "being used",
+            "is using",
+            "active endpoints",
+            "dependent child",
+        ]
+        .iter()
+        .any(|phrase| reason.contains(phrase))
+    }
+}
+
+/// Why a removal didn't remove everything it was given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoveError {
+    /// Each of these couldn't be removed; everything else was
+    Failed(Vec<RemoveFailure>),
+    /// The removal as a whole failed or stopped: read-only mode, the
+    /// policy, a full disk or Ctrl-C
+    Other(String),
+}
+
+impl From<String> for RemoveError {
+    fn from(error: String) -> Self {
+        RemoveError::Other(error)
+    }
+}
+
+impl From<RemoveError> for String {
+    fn from(error: RemoveError) -> Self {
+        match error {
+            RemoveError::Other(e) => e,
+            RemoveError::Failed(failures) => failures
+                .iter()
+                .map(|f| format!("{}: {}", f.id, f.reason))
+                .collect::<Vec<_>>()
+                .join("\n"),
         }
-        let ids: Vec<&str> = images.iter().map(|i| i.id.as_str()).collect();
-        let mut args = vec!["rmi", "-f"];
-        args.extend(ids);
-        Self::run_command(&args)
     }
+}
 
-    pub fn remove_all_volumes() -> Result<String, String> {
-        let volumes = Self::list_volumes()?;
-        if volumes.is_empty() {
-            return Ok(String::new());
+/// Whether a docker error means the resource doesn't exist (any more).
+fn is_not_found_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("no such") || error.contains("not found")
+}
+
+/// Whether a docker error means the daemon has run out of disk space.
+pub fn is_no_space_error(error: &str) -> bool {
+    error.to_lowercase().contains("no space left on device")
+}
+
+fn parse_volume_sizes(output: &str) -> HashMap<String, u64> {
+    let Ok(df) = serde_json::from_str::<serde_json::Value>(output.trim()) else {
+        return HashMap::new();
+    };
+    df["Volumes"]
+        .as_array()
+        .map(|volumes| {
+            volumes
+                .iter()
+                .filter_map(|v| {
+                    let name = v["Name"].as_str()?;
+                    Some((name.to_string(), parse_size(v["Size"].as_str()?)))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The `BuildCache` records of `docker system df -v`, which gives their
+/// times only relative to `now`, such as `3 weeks ago`.
+fn parse_build_cache(output: &str, now: u64) -> Vec<BuildCacheRecord> {
+    let Ok(df) = serde_json::from_str::<serde_json::Value>(output.trim()) else {
+        return vec![];
+    };
+    let since = |v: &serde_json::Value, key: &str| {
+        let age = parse_docker_age(v[key].as_str()?)?;
+        Some(now.saturating_sub(age.as_secs()))
+    };
+    df["BuildCache"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|v| BuildCacheRecord {
+            size: parse_size(v["Size"].as_str().unwrap_or("0")),
+            last_used: since(v, "LastUsedSince").or_else(|| since(v, "CreatedSince")),
+            in_use: v["InUse"].as_bool(),
+        })
+        .collect()
+}
+
+/// Count the distinct vulnerabilities (rule IDs) in a SARIF report.
+fn parse_cve_count(sarif: &str) -> Result<usize, String> {
+    let report: serde_json::Value = serde_json::from_str(sarif)
+        .map_err(|e| format!("Failed to parse docker scout output: {}", e))?;
+    let ids: std::collections::HashSet<&str> = report["runs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|run| run["results"].as_array())
+        .flatten()
+        .filter_map(|result| result["ruleId"].as_str())
+        .collect();
+    Ok(ids.len())
+}
+
+fn parse_diff(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter(|(kind, _)| matches!(*kind, "A" | "C"))
+        .map(|(_, path)| PathBuf::from(path))
+        .collect()
+}
+
+fn parse_log_paths(output: &str) -> Vec<(String, PathBuf)> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(_, path)| !path.trim().is_empty())
+        .map(|(name, path)| {
+            (
+                name.trim_start_matches('/').to_string(),
+                PathBuf::from(path.trim()),
+            )
+        })
+        .collect()
+}
+
+/// The `Total:` line that ends `docker buildx du`.
+fn parse_buildx_total(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Total:"))
+        .map(parse_size)
+}
+
+/// Whether a row of `docker buildx du` output (`ID  RECLAIMABLE  SIZE
+/// LAST ACCESSED`) isn't reclaimable, which a record held by a build isn't.
+fn parse_buildx_in_use(output: &str) -> bool {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("ID"))
+        .skip(1)
+        .any(|line| line.split_whitespace().nth(1) == Some("false"))
+}
+
+/// Parse `docker ps --format '{{.Names}}\t{{.Mounts}}'` output into the
+/// containers mounting each volume (or bind-mounted path).
+fn parse_volume_users(output: &str) -> HashMap<String, Vec<String>> {
+    let mut users: HashMap<String, Vec<String>> = HashMap::new();
+    for (container, mounts) in output.lines().filter_map(|line| line.split_once('\t')) {
+        for mount in mounts.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+            users
+                .entry(mount.to_string())
+                .or_default()
+                .push(container.to_string());
         }
-        let names: Vec<&str> = volumes.iter().map(|v| v.name.as_str()).collect();
-        let mut args = vec!["volume", "rm", "-f"];
-        args.extend(names);
-        Self::run_command(&args)
     }
+    users
+}
 
-    pub fn remove_custom_networks() -> Result<String, String> {
-        let networks = Self::list_networks()?;
-        let custom: Vec<&str> = networks
-            .iter()
-            .filter(|n| !["bridge", "host", "none"].contains(&n.name.as_str()))
-            .map(|n| n.id.as_str())
-            .collect();
-        if custom.is_empty() {
-            return Ok(String::new());
+/// Parse `docker system df --format '{{json .}}'` output.
+fn parse_disk_usage(output: &str) -> DiskUsage {
+    let mut usage = DiskUsage::default();
+
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) {
+            let type_name = entry["Type"].as_str().unwrap_or("");
+            let size = parse_size(entry["Size"].as_str().unwrap_or("0"));
+            let reclaimable_str = entry["Reclaimable"].as_str().unwrap_or("0");
+            let reclaimable = parse_reclaimable(reclaimable_str);
+            let count = entry["TotalCount"].as_i64().unwrap_or(0) as usize;
+            let active = entry["Active"].as_i64().unwrap_or(0) as usize;
+
+            match type_name {
+                "Images" => {
+                    usage.images_size = size;
+                    usage.images_reclaimable = reclaimable;
+                    usage.images_count = count;
+                    usage.images_active = active;
+                }
+                "Containers" => {
+                    usage.containers_size = size;
+                    usage.containers_reclaimable = reclaimable;
+                    usage.containers_count = count;
+                    usage.containers_active = active;
+                }
+                "Local Volumes" => {
+                    usage.volumes_size = size;
+                    usage.volumes_reclaimable = reclaimable;
+                    usage.volumes_count = count;
+                    usage.volumes_active = active;
+                }
+                "Build Cache" => {
+                    usage.build_cache_size = size;
+                    usage.build_cache_reclaimable = reclaimable;
+                    usage.build_cache_count = count;
+                    usage.build_cache_active = active;
+                }
+                _ => {}
+            }
         }
-        let mut args = vec!["network", "rm"];
-        args.extend(custom);
-        Self::run_command(&args)
     }
+    usage
 }
 
-fn parse_size(s: &str) -> u64 {
+pub fn parse_size(s: &str) -> u64 {
     let s = s.trim();
     if s == "0" || s == "0B" || s.is_empty() {
         return 0;
@@ -368,4 +1555,148 @@ mod tests {
         assert_eq!(network.name, "my-network");
         assert!(!network.is_default());
     }
+
+    #[test]
+    fn test_context_deserialize() {
+        let json = r#"{"Current":true,"Description":"Current DOCKER_HOST based configuration","DockerEndpoint":"unix:///var/run/docker.sock","Error":"","Name":"default"}"#;
+        let context: DockerContext = serde_json::from_str(json).unwrap();
+        assert_eq!(context.name, "default");
+        assert_eq!(context.endpoint, "unix:///var/run/docker.sock");
+        assert!(context.current);
+    }
+
+    #[test]
+    fn test_host_machine() {
+        assert_eq!(host_machine("ssh://deploy@prod-db:22"), Some("prod-db"));
+        assert_eq!(
+            host_machine("tcp://prod-db.internal:2376"),
+            Some("prod-db.internal")
+        );
+        assert_eq!(
+            host_machine("ssh://core@build-1/run/podman/podman.sock"),
+            Some("build-1")
+        );
+        assert_eq!(host_machine("tcp://[fd00::1]:2376"), Some("fd00::1"));
+        assert_eq!(host_machine("unix:///var/run/docker.sock"), None);
+    }
+
+    #[test]
+    fn test_parse_disk_usage() {
+        let output = r#"{"Active":"2","Reclaimable":"1.5GB (60%)","Size":"2.5GB","TotalCount":5,"Type":"Images"}
+{"Active":"0","Reclaimable":"100MB","Size":"100MB","TotalCount":1,"Type":"Build Cache"}
+"#;
+        let usage = parse_disk_usage(output);
+        assert_eq!(usage.images_size, 2_500_000_000);
+        assert_eq!(usage.images_reclaimable, 1_500_000_000);
+        assert_eq!(usage.images_count, 5);
+        assert_eq!(usage.build_cache_reclaimable, 100_000_000);
+    }
+
+    #[test]
+    fn test_is_no_space_error() {
+        assert!(is_no_space_error(
+            "Error response from daemon: write /var/lib/docker/tmp/x: No space left on device"
+        ));
+        assert!(!is_no_space_error("Cannot connect to the Docker daemon"));
+    }
+
+    #[test]
+    fn test_parse_cve_count() {
+        let sarif = r#"{"runs":[{"results":[
+            {"ruleId":"CVE-2024-0001","level":"error"},
+            {"ruleId":"CVE-2024-0001","level":"error"},
+            {"ruleId":"CVE-2024-0002","level":"error"}
+        ]}]}"#;
+        assert_eq!(parse_cve_count(sarif), Ok(2));
+        assert_eq!(parse_cve_count(r#"{"runs":[{"results":[]}]}"#), Ok(0));
+        assert!(parse_cve_count("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let output = "C /var\nC /var/log\nA /var/log/app.log\nD /etc/motd\n";
+        assert_eq!(
+            parse_diff(output),
+            vec![
+                PathBuf::from("/var"),
+                PathBuf::from("/var/log"),
+                PathBuf::from("/var/log/app.log")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_log_paths() {
+        let output = "/db\t/var/lib/docker/containers/abc/abc-json.log\n/no-log\t\n";
+        let paths = parse_log_paths(output);
+        assert_eq!(
+            paths,
+            vec![(
+                "db".to_string(),
+                PathBuf::from("/var/lib/docker/containers/abc/abc-json.log")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_buildx_total() {
+        let output = "ID\tRECLAIMABLE\tSIZE\tLAST ACCESSED\nx1\ttrue\t1.2GB\t2 days ago\nShared:\t\t0B\nPrivate:\t1.2GB\nReclaimable:\t1.2GB\nTotal:\t\t1.2GB\n";
+        assert_eq!(parse_buildx_total(output), Some(1_200_000_000));
+        assert_eq!(parse_buildx_total("Total reclaimed space: 0B"), None);
+    }
+
+    #[test]
+    fn test_parse_buildx_in_use() {
+        let idle =
+            "ID\tRECLAIMABLE\tSIZE\tLAST ACCESSED\nx1*\ttrue\t1.2GB\t2 days ago\nTotal:\t\t1.2GB\n";
+        assert!(!parse_buildx_in_use(idle));
+        let busy = "ID\tRECLAIMABLE\tSIZE\tLAST ACCESSED\nx1*\tfalse\t1.2GB\t\nx2\ttrue\t3MB\t2 days ago\nTotal:\t\t1.2GB\n";
+        assert!(parse_buildx_in_use(busy));
+        assert!(!parse_buildx_in_use("Reclaimable:\t0B\nTotal:\t\t0B\n"));
+    }
+
+    #[test]
+    fn test_parse_volume_users() {
+        let output = "db\tpgdata,/srv/backups\nworker\tpgdata\nold-job\t\n";
+        let users = parse_volume_users(output);
+        assert_eq!(users["pgdata"], ["db", "worker"]);
+        assert_eq!(users["/srv/backups"], ["db"]);
+        assert_eq!(users.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_volume_sizes() {
+        let output = r#"{"Images":[],"Containers":[],"Volumes":[{"Name":"pgdata","Size":"250MB","Links":1},{"Name":"scratch","Size":"50MB","Links":0}],"BuildCache":[]}"#;
+        let sizes = parse_volume_sizes(output);
+        assert_eq!(sizes["pgdata"], 250_000_000);
+        assert_eq!(sizes["scratch"], 50_000_000);
+        assert!(parse_volume_sizes("not json").is_empty());
+    }
+
+    #[test]
+    fn test_parse_build_cache_dates_records_from_their_relative_times() {
+        let output = r#"{"Volumes":[],"BuildCache":[{"ID":"a1","Size":"1.5GB","CreatedSince":"3 months ago","LastUsedSince":"2 days ago","InUse":true},{"ID":"b2","Size":"20MB","CreatedSince":"5 weeks ago","LastUsedSince":""},{"ID":"c3","Size":"1kB"}]}"#;
+        let now = 100 * 86_400;
+        assert_eq!(
+            parse_build_cache(output, now),
+            vec![
+                BuildCacheRecord {
+                    size: 1_500_000_000,
+                    last_used: Some(98 * 86_400),
+                    in_use: Some(true),
+                },
+                BuildCacheRecord {
+                    size: 20_000_000,
+                    last_used: Some(65 * 86_400),
+                    in_use: None,
+                },
+                BuildCacheRecord {
+                    size: 1_000,
+                    last_used: None,
+                    in_use: None,
+                },
+            ]
+        );
+        assert!(parse_build_cache("not json", now).is_empty());
+    }
 }
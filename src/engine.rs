@@ -0,0 +1,563 @@
+//! Docker Engine API client over the daemon's Unix socket, for machines
+//! without the docker CLI. Speaks just enough HTTP/1.1 for the endpoints
+//! dockerase needs and converts the answers into the same types the CLI
+//! backend parses.
+
+use crate::api::{
+    array, build_cache_from_api, container_from_api, image_from_api, network_from_api,
+    volume_from_api,
+};
+use crate::docker::{DockerBackend, ResourceType};
+use crate::resources::{BuildCacheRecord, Container, DiskUsage, Image, Network, Volume};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+pub struct EngineApi {
+    socket: PathBuf,
+}
+
+impl EngineApi {
+    pub fn at(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// The socket named by `DOCKER_HOST` (`unix://...`), or the first of the
+    /// usual Linux and Docker Desktop locations that exists.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(host) = env::var("DOCKER_HOST") {
+            return Self::from_host(&host);
+        }
+        let mut candidates = vec![PathBuf::from("/var/run/docker.sock")];
+        if let Some(home) = dirs::home_dir() {
+            candidates.push(home.join(".docker/run/docker.sock"));
+        }
+        candidates.into_iter().find(|p| is_socket(p)).map(Self::at)
+    }
+
+    /// The socket of a `unix://` host; tcp:// and ssh:// hosts need the CLI.
+    pub fn from_host(host: &str) -> Option<Self> {
+        host.strip_prefix("unix://").map(Self::at)
+    }
+
+    /// Send a request and return the status code and body.
+    fn request(&self, method: &str, path: &str) -> Result<(u16, String), String> {
+        let mut stream = UnixStream::connect(&self.socket).map_err(|e| {
+            format!(
+                "Failed to connect to the Docker socket {}: {}",
+                self.socket.display(),
+                e
+            )
+        })?;
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+        )
+        .map_err(|e| format!("Failed to send request to Docker: {}", e))?;
+
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .map_err(|e| format!("Failed to read response from Docker: {}", e))?;
+        parse_response(&response)
+    }
+
+    /// Send a request, turning error statuses into the daemon's message.
+    fn call(&self, method: &str, path: &str) -> Result<String, String> {
+        let (status, body) = self.request(method, path)?;
+        if (200..300).contains(&status) {
+            return Ok(body);
+        }
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|v| v["message"].as_str().map(str::to_string))
+            .unwrap_or(body);
+        Err(format!("{} (HTTP {})", message.trim(), status))
+    }
+
+    fn get_json(&self, path: &str) -> Result<Value, String> {
+        let body = self.call("GET", path)?;
+        serde_json::from_str(&body).map_err(|e| format!("Unexpected response from Docker: {}", e))
+    }
+
+    fn system_df(&self) -> Result<Value, String> {
+        self.get_json("/system/df")
+    }
+}
+
+impl DockerBackend for EngineApi {
+    fn is_available(&self) -> bool {
+        self.call("GET", "/_ping").is_ok()
+    }
+
+    fn disk_usage(&self) -> Result<DiskUsage, String> {
+        Ok(disk_usage_from_api(&self.system_df()?))
+    }
+
+    fn root_dir(&self) -> Result<String, String> {
+        let info = self.get_json("/info")?;
+        Ok(info["DockerRootDir"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    fn operating_system(&self) -> Result<String, String> {
+        let info = self.get_json("/info")?;
+        Ok(info["OperatingSystem"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    fn list_images(&self, filters: &[String]) -> Result<Vec<Image>, String> {
+        let images = self.get_json(&format!("/images/json{}", query(filters, &[])))?;
+        Ok(array(&images).iter().map(image_from_api).collect())
+    }
+
+    fn list_containers(&self, all: bool, filters: &[String]) -> Result<Vec<Container>, String> {
+        let params = [("all", all.to_string())];
+        let containers = self.get_json(&format!("/containers/json{}", query(filters, &params)))?;
+        Ok(array(&containers).iter().map(container_from_api).collect())
+    }
+
+    fn list_containers_with_size(&self) -> Result<Vec<Container>, String> {
+        let params = [("all", "true".to_string()), ("size", "true".to_string())];
+        let containers = self.get_json(&format!("/containers/json{}", query(&[], &params)))?;
+        Ok(array(&containers).iter().map(container_from_api).collect())
+    }
+
+    fn list_volumes(&self, filters: &[String]) -> Result<Vec<Volume>, String> {
+        let volumes = self.get_json(&format!("/volumes{}", query(filters, &[])))?;
+        Ok(array(&volumes["Volumes"])
+            .iter()
+            .map(volume_from_api)
+            .collect())
+    }
+
+    fn volume_sizes(&self) -> Result<HashMap<String, u64>, String> {
+        let df = self.system_df()?;
+        Ok(array(&df["Volumes"])
+            .iter()
+            .filter_map(|v| Some((v["Name"].as_str()?.to_string(), volume_size(v))))
+            .collect())
+    }
+
+    fn build_cache(&self) -> Result<Vec<BuildCacheRecord>, String> {
+        let df = self.system_df()?;
+        Ok(array(&df["BuildCache"])
+            .iter()
+            .map(build_cache_from_api)
+            .collect())
+    }
+
+    fn list_networks(&self, filters: &[String]) -> Result<Vec<Network>, String> {
+        let networks = self.get_json(&format!("/networks{}", query(filters, &[])))?;
+        Ok(array(&networks).iter().map(network_from_api).collect())
+    }
+
+    fn prune(&self, kind: ResourceType, all: bool, filters: &[String]) -> Result<String, String> {
+        let mut filters = filters.to_vec();
+        let mut params = Vec::new();
+        let endpoint = match kind {
+            ResourceType::Container => "/containers/prune",
+            ResourceType::Image => {
+                // The API prunes only dangling images unless told otherwise
+                if all {
+                    filters.push("dangling=false".to_string());
+                }
+                "/images/prune"
+            }
+            ResourceType::Volume => {
+                if all {
+                    filters.push("all=true".to_string());
+                }
+                "/volumes/prune"
+            }
+            ResourceType::Network => "/networks/prune",
+            ResourceType::BuildCache => {
+                params.push(("all", all.to_string()));
+                "/build/prune"
+            }
+        };
+        self.call("POST", &format!("{}{}", endpoint, query(&filters, &params)))
+    }
+
+    fn remove(&self, kind: ResourceType, ids: &[&str], force: bool) -> Result<String, String> {
+        let endpoint = match kind {
+            ResourceType::Container => "/containers",
+            ResourceType::Image => "/images",
+            ResourceType::Volume => "/volumes",
+            ResourceType::Network => "/networks",
+            ResourceType::BuildCache => {
+                return Err("Build cache can only be pruned".to_string());
+            }
+        };
+        let params = match kind {
+            ResourceType::Network => vec![],
+            _ => vec![("force", force.to_string())],
+        };
+        // Like the CLI, remove what can be removed and report the rest
+        let errors: Vec<String> = ids
+            .iter()
+            .filter_map(|id| {
+                let path = format!("{}/{}{}", endpoint, encode(id), query(&[], &params));
+                self.call("DELETE", &path).err()
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(String::new())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    fn pull(&self, reference: &str) -> Result<String, String> {
+        let (image, tag) = split_reference(reference);
+        let params = [("fromImage", image.to_string()), ("tag", tag.to_string())];
+        let body = self.call("POST", &format!("/images/create{}", query(&[], &params)))?;
+        // Pull failures still answer 200, with the error in the progress stream
+        let error = body
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find_map(|v| v["error"].as_str().map(str::to_string));
+        match error {
+            Some(error) => Err(error),
+            None => Ok(String::new()),
+        }
+    }
+
+    fn tag(&self, source: &str, target: &str) -> Result<String, String> {
+        let (repo, tag) = split_reference(target);
+        let params = [("repo", repo.to_string()), ("tag", tag.to_string())];
+        self.call(
+            "POST",
+            &format!("/images/{}/tag{}", encode(source), query(&[], &params)),
+        )
+    }
+
+    fn stop_containers(&self, ids: &[&str]) -> Result<String, String> {
+        let errors: Vec<String> = ids
+            .iter()
+            .filter_map(|id| {
+                self.call("POST", &format!("/containers/{}/stop", encode(id)))
+                    .err()
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(String::new())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+/// Split a raw HTTP response into status code and (de-chunked) body. The
+/// body is only decoded once whole, as a chunk boundary may fall inside a
+/// multi-byte character.
+fn parse_response(raw: &[u8]) -> Result<(u16, String), String> {
+    let split = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or("Malformed response from Docker")?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let body = &raw[split + 4..];
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or("Malformed response from Docker")?;
+    let chunked = head.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    let body = if chunked {
+        dechunk(body)?
+    } else {
+        body.to_vec()
+    };
+    Ok((status, String::from_utf8_lossy(&body).into_owned()))
+}
+
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    loop {
+        let line = body
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or("Malformed chunked response from Docker")?;
+        let size = std::str::from_utf8(&body[..line])
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .ok_or("Malformed chunked response from Docker")?;
+        let rest = &body[line + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        let chunk = rest
+            .get(..size)
+            .ok_or("Truncated chunked response from Docker")?;
+        out.extend_from_slice(chunk);
+        body = rest[size..].strip_prefix(b"\r\n").unwrap_or(&rest[size..]);
+    }
+}
+
+/// Query string with `filters` (`key=value`, as passed to `--filter`)
+/// encoded the way the API expects, plus plain parameters.
+fn query(filters: &[String], params: &[(&str, String)]) -> String {
+    let mut parts: Vec<String> = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, encode(value)))
+        .collect();
+    if !filters.is_empty() {
+        let mut map = Map::new();
+        for filter in filters {
+            let (key, value) = filter.split_once('=').unwrap_or((filter, ""));
+            if let Some(values) = map.entry(key).or_insert_with(|| json!({})).as_object_mut() {
+                values.insert(value.to_string(), json!(true));
+            }
+        }
+        parts.push(format!(
+            "filters={}",
+            encode(&Value::Object(map).to_string())
+        ));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", parts.join("&"))
+    }
+}
+
+/// `name@digest` or `name:tag` split into the name and the digest or tag;
+/// a `:` before the last `/` belongs to a registry port.
+fn split_reference(reference: &str) -> (&str, &str) {
+    if let Some(split) = reference.split_once('@') {
+        return split;
+    }
+    match reference.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => (name, tag),
+        _ => (reference, "latest"),
+    }
+}
+
+/// Percent-encode everything but unreserved characters.
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Volume size from `/system/df`; -1 means the daemon didn't measure it.
+fn volume_size(v: &Value) -> u64 {
+    v["UsageData"]["Size"].as_u64().unwrap_or(0)
+}
+
+/// The same totals `docker system df` prints, computed from `/system/df`.
+fn disk_usage_from_api(df: &Value) -> DiskUsage {
+    let images = array(&df["Images"]);
+    let containers = array(&df["Containers"]);
+    let volumes = array(&df["Volumes"]);
+    let build_cache = array(&df["BuildCache"]);
+    let num = |v: &Value, key: &str| v[key].as_u64().unwrap_or(0);
+    let in_use = |v: &Value| v["Containers"].as_i64().unwrap_or(0) > 0;
+
+    let mut usage = DiskUsage {
+        images_size: df["LayersSize"].as_u64().unwrap_or(0),
+        images_count: images.len(),
+        images_active: images.iter().filter(|i| in_use(i)).count(),
+        images_reclaimable: images
+            .iter()
+            .filter(|i| !in_use(i))
+            .map(|i| num(i, "Size").saturating_sub(num(i, "SharedSize")))
+            .sum(),
+        containers_count: containers.len(),
+        containers_active: containers
+            .iter()
+            .filter(|c| c["State"] == "running")
+            .count(),
+        volumes_count: volumes.len(),
+        volumes_active: volumes
+            .iter()
+            .filter(|v| v["UsageData"]["RefCount"].as_i64().unwrap_or(0) > 0)
+            .count(),
+        build_cache_count: build_cache.len(),
+        build_cache_active: build_cache
+            .iter()
+            .filter(|b| b["InUse"].as_bool().unwrap_or(false))
+            .count(),
+        ..DiskUsage::default()
+    };
+    for c in containers {
+        usage.containers_size += num(c, "SizeRw");
+        if c["State"] != "running" {
+            usage.containers_reclaimable += num(c, "SizeRw");
+        }
+    }
+    for v in volumes {
+        usage.volumes_size += volume_size(v);
+        if v["UsageData"]["RefCount"].as_i64().unwrap_or(0) == 0 {
+            usage.volumes_reclaimable += volume_size(v);
+        }
+    }
+    for b in build_cache {
+        usage.build_cache_size += num(b, "Size");
+        if !b["InUse"].as_bool().unwrap_or(false) && !b["Shared"].as_bool().unwrap_or(false) {
+            usage.build_cache_reclaimable += num(b, "Size");
+        }
+    }
+    usage
+}
+
+fn is_socket(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    path.metadata().is_ok_and(|m| m.file_type().is_socket())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    /// Serve one canned response on a socket in a temp dir, returning the
+    /// client and the request line it received.
+    fn serve_once(
+        response: &'static str,
+    ) -> (EngineApi, thread::JoinHandle<String>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("docker.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            std::io::BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            request_line.trim().to_string()
+        });
+        (EngineApi::at(socket), handle, dir)
+    }
+
+    #[test]
+    fn test_lists_images_over_socket() {
+        let (api, server, _dir) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n\
+             51\r\n[{\"Id\":\"sha256:aaa\",\"RepoTags\":[\"postgres:16\"],\"Size\":1200,\"Created\":1700000000}]\r\n0\r\n\r\n",
+        );
+        let images = api.list_images(&["label=env=dev".to_string()]).unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            "GET /images/json?filters=%7B%22label%22%3A%7B%22env%3Ddev%22%3Atrue%7D%7D HTTP/1.1"
+        );
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].display_name(), "postgres:16");
+        assert_eq!(images[0].size_bytes(), 1200);
+        assert_eq!(images[0].created_at, "2023-11-14 22:13:20 +0000 UTC");
+    }
+
+    #[test]
+    fn test_error_status_keeps_daemon_message() {
+        let (api, server, _dir) = serve_once(
+            "HTTP/1.1 409 Conflict\r\nContent-Length: 51\r\n\r\n{\"message\":\"conflict: unable to remove repository\"}",
+        );
+        let err = api
+            .remove(ResourceType::Image, &["postgres:16"], false)
+            .unwrap_err();
+        assert_eq!(
+            server.join().unwrap(),
+            "DELETE /images/postgres%3A16?force=false HTTP/1.1"
+        );
+        assert_eq!(err, "conflict: unable to remove repository (HTTP 409)");
+    }
+
+    #[test]
+    fn test_prune_all_images_disables_dangling_filter() {
+        assert_eq!(
+            query(&["dangling=false".to_string()], &[]),
+            "?filters=%7B%22dangling%22%3A%7B%22false%22%3Atrue%7D%7D"
+        );
+        assert_eq!(query(&[], &[("all", "true".to_string())]), "?all=true");
+        assert_eq!(query(&[], &[]), "");
+    }
+
+    #[test]
+    fn test_split_reference() {
+        assert_eq!(split_reference("postgres:16"), ("postgres", "16"));
+        assert_eq!(
+            split_reference("postgres@sha256:feed"),
+            ("postgres", "sha256:feed")
+        );
+        assert_eq!(
+            split_reference("registry:5000/app"),
+            ("registry:5000/app", "latest")
+        );
+    }
+
+    #[test]
+    fn test_disk_usage_from_api() {
+        let usage = disk_usage_from_api(&json!({
+            "LayersSize": 3000,
+            "Images": [
+                {"Size": 2000, "SharedSize": 500, "Containers": 1},
+                {"Size": 1000, "SharedSize": 0, "Containers": 0}
+            ],
+            "Containers": [
+                {"SizeRw": 30, "State": "running"},
+                {"SizeRw": 20, "State": "exited"}
+            ],
+            "Volumes": [
+                {"Name": "pgdata", "UsageData": {"Size": 250, "RefCount": 1}},
+                {"Name": "scratch", "UsageData": {"Size": 50, "RefCount": 0}}
+            ],
+            "BuildCache": [
+                {"Size": 400, "InUse": false, "Shared": false},
+                {"Size": 100, "InUse": true, "Shared": false}
+            ]
+        }));
+        assert_eq!(usage.images_size, 3000);
+        assert_eq!((usage.images_count, usage.images_active), (2, 1));
+        assert_eq!(usage.images_reclaimable, 1000);
+        assert_eq!(
+            (usage.containers_size, usage.containers_reclaimable),
+            (50, 20)
+        );
+        assert_eq!((usage.volumes_size, usage.volumes_reclaimable), (300, 50));
+        assert_eq!(
+            (usage.build_cache_size, usage.build_cache_reclaimable),
+            (500, 400)
+        );
+    }
+
+    #[test]
+    fn test_dechunk() {
+        assert_eq!(
+            dechunk(b"3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n").unwrap(),
+            b"abcde"
+        );
+        assert!(dechunk(b"zz\r\n").is_err());
+    }
+
+    #[test]
+    fn test_chunks_may_split_a_character() {
+        // "é" is two bytes, one in each chunk
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                    2\r\n\"\xc3\r\n2\r\n\xa9\"\r\n0\r\n\r\n";
+        assert_eq!(
+            parse_response(raw).unwrap(),
+            (200, "\"\u{e9}\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! What a cleanup does when docker refuses to remove something. By default
+//! the run stops after the step that failed and leaves the rest of its plan
+//! alone; with `--continue-on-error` it carries on with the next step.
+//! Either way each resource that couldn't be removed is reported with
+//! docker's reason. A resource still in use is skipped rather than failed:
+//! it isn't garbage yet.
+
+use crate::cancel;
+use crate::display::{print_failures, print_warning};
+use crate::docker::{is_no_space_error, RemoveError, RemoveFailure};
+use crate::report::{CleanupReport, ItemStatus, ReportItem};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CONTINUE: AtomicBool = AtomicBool::new(false);
+
+/// Returned by a step to stop the run after a failure.
+const STOPPED: &str = "Stopped after a failure";
+
+/// Error on what a stopped run didn't get to.
+pub const NOT_ATTEMPTED: &str = "Not attempted after an earlier failure";
+
+/// Note on resources skipped because something still uses them.
+pub const IN_USE_NOTE: &str = "In use";
+
+/// Keep going after a failed step for the rest of the run (from
+/// `--continue-on-error`).
+pub fn continue_on_error() {
+    CONTINUE.store(true, Ordering::SeqCst);
+}
+
+pub fn is_continuing() -> bool {
+    CONTINUE.load(Ordering::SeqCst)
+}
+
+/// Record a step that removed the `(id, name)` `targets` of `category`:
+/// those that went are marked removed, the rest failed or skipped with
+/// docker's reason. Returns whether all of them went, or an error to stop
+/// the run unless continuing.
+pub fn removed(
+    report: &mut CleanupReport,
+    category: &str,
+    targets: &[(&str, &str)],
+    result: Result<String, RemoveError>,
+) -> Result<bool, String> {
+    let failed = record(report, category, targets, result)?;
+    stop_unless_continuing(failed == Some(true))?;
+    Ok(failed.is_none())
+}
+
+/// Record a removal like [`removed`] without stopping the run, for steps
+/// made of several removals that should all be tried first. Returns
+/// `None` when all of `targets` went, else whether a failure should stop
+/// the run (see [`stop_unless_continuing`]); only errors that stop
+/// everything, like a full disk, are returned right away.
+pub fn record(
+    report: &mut CleanupReport,
+    category: &str,
+    targets: &[(&str, &str)],
+    result: Result<String, RemoveError>,
+) -> Result<Option<bool>, String> {
+    let failures = match result {
+        Ok(_) => vec![],
+        Err(RemoveError::Failed(failures)) => failures,
+        Err(RemoveError::Other(e)) => {
+            if stops_everything(&e) {
+                return Err(e);
+            }
+            targets
+                .iter()
+                .map(|(id, _)| RemoveFailure::new(id, &e))
+                .collect()
+        }
+    };
+    if let Some(full) = failures.iter().find(|f| is_no_space_error(&f.reason)) {
+        return Err(full.reason.clone());
+    }
+
+    for (id, name) in targets {
+        match failures.iter().find(|f| f.id == *id) {
+            Some(failure) => fail(report, category, name, failure),
+            None => report.mark_removed(category, name),
+        }
+    }
+    // Resources docker named that weren't among the targets
+    for failure in failures
+        .iter()
+        .filter(|f| !targets.iter().any(|(id, _)| *id == f.id))
+    {
+        report
+            .items
+            .push(ReportItem::planned(category, &failure.id, None));
+        fail(report, category, &failure.id, failure);
+    }
+    Ok((!failures.is_empty()).then(|| failures.iter().any(|f| !f.in_use())))
+}
+
+/// Record a step whose outcome is the one report item `name` of
+/// `category`, such as a prune. When some resources failed and the rest
+/// went, the item counts as removed and each failure gets an item of its
+/// own. Returns like [`removed`].
+pub fn step(
+    report: &mut CleanupReport,
+    category: &str,
+    name: &str,
+    result: Result<String, impl Into<RemoveError>>,
+) -> Result<bool, String> {
+    match result.map_err(Into::into) {
+        Ok(_) => {
+            report.mark_removed(category, name);
+            Ok(true)
+        }
+        Err(RemoveError::Other(e)) if stops_everything(&e) => Err(e),
+        Err(RemoveError::Other(e)) => {
+            fail(report, category, name, &RemoveFailure::new(name, &e));
+            stop_unless_continuing(true)?;
+            Ok(false)
+        }
+        Err(RemoveError::Failed(failures)) => {
+            report.mark_removed(category, name);
+            removed(report, category, &[], Err(RemoveError::Failed(failures)))
+        }
+    }
+}
+
+/// End a cleanup whose steps returned `result`, like [`cancel::settle`].
+/// A run stopped by a failure marks what it didn't get to and goes on to
+/// report, and either way what couldn't be removed is summed up.
+pub fn settle(report: &mut CleanupReport, result: Result<(), String>) -> Result<(), String> {
+    match result {
+        Err(e) if e == STOPPED && !cancel::is_requested() => {
+            report.mark_unreached(NOT_ATTEMPTED);
+            print_failures(&report.items);
+            print_warning("Stopped after the first failure; pass --continue-on-error to go on");
+            Ok(())
+        }
+        result => {
+            cancel::settle(report, result)?;
+            if !cancel::is_requested() {
+                print_failures(&report.items);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Errors no other step would get past either.
+fn stops_everything(error: &str) -> bool {
+    cancel::is_requested() || is_no_space_error(error)
+}
+
+pub fn stop_unless_continuing(failed: bool) -> Result<(), String> {
+    if failed && !is_continuing() {
+        Err(STOPPED.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Mark the planned item `name` failed, or skipped when still in use.
+fn fail(report: &mut CleanupReport, category: &str, name: &str, failure: &RemoveFailure) {
+    let item = report
+        .items
+        .iter_mut()
+        .find(|i| i.category == category && i.name == name && i.status == ItemStatus::Planned);
+    let Some(item) = item else {
+        return;
+    };
+    if failure.in_use() {
+        item.status = ItemStatus::Skipped;
+        item.note = Some(format!("{}: {}", IN_USE_NOTE, failure.reason));
+    } else {
+        item.status = ItemStatus::Failed;
+        item.error = Some(failure.reason.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(names: &[&str]) -> CleanupReport {
+        let mut report = CleanupReport::new("nuclear", false);
+        for name in names {
+            report.items.push(ReportItem::planned("images", name, None));
+        }
+        report
+    }
+
+    fn statuses(report: &CleanupReport) -> Vec<ItemStatus> {
+        report.items.iter().map(|i| i.status).collect()
+    }
+
+    #[test]
+    fn test_failed_resources_are_reported_and_in_use_ones_skipped() {
+        let mut report = planned(&["a", "b", "c"]);
+        let failures = vec![
+            RemoveFailure::new("id-b", "Error response from daemon: permission denied"),
+            RemoveFailure::new(
+                "id-c",
+                "Error response from daemon: conflict: unable to delete id-c (cannot be forced) - image is being used by running container 1234",
+            ),
+        ];
+        let result = removed(
+            &mut report,
+            "images",
+            &[("id-a", "a"), ("id-b", "b"), ("id-c", "c")],
+            Err(RemoveError::Failed(failures)),
+        );
+        // Continuing isn't enabled in unit tests
+        assert_eq!(result, Err(STOPPED.to_string()));
+        assert_eq!(
+            statuses(&report),
+            [ItemStatus::Removed, ItemStatus::Failed, ItemStatus::Skipped]
+        );
+        assert_eq!(report.items[1].error.as_deref(), Some("permission denied"));
+        assert!(report.items[2]
+            .note
+            .as_ref()
+            .unwrap()
+            .starts_with("In use: "));
+    }
+
+    #[test]
+    fn test_only_in_use_resources_dont_stop_the_run() {
+        let mut report = planned(&["a"]);
+        let failures = vec![RemoveFailure::new("id-a", "volume is in use - [c0ffee]")];
+        let result = removed(
+            &mut report,
+            "images",
+            &[("id-a", "a")],
+            Err(RemoveError::Failed(failures)),
+        );
+        assert_eq!(result, Ok(false));
+        assert_eq!(statuses(&report), [ItemStatus::Skipped]);
+    }
+
+    #[test]
+    fn test_recorded_failures_leave_stopping_to_the_caller() {
+        let mut report = planned(&["a", "b"]);
+        let refused = |id: &str| {
+            Err(RemoveError::Failed(vec![RemoveFailure::new(
+                id,
+                "Error response from daemon: permission denied",
+            )]))
+        };
+        assert_eq!(
+            record(&mut report, "images", &[("id-a", "a")], refused("id-a")),
+            Ok(Some(true))
+        );
+        assert_eq!(
+            record(&mut report, "images", &[("id-b", "b")], Ok(String::new())),
+            Ok(None)
+        );
+        assert_eq!(statuses(&report), [ItemStatus::Failed, ItemStatus::Removed]);
+        assert_eq!(stop_unless_continuing(true), Err(STOPPED.to_string()));
+    }
+
+    #[test]
+    fn test_failed_prune_fails_its_item_and_a_full_disk_passes_through() {
+        let mut report = planned(&["dangling images"]);
+        let result = step(
+            &mut report,
+            "images",
+            "dangling images",
+            Err::<String, _>("daemon hiccup".to_string()),
+        );
+        assert_eq!(result, Err(STOPPED.to_string()));
+        assert_eq!(statuses(&report), [ItemStatus::Failed]);
+
+        let mut report = planned(&["dangling images"]);
+        let full = "Error response from daemon: no space left on device".to_string();
+        let result = step(
+            &mut report,
+            "images",
+            "dangling images",
+            Err::<String, _>(full),
+        );
+        assert!(result.unwrap_err().contains("no space left"));
+    }
+}
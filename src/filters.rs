@@ -0,0 +1,158 @@
+use crate::units::parse_docker_timestamp;
+use std::time::Duration;
+
+/// Restrictions on which Docker resources a cleanup may remove, from
+/// `--older-than`, `--keep` and `--label`. Filters only ever narrow a
+/// cleanup: a resource type that can't honor them is left alone.
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    /// Only resources created at least this long ago
+    pub older_than: Option<Duration>,
+    /// Name patterns (`*` and `?` wildcards) that are never removed
+    pub keep: Vec<String>,
+    /// Only resources with these labels (`key` or `key=value`)
+    pub labels: Vec<String>,
+}
+
+impl Filters {
+    pub fn is_empty(&self) -> bool {
+        self.older_than.is_none() && self.keep.is_empty() && self.labels.is_empty()
+    }
+
+    /// `label=...` arguments for `docker ... --filter`.
+    pub fn label_args(&self) -> Vec<String> {
+        self.labels.iter().map(|l| format!("label={}", l)).collect()
+    }
+
+    /// `until=...` argument for `docker ... prune --filter`.
+    pub fn until_arg(&self) -> Option<String> {
+        self.older_than
+            .map(|age| format!("until={}s", age.as_secs()))
+    }
+
+    /// Whether `name` matches a `--keep` pattern.
+    pub fn is_kept(&self, name: &str) -> bool {
+        self.keep.iter().any(|pattern| glob_match(pattern, name))
+    }
+
+    /// Whether a resource created at `created_at` (as printed by the docker
+    /// CLI) passes `--older-than`. An unknown creation time never does.
+    pub fn is_old_enough(&self, created_at: &str, now: u64) -> bool {
+        let Some(age) = self.older_than else {
+            return true;
+        };
+        parse_docker_timestamp(created_at)
+            .is_some_and(|created| now.saturating_sub(created) >= age.as_secs())
+    }
+
+    /// Whether a listed resource should be removed.
+    pub fn allows(&self, name: &str, created_at: &str, now: u64) -> bool {
+        !self.is_kept(name) && self.is_old_enough(created_at, now)
+    }
+
+    /// Human-readable summary, e.g. `older than 30d, label env=dev, keeping myorg/*`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(age) = self.older_than {
+            parts.push(format!("older than {}", format_age(age)));
+        }
+        for label in &self.labels {
+            parts.push(format!("label {}", label));
+        }
+        if !self.keep.is_empty() {
+            parts.push(format!("keeping {}", self.keep.join(", ")));
+        }
+        parts.join(", ")
+    }
+}
+
+/// A duration in its largest whole unit, e.g. `7d` or `90m`.
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        s if s % 86_400 == 0 => format!("{}d", s / 86_400),
+        s if s % 3_600 == 0 => format!("{}h", s / 3_600),
+        s if s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// Match `text` against a pattern where `*` matches any run of characters
+/// and `?` a single one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("myorg/*", "myorg/api:latest"));
+        assert!(glob_match("*:latest", "alpine:latest"));
+        assert!(glob_match("db-?", "db-1"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("myorg/*", "other/api:latest"));
+        assert!(!glob_match("db-?", "db-10"));
+        assert!(!glob_match("", "x"));
+    }
+
+    #[test]
+    fn test_is_old_enough() {
+        let filters = Filters {
+            older_than: Some(Duration::from_secs(30 * 86_400)),
+            ..Filters::default()
+        };
+        let now = 1_700_000_000 + 31 * 86_400;
+        assert!(filters.is_old_enough("2023-11-14 22:13:20 +0000 UTC", now));
+        assert!(!filters.is_old_enough("2023-11-14 22:13:20 +0000 UTC", now - 2 * 86_400));
+        // Unknown age is never old enough
+        assert!(!filters.is_old_enough("", now));
+        assert!(Filters::default().is_old_enough("", now));
+    }
+
+    #[test]
+    fn test_filter_args_and_description() {
+        let filters = Filters {
+            older_than: Some(Duration::from_secs(30 * 86_400)),
+            keep: vec!["myorg/*".to_string()],
+            labels: vec!["env=dev".to_string()],
+        };
+        assert_eq!(filters.until_arg().as_deref(), Some("until=2592000s"));
+        assert_eq!(filters.label_args(), vec!["label=env=dev"]);
+        assert!(!filters.allows(
+            "myorg/api:1",
+            "2000-01-01 00:00:00 +0000 UTC",
+            1_700_000_000
+        ));
+        assert!(filters.allows("alpine:3", "2000-01-01 00:00:00 +0000 UTC", 1_700_000_000));
+        assert_eq!(
+            filters.describe(),
+            "older than 30d, label env=dev, keeping myorg/*"
+        );
+        assert!(Filters::default().is_empty());
+    }
+}
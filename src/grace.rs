@@ -0,0 +1,238 @@
+//! Two-phase removal of tagged images, when `image_grace_period` is set.
+//! Rather than deleting a tagged image, `select` moves it to a holding tag
+//! (`dockerase-grace:<id>`) and records it. A later `purge` deletes it once
+//! the grace period is over, unless it was tagged again in the meantime
+//! (pulled back or re-tagged), much like a registry's garbage collection.
+//! Until then `docker tag dockerase-grace:<id> <name>` brings it back.
+
+use crate::display::print_info;
+use crate::docker::Docker;
+use crate::filters::format_age;
+use crate::history::now_secs;
+use crate::paths;
+use crate::resources::{Container, Image};
+use crate::undo::ImageRecord;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::Duration;
+
+/// Repository of the holding tags.
+pub const HOLD_REPOSITORY: &str = "dockerase-grace";
+
+/// A tagged image waiting out its grace period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hold {
+    /// The image as it was before it was held
+    pub image: ImageRecord,
+    pub held_at: u64,
+    /// When `purge` may delete it
+    pub expires_at: u64,
+}
+
+impl Hold {
+    pub fn tag(&self) -> String {
+        hold_tag(&self.image.id)
+    }
+}
+
+fn hold_tag(id: &str) -> String {
+    let id = id.trim_start_matches("sha256:");
+    format!("{}:{}", HOLD_REPOSITORY, &id[..id.len().min(12)])
+}
+
+/// What `purge` does with a hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fate {
+    /// The grace period is over and nothing uses the image: delete it
+    Delete,
+    /// Tagged again since it was held: keep it and drop the holding tag
+    Release,
+    /// Already removed some other way
+    Forget,
+    /// Still within its grace period, or a container uses it
+    Keep,
+}
+
+/// Whether `image` can be held: it has a name, and isn't held already.
+pub fn can_hold(image: &Image) -> bool {
+    !image.is_dangling() && image.repository != HOLD_REPOSITORY
+}
+
+/// Move each of `images` to its holding tag for `period` instead of
+/// deleting it. Pass only images for which [`can_hold`] is true.
+pub fn hold(images: &[&Image], period: Duration) -> Result<String, String> {
+    if images.is_empty() {
+        return Ok(String::new());
+    }
+    let now = now_secs();
+    let mut holds = list();
+    let mut errors = Vec::new();
+    for image in images {
+        let tag = hold_tag(&image.id);
+        // Tag first, so dropping the name never leaves the image dangling
+        let held = Docker::tag_image(&image.id, &tag)
+            .and_then(|_| Docker::remove_image(&image.display_name()));
+        match held {
+            Ok(_) if holds.iter().any(|h| h.image.id == image.id) => {}
+            Ok(_) => holds.push(Hold {
+                image: (*image).into(),
+                held_at: now,
+                expires_at: now + period.as_secs(),
+            }),
+            Err(e) => errors.push(format!("{}: {}", image.display_name(), e.trim())),
+        }
+    }
+    save(&holds)?;
+
+    let held = images.len() - errors.len();
+    if held > 0 {
+        print_info(&format!(
+            "Held {} tagged image(s) for {}; `dockerase purge` deletes them after that unless they are tagged again",
+            held,
+            format_age(period)
+        ));
+    }
+    if errors.is_empty() {
+        Ok(String::new())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Report note on an image held for `period` rather than removed.
+pub fn held_note(period: Duration) -> String {
+    format!("Held for {}", format_age(period))
+}
+
+/// Each recorded hold with what `purge` should do with it now.
+pub fn review(images: &[Image], containers: &[Container], now: u64) -> Vec<(Hold, Fate)> {
+    list()
+        .into_iter()
+        .map(|hold| {
+            let fate = fate(&hold, images, containers, now);
+            (hold, fate)
+        })
+        .collect()
+}
+
+fn fate(hold: &Hold, images: &[Image], containers: &[Container], now: u64) -> Fate {
+    // The CLI lists an image once per tag
+    let rows: Vec<&Image> = images.iter().filter(|i| i.id == hold.image.id).collect();
+    if rows.is_empty() {
+        return Fate::Forget;
+    }
+    let holding = hold.tag();
+    if rows
+        .iter()
+        .any(|i| !i.is_dangling() && i.display_name() != holding)
+    {
+        return Fate::Release;
+    }
+    let in_use = containers.iter().any(|c| rows.iter().any(|i| c.uses(i)));
+    if now < hold.expires_at || in_use {
+        Fate::Keep
+    } else {
+        Fate::Delete
+    }
+}
+
+/// Carry out the fates from [`review`], forgetting each hold that is
+/// settled. Holds whose removal fails are kept for the next run.
+pub fn settle(reviewed: &[(Hold, Fate)]) -> Result<String, String> {
+    let mut holds = list();
+    let mut errors = Vec::new();
+    for (hold, fate) in reviewed {
+        let result = match fate {
+            Fate::Keep => continue,
+            Fate::Forget => Ok(String::new()),
+            // Another tag remains, so this only drops the holding one
+            Fate::Release => Docker::remove_image(&hold.tag()),
+            Fate::Delete => Docker::remove_image(&hold.image.id),
+        };
+        match result {
+            Ok(_) => holds.retain(|h| h.image.id != hold.image.id),
+            Err(e) => errors.push(format!("{}: {}", hold.image.name(), e.trim())),
+        }
+    }
+    save(&holds)?;
+    if errors.is_empty() {
+        Ok(String::new())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Every recorded hold; none when the file is missing or unreadable.
+pub fn list() -> Vec<Hold> {
+    paths::image_holds()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save(holds: &[Hold]) -> Result<(), String> {
+    let path = paths::image_holds().ok_or("Could not determine state directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(holds).map_err(|e| e.to_string())?;
+    fs::write(&path, json + "\n").map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(id: &str, repository: &str, tag: &str) -> Image {
+        Image {
+            id: id.to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            size: "1GB".to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        }
+    }
+
+    fn held(id: &str, expires_at: u64) -> Hold {
+        Hold {
+            image: (&image(id, "postgres", "16")).into(),
+            held_at: 0,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_hold_tag_uses_the_short_id() {
+        assert_eq!(
+            hold_tag("sha256:0123456789abcdef"),
+            "dockerase-grace:0123456789ab"
+        );
+        assert_eq!(hold_tag("abc"), "dockerase-grace:abc");
+        assert!(!can_hold(&image("abc", HOLD_REPOSITORY, "abc")));
+        assert!(!can_hold(&image("abc", "<none>", "<none>")));
+        assert!(can_hold(&image("abc", "postgres", "16")));
+    }
+
+    #[test]
+    fn test_fate_of_a_hold() {
+        let hold = held("sha256:aaa", 100);
+        let holding = || image("sha256:aaa", HOLD_REPOSITORY, "aaa");
+
+        assert_eq!(fate(&hold, &[], &[], 200), Fate::Forget);
+        assert_eq!(fate(&hold, &[holding()], &[], 50), Fate::Keep);
+        assert_eq!(fate(&hold, &[holding()], &[], 200), Fate::Delete);
+
+        // Pulled back under its old name
+        let pulled = image("sha256:aaa", "postgres", "16");
+        assert_eq!(fate(&hold, &[holding(), pulled], &[], 200), Fate::Release);
+
+        // A container created from the held image keeps it
+        let container: Container = serde_json::from_str(
+            r#"{"ID":"c1","Names":"job","Image":"dockerase-grace:aaa","State":"exited","Status":""}"#,
+        )
+        .unwrap();
+        assert_eq!(fate(&hold, &[holding()], &[container], 200), Fate::Keep);
+    }
+}
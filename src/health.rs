@@ -0,0 +1,108 @@
+use crate::resources::DiskUsage;
+use crate::system::{outermost, CacheInfo, DiskSpace};
+use serde::Serialize;
+
+/// How much each factor contributes to the score. Factors that can't be
+/// measured are left out and the rest reweighted.
+const DOCKER_WEIGHT: f64 = 0.3;
+const CACHE_WEIGHT: f64 = 0.3;
+const HEADROOM_WEIGHT: f64 = 0.4;
+
+/// Free space at or above this share of the disk earns full headroom marks.
+const HEALTHY_FREE_PERCENT: f64 = 25.0;
+
+/// A single 0-100 number summarizing how tidy the machine is, with the
+/// factors it was derived from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthScore {
+    pub score: u8,
+    /// Share of Docker's disk usage that could be reclaimed
+    pub docker_reclaimable_percent: Option<f64>,
+    /// Share of cache bytes not used in the last 30 days
+    pub stale_cache_percent: Option<f64>,
+    /// Free space on the disk backing Docker
+    pub free_percent: Option<f64>,
+}
+
+impl HealthScore {
+    pub fn compute(usage: &DiskUsage, caches: &[CacheInfo], disk: Option<DiskSpace>) -> Self {
+        let docker_reclaimable_percent = percent(usage.total_reclaimable(), usage.total_size());
+
+        let refs: Vec<&CacheInfo> = caches.iter().filter(|c| c.is_purgeable()).collect();
+        let counted = outermost(&refs);
+        let cache_size: u64 = counted.iter().map(|c| c.size).sum();
+        let stale_size: u64 = counted.iter().map(|c| c.stale_size).sum();
+        let stale_cache_percent = percent(stale_size, cache_size);
+
+        let free_percent = disk.and_then(|d| percent(d.available, d.total));
+
+        let factors = [
+            (docker_reclaimable_percent.map(|p| 100.0 - p), DOCKER_WEIGHT),
+            (stale_cache_percent.map(|p| 100.0 - p), CACHE_WEIGHT),
+            (
+                free_percent.map(|p| (p / HEALTHY_FREE_PERCENT * 100.0).min(100.0)),
+                HEADROOM_WEIGHT,
+            ),
+        ];
+        let (sum, weights) = factors
+            .iter()
+            .filter_map(|(value, weight)| value.map(|v| (v * weight, weight)))
+            .fold((0.0, 0.0), |(s, w), (v, weight)| (s + v, w + weight));
+        let score = if weights > 0.0 { sum / weights } else { 100.0 };
+
+        Self {
+            score: score.round().clamp(0.0, 100.0) as u8,
+            docker_reclaimable_percent,
+            stale_cache_percent,
+            free_percent,
+        }
+    }
+}
+
+fn percent(part: u64, whole: u64) -> Option<f64> {
+    (whole > 0).then(|| part as f64 / whole as f64 * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(total: u64, reclaimable: u64) -> DiskUsage {
+        DiskUsage {
+            images_size: total,
+            images_reclaimable: reclaimable,
+            ..DiskUsage::default()
+        }
+    }
+
+    #[test]
+    fn test_perfect_score_when_nothing_to_clean() {
+        let disk = DiskSpace {
+            total: 100,
+            available: 50,
+        };
+        let score = HealthScore::compute(&usage(10, 0), &[], Some(disk));
+        assert_eq!(score.score, 100);
+        assert_eq!(score.stale_cache_percent, None);
+    }
+
+    #[test]
+    fn test_score_combines_factors() {
+        // Docker: 50% reclaimable -> 50; headroom: 10% free -> 40
+        let disk = DiskSpace {
+            total: 100,
+            available: 10,
+        };
+        let score = HealthScore::compute(&usage(100, 50), &[], Some(disk));
+        assert_eq!(score.docker_reclaimable_percent, Some(50.0));
+        // (50 * 0.3 + 40 * 0.4) / 0.7
+        assert_eq!(score.score, 44);
+    }
+
+    #[test]
+    fn test_score_without_measurements() {
+        let score = HealthScore::compute(&DiskUsage::default(), &[], None);
+        assert_eq!(score.score, 100);
+        assert_eq!(score.free_percent, None);
+    }
+}
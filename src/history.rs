@@ -0,0 +1,203 @@
+use crate::paths;
+use crate::resources::DiskUsage;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Snapshots closer together than this are not recorded, so running
+/// `dockerase` repeatedly doesn't flood the history with identical points.
+const MIN_SNAPSHOT_INTERVAL_SECS: u64 = 3_600;
+
+/// Only this many snapshots are kept on disk.
+const MAX_SNAPSHOTS: usize = 1_000;
+
+/// Growth is estimated from snapshots within this window.
+const FORECAST_WINDOW_SECS: u64 = 30 * 86_400;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    pub timestamp: u64,
+    pub total_size: u64,
+    pub reclaimable: u64,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn load_snapshots() -> Vec<UsageSnapshot> {
+    let path = match paths::usage_history() {
+        Some(p) => p,
+        None => return vec![],
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(content) => parse_snapshots(&content),
+        Err(_) => vec![],
+    }
+}
+
+fn parse_snapshots(content: &str) -> Vec<UsageSnapshot> {
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+pub fn record_snapshot(usage: &DiskUsage) -> Result<(), String> {
+    let path = paths::usage_history().ok_or("Could not determine data directory")?;
+    let mut snapshots = load_snapshots();
+    let now = now_secs();
+
+    if let Some(last) = snapshots.last() {
+        if now.saturating_sub(last.timestamp) < MIN_SNAPSHOT_INTERVAL_SECS {
+            return Ok(());
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let snapshot = UsageSnapshot {
+        timestamp: now,
+        total_size: usage.total_size(),
+        reclaimable: usage.total_reclaimable(),
+    };
+
+    if snapshots.len() >= MAX_SNAPSHOTS {
+        // Rewrite the file keeping only the most recent entries
+        snapshots.push(snapshot);
+        let keep = &snapshots[snapshots.len() - MAX_SNAPSHOTS..];
+        let content: String = keep
+            .iter()
+            .filter_map(|s| serde_json::to_string(s).ok())
+            .map(|l| l + "\n")
+            .collect();
+        fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    } else {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let line = serde_json::to_string(&snapshot).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// Estimate Docker's growth in bytes per day using a least-squares fit over
+/// the recent snapshots. Returns `None` without at least a day of history.
+pub fn growth_rate_per_day(snapshots: &[UsageSnapshot], now: u64) -> Option<f64> {
+    let recent: Vec<&UsageSnapshot> = snapshots
+        .iter()
+        .filter(|s| now.saturating_sub(s.timestamp) <= FORECAST_WINDOW_SECS)
+        .collect();
+
+    if recent.len() < 2 {
+        return None;
+    }
+
+    let first = recent.first()?.timestamp;
+    let last = recent.last()?.timestamp;
+    if (last.saturating_sub(first) as f64) < SECONDS_PER_DAY {
+        return None;
+    }
+
+    let n = recent.len() as f64;
+    let xs: Vec<f64> = recent
+        .iter()
+        .map(|s| (s.timestamp - first) as f64 / SECONDS_PER_DAY)
+        .collect();
+    let ys: Vec<f64> = recent.iter().map(|s| s.total_size as f64).collect();
+
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+
+    let mut num = 0.0;
+    let mut den = 0.0;
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        num += (x - mean_x) * (y - mean_y);
+        den += (x - mean_x) * (x - mean_x);
+    }
+
+    if den == 0.0 {
+        return None;
+    }
+
+    Some(num / den)
+}
+
+/// Days until `free_bytes` is consumed at `rate_per_day`. Returns `None`
+/// when usage is flat or shrinking.
+pub fn days_until_full(free_bytes: u64, rate_per_day: f64) -> Option<f64> {
+    if rate_per_day <= 0.0 {
+        return None;
+    }
+    Some(free_bytes as f64 / rate_per_day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(day: u64, total: u64) -> UsageSnapshot {
+        UsageSnapshot {
+            timestamp: 1_700_000_000 + day * 86_400,
+            total_size: total,
+            reclaimable: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_snapshots_skips_invalid_lines() {
+        let content = "{\"timestamp\":1,\"total_size\":2,\"reclaimable\":3}\nnot json\n\n";
+        let snapshots = parse_snapshots(content);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].total_size, 2);
+    }
+
+    #[test]
+    fn test_growth_rate_linear() {
+        let snapshots = vec![snap(0, 1_000), snap(1, 2_000), snap(2, 3_000)];
+        let now = snapshots[2].timestamp;
+        let rate = growth_rate_per_day(&snapshots, now).unwrap();
+        assert!((rate - 1_000.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_growth_rate_needs_a_day_of_history() {
+        let mut later = snap(0, 2_000);
+        later.timestamp += 3_600;
+        let snapshots = vec![snap(0, 1_000), later];
+        assert!(growth_rate_per_day(&snapshots, snapshots[1].timestamp).is_none());
+        assert!(growth_rate_per_day(&snapshots[..1], snapshots[0].timestamp).is_none());
+    }
+
+    #[test]
+    fn test_growth_rate_ignores_old_snapshots() {
+        let snapshots = vec![snap(0, 100_000), snap(60, 1_000), snap(61, 2_000)];
+        let now = snapshots[2].timestamp;
+        let rate = growth_rate_per_day(&snapshots, now).unwrap();
+        assert!((rate - 1_000.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_days_until_full() {
+        assert_eq!(days_until_full(10_000, 1_000.0), Some(10.0));
+        assert!(days_until_full(10_000, 0.0).is_none());
+        assert!(days_until_full(10_000, -5.0).is_none());
+    }
+}
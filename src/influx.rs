@@ -0,0 +1,135 @@
+use crate::report::UsageReport;
+use std::process::Command;
+
+/// The usage overview as InfluxDB line protocol, one `docker_disk` point per
+/// resource type plus a `dockerase` summary point, all stamped `timestamp`
+/// (UNIX seconds).
+pub fn line_protocol(report: &UsageReport, timestamp: u64) -> String {
+    let usage = &report.usage;
+    let ns = u128::from(timestamp) * 1_000_000_000;
+    let mut lines: Vec<String> = [
+        (
+            "images",
+            usage.images_size,
+            usage.images_reclaimable,
+            usage.images_count,
+            usage.images_active,
+        ),
+        (
+            "containers",
+            usage.containers_size,
+            usage.containers_reclaimable,
+            usage.containers_count,
+            usage.containers_active,
+        ),
+        (
+            "volumes",
+            usage.volumes_size,
+            usage.volumes_reclaimable,
+            usage.volumes_count,
+            usage.volumes_active,
+        ),
+        (
+            "build_cache",
+            usage.build_cache_size,
+            usage.build_cache_reclaimable,
+            usage.build_cache_count,
+            usage.build_cache_active,
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, size, reclaimable, count, active)| {
+        format!(
+            "docker_disk,type={} size={}i,reclaimable={}i,count={}i,active={}i {}",
+            kind, size, reclaimable, count, active, ns
+        )
+    })
+    .collect();
+
+    let mut fields = vec![
+        format!("total_size={}i", report.total_size),
+        format!("total_reclaimable={}i", report.total_reclaimable),
+        format!("health_score={}i", report.health.score),
+        format!("orphaned_volumes={}i", report.unused_volumes.orphaned.len()),
+        format!(
+            "unmounted_volumes={}i",
+            report.unused_volumes.unmounted.len()
+        ),
+    ];
+    // Fields without a value are left out; line protocol has no null
+    if let Some(days) = report.forecast_days_until_full {
+        fields.push(format!("days_until_full={}", days));
+    }
+    if let Some(free) = report.health.free_percent {
+        fields.push(format!("free_percent={}", free));
+    }
+    lines.push(format!("dockerase {} {}", fields.join(","), ns));
+
+    lines.join("\n") + "\n"
+}
+
+/// POST line protocol to an InfluxDB write endpoint (or a Telegraf
+/// `http_listener_v2`) using curl.
+pub fn push(url: &str, body: &str) -> Result<(), String> {
+    let output = Command::new("curl")
+        .args([
+            "-fsS",
+            "-X",
+            "POST",
+            "-H",
+            "Content-Type: text/plain; charset=utf-8",
+            // `-d` would strip the newlines between points
+            "--data-binary",
+            body,
+            url,
+        ])
+        .output()
+        .map_err(|e| format!("Failed to execute curl: {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Push to {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::HealthScore;
+    use crate::resources::{DiskUsage, UnusedVolumes};
+
+    #[test]
+    fn test_line_protocol() {
+        let usage = DiskUsage {
+            images_size: 1_000,
+            images_reclaimable: 400,
+            images_count: 3,
+            images_active: 1,
+            ..DiskUsage::default()
+        };
+        let health = HealthScore::compute(&usage, &[], None);
+        let unused = UnusedVolumes {
+            orphaned: vec!["4f4f".to_string()],
+            unmounted: vec![],
+        };
+        let report = UsageReport::new(usage, unused, Some(12.5), health);
+
+        let lines = line_protocol(&report, 1_700_000_000);
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "docker_disk,type=images size=1000i,reclaimable=400i,count=3i,active=1i 1700000000000000000"
+        );
+        assert!(lines[3].starts_with("docker_disk,type=build_cache size=0i,"));
+        assert!(lines[4].starts_with("dockerase total_size=1000i,total_reclaimable=400i,"));
+        assert!(lines[4].contains(",orphaned_volumes=1i,unmounted_volumes=0i,"));
+        assert!(lines[4].contains(",days_until_full=12.5"));
+        assert!(!lines[4].contains("free_percent"));
+    }
+}
@@ -0,0 +1,390 @@
+//! What images really take on disk. `docker images` shows each image's full
+//! size, but images built from the same base share its layers, so adding up
+//! that column overstates the total and removing an image frees only the
+//! layers no other image uses. Layers come from `docker image inspect`,
+//! their sizes and the instructions that made them from `docker history`.
+
+use crate::docker::parse_size;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// One filesystem layer of an image.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Layer {
+    pub digest: String,
+    pub size: u64,
+    /// The instruction that created it, e.g. `RUN apt-get install ...`
+    pub created_by: String,
+}
+
+/// An image and its layers, base first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageLayers {
+    pub id: String,
+    pub name: String,
+    pub layers: Vec<Layer>,
+}
+
+/// `(size, created_by)` of each step of `docker history --no-trunc
+/// --human=false --format '{{.Size}}\t{{.CreatedBy}}'`, oldest first.
+pub fn parse_history(output: &str) -> Vec<(u64, String)> {
+    let mut steps: Vec<(u64, String)> = output
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|line| {
+            let (size, created_by) = line.split_once('\t').unwrap_or((line, ""));
+            let size = size.trim().parse().unwrap_or_else(|_| parse_size(size));
+            (size, created_by.trim().to_string())
+        })
+        .collect();
+    // Docker lists the newest step first
+    steps.reverse();
+    steps
+}
+
+/// Instructions that only change an image's config and add no layer.
+const METADATA_INSTRUCTIONS: [&str; 13] = [
+    "ARG",
+    "CMD",
+    "ENTRYPOINT",
+    "ENV",
+    "EXPOSE",
+    "HEALTHCHECK",
+    "LABEL",
+    "MAINTAINER",
+    "ONBUILD",
+    "SHELL",
+    "STOPSIGNAL",
+    "USER",
+    "VOLUME",
+];
+
+/// Pair the layer `digests` of an image with the `history` steps that
+/// created them. Steps like `ENV` add no layer, and `docker history` can't
+/// tell them from a layer that happened to be empty, so when the counts
+/// differ both are walked together from the newest: a step with a size
+/// always has a layer, and one without has an empty layer only while there
+/// are more layers left than sized steps and it isn't a metadata
+/// instruction. Layers left over get no size.
+pub fn assign(digests: Vec<String>, history: &[(u64, String)]) -> Vec<Layer> {
+    let layer = |digest, step: Option<&(u64, String)>| Layer {
+        digest,
+        size: step.map(|(size, _)| *size).unwrap_or(0),
+        created_by: step.map(|(_, by)| by.clone()).unwrap_or_default(),
+    };
+    if history.len() == digests.len() {
+        return digests
+            .into_iter()
+            .zip(history)
+            .map(|(digest, step)| layer(digest, Some(step)))
+            .collect();
+    }
+
+    let mut sized_left = history.iter().filter(|(size, _)| *size > 0).count();
+    let mut layers_left = digests.len();
+    let mut steps = history.iter().rev();
+    let mut layers: Vec<Layer> = digests
+        .into_iter()
+        .rev()
+        .map(|digest| {
+            let spare = layers_left > sized_left;
+            let step = steps.find(|(size, by)| *size > 0 || (spare && !is_metadata(by)));
+            if step.is_some_and(|(size, _)| *size > 0) {
+                sized_left -= 1;
+            }
+            layers_left -= 1;
+            layer(digest, step)
+        })
+        .collect();
+    layers.reverse();
+    layers
+}
+
+/// Whether a `docker history` step, as the classic builder
+/// (`/bin/sh -c #(nop)  ENV ...`) or BuildKit (`ENV ...`) records it, only
+/// changed the config.
+fn is_metadata(created_by: &str) -> bool {
+    let instruction = created_by
+        .strip_prefix("/bin/sh -c #(nop)")
+        .unwrap_or(created_by)
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    METADATA_INSTRUCTIONS.contains(&instruction)
+}
+
+/// How much of an image is its own.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageAnalysis {
+    pub id: String,
+    pub name: String,
+    pub layers: usize,
+    /// Every layer, as `docker images` counts it
+    pub size: u64,
+    /// Layers other images use too
+    pub shared: u64,
+    /// Layers only this image uses: what removing it would free
+    pub unique: u64,
+    /// Containers created from it, which keep it from being removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub containers: Vec<String>,
+}
+
+/// A layer more than one image is built on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SharedLayer {
+    pub digest: String,
+    pub size: u64,
+    pub created_by: String,
+    pub images: Vec<String>,
+}
+
+/// A layer only one image uses.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UniqueLayer {
+    pub digest: String,
+    pub size: u64,
+    pub created_by: String,
+    pub image: String,
+}
+
+/// Layers of every image, by `analyze images`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayerReport {
+    /// What the images add up to in `docker images`
+    pub listed_size: u64,
+    /// What they take on disk, counting each layer once
+    pub disk_size: u64,
+    /// Largest `unique` first
+    pub images: Vec<ImageAnalysis>,
+    /// Largest first
+    pub shared_layers: Vec<SharedLayer>,
+    /// Largest first, at most `top` of them
+    pub largest_unique_layers: Vec<UniqueLayer>,
+}
+
+impl LayerReport {
+    /// Analyze `images`, given the containers created from each image ID,
+    /// keeping the `top` largest unique layers.
+    pub fn new(
+        images: &[ImageLayers],
+        containers: &HashMap<String, Vec<String>>,
+        top: usize,
+    ) -> Self {
+        // Which images use each layer, in the order they were given
+        let mut users: BTreeMap<&str, (&Layer, Vec<&str>)> = BTreeMap::new();
+        for image in images {
+            for layer in distinct(&image.layers) {
+                let (_, names) = users.entry(&layer.digest).or_insert((layer, vec![]));
+                names.push(&image.name);
+            }
+        }
+
+        let mut analyses: Vec<ImageAnalysis> = images
+            .iter()
+            .map(|image| {
+                let (unique, shared): (Vec<&Layer>, Vec<&Layer>) = distinct(&image.layers)
+                    .into_iter()
+                    .partition(|l| users[l.digest.as_str()].1.len() == 1);
+                let shared: u64 = shared.iter().map(|l| l.size).sum();
+                let unique: u64 = unique.iter().map(|l| l.size).sum();
+                ImageAnalysis {
+                    id: image.id.clone(),
+                    name: image.name.clone(),
+                    layers: image.layers.len(),
+                    size: shared + unique,
+                    shared,
+                    unique,
+                    containers: containers.get(&image.id).cloned().unwrap_or_default(),
+                }
+            })
+            .collect();
+        analyses.sort_by_key(|a| Reverse(a.unique));
+
+        let mut shared_layers = vec![];
+        let mut unique_layers = vec![];
+        for (digest, (layer, names)) in &users {
+            if names.len() > 1 {
+                shared_layers.push(SharedLayer {
+                    digest: digest.to_string(),
+                    size: layer.size,
+                    created_by: layer.created_by.clone(),
+                    images: names.iter().map(|n| n.to_string()).collect(),
+                });
+            } else if layer.size > 0 {
+                unique_layers.push(UniqueLayer {
+                    digest: digest.to_string(),
+                    size: layer.size,
+                    created_by: layer.created_by.clone(),
+                    image: names[0].to_string(),
+                });
+            }
+        }
+        shared_layers.sort_by_key(|l| Reverse(l.size));
+        unique_layers.sort_by_key(|l| Reverse(l.size));
+        unique_layers.truncate(top);
+
+        Self {
+            listed_size: analyses.iter().map(|a| a.size).sum(),
+            disk_size: users.values().map(|(layer, _)| layer.size).sum(),
+            images: analyses,
+            shared_layers,
+            largest_unique_layers: unique_layers,
+        }
+    }
+
+    /// Bytes `docker images` counts more than once.
+    pub fn shared_savings(&self) -> u64 {
+        self.listed_size.saturating_sub(self.disk_size)
+    }
+}
+
+/// Each layer once; an image can repeat one (two identical `COPY`s).
+fn distinct(layers: &[Layer]) -> Vec<&Layer> {
+    let mut seen = HashSet::new();
+    layers
+        .iter()
+        .filter(|l| seen.insert(l.digest.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(digest: &str, size: u64) -> Layer {
+        Layer {
+            digest: digest.to_string(),
+            size,
+            created_by: format!("RUN make {}", digest),
+        }
+    }
+
+    fn image(name: &str, layers: &[(&str, u64)]) -> ImageLayers {
+        ImageLayers {
+            id: format!("sha256:{}", name),
+            name: name.to_string(),
+            layers: layers.iter().map(|(d, s)| layer(d, *s)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_history_is_oldest_first() {
+        let output = "0\tCMD [\"postgres\"]\n\
+                      12000000\tRUN apt-get install -y postgresql\n\
+                      80MB\t/bin/sh -c #(nop) ADD file:abc in /\n";
+        assert_eq!(
+            parse_history(output),
+            vec![
+                (
+                    80_000_000,
+                    "/bin/sh -c #(nop) ADD file:abc in /".to_string()
+                ),
+                (12_000_000, "RUN apt-get install -y postgresql".to_string()),
+                (0, "CMD [\"postgres\"]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assign_skips_steps_without_a_layer() {
+        let history = vec![
+            (80, "ADD rootfs".to_string()),
+            (0, "ENV PATH=/usr/bin".to_string()),
+            (12, "RUN install".to_string()),
+            (0, "CMD sh".to_string()),
+        ];
+        let layers = assign(vec!["sha256:a".into(), "sha256:b".into()], &history);
+        assert_eq!(
+            layers
+                .iter()
+                .map(|l| (l.size, l.created_by.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(80, "ADD rootfs"), (12, "RUN install")]
+        );
+
+        // One layer too many: the base gets no size rather than the wrong one
+        let layers = assign(
+            vec!["sha256:x".into(), "sha256:a".into(), "sha256:b".into()],
+            &history,
+        );
+        assert_eq!(
+            layers.iter().map(|l| l.size).collect::<Vec<_>>(),
+            vec![0, 80, 12]
+        );
+    }
+
+    #[test]
+    fn test_assign_gives_an_empty_layer_no_size() {
+        let history = vec![
+            (80, "ADD rootfs".to_string()),
+            (0, "RUN mkdir -p /data".to_string()),
+            (0, "/bin/sh -c #(nop)  ENV PATH=/usr/bin".to_string()),
+            (12, "RUN install".to_string()),
+            (0, "CMD sh".to_string()),
+        ];
+        let layers = assign(
+            vec!["sha256:a".into(), "sha256:e".into(), "sha256:b".into()],
+            &history,
+        );
+        assert_eq!(
+            layers
+                .iter()
+                .map(|l| (l.size, l.created_by.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (80, "ADD rootfs"),
+                (0, "RUN mkdir -p /data"),
+                (12, "RUN install")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_removing_an_image_frees_only_its_unique_layers() {
+        let images = vec![
+            image("app:1", &[("base", 80), ("deps", 300), ("app1", 20)]),
+            image("app:2", &[("base", 80), ("deps", 300), ("app2", 25)]),
+            image("tool", &[("base", 80), ("tool", 500)]),
+        ];
+        let containers = HashMap::from([("sha256:app:2".to_string(), vec!["web".to_string()])]);
+        let report = LayerReport::new(&images, &containers, 2);
+
+        assert_eq!(report.listed_size, 405 + 400 + 580);
+        assert_eq!(report.disk_size, 80 + 300 + 20 + 25 + 500);
+        assert_eq!(report.shared_savings(), 2 * 80 + 300);
+
+        let freed: Vec<(&str, u64, u64)> = report
+            .images
+            .iter()
+            .map(|a| (a.name.as_str(), a.unique, a.shared))
+            .collect();
+        assert_eq!(
+            freed,
+            vec![("tool", 500, 80), ("app:2", 25, 380), ("app:1", 20, 380)]
+        );
+        assert_eq!(report.images[1].containers, vec!["web"]);
+
+        assert_eq!(report.shared_layers[0].digest, "deps");
+        assert_eq!(
+            report.shared_layers[1].images,
+            vec!["app:1", "app:2", "tool"]
+        );
+        let largest: Vec<&str> = report
+            .largest_unique_layers
+            .iter()
+            .map(|l| l.digest.as_str())
+            .collect();
+        assert_eq!(largest, vec!["tool", "app2"]);
+    }
+
+    #[test]
+    fn test_a_repeated_layer_counts_once() {
+        let images = vec![image("a", &[("x", 10), ("x", 10), ("y", 5)])];
+        let report = LayerReport::new(&images, &HashMap::new(), 10);
+        assert_eq!((report.listed_size, report.disk_size), (15, 15));
+        assert_eq!(report.images[0].unique, 15);
+        assert!(report.shared_layers.is_empty());
+    }
+}
@@ -1,12 +1,53 @@
+mod active;
+mod advice;
+mod ages;
+mod alias;
+mod api;
+mod artifacts;
+mod bazel;
+mod cancel;
+mod cargo;
 mod commands;
+mod config;
+mod dedup;
 mod display;
 mod docker;
+#[cfg(unix)]
+mod engine;
+mod failures;
+mod filters;
+mod grace;
+mod health;
+mod history;
+mod influx;
+mod layers;
+mod nix;
+mod notes;
+mod notify;
+mod paths;
+mod plugins;
+mod podman;
+mod policy;
+mod production;
+mod quota;
+mod read_only;
+mod render;
+mod report;
 mod resources;
+mod schedule;
+mod stats;
 mod system;
+mod transcript;
+mod trash;
+mod undo;
+mod units;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use display::print_error;
+use report::{OutputFormat, Report};
+use std::path::PathBuf;
 use std::process::ExitCode;
+use std::time::Duration;
 
 const BANNER: &str = r#"
  ___     ___      __  __  _    ___  ____    ____  _____   ___
@@ -41,11 +82,82 @@ struct Cli {
     /// Show what would be removed without making changes
     #[arg(long)]
     dry_run: bool,
+
+    /// Make changes even when the config defaults to a dry run
+    #[arg(long, global = true)]
+    no_dry_run: bool,
+
+    /// Keep removing after something fails to be removed, instead of stopping after that step
+    #[arg(long, global = true)]
+    continue_on_error: bool,
+
+    /// Let --nuclear and select remove resources in active use (running
+    /// compose projects, volumes of running containers, auto-restarting
+    /// containers and their images), after a second confirmation
+    #[arg(long, global = true)]
+    include_active: bool,
+
+    #[command(flatten)]
+    filters: FilterArgs,
+
+    /// Container runtime to drive (default: docker, or podman when only it
+    /// is installed)
+    #[arg(long, global = true, value_enum)]
+    runtime: Option<docker::Runtime>,
+
+    /// Docker context (or Podman connection) to operate on instead of the
+    /// current one
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "host")]
+    context: Option<String>,
+
+    /// Docker daemon to operate on (e.g. ssh://builder, tcp://10.0.0.5:2376)
+    #[arg(short = 'H', long, global = true, value_name = "DOCKER_HOST")]
+    host: Option<String>,
+
+    /// Name of the production host being cleaned, instead of typing it at a
+    /// prompt (for unattended runs on hosts with `production = true`)
+    #[arg(long, global = true, value_name = "NAME")]
+    confirm_production: Option<String>,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Colors for good, caution and danger (overrides `palette` in the config)
+    #[arg(long, global = true, value_enum)]
+    palette: Option<display::Palette>,
+
+    /// How to print sizes: `docker` matches `docker system df` exactly
+    /// (overrides `sizes` in the config)
+    #[arg(long, global = true, value_enum, value_name = "FORMAT")]
+    sizes: Option<display::SizeFormat>,
+
+    /// Plain text without colors or symbols (the default when stdout isn't a terminal)
+    #[arg(long, global = true)]
+    plain: bool,
+
+    /// Suppress all output except errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Write a JSON report of the run to this file
+    #[arg(long, global = true, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Append a plain-text transcript of the session (output, prompts and
+    /// answers) to this file
+    #[arg(long, global = true, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Write the tagged images a cleanup removes to this file, as a `docker
+    /// pull` script or, for .yml/.yaml, a compose file
+    #[arg(long, global = true, value_name = "FILE")]
+    export_images: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Safely remove unused Docker resources (dangling images, stopped containers, unused volumes)
+    /// Safely remove unused Docker resources (dangling images, stopped containers, orphaned volumes)
     Purge {
         /// Skip confirmation prompts
         #[arg(short, long)]
@@ -54,6 +166,22 @@ enum Commands {
         /// Show what would be removed without making changes
         #[arg(long)]
         dry_run: bool,
+
+        /// Also remove named volumes that no container mounts (by default
+        /// only orphaned anonymous volumes are removed)
+        #[arg(long)]
+        named_volumes: bool,
+
+        /// Also prune the cache of this buildx builder (repeatable)
+        #[arg(long = "builder", value_name = "NAME")]
+        builders: Vec<String>,
+
+        /// Also prune the caches of every docker-container buildx builder (name remote ones with --builder)
+        #[arg(long)]
+        all_builders: bool,
+
+        #[command(flatten)]
+        filters: FilterArgs,
     },
     /// Interactively select which resources to purge
     Select {
@@ -64,8 +192,17 @@ enum Commands {
         /// Show what would be removed without making changes
         #[arg(long)]
         dry_run: bool,
+
+        /// Scan images with `docker scout` and offer to remove those with critical CVEs
+        #[arg(long)]
+        cves: bool,
+
+        /// Hold tagged images this long before deleting them (e.g. 7d); a
+        /// later `purge` deletes them unless they are tagged again
+        #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+        grace_period: Option<Duration>,
     },
-    /// Manage macOS system caches (Homebrew, npm, Xcode, etc.)
+    /// Manage system caches (npm, pip, Cargo, Xcode, etc.)
     System {
         #[command(subcommand)]
         action: Option<SystemAction>,
@@ -78,12 +215,210 @@ enum Commands {
         #[arg(long)]
         dry_run: bool,
     },
+    /// Free space on a Docker daemon that has run out of disk
+    Rescue {
+        /// Run every step without asking
+        #[arg(short, long)]
+        force: bool,
+
+        /// Show what would be done without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Return space freed inside Docker Desktop's VM to the host by compacting its disk image
+    Reclaim {
+        /// Also restart Docker Desktop, which compacts the disk image further
+        #[arg(long)]
+        restart: bool,
+
+        /// Skip confirmation prompts
+        #[arg(short, long)]
+        force: bool,
+
+        /// Show the disk image's size without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Pick what to do from a single menu
+    Interactive,
+    /// Show Docker disk usage (the same overview as running without a command)
+    Usage {
+        /// Also POST the metrics as InfluxDB line protocol to this URL
+        #[arg(long, value_name = "URL")]
+        push: Option<String>,
+    },
+    /// Time how fast each backend and strategy removes resources on this machine
+    Bench {
+        /// Resources of each kind to create and remove per strategy
+        #[arg(long, default_value_t = 10)]
+        count: usize,
+
+        /// Threads for the parallel strategy
+        #[arg(short, long, default_value_t = commands::system::default_jobs())]
+        jobs: usize,
+    },
+    /// List buildx builders that keep a build cache of their own, with its size
+    Builders,
+    /// List docker contexts with reachability and disk usage
+    Contexts,
+    /// Show docker compose projects and their disk usage
+    Compose {
+        #[command(subcommand)]
+        action: Option<ComposeAction>,
+    },
+    /// Remove old image tags, keeping the newest few of each repository
+    Images {
+        #[command(subcommand)]
+        action: ImagesAction,
+    },
+    /// Truncate container logs that have grown large
+    Logs {
+        #[command(subcommand)]
+        action: LogsAction,
+    },
+    /// List docker volumes with their size, creation date and the containers using them
+    Volumes {
+        #[command(subcommand)]
+        action: Option<VolumesAction>,
+    },
+    /// List past cleanups and what each removed
+    History,
+    /// Show how much space past cleanups freed, in total and over time
+    Stats,
+    /// Look into what resources really take on disk (read-only)
+    Analyze {
+        #[command(subcommand)]
+        action: AnalyzeAction,
+    },
+    /// Pull back the tagged images a past cleanup removed
+    Restore {
+        /// Cleanup ID, as listed by `dockerase history`
+        id: String,
+
+        /// Skip confirmation prompts
+        #[arg(short, long)]
+        force: bool,
+
+        /// Show what would be pulled without making changes
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Instead of pulling, write the images to this file as a `docker pull`
+        /// script or, for .yml/.yaml, a compose file
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["force", "dry_run"])]
+        export: Option<PathBuf>,
+    },
+    /// Show where dockerase keeps its config, history and caches
+    Paths,
+    /// Attach a note to an image, volume or system cache, which also protects it; without
+    /// arguments, list every note
+    Note {
+        /// What kind of resource the note is about
+        #[arg(value_enum, requires = "name")]
+        kind: Option<notes::Kind>,
+
+        /// The image (repository:tag or repository), volume or cache, as listed
+        name: Option<String>,
+
+        /// Why it matters; without it, the current note is shown
+        text: Option<String>,
+
+        /// Remove the note, so cleanups may remove the resource again
+        #[arg(long, requires = "kind", conflicts_with = "text")]
+        remove: bool,
+    },
+    /// Compare Docker's usage with the [quota] in the config
+    Quota {
+        /// Say nothing unless over the quota, then offer to clean up (for the shell hook)
+        #[arg(long)]
+        nag: bool,
+    },
+    /// Print a snippet for your shell's startup file that nags while over the quota
+    Hook {
+        #[arg(value_enum)]
+        shell: quota::Shell,
+    },
+    /// Fail when too much space is reclaimable, or purge it (for CI agents; never prompts)
+    Check {
+        /// Exit with code 3 when more than this is reclaimable (e.g. 20GB)
+        #[arg(
+            long,
+            visible_alias = "fail-above",
+            value_name = "SIZE",
+            value_parser = units::parse_byte_size
+        )]
+        max_reclaimable: u64,
+
+        /// Count purgeable system caches too (purged along with Docker by --auto)
+        #[arg(long)]
+        system: bool,
+
+        /// Run `purge --force` instead of failing when over the limit
+        #[arg(long)]
+        auto: bool,
+
+        /// Show what --auto would remove without making changes
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
+    /// Monitor disk usage and send advisory notifications (deletes nothing unless --purge)
+    Watch {
+        /// Time between checks (e.g. 30m, 6h, 1d)
+        #[arg(long, default_value = "1h", value_parser = units::parse_duration)]
+        interval: Duration,
+
+        /// Notify when the disk holding Docker data is fuller than this (e.g. 85%)
+        #[arg(long, default_value = "85%", value_parser = units::parse_percent)]
+        when_disk_above: u8,
+
+        /// Notify when Docker reclaimable space exceeds this size (e.g. 20GB)
+        #[arg(long, value_parser = units::parse_byte_size)]
+        when_reclaimable_above: Option<u64>,
+
+        /// Also POST a JSON notification to this URL
+        #[arg(long)]
+        webhook: Option<String>,
+
+        /// Run a single check and exit
+        #[arg(long)]
+        once: bool,
+
+        /// Run the safe purge (as `purge --force`) whenever a threshold is crossed
+        #[arg(long)]
+        purge: bool,
+
+        /// With --purge, show what would be removed without making changes
+        #[arg(long)]
+        dry_run: bool,
+
+        /// With --purge, only purge within this local time window, e.g. "Mon-Fri 19:00-07:00"
+        /// (repeatable; replaces `[watch] windows`)
+        #[arg(long, value_name = "WINDOW", value_parser = schedule::parse_window, requires = "purge")]
+        window: Vec<schedule::Window>,
+
+        /// With --purge, only purge while no build runs, on AC power and with a low CPU load
+        #[arg(long, requires = "purge")]
+        when_idle: bool,
+
+        /// CPU load, as a percentage of all cores, above which --when-idle waits (default 50%)
+        #[arg(long, value_name = "PERCENT", value_parser = units::parse_percent, requires = "when_idle")]
+        max_load: Option<u8>,
+
+        #[command(flatten)]
+        filters: FilterArgs,
+    },
 }
 
 #[derive(Subcommand)]
-enum SystemAction {
-    /// Purge all system caches
+enum ComposeAction {
+    /// Remove every container, built image, volume and network of a stopped project
     Purge {
+        /// Project name, as listed by `dockerase compose`
+        project: String,
+
         /// Skip confirmation prompts
         #[arg(short, long)]
         force: bool,
@@ -92,9 +427,21 @@ enum SystemAction {
         #[arg(long)]
         dry_run: bool,
     },
-    /// Interactively select which system caches to purge
-    Select {
-        /// Skip confirmation prompts (select all)
+}
+
+#[derive(Subcommand)]
+enum ImagesAction {
+    /// Keep the newest tags of each repository and remove the rest
+    Prune {
+        /// Tags to keep per repository, newest first (default: `image_keep_latest` from the config)
+        #[arg(long, value_name = "N")]
+        keep_latest: Option<usize>,
+
+        /// Hold removed tags for this long before deleting them (e.g. 7d)
+        #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+        grace_period: Option<Duration>,
+
+        /// Skip confirmation prompts
         #[arg(short, long)]
         force: bool,
 
@@ -104,48 +451,616 @@ enum SystemAction {
     },
 }
 
+#[derive(Subcommand)]
+enum LogsAction {
+    /// Truncate large json-file logs in place, listing each container's log size
+    Prune {
+        /// Only these containers (default: every container with a large log)
+        containers: Vec<String>,
+
+        /// Only logs at least this large (default: 100MB, or any size for named containers)
+        #[arg(long, value_name = "SIZE", value_parser = units::parse_byte_size)]
+        min_size: Option<u64>,
+
+        /// Truncate every listed log without asking
+        #[arg(short, long)]
+        force: bool,
+
+        /// Show the logs that would be truncated without making changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum VolumesAction {
+    /// Report files duplicated between volumes (read-only)
+    Duplicates {
+        /// Only compare these volumes (default: all)
+        volumes: Vec<String>,
+
+        /// Ignore files smaller than this
+        #[arg(long, value_name = "SIZE", default_value = "1KB", value_parser = units::parse_byte_size)]
+        min_file_size: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum AnalyzeAction {
+    /// Show the layers images share and what removing each image would free
+    Images {
+        /// Largest unique layers to list
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        top: usize,
+    },
+    /// Chart the space images and build cache take by age, to pick an --older-than
+    Ages,
+}
+
+#[derive(Subcommand)]
+enum SystemAction {
+    /// Purge all system caches
+    Purge(CachePurgeArgs),
+    /// Interactively select which system caches to purge
+    Select(CachePurgeArgs),
+    /// Find build artifacts (node_modules, target, .venv, ...) under a directory and delete stale ones
+    Scan {
+        /// The directory to search, such as where your projects live
+        dir: PathBuf,
+
+        /// Offer artifacts untouched for this long (default 60d, or `[scan] older_than`)
+        #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+        older_than: Option<Duration>,
+
+        /// Delete every stale artifact without asking
+        #[arg(short, long)]
+        force: bool,
+
+        /// Show what would be deleted without making changes
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also take Rust targets nothing builds into any more, because builds go to a shared target dir or the project's workspace
+        #[arg(long)]
+        redundant_targets: bool,
+    },
+}
+
+/// Restrict `purge` and `--nuclear` to a subset of resources
+#[derive(Args, Default)]
+struct FilterArgs {
+    /// Only remove resources created longer ago than this (e.g. 7d, 12h)
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    older_than: Option<Duration>,
+
+    /// Never remove resources whose name matches this pattern (`*` and `?` wildcards; repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    keep: Vec<String>,
+
+    /// Only remove resources with this label (`key` or `key=value`; repeatable)
+    #[arg(long, value_name = "KEY=VALUE")]
+    label: Vec<String>,
+}
+
+impl FilterArgs {
+    /// Combine the top-level and subcommand flags with the config defaults.
+    fn merge(self, other: FilterArgs, defaults: &config::Defaults) -> filters::Filters {
+        filters::Filters {
+            older_than: other.older_than.or(self.older_than).or(defaults.older_than),
+            keep: self.keep.into_iter().chain(other.keep).collect(),
+            labels: self.label.into_iter().chain(other.label).collect(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct CachePurgeArgs {
+    /// Skip confirmation prompts
+    #[arg(short, long)]
+    force: bool,
+
+    /// Show what would be removed without making changes
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Number of caches to purge in parallel
+    #[arg(short, long, default_value_t = commands::system::default_jobs())]
+    jobs: usize,
+
+    /// List the largest entries inside each selected cache
+    #[arg(long)]
+    detail: bool,
+
+    /// Number of entries to list per cache with --detail
+    #[arg(long, default_value_t = 10, requires = "detail")]
+    top: usize,
+
+    /// Only delete files not used for this long (e.g. 30d), keeping the rest of each cache
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    older_than: Option<Duration>,
+
+    /// Move files to the trash instead of deleting them
+    #[arg(long, conflicts_with = "permanent")]
+    trash: bool,
+
+    /// Delete files even if `trash = true` is set in the config's [defaults]
+    #[arg(long)]
+    permanent: bool,
+}
+
+impl CachePurgeArgs {
+    fn trash(&self, defaults: &config::Defaults) -> bool {
+        self.trash || (defaults.trash && !self.permanent)
+    }
+}
+
+/// The command behind an action picked in `dockerase interactive`;
+/// `--nuclear` is a flag rather than a command, so it maps to none.
+fn interactive_command(action: commands::interactive::Action) -> Option<Commands> {
+    use commands::interactive::Action;
+    match action {
+        Action::Usage | Action::Nuclear => None,
+        Action::Purge => Some(Commands::Purge {
+            force: false,
+            dry_run: false,
+            named_volumes: false,
+            builders: vec![],
+            all_builders: false,
+            filters: FilterArgs::default(),
+        }),
+        Action::Select => Some(Commands::Select {
+            force: false,
+            dry_run: false,
+            cves: false,
+            grace_period: None,
+        }),
+        Action::SystemCaches => Some(Commands::System {
+            action: Some(SystemAction::Select(CachePurgeArgs {
+                force: false,
+                dry_run: false,
+                jobs: commands::system::default_jobs(),
+                detail: false,
+                top: 10,
+                older_than: None,
+                trash: false,
+                permanent: false,
+            })),
+            force: false,
+            dry_run: false,
+        }),
+    }
+}
+
+/// Whether the command can delete something, unless previewing.
+fn is_destructive(cli: &Cli) -> bool {
+    if cli.nuclear {
+        return !cli.dry_run;
+    }
+    match &cli.command {
+        Some(
+            Commands::Purge { dry_run, .. }
+            | Commands::Select { dry_run, .. }
+            | Commands::Rescue { dry_run, .. },
+        ) => !dry_run,
+        Some(Commands::System {
+            action: Some(SystemAction::Purge(args) | SystemAction::Select(args)),
+            dry_run,
+            ..
+        }) => !dry_run && !args.dry_run,
+        Some(Commands::System {
+            action:
+                Some(SystemAction::Scan {
+                    dry_run: scan_dry_run,
+                    ..
+                }),
+            dry_run,
+            ..
+        }) => !dry_run && !scan_dry_run,
+        Some(Commands::Compose {
+            action: Some(ComposeAction::Purge { dry_run, .. }),
+        }) => !dry_run,
+        Some(Commands::Images {
+            action: ImagesAction::Prune { dry_run, .. },
+        })
+        | Some(Commands::Logs {
+            action: LogsAction::Prune { dry_run, .. },
+        }) => !dry_run,
+        Some(Commands::Check { auto, dry_run, .. }) => *auto && !dry_run,
+        Some(Commands::Watch { purge, dry_run, .. }) => *purge && !dry_run,
+        _ => false,
+    }
+}
+
+/// Add the cleanups behind `report` to the stats. Best effort: a failed
+/// write only leaves a gap in `dockerase stats`.
+fn record_stats(report: &Report) {
+    match report {
+        Report::Cleanup(cleanup) => {
+            stats::record(cleanup).ok();
+        }
+        Report::Check(check) => {
+            for cleanup in &check.cleanups {
+                stats::record(cleanup).ok();
+            }
+        }
+        Report::Quota(quota) => {
+            if let Some(cleanup) = &quota.cleanup {
+                stats::record(cleanup).ok();
+            }
+        }
+        _ => {}
+    }
+}
+
 fn main() -> ExitCode {
-    let cli = Cli::parse();
+    // A broken config shouldn't keep `--help` or `--version` from working;
+    // the commands that read it still fail on it below
+    let (aliases, alias_error) = match config::aliases() {
+        Ok(aliases) => (aliases, None),
+        Err(e) => (Default::default(), Some(e)),
+    };
+    let args = alias::expand(std::env::args_os().collect(), &aliases, &Cli::command());
+    let mut cli = match args {
+        Ok(args) => Cli::parse_from(args),
+        Err(e) => {
+            print_error(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(path) = &cli.record {
+        let command: Vec<String> = std::env::args_os()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        if let Err(e) = transcript::start(path, &command.join(" ")) {
+            print_error(&e);
+            return ExitCode::FAILURE;
+        }
+    }
+    render::configure(render::select(cli.output, cli.quiet, cli.plain));
+    if let Some(e) = alias_error {
+        display::print_warning(&format!("Aliases not expanded: {}", e));
+    }
+    cancel::install_handler();
+    if let Some(Commands::Hook { shell }) = cli.command {
+        display::print_output(&quota::hook(shell));
+        return ExitCode::SUCCESS;
+    }
+    docker::configure_runtime(cli.runtime);
+    docker::configure_target(match (cli.context.clone(), cli.host.clone()) {
+        (Some(context), _) => Some(docker::Target::Context(context)),
+        (None, Some(host)) => Some(docker::Target::Host(host)),
+        (None, None) => None,
+    });
+
+    let config = match config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            print_error(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    display::configure_styles(config.display.styles(cli.palette));
+    display::configure_size_format(cli.sizes.unwrap_or(config.display.sizes));
+
+    if let Some(Commands::Interactive) = cli.command {
+        match commands::interactive::choose() {
+            Ok(Some(action)) => {
+                cli.nuclear = action == commands::interactive::Action::Nuclear;
+                cli.command = interactive_command(action);
+            }
+            Ok(None) => return ExitCode::SUCCESS,
+            Err(e) => {
+                print_error(&e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let local_only = matches!(
+        cli.command,
+        Some(
+            Commands::System { .. }
+                | Commands::Paths
+                | Commands::History
+                | Commands::Stats
+                | Commands::Contexts
+                | Commands::Note { .. }
+        )
+    );
+    if !local_only {
+        if let Some(target) = docker::describe_target() {
+            display::print_info(&format!("Using {}", target));
+        }
+    }
+
+    // Over the soft quota, every run that talks to docker starts with a
+    // nudge to clean up
+    if display::is_text() && !local_only && !matches!(cli.command, Some(Commands::Quota { .. })) {
+        if let Some((status, offenders)) = quota::over_quota(&config.quota) {
+            display::print_quota_nag(&status, &offenders);
+        }
+    }
+
+    let overview = !cli.nuclear && matches!(cli.command, None | Some(Commands::Usage { .. }));
+    if cli.output == OutputFormat::Influx && !overview {
+        print_error("--output influx is only supported by `dockerase usage`");
+        return ExitCode::FAILURE;
+    }
+
+    if config.read_only {
+        read_only::enable();
+    }
+    if let Some(path) = cli.export_images.clone() {
+        undo::export_to(path);
+    }
+    if cli.continue_on_error {
+        failures::continue_on_error();
+    }
+    if cli.include_active {
+        active::include_active();
+    }
+    policy::configure(config.policy.clone());
+    if cli.nuclear {
+        if let Err(e) = policy::check_nuclear() {
+            print_error(&e);
+            return ExitCode::FAILURE;
+        }
+    }
+    // Read-only mode always previews; `dry_run = true` in the config does
+    // unless overridden
+    let preview =
+        cli.dry_run || read_only::is_enabled() || (config.defaults.dry_run && !cli.no_dry_run);
+
+    if config.production && !preview && is_destructive(&cli) {
+        if let Err(e) = production::confirm(cli.confirm_production.as_deref()) {
+            print_error(&e);
+            return ExitCode::FAILURE;
+        }
+    }
 
     let result = if cli.nuclear {
-        commands::nuclear::run(cli.force, cli.dry_run)
+        let filters = cli.filters.merge(FilterArgs::default(), &config.defaults);
+        commands::nuclear::run(cli.force, preview, &filters, &config.protect)
     } else {
         match cli.command {
-            Some(Commands::Purge { force, dry_run }) => {
-                commands::purge::run(force || cli.force, dry_run || cli.dry_run)
-            }
-            Some(Commands::Select { force, dry_run }) => {
-                commands::select::run(force || cli.force, dry_run || cli.dry_run)
-            }
+            Some(Commands::Purge {
+                force,
+                dry_run,
+                named_volumes,
+                builders,
+                all_builders,
+                filters,
+            }) => commands::purge::run(
+                force || cli.force,
+                dry_run || preview,
+                named_volumes || config.defaults.named_volumes,
+                &cli.filters.merge(filters, &config.defaults),
+                &config.protect,
+                &commands::builders::Selection {
+                    names: builders,
+                    all: all_builders,
+                },
+            ),
+            Some(Commands::Select {
+                force,
+                dry_run,
+                cves,
+                grace_period,
+            }) => commands::select::run(
+                force || cli.force,
+                dry_run || preview,
+                cves,
+                grace_period.or(config.image_grace_period),
+                &config.protect,
+            ),
             Some(Commands::System {
                 action,
                 force,
                 dry_run,
             }) => match action {
-                Some(SystemAction::Purge {
-                    force: purge_force,
-                    dry_run: purge_dry_run,
-                }) => commands::system::purge(
-                    force || purge_force || cli.force,
-                    dry_run || purge_dry_run || cli.dry_run,
-                    false, // not interactive
+                Some(SystemAction::Purge(args)) => commands::system::purge(
+                    &commands::system::PurgeOptions {
+                        force: force || args.force || cli.force,
+                        dry_run: dry_run || args.dry_run || preview,
+                        interactive: false,
+                        jobs: args.jobs,
+                        detail: args.detail,
+                        top: args.top,
+                        older_than: args.older_than,
+                        trash: args.trash(&config.defaults),
+                    },
+                    &config,
                 ),
-                Some(SystemAction::Select {
-                    force: select_force,
-                    dry_run: select_dry_run,
-                }) => commands::system::purge(
-                    force || select_force || cli.force,
-                    dry_run || select_dry_run || cli.dry_run,
-                    true, // interactive
+                Some(SystemAction::Select(args)) => commands::system::purge(
+                    &commands::system::PurgeOptions {
+                        force: force || args.force || cli.force,
+                        dry_run: dry_run || args.dry_run || preview,
+                        interactive: true,
+                        jobs: args.jobs,
+                        detail: args.detail,
+                        top: args.top,
+                        older_than: args.older_than,
+                        trash: args.trash(&config.defaults),
+                    },
+                    &config,
                 ),
-                None => commands::system::list(),
+                Some(SystemAction::Scan {
+                    dir,
+                    older_than,
+                    force: scan_force,
+                    dry_run: scan_dry_run,
+                    redundant_targets,
+                }) => commands::system::scan(
+                    &dir,
+                    &commands::system::ScanOptions {
+                        force: force || scan_force || cli.force,
+                        dry_run: dry_run || scan_dry_run || preview,
+                        older_than,
+                        redundant_targets,
+                    },
+                    &config,
+                ),
+                None => commands::system::list(&config),
             },
-            None => commands::list::run(),
+            Some(Commands::Watch {
+                interval,
+                when_disk_above,
+                when_reclaimable_above,
+                webhook,
+                once,
+                purge,
+                dry_run,
+                window,
+                when_idle,
+                max_load,
+                filters,
+            }) => {
+                let mut schedule = config.watch.clone();
+                if !window.is_empty() {
+                    schedule.windows = window;
+                }
+                schedule.when_idle |= when_idle;
+                schedule.max_load = max_load.or(schedule.max_load);
+                let opts = commands::watch::WatchOptions {
+                    interval,
+                    disk_above: when_disk_above,
+                    reclaimable_above: when_reclaimable_above,
+                    webhook,
+                    once,
+                    purge,
+                    dry_run: dry_run || preview,
+                    named_volumes: config.defaults.named_volumes,
+                    schedule,
+                };
+                let filters = cli.filters.merge(filters, &config.defaults);
+                return match commands::watch::run(&opts, &filters, &config.protect) {
+                    Ok(()) => ExitCode::SUCCESS,
+                    Err(e) => {
+                        print_error(&e);
+                        ExitCode::FAILURE
+                    }
+                };
+            }
+            Some(Commands::Check {
+                max_reclaimable,
+                system,
+                auto,
+                dry_run,
+                filters,
+            }) => commands::check::run(
+                &commands::check::CheckOptions {
+                    max_reclaimable,
+                    system,
+                    auto,
+                    dry_run: dry_run || preview,
+                    named_volumes: config.defaults.named_volumes,
+                },
+                &cli.filters.merge(filters, &config.defaults),
+                &config,
+            ),
+            Some(Commands::Rescue { force, dry_run }) => {
+                commands::rescue::run(force || cli.force, dry_run || preview)
+            }
+            Some(Commands::Reclaim {
+                restart,
+                force,
+                dry_run,
+            }) => commands::reclaim::run(force || cli.force, dry_run || preview, restart),
+            // Replaced by the chosen command above
+            Some(Commands::Interactive) => unreachable!(),
+            Some(Commands::Usage { push }) => commands::list::usage(&config, push.as_deref()),
+            Some(Commands::Contexts) => commands::contexts::run(),
+            Some(Commands::Builders) => commands::builders::list(),
+            Some(Commands::Bench { count, jobs }) => commands::bench::run(count, jobs),
+            Some(Commands::History) => commands::undo::history(),
+            Some(Commands::Stats) => commands::stats::run(),
+            Some(Commands::Analyze { action }) => match action {
+                AnalyzeAction::Images { top } => commands::analyze::images(top),
+                AnalyzeAction::Ages => commands::analyze::ages(),
+            },
+            Some(Commands::Paths) => commands::paths::run(),
+            Some(Commands::Note {
+                kind,
+                name,
+                text,
+                remove,
+            }) => commands::notes::run(kind.zip(name), text, remove),
+            Some(Commands::Quota { nag }) => commands::quota::run(nag, &config),
+            Some(Commands::Hook { .. }) => unreachable!(),
+            Some(Commands::Restore {
+                id,
+                force,
+                dry_run,
+                export,
+            }) => match export {
+                Some(path) => commands::undo::export(&id, &path),
+                None => commands::undo::restore(&id, force || cli.force, dry_run || preview),
+            },
+            Some(Commands::Compose { action }) => match action {
+                Some(ComposeAction::Purge {
+                    project,
+                    force,
+                    dry_run,
+                }) => commands::compose::purge(
+                    &project,
+                    force || cli.force,
+                    dry_run || preview,
+                    &config.protect,
+                ),
+                None => commands::compose::list(),
+            },
+            Some(Commands::Logs {
+                action:
+                    LogsAction::Prune {
+                        containers,
+                        min_size,
+                        force,
+                        dry_run,
+                    },
+            }) => commands::logs::prune(
+                &containers,
+                min_size,
+                force || cli.force,
+                dry_run || preview,
+            ),
+            Some(Commands::Volumes { action }) => match action {
+                Some(VolumesAction::Duplicates {
+                    volumes,
+                    min_file_size,
+                }) => commands::volumes::duplicates(&volumes, min_file_size),
+                None => commands::volumes::list(),
+            },
+            Some(Commands::Images {
+                action:
+                    ImagesAction::Prune {
+                        keep_latest,
+                        grace_period,
+                        force,
+                        dry_run,
+                    },
+            }) => match keep_latest.or(config.image_keep_latest) {
+                Some(keep_latest) => commands::images::prune(
+                    keep_latest,
+                    force || cli.force,
+                    dry_run || preview,
+                    grace_period.or(config.image_grace_period),
+                    &config.protect,
+                ),
+                None => {
+                    Err("Pass --keep-latest N or set image_keep_latest in the config".to_string())
+                }
+            },
+            None => commands::list::run(&config),
         }
     };
 
-    match result {
-        Ok(_) => ExitCode::SUCCESS,
+    if let Ok(report) = &result {
+        record_stats(report);
+        advice::after(report, &config);
+    }
+    match result.and_then(|r| report::emit(&r, cli.report.as_deref()).map(|_| r)) {
+        Ok(r) => ExitCode::from(r.exit_code()),
         Err(e) => {
             print_error(&e);
             ExitCode::FAILURE
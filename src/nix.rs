@@ -0,0 +1,147 @@
+//! The Nix store, freed by Nix's own garbage collector. Store paths are
+//! read-only, shared between profiles and referenced from outside the store,
+//! so they are never deleted file by file: `nix-store --gc --print-dead` says
+//! what is dead and `nix store gc` removes it. With an age, old profile
+//! generations are deleted first (`nix-collect-garbage --delete-older-than`)
+//! so what only they kept alive goes too.
+
+use crate::units::parse_byte_size;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// `nix store` is still behind this feature flag on many installs.
+const FEATURES: [&str; 2] = ["--extra-experimental-features", "nix-command"];
+
+/// Where the store lives: `$NIX_STORE_DIR`, as for Nix itself, else
+/// `/nix/store`.
+pub fn store_dir() -> PathBuf {
+    env::var_os("NIX_STORE_DIR")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| PathBuf::from("/nix/store"))
+}
+
+/// The store paths the garbage collector would delete now.
+pub fn dead_paths(store: &Path) -> Result<Vec<PathBuf>, String> {
+    let output = run("nix-store", &["--gc", "--print-dead"])?;
+    Ok(parse_dead_paths(&output, store))
+}
+
+/// Collect garbage; with `older_than`, profile generations older than that
+/// are deleted first. Returns the bytes Nix reports freed, if it says.
+pub fn collect_garbage(older_than: Option<Duration>) -> Result<Option<u64>, String> {
+    let output = match older_than {
+        Some(age) => run(
+            "nix-collect-garbage",
+            &["--delete-older-than", &generation_age(age)],
+        )?,
+        None => run("nix", &[&FEATURES[..], &["store", "gc"]].concat())?,
+    };
+    Ok(parse_freed(&output))
+}
+
+/// `age` as `--delete-older-than` takes it, in whole days, rounded up so
+/// nothing younger than asked goes.
+fn generation_age(age: Duration) -> String {
+    format!("{}d", age.as_secs().div_ceil(24 * 60 * 60).max(1))
+}
+
+/// Runs a Nix command; stdout and stderr together, as Nix reports progress
+/// and totals on stderr.
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            stderr.trim()
+        ));
+    }
+    Ok(format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        stderr
+    ))
+}
+
+/// The store paths listed one per line by `--print-dead`, each once; the
+/// progress lines Nix prints on stderr are left out.
+pub fn parse_dead_paths(output: &str, store: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = output
+        .lines()
+        .map(|line| Path::new(line.trim()))
+        .filter(|path| path.parent() == Some(store))
+        // Nix's own bookkeeping, such as the hard link pool `.links`
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        })
+        .map(Path::to_path_buf)
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// The total from a summary such as `3 store paths deleted, 12.50 MiB
+/// freed`.
+pub fn parse_freed(output: &str) -> Option<u64> {
+    output.lines().rev().find_map(|line| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let at = words.iter().position(|w| w.starts_with("freed"))?;
+        let size = words.get(at.checked_sub(2)?..at)?.join(" ");
+        parse_byte_size(&size).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dead_paths_keeps_store_paths_only() {
+        let store = Path::new("/nix/store");
+        // stdout, then the progress on stderr
+        let output = "/nix/store/bbb-hello-2.12\n\
+                      /nix/store/aaa-glibc-2.38\n\
+                      /nix/store/.links\n\
+                      \n\
+                      finding garbage collector roots...\n\
+                      removing stale link from '/nix/var/nix/gcroots/auto/abc' to '/home/dev/result'\n\
+                      determining live/dead paths...\n";
+        assert_eq!(
+            parse_dead_paths(output, store),
+            vec![
+                PathBuf::from("/nix/store/aaa-glibc-2.38"),
+                PathBuf::from("/nix/store/bbb-hello-2.12"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_freed() {
+        assert_eq!(
+            parse_freed("deleting '/nix/store/x'\n3 store paths deleted, 1.50 KiB freed\n"),
+            Some(1536)
+        );
+        assert_eq!(
+            parse_freed("0 store paths deleted, 0.00 MiB freed"),
+            Some(0)
+        );
+        assert_eq!(parse_freed("nothing to see"), None);
+    }
+
+    #[test]
+    fn test_generation_age_rounds_up_to_days() {
+        assert_eq!(generation_age(Duration::from_secs(30 * 86400)), "30d");
+        assert_eq!(generation_age(Duration::from_secs(3600)), "1d");
+    }
+}
@@ -0,0 +1,110 @@
+//! Notes attached to resources with `dockerase note`, such as why a volume
+//! must stay. They are kept in the state directory, so they outlive the
+//! people who remember why; listings show them, and a noted resource is
+//! protected as if it were in `[protect]`.
+
+use crate::history::now_secs;
+use crate::paths;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// What kind of resource a note is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// An image, by `repository:tag` or repository
+    Image,
+    Volume,
+    /// A system cache, by its name in `dockerase system`
+    Cache,
+}
+
+impl Kind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Image => "image",
+            Kind::Volume => "volume",
+            Kind::Cache => "cache",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub kind: Kind,
+    pub name: String,
+    pub text: String,
+    /// When the note was last written (UNIX seconds)
+    pub noted_at: u64,
+}
+
+/// The note on `name`, if any.
+pub fn find<'a>(notes: &'a [Note], kind: Kind, name: &str) -> Option<&'a Note> {
+    notes.iter().find(|n| n.kind == kind && n.name == name)
+}
+
+/// The note on the image `name` (`repository:tag`), else on its repository.
+pub fn find_image<'a>(notes: &'a [Note], name: &str) -> Option<&'a Note> {
+    find(notes, Kind::Image, name).or_else(|| {
+        let (repository, _) = name.rsplit_once(':')?;
+        find(notes, Kind::Image, repository)
+    })
+}
+
+/// Attach `text` to `name`, replacing any note it had.
+pub fn set(kind: Kind, name: &str, text: &str) -> Result<Note, String> {
+    let note = Note {
+        kind,
+        name: name.to_string(),
+        text: text.to_string(),
+        noted_at: now_secs(),
+    };
+    let mut notes = list()?;
+    notes.retain(|n| n.kind != kind || n.name != name);
+    notes.push(note.clone());
+    save(&notes)?;
+    Ok(note)
+}
+
+/// Drop the note on `name`; returns it, or `None` if there was none.
+pub fn remove(kind: Kind, name: &str) -> Result<Option<Note>, String> {
+    let mut notes = list()?;
+    let Some(at) = notes.iter().position(|n| n.kind == kind && n.name == name) else {
+        return Ok(None);
+    };
+    let note = notes.remove(at);
+    save(&notes)?;
+    Ok(Some(note))
+}
+
+/// Every note, by kind and name; none when the file is missing. A file
+/// that can't be read is an error rather than no notes, since the notes
+/// protect what they are on.
+pub fn list() -> Result<Vec<Note>, String> {
+    let Some(path) = paths::resource_notes().filter(|p| p.exists()) else {
+        return Ok(Vec::new());
+    };
+    let unreadable = |e: String| {
+        format!(
+            "Failed to read notes {}: {}; fix or remove it",
+            path.display(),
+            e
+        )
+    };
+    let json = fs::read_to_string(&path).map_err(|e| unreadable(e.to_string()))?;
+    let mut notes: Vec<Note> =
+        serde_json::from_str(&json).map_err(|e| unreadable(e.to_string()))?;
+    notes.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+    Ok(notes)
+}
+
+fn save(notes: &[Note]) -> Result<(), String> {
+    let path = paths::resource_notes().ok_or("Could not determine state directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(notes).map_err(|e| e.to_string())?;
+    fs::write(&path, json + "\n").map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::process::Command;
+
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub message: String,
+}
+
+/// Show a desktop notification. Failures are ignored: headless machines
+/// simply have no notification center.
+pub fn desktop(notification: &Notification) {
+    if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            escape_applescript(&notification.message),
+            escape_applescript(&notification.title)
+        );
+        Command::new("osascript")
+            .args(["-e", &script])
+            .output()
+            .ok();
+    } else if cfg!(target_os = "linux") {
+        Command::new("notify-send")
+            .args([&notification.title, &notification.message])
+            .output()
+            .ok();
+    }
+}
+
+/// POST the notification as JSON to `url` using curl.
+pub fn webhook(url: &str, notification: &Notification) -> Result<(), String> {
+    let body = serde_json::to_string(notification).map_err(|e| e.to_string())?;
+    let output = Command::new("curl")
+        .args([
+            "-fsS",
+            "-X",
+            "POST",
+            "-H",
+            "Content-Type: application/json",
+            "-d",
+            &body,
+            url,
+        ])
+        .output()
+        .map_err(|e| format!("Failed to execute curl: {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Webhook request failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn escape_applescript(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_applescript() {
+        assert_eq!(escape_applescript(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(escape_applescript(r"a\b"), r"a\\b");
+    }
+
+    #[test]
+    fn test_notification_serializes() {
+        let n = Notification {
+            title: "dockerase".to_string(),
+            message: "Disk is 90% full".to_string(),
+        };
+        let json = serde_json::to_string(&n).unwrap();
+        assert!(json.contains("\"title\":\"dockerase\""));
+        assert!(json.contains("\"message\":\"Disk is 90% full\""));
+    }
+}
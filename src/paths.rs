@@ -0,0 +1,157 @@
+//! Where dockerase keeps its files, following the XDG base directory layout
+//! on every platform: config in `~/.config/dockerase`, state (usage and
+//! cleanup history) in `~/.local/state/dockerase` and caches in
+//! `~/.cache/dockerase`. Also finds the programs dockerase drives on `PATH`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether `binary` is on the `PATH`.
+pub fn installed(binary: &str) -> bool {
+    let names = [binary.to_string(), format!("{}.exe", binary)];
+    env::var_os("PATH")
+        .map(|path| {
+            env::split_paths(&path).any(|dir| names.iter().any(|name| dir.join(name).is_file()))
+        })
+        .unwrap_or(false)
+}
+
+/// `$<var>` when set to an absolute path, else `<home>/<default>`.
+pub fn xdg_dir(var: &str, home: &Path, default: &str) -> PathBuf {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home.join(default))
+}
+
+fn app_dir(var: &str, default: &str) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    Some(xdg_dir(var, &home, default).join("dockerase"))
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    app_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    app_dir("XDG_STATE_HOME", ".local/state")
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    app_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$DOCKERASE_CONFIG`, else `config.toml` in the config directory.
+pub fn config_file() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("DOCKERASE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    Some(config_dir()?.join("config.toml"))
+}
+
+/// The machine-wide config an administrator may provide:
+/// `/etc/dockerase/config.toml`, or on Windows
+/// `%ProgramData%\dockerase\config.toml`. Its `[protect]` rules bind every
+/// user, so only debug builds (the tests) may point elsewhere with
+/// `$DOCKERASE_SYSTEM_CONFIG`.
+pub fn system_config_file() -> Option<PathBuf> {
+    if cfg!(debug_assertions) {
+        if let Some(path) = env::var_os("DOCKERASE_SYSTEM_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    if cfg!(windows) {
+        let data = env::var_os("ProgramData")?;
+        Some(PathBuf::from(data).join("dockerase").join("config.toml"))
+    } else {
+        Some(PathBuf::from("/etc/dockerase/config.toml"))
+    }
+}
+
+/// Disk usage snapshots behind the growth forecast.
+pub fn usage_history() -> Option<PathBuf> {
+    state_file("usage_history.jsonl")
+}
+
+/// One line per `watch` check.
+pub fn watch_log() -> Option<PathBuf> {
+    state_file("watch_log.jsonl")
+}
+
+/// Tagged images waiting out `image_grace_period`.
+pub fn image_holds() -> Option<PathBuf> {
+    state_file("image_holds.json")
+}
+
+/// Notes attached to resources with `dockerase note`.
+pub fn resource_notes() -> Option<PathBuf> {
+    state_file("notes.json")
+}
+
+/// Space freed by each cleanup, behind `dockerase stats`.
+pub fn cleanup_stats() -> Option<PathBuf> {
+    state_file("cleanup_stats.jsonl")
+}
+
+/// Undo manifests written before each cleanup.
+pub fn undo_history() -> Option<PathBuf> {
+    state_file("history")
+}
+
+/// `name` in the state directory. Older versions kept state in the platform
+/// data directory (`~/.local/share/dockerase`); it is moved over the first
+/// time it is needed.
+fn state_file(name: &str) -> Option<PathBuf> {
+    let path = state_dir()?.join(name);
+    if let Some(legacy) = dirs::data_local_dir().map(|d| d.join("dockerase").join(name)) {
+        migrate(&legacy, &path);
+    }
+    Some(path)
+}
+
+/// Move `legacy` to `path` unless `path` already exists. Best effort: a
+/// failed move only means starting over with empty history.
+fn migrate(legacy: &Path, path: &Path) {
+    if path.exists() || !legacy.exists() || legacy == path {
+        return;
+    }
+    if let Some(parent) = path.parent() {
+        if fs::create_dir_all(parent).is_err() {
+            return;
+        }
+    }
+    fs::rename(legacy, path).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xdg_dir_ignores_relative_overrides() {
+        let home = Path::new("/home/dev");
+        assert_eq!(
+            xdg_dir("DOCKERASE_TEST_UNSET_DIR", home, ".local/state"),
+            PathBuf::from("/home/dev/.local/state")
+        );
+    }
+
+    #[test]
+    fn test_migrate_moves_legacy_state_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("share/dockerase/usage_history.jsonl");
+        let path = dir.path().join("state/dockerase/usage_history.jsonl");
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, "old\n").unwrap();
+
+        migrate(&legacy, &path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+        assert!(!legacy.exists());
+
+        // Never overwrites newer state
+        fs::write(&legacy, "older\n").unwrap();
+        migrate(&legacy, &path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+    }
+}
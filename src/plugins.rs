@@ -0,0 +1,210 @@
+//! Cache providers outside dockerase: any executable named
+//! `dockerase-cache-<name>` on `PATH` adds cleanup targets to `dockerase
+//! system`, so caches such as a team's own build cache can be covered
+//! without changing the built-in list in `system`.
+//!
+//! The contract is two verbs:
+//!
+//! - `dockerase-cache-<name> list` prints
+//!   `{"items": [{"name": ..., "path": ..., "size": ...}]}` on stdout. `path`
+//!   is where the data lives, `size` is in bytes, and an item may add a
+//!   `description`.
+//! - `dockerase-cache-<name> purge <item name>` frees that item and exits
+//!   with 0. It may print `{"freed": <bytes>}`; otherwise the listed size
+//!   counts as freed.
+//!
+//! When a verb exits with anything else, its stderr is shown as the reason.
+
+use crate::display::print_warning;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What a plugin's executable name starts with.
+pub const PREFIX: &str = "dockerase-cache-";
+
+/// A cache provider found on `PATH`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Plugin {
+    pub name: String,
+    #[serde(skip)]
+    pub program: PathBuf,
+}
+
+/// One cleanup target a plugin lists.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginItem {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Listing {
+    items: Vec<PluginItem>,
+}
+
+#[derive(Deserialize)]
+struct Purged {
+    freed: u64,
+}
+
+impl Plugin {
+    /// The targets this plugin offers now.
+    pub fn list(&self) -> Result<Vec<PluginItem>, String> {
+        parse_listing(&self.run(&["list"])?)
+    }
+
+    /// Have the plugin free the item called `item`, listed at `size`;
+    /// returns the bytes freed.
+    pub fn purge(&self, item: &str, size: u64) -> Result<u64, String> {
+        let output = self.run(&["purge", item])?;
+        Ok(serde_json::from_str::<Purged>(&output).map_or(size, |p| p.freed))
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::new(&self.program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", self.program.display(), e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "{} {} failed: {}",
+                self.program.display(),
+                args.join(" "),
+                stderr.trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Parse the output of a plugin's `list` verb.
+pub fn parse_listing(output: &str) -> Result<Vec<PluginItem>, String> {
+    let listing: Listing =
+        serde_json::from_str(output).map_err(|e| format!("Invalid plugin listing: {}", e))?;
+    if let Some(item) = listing.items.iter().find(|i| !i.path.is_absolute()) {
+        return Err(format!(
+            "Invalid plugin listing: path of {} is not absolute",
+            item.name
+        ));
+    }
+    Ok(listing.items)
+}
+
+/// The plugins on `PATH`; for a name found twice, the first wins, as a
+/// shell would run it.
+pub fn discover() -> Vec<Plugin> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    discover_in(env::split_paths(&path))
+}
+
+fn discover_in(dirs: impl IntoIterator<Item = PathBuf>) -> Vec<Plugin> {
+    let mut seen = HashSet::new();
+    let mut plugins = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<Plugin> = entries
+            .flatten()
+            .filter(|e| is_executable(&e.path()))
+            .filter_map(|e| {
+                let file_name = e.file_name().to_string_lossy().into_owned();
+                let name = file_name.strip_prefix(PREFIX)?;
+                let name = name.strip_suffix(".exe").unwrap_or(name);
+                (!name.is_empty()).then(|| Plugin {
+                    name: name.to_string(),
+                    program: e.path(),
+                })
+            })
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins.extend(found.into_iter().filter(|p| seen.insert(p.name.clone())));
+    }
+    plugins
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("exe"))
+}
+
+/// Every item of every plugin on `PATH`. A plugin that fails to list is
+/// left out with a warning.
+pub fn items() -> Vec<(Plugin, PluginItem)> {
+    let mut all = Vec::new();
+    for plugin in discover() {
+        match plugin.list() {
+            Ok(items) => all.extend(items.into_iter().map(|item| (plugin.clone(), item))),
+            Err(e) => print_warning(&format!("Skipping cache plugin {}: {}", plugin.name, e)),
+        }
+    }
+    all
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listing() {
+        let items = parse_listing(
+            r#"{"items": [
+                {"name": "Bazel disk cache", "path": "/home/dev/.cache/bazel", "size": 2048,
+                 "description": "Remote cache blobs"},
+                {"name": "Bazel repos", "path": "/home/dev/.cache/bazel-repos", "size": 10}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].size, 2048);
+        assert_eq!(items[0].description.as_deref(), Some("Remote cache blobs"));
+        assert_eq!(items[1].description, None);
+
+        assert!(parse_listing("not json").is_err());
+        assert!(parse_listing(r#"{"items": [{"name": "x", "path": "rel", "size": 1}]}"#).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_finds_executables_first_on_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let write = |dir: &Path, name: &str, mode: u32| {
+            let path = dir.join(name);
+            fs::write(&path, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        };
+        write(first.path(), "dockerase-cache-nix", 0o755);
+        write(first.path(), "dockerase-cache-notes", 0o644);
+        write(first.path(), "other-tool", 0o755);
+        write(second.path(), "dockerase-cache-nix", 0o755);
+        write(second.path(), "dockerase-cache-bazel", 0o755);
+
+        let plugins = discover_in([first.path().to_path_buf(), second.path().to_path_buf()]);
+        let found: Vec<(&str, &Path)> = plugins
+            .iter()
+            .map(|p| (p.name.as_str(), p.program.parent().unwrap()))
+            .collect();
+        assert_eq!(found, vec![("nix", first.path()), ("bazel", second.path())]);
+    }
+}
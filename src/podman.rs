@@ -0,0 +1,291 @@
+//! Podman backend. Podman's CLI mirrors docker's commands, but its
+//! `--format json` output follows the Engine API's layout, sizes come in
+//! bytes, and some prunes reach further than docker's; this smooths over
+//! those differences so commands work the same on either runtime.
+
+use crate::api::{container_from_api, image_from_api, network_from_api, volume_from_api};
+use crate::docker::{parse_size, with_filters, Docker, DockerBackend, ResourceType};
+use crate::resources::{BuildCacheRecord, Container, DiskUsage, Image, Network, Volume};
+use serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+
+pub struct Podman;
+
+impl Podman {
+    /// Run a listing command with `--format json` and `convert` each
+    /// element of its array as it streams in, so only one is held as JSON
+    /// at a time. No output, blank output or `null` is an empty list.
+    fn list<T>(
+        args: &[&str],
+        filters: &[String],
+        convert: fn(&Value) -> T,
+    ) -> Result<Vec<T>, String> {
+        let mut args = args.to_vec();
+        args.extend(["--format", "json"]);
+        Docker::stream_command(&with_filters(&args, filters), |output| {
+            if at_end(output).map_err(|e| format!("Failed to read podman output: {}", e))? {
+                return Ok(vec![]);
+            }
+            Elements(convert)
+                .deserialize(&mut serde_json::Deserializer::from_reader(output))
+                .map_err(|e| format!("Failed to parse podman output: {}", e))
+        })
+    }
+}
+
+/// Skip leading whitespace in `output`; true if nothing else is left.
+fn at_end(output: &mut dyn BufRead) -> std::io::Result<bool> {
+    loop {
+        let buf = output.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(true);
+        }
+        let blank = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        if blank < buf.len() {
+            output.consume(blank);
+            return Ok(false);
+        }
+        let len = buf.len();
+        output.consume(len);
+    }
+}
+
+/// Reads a JSON array one element at a time, converting each; `null`, as
+/// podman prints for nothing to list, reads as empty.
+struct Elements<T>(fn(&Value) -> T);
+
+impl<'de, T> DeserializeSeed<'de> for Elements<T> {
+    type Value = Vec<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<T>, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for Elements<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON array or null")
+    }
+
+    fn visit_unit<E>(self) -> Result<Vec<T>, E> {
+        Ok(Vec::new())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+        let mut items = Vec::new();
+        while let Some(value) = seq.next_element::<Value>()? {
+            items.push((self.0)(&value));
+        }
+        Ok(items)
+    }
+}
+
+impl DockerBackend for Podman {
+    fn is_available(&self) -> bool {
+        Docker::run_command(&["version"]).is_ok()
+    }
+
+    fn disk_usage(&self) -> Result<DiskUsage, String> {
+        let df = Self::list(&["system", "df"], &[], Value::clone)?;
+        Ok(disk_usage_from_df(&df))
+    }
+
+    fn root_dir(&self) -> Result<String, String> {
+        let output = Docker::run_command(&["info", "--format", "{{.Store.GraphRoot}}"])?;
+        Ok(output.trim().to_string())
+    }
+
+    fn operating_system(&self) -> Result<String, String> {
+        let output =
+            Docker::run_command(&["info", "--format", "{{.Host.Distribution.Distribution}}"])?;
+        Ok(output.trim().to_string())
+    }
+
+    fn list_images(&self, filters: &[String]) -> Result<Vec<Image>, String> {
+        Self::list(&["images"], filters, image_from_api)
+    }
+
+    fn list_containers(&self, all: bool, filters: &[String]) -> Result<Vec<Container>, String> {
+        let args: &[&str] = if all { &["ps", "-a"] } else { &["ps"] };
+        Self::list(args, filters, container_from_api)
+    }
+
+    fn list_containers_with_size(&self) -> Result<Vec<Container>, String> {
+        Self::list(&["ps", "-a", "--size"], &[], container_from_api)
+    }
+
+    fn list_volumes(&self, filters: &[String]) -> Result<Vec<Volume>, String> {
+        Self::list(&["volume", "ls"], filters, volume_from_api)
+    }
+
+    /// Podman doesn't report per-volume sizes in a parseable form, so
+    /// volumes show without one.
+    fn volume_sizes(&self) -> Result<HashMap<String, u64>, String> {
+        Ok(HashMap::new())
+    }
+
+    /// Podman's builds go through Buildah, which keeps no cache apart from
+    /// the images themselves.
+    fn build_cache(&self) -> Result<Vec<BuildCacheRecord>, String> {
+        Ok(vec![])
+    }
+
+    fn list_networks(&self, filters: &[String]) -> Result<Vec<Network>, String> {
+        Self::list(&["network", "ls"], filters, network_from_api)
+    }
+
+    fn prune(&self, kind: ResourceType, all: bool, filters: &[String]) -> Result<String, String> {
+        let flags = if all { "-af" } else { "-f" };
+        let args = match kind {
+            ResourceType::Container => ["container", "prune", "-f"],
+            ResourceType::Image => ["image", "prune", flags],
+            ResourceType::Volume if all => ["volume", "prune", "-f"],
+            ResourceType::Volume => {
+                // `podman volume prune` takes named volumes too, where docker
+                // only prunes anonymous ones; remove those by name instead
+                let mut filters = filters.to_vec();
+                filters.push("dangling=true".to_string());
+                let anonymous: Vec<String> = self
+                    .list_volumes(&filters)?
+                    .into_iter()
+                    .filter(Volume::is_anonymous)
+                    .map(|v| v.name)
+                    .collect();
+                if anonymous.is_empty() {
+                    return Ok(String::new());
+                }
+                let names: Vec<&str> = anonymous.iter().map(String::as_str).collect();
+                return self.remove(ResourceType::Volume, &names, true);
+            }
+            ResourceType::Network => ["network", "prune", "-f"],
+            // Podman keeps build layers as images, which image prune covers
+            ResourceType::BuildCache => return Ok(String::new()),
+        };
+        Docker::run_command(&with_filters(&args, filters))
+    }
+
+    fn remove(&self, kind: ResourceType, ids: &[&str], force: bool) -> Result<String, String> {
+        let mut args = match kind {
+            ResourceType::Container => vec!["rm"],
+            ResourceType::Image => vec!["rmi"],
+            ResourceType::Volume => vec!["volume", "rm"],
+            ResourceType::Network => vec!["network", "rm"],
+            ResourceType::BuildCache => return Err("Build cache can only be pruned".to_string()),
+        };
+        // `podman network rm -f` would remove the containers using it
+        if force && kind != ResourceType::Network {
+            args.push("-f");
+        }
+        args.extend(ids);
+        Docker::run_command(&args)
+    }
+
+    fn stop_containers(&self, ids: &[&str]) -> Result<String, String> {
+        let mut args = vec!["stop"];
+        args.extend(ids);
+        Docker::run_command(&args)
+    }
+
+    fn pull(&self, reference: &str) -> Result<String, String> {
+        Docker::run_command(&["pull", "--quiet", reference])
+    }
+
+    fn tag(&self, source: &str, target: &str) -> Result<String, String> {
+        Docker::run_command(&["tag", source, target])
+    }
+}
+
+/// Parse `podman system df --format json`: one entry per type, with sizes
+/// in bytes (older releases print them human-readable, like docker).
+fn disk_usage_from_df(df: &[Value]) -> DiskUsage {
+    let bytes = |v: &Value| match v {
+        Value::String(s) => parse_size(s.split('(').next().unwrap_or_default()),
+        v => v.as_u64().unwrap_or(0),
+    };
+    let mut usage = DiskUsage::default();
+    for entry in df {
+        let size = bytes(&entry["Size"]);
+        let reclaimable = bytes(&entry["Reclaimable"]);
+        let count = entry["Total"].as_u64().unwrap_or(0) as usize;
+        let active = entry["Active"].as_u64().unwrap_or(0) as usize;
+        match entry["Type"].as_str().unwrap_or_default() {
+            "Images" => {
+                usage.images_size = size;
+                usage.images_reclaimable = reclaimable;
+                usage.images_count = count;
+                usage.images_active = active;
+            }
+            "Containers" => {
+                usage.containers_size = size;
+                usage.containers_reclaimable = reclaimable;
+                usage.containers_count = count;
+                usage.containers_active = active;
+            }
+            "Local Volumes" => {
+                usage.volumes_size = size;
+                usage.volumes_reclaimable = reclaimable;
+                usage.volumes_count = count;
+                usage.volumes_active = active;
+            }
+            _ => {}
+        }
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::array;
+    use serde_json::json;
+
+    #[test]
+    fn test_elements_are_converted_as_they_are_read() {
+        let output = br#"[{"Name":"a"}, {"Name":"b"}]"#;
+        let names = Elements(|v: &Value| v["Name"].as_str().unwrap().to_string())
+            .deserialize(&mut serde_json::Deserializer::from_reader(&output[..]))
+            .unwrap();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_blank_or_null_output_is_an_empty_list() {
+        let name = |v: &Value| v["Name"].to_string();
+        for output in [&b""[..], b"  \n", b"null\n", b" []"] {
+            let mut reader = output;
+            let items = if at_end(&mut reader).unwrap() {
+                vec![]
+            } else {
+                Elements(name)
+                    .deserialize(&mut serde_json::Deserializer::from_reader(reader))
+                    .unwrap()
+            };
+            assert!(items.is_empty(), "{:?}", String::from_utf8_lossy(output));
+        }
+    }
+
+    #[test]
+    fn test_disk_usage_from_df() {
+        let usage = disk_usage_from_df(array(&json!([
+            {"Type": "Images", "Total": 4, "Active": 1, "Size": 3000, "Reclaimable": 2000},
+            {"Type": "Containers", "Total": 2, "Active": 1, "Size": 50, "Reclaimable": 20},
+            {"Type": "Local Volumes", "Total": 3, "Active": 1, "Size": "1.5kB", "Reclaimable": "1kB (66%)"}
+        ])));
+        assert_eq!((usage.images_count, usage.images_active), (4, 1));
+        assert_eq!(usage.images_reclaimable, 2000);
+        assert_eq!(
+            (usage.containers_size, usage.containers_reclaimable),
+            (50, 20)
+        );
+        assert_eq!(
+            (usage.volumes_size, usage.volumes_reclaimable),
+            (1500, 1000)
+        );
+        assert_eq!(usage.build_cache_size, 0);
+    }
+}
@@ -0,0 +1,105 @@
+//! Limits from the config's `[policy]`, meant for the system-wide config an
+//! administrator ships to managed machines: `--nuclear` and whole
+//! categories of resources can be taken off the table for every user.
+//! Like read-only mode, the check sits in front of every removal, so a
+//! command that forgets to ask still can't get past it.
+
+use crate::docker::ResourceType;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Report note on resources left alone because of `[policy]`.
+pub const POLICY_NOTE: &str = "disabled by policy";
+
+/// What a policy can disable, named as in cleanup reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Containers,
+    Images,
+    Volumes,
+    Networks,
+    BuildCache,
+    /// Caches on the host, as purged by `dockerase system`
+    SystemCaches,
+}
+
+impl Category {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Containers => "containers",
+            Category::Images => "images",
+            Category::Volumes => "volumes",
+            Category::Networks => "networks",
+            Category::BuildCache => "build_cache",
+            Category::SystemCaches => "system_caches",
+        }
+    }
+}
+
+impl From<ResourceType> for Category {
+    fn from(kind: ResourceType) -> Self {
+        match kind {
+            ResourceType::Container => Category::Containers,
+            ResourceType::Image => Category::Images,
+            ResourceType::Volume => Category::Volumes,
+            ResourceType::Network => Category::Networks,
+            ResourceType::BuildCache => Category::BuildCache,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Refuse `--nuclear`, even as a dry run
+    pub disable_nuclear: bool,
+    /// Categories nothing may be removed from
+    pub disabled: Vec<Category>,
+}
+
+impl Policy {
+    /// Add the limits of `other`; policies only ever get stricter.
+    pub fn tighten(&mut self, other: &Policy) {
+        self.disable_nuclear |= other.disable_nuclear;
+        self.disabled.extend_from_slice(&other.disabled);
+        self.disabled.sort_unstable();
+        self.disabled.dedup();
+    }
+}
+
+/// Set the policy for the rest of the run. Only the first call counts.
+pub fn configure(policy: Policy) {
+    POLICY.set(policy).ok();
+}
+
+fn current() -> Option<&'static Policy> {
+    POLICY.get()
+}
+
+pub fn allows(category: Category) -> bool {
+    current().is_none_or(|p| !p.disabled.contains(&category))
+}
+
+/// Checked before anything in `category` is removed.
+pub fn check(category: Category, action: &str) -> Result<(), String> {
+    if allows(category) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Policy: {} are disabled on this machine; refusing to {}",
+            category.as_str(),
+            action
+        ))
+    }
+}
+
+pub fn check_nuclear() -> Result<(), String> {
+    if current().is_some_and(|p| p.disable_nuclear) {
+        Err("Policy: --nuclear is disabled on this machine".to_string())
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Extra confirmation before destructive commands on hosts flagged
+//! `production = true`, so a cleanup meant for a laptop can't run in the
+//! wrong terminal.
+
+use crate::config;
+use crate::display::{input, print_warning};
+use crate::docker;
+use std::io::{self, IsTerminal};
+
+/// The name to type: the daemon being targeted when it isn't the local
+/// default (the machine, for a `--host` URL), else this machine's hostname.
+pub fn target_name() -> String {
+    docker::target_machine()
+        .or_else(|| docker::explicit_target().map(|t| t.name().to_string()))
+        .or_else(config::hostname)
+        .unwrap_or_else(|| "production".to_string())
+}
+
+/// Require the target's name before a destructive run: `confirmed` when
+/// given with `--confirm-production`, else typed at a prompt. `--force`
+/// doesn't skip this.
+pub fn confirm(confirmed: Option<&str>) -> Result<(), String> {
+    let name = target_name();
+    let typed = match confirmed {
+        Some(typed) => typed.to_string(),
+        None if io::stdin().is_terminal() => {
+            print_warning(&format!("{} is flagged as a production host", name));
+            input(&format!("Type {} to continue", name))?
+        }
+        None => {
+            return Err(format!(
+                "{} is a production host; pass --confirm-production {} to run unattended",
+                name, name
+            ))
+        }
+    };
+
+    if matches(&typed, &name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{}' does not match production host {}; nothing was changed",
+            typed.trim(),
+            name
+        ))
+    }
+}
+
+fn matches(typed: &str, name: &str) -> bool {
+    typed.trim() == name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_name_must_match_exactly() {
+        assert!(matches("prod-db-1", "prod-db-1"));
+        assert!(matches(" prod-db-1\n", "prod-db-1"));
+        assert!(!matches("prod-db", "prod-db-1"));
+        assert!(!matches("PROD-DB-1", "prod-db-1"));
+        assert!(!matches("", "prod-db-1"));
+    }
+}
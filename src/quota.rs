@@ -0,0 +1,208 @@
+//! Soft quota from the config's `[quota]`: once Docker uses more than
+//! `max_usage`, every dockerase run starts with a short nag naming the
+//! largest resources and how to clean up. Nothing is removed or refused; it
+//! only nudges whoever never cleans up. `dockerase hook bash` brings the nag
+//! to every new shell too.
+
+use crate::config::byte_size;
+use crate::docker::Docker;
+use crate::history;
+use crate::resources::{DiskUsage, Image};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Offenders named in the nag.
+pub const TOP_OFFENDERS: usize = 3;
+
+/// How long a usage snapshot from the history stands in for measuring
+/// before the nag.
+const MEASURED_WITHIN_SECS: u64 = 3_600;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    /// Nag once images, containers, volumes and build cache together take
+    /// more than this, e.g. `"50GB"`
+    #[serde(deserialize_with = "byte_size")]
+    pub max_usage: Option<u64>,
+}
+
+/// One of the largest things Docker keeps.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Offender {
+    pub kind: &'static str,
+    pub name: String,
+    pub size: u64,
+}
+
+/// Docker's usage measured against the quota.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub max_usage: u64,
+    pub used: u64,
+}
+
+impl Status {
+    pub fn exceeded(&self) -> bool {
+        self.used > self.max_usage
+    }
+}
+
+/// Where Docker stands against `quota`; `None` without a quota.
+pub fn status(quota: &Quota, usage: &DiskUsage) -> Option<Status> {
+    Some(Status {
+        max_usage: quota.max_usage?,
+        used: usage.total_size(),
+    })
+}
+
+/// Docker's usage against `quota` and the largest offenders, when it is
+/// over. Checked quietly: without a quota, or when docker can't be reached,
+/// there is nothing to say. A usage snapshot from the last hour that was
+/// under the quota saves measuring again.
+pub fn over_quota(quota: &Quota) -> Option<(Status, Vec<Offender>)> {
+    let max_usage = quota.max_usage?;
+    let now = history::now_secs();
+    if history::load_snapshots().last().is_some_and(|s| {
+        now.saturating_sub(s.timestamp) < MEASURED_WITHIN_SECS && s.total_size <= max_usage
+    }) {
+        return None;
+    }
+    let usage = Docker::get_disk_usage().ok()?;
+    history::record_snapshot(&usage).ok();
+    let status = status(quota, &usage).filter(Status::exceeded)?;
+    Some((status, find_offenders(&usage)))
+}
+
+/// The largest images and volumes, and the build cache, biggest first.
+pub fn offenders(
+    images: &[Image],
+    volume_sizes: &HashMap<String, u64>,
+    build_cache: u64,
+    top: usize,
+) -> Vec<Offender> {
+    let mut offenders: Vec<Offender> = images
+        .iter()
+        .map(|i| Offender {
+            kind: "image",
+            name: i.display_name(),
+            size: i.size_bytes(),
+        })
+        .chain(volume_sizes.iter().map(|(name, &size)| Offender {
+            kind: "volume",
+            name: name.clone(),
+            size,
+        }))
+        .chain((build_cache > 0).then(|| Offender {
+            kind: "cache",
+            name: "build cache".to_string(),
+            size: build_cache,
+        }))
+        .collect();
+    offenders.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    offenders.truncate(top);
+    offenders
+}
+
+/// The largest things Docker keeps right now. Best effort: whatever can't be
+/// listed is left out.
+pub fn find_offenders(usage: &DiskUsage) -> Vec<Offender> {
+    let images = Docker::list_images(&[]).unwrap_or_default();
+    let volume_sizes = Docker::volume_sizes().unwrap_or_default();
+    offenders(
+        &images,
+        &volume_sizes,
+        usage.build_cache_size,
+        TOP_OFFENDERS,
+    )
+}
+
+/// Shells `dockerase hook` writes a snippet for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+}
+
+impl Shell {
+    fn rc_file(self) -> &'static str {
+        match self {
+            Shell::Bash => "~/.bashrc",
+            Shell::Zsh => "~/.zshrc",
+        }
+    }
+}
+
+/// A snippet for `shell`'s startup file that nags in each new interactive
+/// shell while over the quota, and stays silent otherwise.
+pub fn hook(shell: Shell) -> String {
+    format!(
+        "# dockerase: nag about the Docker quota in new shells. Add to {}:\n\
+         #   eval \"$(dockerase hook {})\"\n\
+         case $- in\n    \
+             *i*) command -v dockerase >/dev/null 2>&1 && dockerase quota --nag ;;\n\
+         esac\n",
+        shell.rc_file(),
+        match shell {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(repository: &str, size: &str) -> Image {
+        Image {
+            id: format!("sha256:{repository}"),
+            repository: repository.to_string(),
+            tag: "latest".to_string(),
+            size: size.to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_offenders_are_the_largest_of_any_kind() {
+        let images = vec![image("small", "10MB"), image("big", "3GB")];
+        let volumes = HashMap::from([("pgdata".to_string(), 2_000_000_000)]);
+        let offenders = offenders(&images, &volumes, 500_000_000, 3);
+        let names: Vec<&str> = offenders.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["big:latest", "pgdata", "build cache"]);
+        assert_eq!(offenders[1].kind, "volume");
+    }
+
+    #[test]
+    fn test_quota_is_exceeded_only_above_max_usage() {
+        let usage = DiskUsage {
+            images_size: 600,
+            volumes_size: 400,
+            ..Default::default()
+        };
+        assert_eq!(status(&Quota::default(), &usage), None);
+        let at = |max| {
+            status(
+                &Quota {
+                    max_usage: Some(max),
+                },
+                &usage,
+            )
+            .unwrap()
+        };
+        assert!(!at(1000).exceeded());
+        assert!(at(999).exceeded());
+    }
+
+    #[test]
+    fn test_hook_only_nags_interactive_shells() {
+        let script = hook(Shell::Zsh);
+        assert!(script.contains("eval \"$(dockerase hook zsh)\""));
+        assert!(script.contains("~/.zshrc"));
+        assert!(script.contains("*i*) command -v dockerase"));
+        assert!(script.contains("dockerase quota --nag"));
+    }
+}
@@ -0,0 +1,49 @@
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn on read-only mode for the rest of the run (from `read_only = true`
+/// in the config).
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Whether every destructive operation is disabled, by the config or by
+/// `DOCKERASE_READ_ONLY` set to anything but empty, `0` or `false`.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+        || env::var("DOCKERASE_READ_ONLY").is_ok_and(|value| is_set(&value))
+}
+
+fn is_set(value: &str) -> bool {
+    !matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "" | "0" | "false" | "no"
+    )
+}
+
+/// Checked by every code path that deletes or changes something, so that a
+/// read-only install can only ever plan, whatever a command asks for.
+pub fn check(action: &str) -> Result<(), String> {
+    if is_enabled() {
+        Err(format!("Read-only mode: refusing to {}", action))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_values() {
+        for value in ["1", "true", "yes", "on"] {
+            assert!(is_set(value), "{value}");
+        }
+        for value in ["", "0", "false", "FALSE", "no", " 0 "] {
+            assert!(!is_set(value), "{value:?}");
+        }
+    }
+}
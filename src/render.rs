@@ -0,0 +1,193 @@
+//! How output reaches the user. Commands print through the helpers in
+//! `display`, which hand everything to the renderer picked at startup from
+//! the output flags and whether stdout is a terminal.
+
+use crate::display::Styled;
+use crate::report::{OutputFormat, Report};
+use colored::Colorize;
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Success,
+    Info,
+    Warning,
+    /// Always printed, to stderr
+    Error,
+}
+
+pub trait Renderer: Send + Sync {
+    /// Whether human-oriented text (headings, tables, progress) is shown.
+    fn shows_text(&self) -> bool;
+
+    /// Whether text may carry ANSI colors.
+    fn colors(&self) -> bool {
+        true
+    }
+
+    /// A status message as it should be printed, or `None` to drop it.
+    fn message(&self, level: Level, message: &str) -> Option<String>;
+
+    /// Whether the report lists every resource, which takes extra calls to
+    /// gather.
+    fn lists_resources(&self) -> bool {
+        false
+    }
+
+    /// The structured report printed at the end of a run, if any.
+    fn report(&self, _report: &Report) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+}
+
+/// Colors and symbols, for an interactive terminal.
+pub struct Terminal;
+
+impl Renderer for Terminal {
+    fn shows_text(&self) -> bool {
+        true
+    }
+
+    fn message(&self, level: Level, message: &str) -> Option<String> {
+        let symbol = match level {
+            Level::Success => "✓".good(),
+            Level::Info => "→".info(),
+            Level::Warning => "⚠".caution(),
+            Level::Error => "✗".danger(),
+        };
+        Some(format!("{} {}", symbol.bold(), message))
+    }
+}
+
+/// No colors, and `warning:`-style prefixes instead of symbols, for log
+/// files and pipes.
+pub struct Plain;
+
+impl Renderer for Plain {
+    fn shows_text(&self) -> bool {
+        true
+    }
+
+    fn colors(&self) -> bool {
+        false
+    }
+
+    fn message(&self, level: Level, message: &str) -> Option<String> {
+        let prefix = match level {
+            Level::Success => "ok",
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        Some(format!("{}: {}", prefix, message))
+    }
+}
+
+/// Only the report, as JSON, YAML or InfluxDB line protocol on stdout;
+/// errors still go to stderr.
+pub struct Structured(pub OutputFormat);
+
+impl Renderer for Structured {
+    fn shows_text(&self) -> bool {
+        false
+    }
+
+    fn message(&self, level: Level, message: &str) -> Option<String> {
+        errors_only(level, message)
+    }
+
+    fn lists_resources(&self) -> bool {
+        matches!(self.0, OutputFormat::Json | OutputFormat::Yaml)
+    }
+
+    fn report(&self, report: &Report) -> Result<Option<String>, String> {
+        let output = match self.0 {
+            OutputFormat::Text => return Ok(None),
+            OutputFormat::Json => report.to_json()? + "\n",
+            OutputFormat::Yaml => report.to_yaml()?,
+            OutputFormat::Influx => report.to_influx()?,
+        };
+        Ok(Some(output))
+    }
+}
+
+/// Nothing but errors.
+pub struct Quiet;
+
+impl Renderer for Quiet {
+    fn shows_text(&self) -> bool {
+        false
+    }
+
+    fn message(&self, level: Level, message: &str) -> Option<String> {
+        errors_only(level, message)
+    }
+}
+
+fn errors_only(level: Level, message: &str) -> Option<String> {
+    match level {
+        Level::Error => Terminal.message(level, message),
+        _ => None,
+    }
+}
+
+/// The renderer for the output flags: structured formats win over
+/// `--quiet`, and text is plain unless stdout is a terminal.
+pub fn select(format: OutputFormat, quiet: bool, plain: bool) -> Box<dyn Renderer> {
+    match format {
+        OutputFormat::Text if quiet => Box::new(Quiet),
+        OutputFormat::Text if plain || !io::stdout().is_terminal() => Box::new(Plain),
+        OutputFormat::Text => Box::new(Terminal),
+        format => Box::new(Structured(format)),
+    }
+}
+
+static RENDERER: OnceLock<Box<dyn Renderer>> = OnceLock::new();
+
+/// Set the global renderer. Must be called once, before any output.
+pub fn configure(renderer: Box<dyn Renderer>) {
+    if !renderer.colors() {
+        colored::control::set_override(false);
+    }
+    RENDERER.set(renderer).ok();
+}
+
+/// The configured renderer; the terminal one until `configure` is called.
+pub fn current() -> &'static dyn Renderer {
+    RENDERER.get_or_init(|| Box::new(Terminal)).as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::CleanupReport;
+
+    #[test]
+    fn test_select_by_flags() {
+        assert!(!select(OutputFormat::Text, true, false).shows_text());
+        assert!(select(OutputFormat::Text, false, true).shows_text());
+        assert!(!select(OutputFormat::Text, false, true).colors());
+
+        // Structured output ignores --quiet
+        let report = Report::Cleanup(CleanupReport::new("purge", true));
+        let json = select(OutputFormat::Json, true, false).report(&report);
+        assert!(json.unwrap().unwrap().contains("\"kind\": \"cleanup\""));
+        assert_eq!(Quiet.report(&report), Ok(None));
+    }
+
+    #[test]
+    fn test_non_terminal_renderers_only_keep_errors() {
+        for renderer in [&Quiet as &dyn Renderer, &Structured(OutputFormat::Json)] {
+            assert_eq!(renderer.message(Level::Warning, "low space"), None);
+            assert!(renderer
+                .message(Level::Error, "failed")
+                .unwrap()
+                .contains("failed"));
+        }
+        assert_eq!(
+            Plain.message(Level::Warning, "low space").unwrap(),
+            "warning: low space"
+        );
+    }
+}
@@ -0,0 +1,770 @@
+use crate::ages::AgeReport;
+use crate::dedup::DuplicateGroup;
+use crate::display;
+use crate::health::HealthScore;
+use crate::history;
+use crate::influx;
+use crate::layers::LayerReport;
+use crate::notes::{self, Kind, Note};
+use crate::quota::Offender;
+use crate::read_only;
+use crate::render;
+use crate::resources::{Container, DiskUsage, Image, Provenance, UnusedVolumes, Volume};
+use crate::stats::StatsReport;
+use crate::system::{self, CacheEntry, CacheInfo, SkippedEntry};
+use crate::undo::Manifest;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Exit code when a command completed but some items could not be removed.
+pub const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// Exit code when `check` finds more reclaimable space than allowed.
+pub const EXIT_THRESHOLD_EXCEEDED: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables and colors
+    Text,
+    /// Structured JSON on stdout
+    Json,
+    /// The same structure as YAML on stdout
+    Yaml,
+    /// InfluxDB line protocol on stdout (usage overview only)
+    Influx,
+}
+
+/// Structured result of a command, shared by the Docker and system halves
+/// so automation can treat both uniformly.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Report {
+    Usage(Box<UsageReport>),
+    Caches(CacheListReport),
+    Cleanup(CleanupReport),
+    Contexts(ContextListReport),
+    Compose(ComposeListReport),
+    Volumes(VolumeListReport),
+    Builders(BuilderListReport),
+    Duplicates(DuplicatesReport),
+    History(HistoryReport),
+    Paths(PathsReport),
+    Check(CheckReport),
+    Bench(BenchReport),
+    Stats(StatsReport),
+    Layers(LayerReport),
+    Ages(AgeReport),
+    Quota(QuotaReport),
+    Notes(NotesReport),
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    pub usage: DiskUsage,
+    pub total_size: u64,
+    pub total_reclaimable: u64,
+    pub unused_volumes: UnusedVolumes,
+    pub forecast_days_until_full: Option<f64>,
+    pub health: HealthScore,
+    pub resources: ResourceListing,
+}
+
+impl UsageReport {
+    pub fn new(
+        usage: DiskUsage,
+        unused_volumes: UnusedVolumes,
+        forecast_days_until_full: Option<f64>,
+        health: HealthScore,
+    ) -> Self {
+        Self {
+            total_size: usage.total_size(),
+            total_reclaimable: usage.total_reclaimable(),
+            usage,
+            unused_volumes,
+            forecast_days_until_full,
+            health,
+            resources: ResourceListing::default(),
+        }
+    }
+
+    pub fn with_resources(mut self, resources: ResourceListing) -> Self {
+        self.resources = resources;
+        self
+    }
+}
+
+/// Everything on the daemon, for monitoring scripts reading the overview.
+#[derive(Debug, Default, Serialize)]
+pub struct ResourceListing {
+    pub images: Vec<ImageSummary>,
+    pub containers: Vec<ContainerSummary>,
+    pub volumes: Vec<VolumeSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageSummary {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    pub created_at: String,
+    pub dangling: bool,
+    pub provenance: Provenance,
+    /// From `dockerase note`, on the tag or its repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContainerSummary {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub state: String,
+    pub status: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VolumeSummary {
+    pub name: String,
+    pub driver: String,
+    /// `in use`, `orphaned` or `not mounted`
+    pub state: &'static str,
+    /// From `dockerase note`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl ResourceListing {
+    pub fn new(
+        images: &[Image],
+        containers: &[Container],
+        volumes: &[Volume],
+        unused: &UnusedVolumes,
+        notes: &[Note],
+    ) -> Self {
+        Self {
+            images: images
+                .iter()
+                .map(|i| ImageSummary {
+                    id: i.id.clone(),
+                    name: i.display_name(),
+                    size: i.size_bytes(),
+                    created_at: i.created_at.clone(),
+                    dangling: i.is_dangling(),
+                    provenance: i.provenance(),
+                    note: notes::find_image(notes, &i.display_name()).map(|n| n.text.clone()),
+                })
+                .collect(),
+            containers: containers
+                .iter()
+                .map(|c| ContainerSummary {
+                    id: c.id.clone(),
+                    name: c.names.clone(),
+                    image: c.image.clone(),
+                    state: c.state.clone(),
+                    status: c.status.clone(),
+                })
+                .collect(),
+            volumes: volumes
+                .iter()
+                .map(|v| VolumeSummary {
+                    name: v.name.clone(),
+                    driver: v.driver.clone(),
+                    state: unused.describe(&v.name),
+                    note: notes::find(notes, Kind::Volume, &v.name).map(|n| n.text.clone()),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheListReport {
+    pub caches: Vec<CacheInfo>,
+    pub total_size: u64,
+}
+
+impl CacheListReport {
+    pub fn new(caches: Vec<CacheInfo>) -> Self {
+        let refs: Vec<&CacheInfo> = caches.iter().collect();
+        Self {
+            total_size: system::total_size(&refs),
+            caches,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContextListReport {
+    pub contexts: Vec<ContextSummary>,
+}
+
+/// A docker context with the usage of the daemon behind it, when reachable.
+#[derive(Debug, Serialize)]
+pub struct ContextSummary {
+    pub name: String,
+    pub description: String,
+    pub endpoint: String,
+    pub current: bool,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_reclaimable: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComposeListReport {
+    pub projects: Vec<ComposeSummary>,
+}
+
+/// A docker compose project and what it takes up.
+#[derive(Debug, Serialize)]
+pub struct ComposeSummary {
+    pub name: String,
+    pub containers: usize,
+    pub running: usize,
+    pub images: usize,
+    pub volumes: usize,
+    pub networks: usize,
+    /// Container writable layers, built images and volumes
+    pub size: u64,
+}
+
+/// Every volume, largest first, by `dockerase volumes`.
+#[derive(Debug, Serialize)]
+pub struct VolumeListReport {
+    pub volumes: Vec<VolumeDetails>,
+    pub total_size: u64,
+}
+
+/// What is known about a volume before deciding to remove it.
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeDetails {
+    pub name: String,
+    /// `None` when the daemon doesn't report it
+    pub size: Option<u64>,
+    /// RFC 3339, when the daemon reports it
+    pub created_at: Option<String>,
+    /// Containers, running or stopped, that mount it
+    pub used_by: Vec<String>,
+    pub compose_project: Option<String>,
+    /// `in use`, `not mounted` or `orphaned`
+    pub state: &'static str,
+    /// From `dockerase note`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Buildx builders with a cache of their own, by `dockerase builders`.
+#[derive(Debug, Serialize)]
+pub struct BuilderListReport {
+    pub builders: Vec<BuilderSummary>,
+    pub total_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuilderSummary {
+    pub name: String,
+    pub driver: String,
+    pub running: bool,
+    /// `None` for builders that aren't running, which aren't started just
+    /// to measure them
+    pub cache_size: Option<u64>,
+}
+
+impl BuilderSummary {
+    /// On the `docker-container` driver, so its cache is this machine's.
+    /// `remote`, `kubernetes` and `cloud` builders can hold a team's shared
+    /// cache, so only a cleanup that names them prunes them.
+    pub fn is_local(&self) -> bool {
+        self.driver == "docker-container"
+    }
+}
+
+/// Files found in more than one volume, by `volumes duplicates`.
+#[derive(Debug, Serialize)]
+pub struct DuplicatesReport {
+    pub volumes_scanned: usize,
+    pub files_scanned: usize,
+    /// Bytes beyond the first copy of each shared file
+    pub total_duplicated: u64,
+    pub groups: Vec<DuplicateGroup>,
+    /// Volumes whose directory couldn't be read
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unreadable: Vec<String>,
+}
+
+/// Undo manifests of past cleanups, newest first.
+#[derive(Debug, Serialize)]
+pub struct HistoryReport {
+    pub manifests: Vec<Manifest>,
+}
+
+/// Notes on resources, by `dockerase note`.
+#[derive(Debug, Serialize)]
+pub struct NotesReport {
+    pub notes: Vec<Note>,
+}
+
+/// Docker's usage measured against the `[quota]` in the config.
+#[derive(Debug, Default, Serialize)]
+pub struct QuotaReport {
+    /// `None` when no quota is set
+    pub max_usage: Option<u64>,
+    pub used: u64,
+    pub exceeded: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub offenders: Vec<Offender>,
+    /// The cleanup run from the nag, if it was accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleanup: Option<CleanupReport>,
+}
+
+/// Reclaimable space measured against the limit given to `check`.
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub threshold: u64,
+    pub docker_reclaimable: u64,
+    /// Purgeable system caches, when they were counted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_reclaimable: Option<u64>,
+    pub total_reclaimable: u64,
+    pub exceeded: bool,
+    /// Cleanups run by `--auto` because the limit was exceeded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cleanups: Vec<CleanupReport>,
+    /// Reclaimable space measured again after the cleanups, when they ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reclaimable_after: Option<u64>,
+}
+
+impl CheckReport {
+    pub fn new(threshold: u64, docker_reclaimable: u64, system_reclaimable: Option<u64>) -> Self {
+        let total_reclaimable = docker_reclaimable + system_reclaimable.unwrap_or(0);
+        Self {
+            threshold,
+            docker_reclaimable,
+            system_reclaimable,
+            total_reclaimable,
+            exceeded: total_reclaimable > threshold,
+            cleanups: Vec::new(),
+            reclaimable_after: None,
+        }
+    }
+
+    pub fn add_cleanup(&mut self, report: Report) {
+        if let Report::Cleanup(cleanup) = report {
+            self.cleanups.push(cleanup);
+        }
+    }
+
+    /// Over the limit and not brought under it: no `--auto`, a dry run, or
+    /// a cleanup that left more than the limit reclaimable.
+    pub fn unresolved(&self) -> bool {
+        self.exceeded
+            && self
+                .reclaimable_after
+                .is_none_or(|after| after > self.threshold)
+    }
+}
+
+/// Removal throughput measured by `dockerase bench`.
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    /// Value of the `dockerase.bench` label on everything the run created
+    pub run_id: String,
+    /// Resources of each kind removed by each strategy
+    pub count: usize,
+    pub jobs: usize,
+    pub results: Vec<BenchResult>,
+    /// The quickest backend and strategy over every kind
+    pub fastest: Option<BenchChoice>,
+    /// Benchmark resources that couldn't be cleaned up
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub leftovers: Vec<String>,
+}
+
+impl BenchReport {
+    /// A strategy failed or left resources behind.
+    pub fn has_failures(&self) -> bool {
+        !self.leftovers.is_empty() || self.results.iter().any(|r| r.error.is_some())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub kind: &'static str,
+    pub backend: &'static str,
+    pub strategy: &'static str,
+    pub seconds: f64,
+    pub per_second: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchChoice {
+    pub backend: &'static str,
+    pub strategy: &'static str,
+    /// Time taken over every kind
+    pub seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PathsReport {
+    pub paths: Vec<PathEntry>,
+}
+
+/// One of the files or directories dockerase uses.
+#[derive(Debug, Serialize)]
+pub struct PathEntry {
+    pub name: String,
+    pub path: String,
+    pub exists: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    /// Would be removed (dry run, or before execution)
+    Planned,
+    Removed,
+    /// Brought back by `restore`
+    Restored,
+    Failed,
+    /// Deliberately left alone (see the item's note)
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportItem {
+    pub category: String,
+    pub name: String,
+    pub size: Option<u64>,
+    pub status: ItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Entries left in place (locked, permission denied) while the rest of
+    /// the item was removed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedEntry>,
+    /// Largest entries inside the item, when a detailed listing was requested
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<CacheEntry>,
+}
+
+impl ReportItem {
+    pub fn planned(category: &str, name: &str, size: Option<u64>) -> Self {
+        Self {
+            category: category.to_string(),
+            name: name.to_string(),
+            size,
+            status: ItemStatus::Planned,
+            error: None,
+            note: None,
+            skipped: Vec::new(),
+            details: Vec::new(),
+        }
+    }
+
+    /// An item deliberately left alone, with the reason as its note.
+    pub fn skipped(category: &str, name: &str, reason: &str) -> Self {
+        Self {
+            status: ItemStatus::Skipped,
+            note: Some(reason.to_string()),
+            ..Self::planned(category, name, None)
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanupReport {
+    pub command: String,
+    pub dry_run: bool,
+    /// Read-only mode was on, so nothing could have been removed
+    pub read_only: bool,
+    pub aborted: bool,
+    pub items: Vec<ReportItem>,
+    pub space_freed: u64,
+    /// Space freed in each category, where Docker could measure it
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub freed: BTreeMap<String, u64>,
+}
+
+impl CleanupReport {
+    pub fn new(command: &str, dry_run: bool) -> Self {
+        Self {
+            command: command.to_string(),
+            dry_run,
+            read_only: read_only::is_enabled(),
+            aborted: false,
+            items: Vec::new(),
+            space_freed: 0,
+            freed: BTreeMap::new(),
+        }
+    }
+
+    pub fn aborted(mut self) -> Self {
+        self.aborted = true;
+        self
+    }
+
+    /// Mark every planned item as removed once execution has succeeded.
+    pub fn mark_all_removed(&mut self) {
+        for item in &mut self.items {
+            if item.status == ItemStatus::Planned {
+                item.status = ItemStatus::Removed;
+            }
+        }
+    }
+
+    /// Mark the planned item `name` in `category` as removed as soon as its
+    /// step is done, so a cancelled run knows what it got through.
+    pub fn mark_removed(&mut self, category: &str, name: &str) {
+        for item in &mut self.items {
+            if item.status == ItemStatus::Planned && item.category == category && item.name == name
+            {
+                item.status = ItemStatus::Removed;
+            }
+        }
+    }
+
+    /// Mark the planned item `name` in `category` as skipped with `reason`,
+    /// such as an image held for its grace period rather than removed.
+    pub fn mark_skipped(&mut self, category: &str, name: &str, reason: &str) {
+        for item in &mut self.items {
+            if item.status == ItemStatus::Planned && item.category == category && item.name == name
+            {
+                item.status = ItemStatus::Skipped;
+                item.note = Some(reason.to_string());
+            }
+        }
+    }
+
+    /// Fail every item still planned when a run stopped early (Ctrl-C, or
+    /// a failure without `--continue-on-error`), with `reason` as the error.
+    pub fn mark_unreached(&mut self, reason: &str) {
+        for item in &mut self.items {
+            if item.status == ItemStatus::Planned {
+                item.status = ItemStatus::Failed;
+                item.error = Some(reason.to_string());
+            }
+        }
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.items.iter().any(|i| i.status == ItemStatus::Failed)
+    }
+
+    /// Record the space freed overall and per category from Docker's disk
+    /// usage before and after the run.
+    pub fn measure_freed(&mut self, before: &DiskUsage, after: &DiskUsage) {
+        self.space_freed = before.total_size().saturating_sub(after.total_size());
+        let categories = [
+            ("images", before.images_size, after.images_size),
+            ("containers", before.containers_size, after.containers_size),
+            ("volumes", before.volumes_size, after.volumes_size),
+            (
+                "build_cache",
+                before.build_cache_size,
+                after.build_cache_size,
+            ),
+        ];
+        self.freed = categories
+            .into_iter()
+            .filter(|(_, before, after)| before > after)
+            .map(|(category, before, after)| (category.to_string(), before - after))
+            .collect();
+    }
+}
+
+impl Report {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Report::Cleanup(cleanup) if cleanup.has_failures() => EXIT_PARTIAL_FAILURE,
+            Report::Check(check) if check.cleanups.iter().any(|c| c.has_failures()) => {
+                EXIT_PARTIAL_FAILURE
+            }
+            Report::Check(check) if check.unresolved() => EXIT_THRESHOLD_EXCEEDED,
+            Report::Bench(bench) if bench.has_failures() => EXIT_PARTIAL_FAILURE,
+            _ => 0,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// Usage metrics as InfluxDB line protocol; other reports have no
+    /// metrics to export.
+    pub fn to_influx(&self) -> Result<String, String> {
+        match self {
+            Report::Usage(usage) => Ok(influx::line_protocol(usage, history::now_secs())),
+            _ => Err("--output influx is only supported by `dockerase usage`".to_string()),
+        }
+    }
+}
+
+/// Print the report on stdout when the renderer shows one (structured
+/// output) and write it to `report_path` (always JSON) when `--report` is
+/// given.
+pub fn emit(report: &Report, report_path: Option<&Path>) -> Result<(), String> {
+    if let Some(output) = render::current().report(report)? {
+        display::print_output(&output);
+    }
+
+    if let Some(path) = report_path {
+        fs::write(path, report.to_json()? + "\n")
+            .map_err(|e| format!("Failed to write report to {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn failed_item() -> ReportItem {
+        ReportItem {
+            status: ItemStatus::Failed,
+            error: Some("permission denied".to_string()),
+            ..ReportItem::planned("cache", "npm", Some(10))
+        }
+    }
+
+    #[test]
+    fn test_exit_code_success() {
+        let mut cleanup = CleanupReport::new("purge", false);
+        cleanup
+            .items
+            .push(ReportItem::planned("images", "dangling", None));
+        cleanup.mark_all_removed();
+        assert_eq!(Report::Cleanup(cleanup).exit_code(), 0);
+    }
+
+    #[test]
+    fn test_exit_code_partial_failure() {
+        let mut cleanup = CleanupReport::new("system purge", false);
+        cleanup.items.push(failed_item());
+        assert_eq!(Report::Cleanup(cleanup).exit_code(), EXIT_PARTIAL_FAILURE);
+    }
+
+    #[test]
+    fn test_exit_code_check_threshold() {
+        assert_eq!(
+            Report::Check(CheckReport::new(100, 60, Some(40))).exit_code(),
+            0
+        );
+        assert_eq!(
+            Report::Check(CheckReport::new(100, 60, Some(41))).exit_code(),
+            EXIT_THRESHOLD_EXCEEDED
+        );
+
+        // A cleanup resolves the breach only if what it left is within the
+        // limit
+        let mut check = CheckReport::new(100, 200, None);
+        check.add_cleanup(Report::Cleanup(CleanupReport::new("purge", false)));
+        assert!(check.unresolved());
+        check.reclaimable_after = Some(150);
+        assert!(check.unresolved());
+        check.reclaimable_after = Some(100);
+        assert_eq!(Report::Check(check).exit_code(), 0);
+    }
+
+    #[test]
+    fn test_mark_all_removed_keeps_failures() {
+        let mut cleanup = CleanupReport::new("system purge", false);
+        cleanup
+            .items
+            .push(ReportItem::planned("cache", "Yarn", Some(5)));
+        cleanup.items.push(failed_item());
+        cleanup.mark_all_removed();
+        assert_eq!(cleanup.items[0].status, ItemStatus::Removed);
+        assert_eq!(cleanup.items[1].status, ItemStatus::Failed);
+    }
+
+    #[test]
+    fn test_mark_unreached_fails_what_was_not_reached() {
+        let mut cleanup = CleanupReport::new("nuclear", false);
+        for name in ["web", "db"] {
+            cleanup
+                .items
+                .push(ReportItem::planned("containers", name, None));
+        }
+        cleanup.mark_removed("containers", "web");
+        cleanup.mark_unreached("Cancelled");
+        cleanup.mark_all_removed();
+        assert_eq!(cleanup.items[0].status, ItemStatus::Removed);
+        assert_eq!(cleanup.items[1].status, ItemStatus::Failed);
+        assert_eq!(cleanup.items[1].error.as_deref(), Some("Cancelled"));
+    }
+
+    #[test]
+    fn test_report_json_is_tagged() {
+        let usage = DiskUsage::default();
+        let health = HealthScore::compute(&usage, &[], None);
+        let report = Report::Usage(Box::new(UsageReport::new(
+            usage,
+            UnusedVolumes::default(),
+            None,
+            health,
+        )));
+        let json = report.to_json().unwrap();
+        assert!(json.contains("\"kind\": \"usage\""));
+        assert!(json.contains("\"score\": 100"));
+        assert!(json.contains("\"total_reclaimable\": 0"));
+
+        let yaml = report.to_yaml().unwrap();
+        assert!(yaml.contains("kind: usage"));
+        assert!(yaml.contains("total_reclaimable: 0"));
+    }
+
+    #[test]
+    fn test_resource_listing_marks_volume_state() {
+        let unused = UnusedVolumes {
+            orphaned: vec!["4f4f".to_string()],
+            unmounted: vec!["scratch".to_string()],
+        };
+        let volume = |name: &str| Volume {
+            name: name.to_string(),
+            driver: "local".to_string(),
+            mountpoint: String::new(),
+            labels: String::new(),
+        };
+        let listing = ResourceListing::new(
+            &[],
+            &[],
+            &[volume("pgdata"), volume("scratch"), volume("4f4f")],
+            &unused,
+            &[],
+        );
+        let states: Vec<&str> = listing.volumes.iter().map(|v| v.state).collect();
+        assert_eq!(states, vec!["in use", "not mounted", "orphaned"]);
+    }
+
+    #[test]
+    fn test_emit_writes_report_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        let report = Report::Cleanup(CleanupReport::new("purge", true));
+        emit(&report, Some(&path)).unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.contains("\"kind\": \"cleanup\""));
+        assert!(written.contains("\"dry_run\": true"));
+    }
+}
@@ -1,59 +1,337 @@
-use serde::Deserialize;
+use crate::docker::parse_size;
+use crate::units::parse_docker_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Label docker compose puts on every container, volume, network and built
+/// image of a project.
+pub const COMPOSE_PROJECT_LABEL: &str = "com.docker.compose.project";
+
+/// Value of `key` in a comma-separated `key=value` label list, as printed
+/// by the docker CLI.
+pub fn label_value<'a>(labels: &'a str, key: &str) -> Option<&'a str> {
+    labels.split(',').find_map(|label| {
+        let (k, v) = label.split_once('=').unwrap_or((label, ""));
+        (k == key).then_some(v)
+    })
+}
 
 #[derive(Debug, Deserialize)]
 pub struct Image {
     #[serde(rename = "ID")]
     pub id: String,
-    #[allow(dead_code)]
     #[serde(rename = "Repository")]
     pub repository: String,
-    #[allow(dead_code)]
     #[serde(rename = "Tag")]
     pub tag: String,
-    #[allow(dead_code)]
     #[serde(rename = "Size")]
     pub size: String,
-    #[allow(dead_code)]
     #[serde(rename = "CreatedAt", default)]
     pub created_at: String,
+    /// Registry digest; only filled in when listed with `--digests`
+    #[serde(rename = "Digest", default)]
+    pub digest: String,
+}
+
+/// Where an image came from, which decides whether removing it can be
+/// undone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provenance {
+    /// Pulled from, or pushed to, a registry: `docker pull` brings it back
+    Registry,
+    /// Built here and never pushed anywhere: gone for good once removed
+    Local,
+}
+
+impl Provenance {
+    pub fn label(self) -> &'static str {
+        match self {
+            Provenance::Registry => "registry",
+            Provenance::Local => "built locally",
+        }
+    }
+}
+
+impl Image {
+    /// The registry digest, if the image came from (or went to) a registry.
+    pub fn digest(&self) -> Option<&str> {
+        Some(self.digest.as_str()).filter(|d| !d.is_empty() && *d != "<none>")
+    }
+
+    /// Whether the image can be pulled back, judged by its registry digest
+    /// in the tag's own repository. A dangling image has no tag to pull it
+    /// back by.
+    pub fn provenance(&self) -> Provenance {
+        match self.digest() {
+            Some(_) if !self.is_dangling() => Provenance::Registry,
+            _ => Provenance::Local,
+        }
+    }
+
+    /// Untagged, e.g. left behind when a newer build took over its tag.
+    pub fn is_dangling(&self) -> bool {
+        self.repository == "<none>" || self.repository.is_empty()
+    }
+
+    /// What removing the image goes by: its tag, so other tags of the same
+    /// image (a protected or kept one, say) stay, or the ID of an untagged
+    /// image.
+    pub fn reference(&self) -> String {
+        if self.is_dangling() {
+            self.id.clone()
+        } else {
+            self.display_name()
+        }
+    }
+
+    /// `repository:tag`, or the short ID for untagged images.
+    pub fn display_name(&self) -> String {
+        if self.is_dangling() {
+            self.id
+                .trim_start_matches("sha256:")
+                .chars()
+                .take(12)
+                .collect()
+        } else {
+            format!("{}:{}", self.repository, self.tag)
+        }
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        parse_size(&self.size)
+    }
+
+    /// When the image was built, as a UNIX timestamp.
+    pub fn created(&self) -> Option<u64> {
+        parse_docker_timestamp(&self.created_at)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Container {
     #[serde(rename = "ID")]
     pub id: String,
-    #[allow(dead_code)]
     #[serde(rename = "Names")]
     pub names: String,
-    #[allow(dead_code)]
     #[serde(rename = "Image")]
     pub image: String,
     #[serde(rename = "State")]
     pub state: String,
-    #[allow(dead_code)]
     #[serde(rename = "Status")]
     pub status: String,
-    #[allow(dead_code)]
+    /// Writable layer size, e.g. `20MB (virtual 1GB)`; only filled in when
+    /// listed with `--size`
     #[serde(rename = "Size", default)]
     pub size: String,
+    #[serde(rename = "CreatedAt", default)]
+    pub created_at: String,
+    /// Comma-separated `key=value` pairs
+    #[serde(rename = "Labels", default)]
+    pub labels: String,
 }
 
 impl Container {
     pub fn is_running(&self) -> bool {
         self.state == "running"
     }
+
+    /// Whether this container was created from `image`. The docker CLI
+    /// reports a container's image by reference (`alpine` for
+    /// `alpine:latest`) or, once the tag has moved on, by short ID.
+    pub fn uses(&self, image: &Image) -> bool {
+        let reference = self.image.trim_start_matches("sha256:");
+        self.image == image.display_name()
+            || (image.tag == "latest" && self.image == image.repository)
+            || (reference.len() >= 12
+                && image
+                    .id
+                    .trim_start_matches("sha256:")
+                    .starts_with(reference))
+    }
+
+    /// Size of the writable layer, excluding the shared image.
+    pub fn size_bytes(&self) -> u64 {
+        parse_size(self.size.split('(').next().unwrap_or_default())
+    }
+
+    pub fn compose_project(&self) -> Option<&str> {
+        label_value(&self.labels, COMPOSE_PROJECT_LABEL)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Volume {
     #[serde(rename = "Name")]
     pub name: String,
-    #[allow(dead_code)]
     #[serde(rename = "Driver")]
     pub driver: String,
-    #[allow(dead_code)]
     #[serde(rename = "Mountpoint", default)]
     pub mountpoint: String,
+    /// Comma-separated `key=value` pairs
+    #[serde(rename = "Labels", default)]
+    pub labels: String,
+}
+
+impl Volume {
+    /// Created implicitly for a container (`-v /data`, or a `VOLUME` in the
+    /// image) rather than by name. Docker labels these since 23.0; older
+    /// daemons only give them a random 64-character hex name.
+    pub fn is_anonymous(&self) -> bool {
+        label_value(&self.labels, "com.docker.volume.anonymous").is_some()
+            || (self.name.len() == 64 && self.name.chars().all(|c| c.is_ascii_hexdigit()))
+    }
+
+    pub fn compose_project(&self) -> Option<&str> {
+        label_value(&self.labels, COMPOSE_PROJECT_LABEL)
+    }
+}
+
+/// Volumes that no container, running or stopped, references.
+#[derive(Debug, Default, Serialize)]
+pub struct UnusedVolumes {
+    /// Anonymous volumes whose container is gone; nothing can reach them
+    pub orphaned: Vec<String>,
+    /// Named volumes not mounted right now, which may still hold data
+    /// someone means to reattach
+    pub unmounted: Vec<String>,
+}
+
+impl UnusedVolumes {
+    pub fn classify(volumes: Vec<Volume>) -> Self {
+        let (orphaned, unmounted): (Vec<_>, Vec<_>) =
+            volumes.into_iter().partition(|v| v.is_anonymous());
+        Self {
+            orphaned: orphaned.into_iter().map(|v| v.name).collect(),
+            unmounted: unmounted.into_iter().map(|v| v.name).collect(),
+        }
+    }
+
+    /// Short description of a volume's state, for listings.
+    pub fn describe(&self, name: &str) -> &'static str {
+        let name = name.to_string();
+        if self.orphaned.contains(&name) {
+            "orphaned"
+        } else if self.unmounted.contains(&name) {
+            "not mounted"
+        } else {
+            "in use"
+        }
+    }
+}
+
+/// Restart policies under which docker starts a container again by itself.
+const RESTARTING_POLICIES: [&str; 2] = ["always", "unless-stopped"];
+
+/// Resources something live depends on: everything of a compose project
+/// with a running container, volumes mounted by running containers, and
+/// containers docker restarts by itself along with their images. Sweeping
+/// cleanups leave these alone unless told otherwise. Each is mapped to the
+/// reason it counts as active.
+#[derive(Debug, Default)]
+pub struct ActiveResources {
+    /// By container ID
+    containers: BTreeMap<String, String>,
+    /// By image ID
+    images: BTreeMap<String, String>,
+    /// By volume name
+    volumes: BTreeMap<String, String>,
+    /// Compose projects with a running container
+    projects: Vec<String>,
+}
+
+impl ActiveResources {
+    /// Work out what is active from every container, the containers
+    /// mounting each volume and each container's restart policy, both by
+    /// container name.
+    pub fn new(
+        containers: &[Container],
+        images: &[Image],
+        volume_users: &HashMap<String, Vec<String>>,
+        restart_policies: &HashMap<String, String>,
+    ) -> Self {
+        let mut active = Self::default();
+        for container in containers.iter().filter(|c| c.is_running()) {
+            if let Some(project) = container.compose_project() {
+                if !active.projects.iter().any(|p| p == project) {
+                    active.projects.push(project.to_string());
+                }
+            }
+        }
+
+        for container in containers {
+            let restarts = restart_policies
+                .get(&container.names)
+                .filter(|p| RESTARTING_POLICIES.contains(&p.as_str()));
+            let (reason, image_reason) = match (active.project_of(container), restarts) {
+                (Some(project), _) => (
+                    format!("compose project '{}' is running", project),
+                    format!("used by running compose project '{}'", project),
+                ),
+                (None, Some(policy)) => (
+                    format!("restarts by itself (restart: {})", policy),
+                    format!(
+                        "used by '{}', which restarts by itself (restart: {})",
+                        container.names, policy
+                    ),
+                ),
+                (None, None) => continue,
+            };
+            active.containers.insert(container.id.clone(), reason);
+            for image in images.iter().filter(|i| container.uses(i)) {
+                active
+                    .images
+                    .entry(image.id.clone())
+                    .or_insert_with(|| image_reason.clone());
+            }
+        }
+
+        for (volume, users) in volume_users {
+            let running: Vec<&str> = users
+                .iter()
+                .filter(|u| containers.iter().any(|c| c.names == **u && c.is_running()))
+                .map(String::as_str)
+                .collect();
+            if !running.is_empty() {
+                active.volumes.insert(
+                    volume.clone(),
+                    format!("mounted by running {}", running.join(", ")),
+                );
+            }
+        }
+        active
+    }
+
+    fn project_of<'a>(&self, container: &'a Container) -> Option<&'a str> {
+        container
+            .compose_project()
+            .filter(|p| self.projects.iter().any(|r| r == p))
+    }
+
+    fn running_project(&self, labels: &str) -> Option<String> {
+        label_value(labels, COMPOSE_PROJECT_LABEL)
+            .filter(|p| self.projects.iter().any(|r| r == p))
+            .map(|p| format!("compose project '{}' is running", p))
+    }
+
+    pub fn container(&self, container: &Container) -> Option<String> {
+        self.containers.get(&container.id).cloned()
+    }
+
+    pub fn image(&self, image: &Image) -> Option<String> {
+        self.images.get(&image.id).cloned()
+    }
+
+    pub fn volume(&self, volume: &Volume) -> Option<String> {
+        self.volumes
+            .get(&volume.name)
+            .cloned()
+            .or_else(|| self.running_project(&volume.labels))
+    }
+
+    pub fn network(&self, network: &Network) -> Option<String> {
+        self.running_project(&network.labels)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -68,15 +346,140 @@ pub struct Network {
     #[allow(dead_code)]
     #[serde(rename = "Scope", default)]
     pub scope: String,
+    #[serde(rename = "CreatedAt", default)]
+    pub created_at: String,
+    /// Comma-separated `key=value` pairs
+    #[serde(rename = "Labels", default)]
+    pub labels: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DockerContext {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Description", default)]
+    pub description: String,
+    #[serde(rename = "DockerEndpoint", default)]
+    pub endpoint: String,
+    #[serde(rename = "Current", default)]
+    pub current: bool,
+}
+
+/// A buildx builder, as `docker buildx ls --format json` lists it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Builder {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Driver", default)]
+    pub driver: String,
+    #[serde(rename = "Nodes", default)]
+    pub nodes: Vec<BuilderNode>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BuilderNode {
+    #[serde(rename = "Status", default)]
+    pub status: String,
+}
+
+impl Builder {
+    /// Builders on the `docker` driver build inside the daemon and share
+    /// its build cache; the others (e.g. `docker-container`, `remote`) keep
+    /// their own.
+    pub fn has_own_cache(&self) -> bool {
+        self.driver != "docker"
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.nodes.iter().any(|n| n.status == "running")
+    }
 }
 
 impl Network {
+    /// Created by the runtime itself (`podman` is Podman's bridge), so it
+    /// can't be removed.
     pub fn is_default(&self) -> bool {
-        matches!(self.name.as_str(), "bridge" | "host" | "none")
+        matches!(self.name.as_str(), "bridge" | "host" | "none" | "podman")
+    }
+
+    pub fn compose_project(&self) -> Option<&str> {
+        label_value(&self.labels, COMPOSE_PROJECT_LABEL)
     }
 }
 
+/// Everything docker compose created for one project. Images are only
+/// those compose built for it; pulled images are shared and stay out.
 #[derive(Debug, Default)]
+pub struct ComposeProject {
+    pub name: String,
+    pub containers: Vec<Container>,
+    pub images: Vec<Image>,
+    pub volumes: Vec<Volume>,
+    pub networks: Vec<Network>,
+}
+
+impl ComposeProject {
+    /// Group labeled resources by project, sorted by name. Unlabeled
+    /// resources are dropped.
+    pub fn group(
+        containers: Vec<Container>,
+        volumes: Vec<Volume>,
+        networks: Vec<Network>,
+    ) -> Vec<ComposeProject> {
+        let mut projects: BTreeMap<String, ComposeProject> = BTreeMap::new();
+        for c in containers {
+            if let Some(name) = c.compose_project().map(str::to_string) {
+                Self::entry(&mut projects, name).containers.push(c);
+            }
+        }
+        for v in volumes {
+            if let Some(name) = v.compose_project().map(str::to_string) {
+                Self::entry(&mut projects, name).volumes.push(v);
+            }
+        }
+        for n in networks {
+            if let Some(name) = n.compose_project().map(str::to_string) {
+                Self::entry(&mut projects, name).networks.push(n);
+            }
+        }
+        projects.into_values().collect()
+    }
+
+    fn entry(projects: &mut BTreeMap<String, ComposeProject>, name: String) -> &mut ComposeProject {
+        projects
+            .entry(name.clone())
+            .or_insert_with(|| ComposeProject {
+                name,
+                ..Default::default()
+            })
+    }
+
+    pub fn running(&self) -> usize {
+        self.containers.iter().filter(|c| c.is_running()).count()
+    }
+
+    /// Container writable layers plus built images, without volumes (whose
+    /// sizes come from a separate, slower listing).
+    pub fn size_bytes(&self) -> u64 {
+        self.containers
+            .iter()
+            .map(Container::size_bytes)
+            .sum::<u64>()
+            + self.images.iter().map(Image::size_bytes).sum::<u64>()
+    }
+}
+
+/// One build cache record, from `docker system df -v`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildCacheRecord {
+    pub size: u64,
+    /// When a build last used it, or else when it was created (UNIX seconds)
+    pub last_used: Option<u64>,
+    /// Held by a build running now; `None` when the daemon doesn't say
+    pub in_use: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize)]
 pub struct DiskUsage {
     pub images_size: u64,
     pub images_reclaimable: u64,
@@ -124,6 +527,8 @@ mod tests {
             state: state.to_string(),
             status: "Up 1 hour".to_string(),
             size: "0B".to_string(),
+            created_at: String::new(),
+            labels: String::new(),
         }
     }
 
@@ -133,9 +538,229 @@ mod tests {
             name: name.to_string(),
             driver: "bridge".to_string(),
             scope: "local".to_string(),
+            created_at: String::new(),
+            labels: String::new(),
         }
     }
 
+    #[test]
+    fn test_container_size_bytes_ignores_virtual_size() {
+        let mut container = make_container("exited");
+        container.size = "20MB (virtual 1.2GB)".to_string();
+        assert_eq!(container.size_bytes(), 20_000_000);
+        assert_eq!(make_container("exited").size_bytes(), 0);
+    }
+
+    #[test]
+    fn test_image_display_name() {
+        let tagged = Image {
+            id: "sha256:0123456789abcdef".to_string(),
+            repository: "alpine".to_string(),
+            tag: "3.19".to_string(),
+            size: "5MB".to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        };
+        assert_eq!(tagged.display_name(), "alpine:3.19");
+
+        let dangling = Image {
+            repository: "<none>".to_string(),
+            tag: "<none>".to_string(),
+            ..tagged
+        };
+        assert_eq!(dangling.display_name(), "0123456789ab");
+    }
+
+    #[test]
+    fn test_image_provenance_comes_from_its_registry_digest() {
+        let mut image = Image {
+            id: "sha256:0123456789abcdef".to_string(),
+            repository: "myapp".to_string(),
+            tag: "dev".to_string(),
+            size: "5MB".to_string(),
+            created_at: String::new(),
+            digest: "<none>".to_string(),
+        };
+        assert_eq!(image.provenance(), Provenance::Local);
+        image.digest = "sha256:feed".to_string();
+        assert_eq!(image.provenance(), Provenance::Registry);
+        image.repository = "<none>".to_string();
+        assert_eq!(image.provenance(), Provenance::Local);
+    }
+
+    #[test]
+    fn test_active_resources_cover_running_projects_mounts_and_services() {
+        let container = |id: &str, name: &str, image: &str, state: &str, labels: &str| Container {
+            id: id.to_string(),
+            names: name.to_string(),
+            image: image.to_string(),
+            labels: labels.to_string(),
+            ..make_container(state)
+        };
+        let containers = [
+            container(
+                "c1",
+                "shop-web",
+                "shop-web",
+                "running",
+                "com.docker.compose.project=shop",
+            ),
+            container("c2", "db", "postgres:16", "running", ""),
+            container("c3", "proxy", "nginx:latest", "exited", ""),
+            container(
+                "c4",
+                "job",
+                "alpine:latest",
+                "exited",
+                "com.docker.compose.project=old",
+            ),
+        ];
+        let image = |id: &str, repository: &str, tag: &str| Image {
+            id: id.to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            size: "5MB".to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        };
+        let images = [
+            image("sha256:1", "shop-web", "latest"),
+            image("sha256:2", "postgres", "16"),
+            image("sha256:3", "nginx", "latest"),
+        ];
+        let users = HashMap::from([
+            ("pgdata".to_string(), vec!["db".to_string()]),
+            ("certs".to_string(), vec!["proxy".to_string()]),
+        ]);
+        let policies = HashMap::from([
+            ("proxy".to_string(), "always".to_string()),
+            ("db".to_string(), "no".to_string()),
+        ]);
+        let active = ActiveResources::new(&containers, &images, &users, &policies);
+
+        let reasons: Vec<Option<String>> = containers.iter().map(|c| active.container(c)).collect();
+        assert_eq!(
+            reasons,
+            [
+                Some("compose project 'shop' is running".to_string()),
+                None,
+                Some("restarts by itself (restart: always)".to_string()),
+                // Nothing of the old project runs any more
+                None,
+            ]
+        );
+        assert!(active.image(&images[0]).is_some());
+        assert_eq!(active.image(&images[1]), None);
+        assert!(active.image(&images[2]).unwrap().contains("'proxy'"));
+
+        let volume = |name: &str, labels: &str| Volume {
+            name: name.to_string(),
+            driver: "local".to_string(),
+            mountpoint: String::new(),
+            labels: labels.to_string(),
+        };
+        assert_eq!(
+            active.volume(&volume("pgdata", "")).as_deref(),
+            Some("mounted by running db")
+        );
+        // Only mounted by a stopped container
+        assert_eq!(active.volume(&volume("certs", "")), None);
+        assert!(active
+            .volume(&volume("shop_data", "com.docker.compose.project=shop"))
+            .is_some());
+
+        let mut network = make_network("shop_default");
+        network.labels = "com.docker.compose.project=shop".to_string();
+        assert!(active.network(&network).is_some());
+        assert_eq!(active.network(&make_network("bridge")), None);
+    }
+
+    #[test]
+    fn test_container_uses_image() {
+        let image = Image {
+            id: "sha256:0123456789abcdef".to_string(),
+            repository: "alpine".to_string(),
+            tag: "latest".to_string(),
+            size: "5MB".to_string(),
+            created_at: String::new(),
+            digest: String::new(),
+        };
+        let mut container = make_container("running");
+        for reference in ["alpine", "alpine:latest", "0123456789ab"] {
+            container.image = reference.to_string();
+            assert!(container.uses(&image), "{reference}");
+        }
+        for reference in ["alpine:3.19", "postgres", "0123"] {
+            container.image = reference.to_string();
+            assert!(!container.uses(&image), "{reference}");
+        }
+    }
+
+    fn make_volume(name: &str, labels: &str) -> Volume {
+        Volume {
+            name: name.to_string(),
+            driver: "local".to_string(),
+            mountpoint: String::new(),
+            labels: labels.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_unused_volumes_split_orphans_from_named() {
+        let hex = "4f".repeat(32);
+        let unused = UnusedVolumes::classify(vec![
+            make_volume("pgdata", ""),
+            make_volume(&hex, ""),
+            make_volume("cache01", "com.docker.volume.anonymous="),
+        ]);
+        assert_eq!(unused.orphaned, vec![hex.clone(), "cache01".to_string()]);
+        assert_eq!(unused.unmounted, vec!["pgdata"]);
+        assert_eq!(unused.describe(&hex), "orphaned");
+        assert_eq!(unused.describe("pgdata"), "not mounted");
+        assert_eq!(unused.describe("other"), "in use");
+    }
+
+    #[test]
+    fn test_label_value() {
+        let labels = "com.docker.compose.project=shop,com.docker.volume.anonymous=";
+        assert_eq!(label_value(labels, COMPOSE_PROJECT_LABEL), Some("shop"));
+        assert_eq!(label_value(labels, "com.docker.volume.anonymous"), Some(""));
+        assert_eq!(label_value(labels, "com.docker.compose"), None);
+        assert_eq!(label_value("", COMPOSE_PROJECT_LABEL), None);
+    }
+
+    #[test]
+    fn test_group_compose_projects() {
+        let labeled = |project: &str| format!("{}={}", COMPOSE_PROJECT_LABEL, project);
+        let mut web = make_container("exited");
+        web.labels = labeled("shop") + ",com.docker.compose.service=web";
+        web.size = "10MB".to_string();
+        let mut api = make_container("running");
+        api.labels = labeled("blog");
+        let plain = make_container("running");
+        let mut net = make_network("shop_default");
+        net.labels = labeled("shop");
+
+        let projects = ComposeProject::group(
+            vec![web, api, plain],
+            vec![
+                make_volume("shop_data", &labeled("shop")),
+                make_volume("pgdata", ""),
+            ],
+            vec![net, make_network("bridge")],
+        );
+        let names: Vec<&str> = projects.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["blog", "shop"]);
+
+        let shop = &projects[1];
+        assert_eq!(shop.containers.len(), 1);
+        assert_eq!(shop.volumes[0].name, "shop_data");
+        assert_eq!(shop.networks[0].name, "shop_default");
+        assert_eq!(shop.running(), 0);
+        assert_eq!(shop.size_bytes(), 10_000_000);
+        assert_eq!(projects[0].running(), 1);
+    }
+
     #[test]
     fn test_container_is_running() {
         let running = make_container("running");
@@ -152,6 +777,7 @@ mod tests {
         assert!(make_network("bridge").is_default());
         assert!(make_network("host").is_default());
         assert!(make_network("none").is_default());
+        assert!(make_network("podman").is_default());
         assert!(!make_network("my-network").is_default());
         assert!(!make_network("custom_net").is_default());
     }
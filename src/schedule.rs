@@ -0,0 +1,371 @@
+//! When `watch --purge` may clean up. Cleanups can be held to time windows
+//! in local time, such as nights and weekends, and to moments the machine
+//! is idle: no docker build running, on AC power and a CPU load below
+//! `max_load`. A purge that may not run now is postponed to the next check
+//! on which it may.
+
+use crate::docker::Docker;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+/// CPU load, as a percentage of all cores, above which the machine is busy
+/// unless `max_load` says otherwise.
+pub const DEFAULT_MAX_LOAD: u8 = 50;
+
+const DAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// `[watch]`: when automated cleanups may run.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schedule {
+    /// Local times cleanups may run in, such as `"Mon-Fri 19:00-07:00"`;
+    /// any time when empty
+    pub windows: Vec<Window>,
+    /// Only clean up while no build runs, on AC power and under `max_load`
+    pub when_idle: bool,
+    /// CPU load, as a percentage of all cores, above which the machine is
+    /// busy
+    pub max_load: Option<u8>,
+}
+
+impl Schedule {
+    /// Whether cleanups can't run at just any time.
+    pub fn is_restricted(&self) -> bool {
+        !self.windows.is_empty() || self.when_idle
+    }
+
+    /// Why a cleanup may not run now, or `None` if it may.
+    pub fn blocker(&self) -> Option<String> {
+        if !self.windows.is_empty() {
+            let Some((day, minute)) = local_time() else {
+                return Some("the local time could not be read".to_string());
+            };
+            if !self.windows.iter().any(|w| w.contains(day, minute)) {
+                return Some(format!("outside {}", self.describe_windows()));
+            }
+        }
+        if !self.when_idle {
+            return None;
+        }
+        if build_running() {
+            return Some("a docker build is running".to_string());
+        }
+        if on_battery() {
+            return Some("running on battery".to_string());
+        }
+        let max_load = self.max_load.unwrap_or(DEFAULT_MAX_LOAD);
+        match load_percent() {
+            Some(load) if load > u32::from(max_load) => Some(format!(
+                "the CPU is busy ({}% load, limit {}%)",
+                load, max_load
+            )),
+            _ => None,
+        }
+    }
+
+    /// The windows as configured, e.g. `Mon-Fri 19:00-07:00 or Sat-Sun`.
+    pub fn describe_windows(&self) -> String {
+        let specs: Vec<&str> = self.windows.iter().map(|w| w.spec.as_str()).collect();
+        specs.join(" or ")
+    }
+}
+
+/// Days of the week and a time of day, in local time. Either may be left
+/// out: `Sat-Sun` is all weekend, `22:00-06:00` every night. A window
+/// ending before it starts runs past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Window {
+    spec: String,
+    /// Monday first
+    days: [bool; 7],
+    /// Minutes since midnight; the end is exclusive
+    start: u16,
+    end: u16,
+}
+
+impl TryFrom<String> for Window {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, String> {
+        parse_window(&spec)
+    }
+}
+
+impl Window {
+    /// Whether `minute` of `day` (1 = Monday, as `date +%u`) is inside.
+    pub fn contains(&self, day: u8, minute: u16) -> bool {
+        let today = usize::from(day.clamp(1, 7) - 1);
+        let yesterday = (today + 6) % 7;
+        if self.start < self.end {
+            self.days[today] && (self.start..self.end).contains(&minute)
+        } else {
+            (self.days[today] && minute >= self.start)
+                || (self.days[yesterday] && minute < self.end)
+        }
+    }
+}
+
+/// Parse a window such as `Mon-Fri 19:00-07:00`, `Sat,Sun` or
+/// `22:00-06:00`.
+pub fn parse_window(spec: &str) -> Result<Window, String> {
+    let mut days = None;
+    let mut hours = None;
+    for part in spec.split_whitespace() {
+        if part.contains(':') && hours.is_none() {
+            hours = Some(parse_hours(part)?);
+        } else if !part.contains(':') && days.is_none() {
+            days = Some(parse_days(part)?);
+        } else {
+            return Err(format!(
+                "Invalid window '{}' (expected e.g. \"Mon-Fri 19:00-07:00\")",
+                spec
+            ));
+        }
+    }
+    if days.is_none() && hours.is_none() {
+        return Err("Empty window (expected e.g. \"Mon-Fri 19:00-07:00\")".to_string());
+    }
+    let (start, end) = hours.unwrap_or((0, 24 * 60));
+    Ok(Window {
+        spec: spec.split_whitespace().collect::<Vec<_>>().join(" "),
+        days: days.unwrap_or([true; 7]),
+        start,
+        end,
+    })
+}
+
+/// `Mon-Fri`, `Sat,Sun` or `Fri-Mon`, as a set of days. Days are named by
+/// at least their first three letters.
+fn parse_days(s: &str) -> Result<[bool; 7], String> {
+    let day = |name: &str| {
+        let lower = name.to_lowercase();
+        DAYS.iter()
+            .position(|d| lower.len() >= 3 && d.starts_with(&lower))
+            .ok_or(format!("Unknown day '{}' (use Mon, Tue, ... Sun)", name))
+    };
+    let mut days = [false; 7];
+    for item in s.split(',') {
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (day(first)?, day(last)?),
+            None => (day(item)?, day(item)?),
+        };
+        let mut d = first;
+        loop {
+            days[d] = true;
+            if d == last {
+                break;
+            }
+            d = (d + 1) % 7;
+        }
+    }
+    Ok(days)
+}
+
+/// `19:00-07:00` as minutes since midnight.
+fn parse_hours(s: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("Invalid hours '{}' (expected e.g. 19:00-07:00)", s);
+    let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+    let minute = |t: &str| {
+        let (h, m) = t.split_once(':')?;
+        let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+        (m < 60 && (h < 24 || (h == 24 && m == 0))).then_some(h * 60 + m)
+    };
+    let (start, end) = (
+        minute(start).ok_or_else(invalid)?,
+        minute(end).ok_or_else(invalid)?,
+    );
+    if start == end || start == 24 * 60 {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// The local day of the week (1 = Monday) and minute of the day, from
+/// `date`, which knows the time zone.
+fn local_time() -> Option<(u8, u16)> {
+    let output = if cfg!(windows) {
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command", "Get-Date -UFormat '%u %H:%M'"])
+            .output()
+    } else {
+        Command::new("date").arg("+%u %H:%M").output()
+    }
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_local_time(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `3 19:45` as Wednesday, minute 1185.
+fn parse_local_time(s: &str) -> Option<(u8, u16)> {
+    let (day, time) = s.trim().split_once(' ')?;
+    let day: u8 = day.parse().ok().filter(|d| (1..=7).contains(d))?;
+    let (h, m) = time.split_once(':')?;
+    let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+    Some((day, h * 60 + m))
+}
+
+/// Whether a build may be running: one holds build cache records of the
+/// daemon's or of a running `docker-container` builder. A cache that can't
+/// be read, or records that don't say, count as busy; without buildx there
+/// are no builders to check.
+fn build_running() -> bool {
+    let daemon = Docker::build_cache().map_or(true, |records| {
+        records.iter().any(|r| r.in_use != Some(false))
+    });
+    daemon
+        || Docker::buildx_builders()
+            .unwrap_or_default()
+            .iter()
+            .filter(|b| b.driver == "docker-container" && b.is_running())
+            .any(|b| Docker::builder_in_use(&b.name).unwrap_or(true))
+}
+
+/// Whether a laptop is running off its battery; machines without one
+/// never are.
+fn on_battery() -> bool {
+    if cfg!(target_os = "macos") {
+        Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains("'Battery Power'"))
+    } else {
+        discharging(Path::new("/sys/class/power_supply"))
+    }
+}
+
+/// Whether a battery under `power_supply` (Linux's sysfs class) is
+/// discharging.
+fn discharging(power_supply: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(power_supply) else {
+        return false;
+    };
+    let read = |path: &Path, file: &str| fs::read_to_string(path.join(file)).unwrap_or_default();
+    entries.flatten().any(|e| {
+        read(&e.path(), "type").trim() == "Battery"
+            && read(&e.path(), "status").trim() == "Discharging"
+    })
+}
+
+/// The one-minute load average as a percentage of all cores; unknown on
+/// Windows.
+fn load_percent() -> Option<u32> {
+    let cores = thread::available_parallelism().ok()?.get();
+    let loadavg = if cfg!(target_os = "macos") {
+        let output = Command::new("sysctl")
+            .args(["-n", "vm.loadavg"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    } else {
+        fs::read_to_string("/proc/loadavg").ok()?
+    };
+    parse_load(&loadavg, cores)
+}
+
+/// The first figure of `/proc/loadavg` (`0.52 0.58 0.59 1/467 12345`) or
+/// `sysctl vm.loadavg` (`{ 0.52 0.58 0.59 }`) over `cores`.
+fn parse_load(s: &str, cores: usize) -> Option<u32> {
+    let load: f64 = s.split_whitespace().find(|w| *w != "{")?.parse().ok()?;
+    Some((load / cores.max(1) as f64 * 100.0).round() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MON: u8 = 1;
+    const FRI: u8 = 5;
+    const SAT: u8 = 6;
+    const SUN: u8 = 7;
+
+    fn at(hours: u16, minutes: u16) -> u16 {
+        hours * 60 + minutes
+    }
+
+    #[test]
+    fn test_overnight_window_runs_into_the_next_day() {
+        let nights = parse_window("Mon-Fri 19:00-07:00").unwrap();
+        assert!(nights.contains(MON, at(19, 0)));
+        assert!(!nights.contains(MON, at(18, 59)));
+        assert!(!nights.contains(MON, at(7, 0)));
+        // Friday night ends Saturday morning, and nothing starts Sunday
+        assert!(nights.contains(SAT, at(6, 59)));
+        assert!(!nights.contains(SAT, at(19, 0)));
+        assert!(!nights.contains(MON, at(3, 0)));
+        assert!(nights.contains(FRI, at(3, 0)));
+    }
+
+    #[test]
+    fn test_windows_of_days_or_hours_only() {
+        let weekend = parse_window("sat,Sunday").unwrap();
+        assert!(weekend.contains(SAT, 0) && weekend.contains(SUN, at(23, 59)));
+        assert!(!weekend.contains(MON, at(12, 0)));
+
+        let wrapping = parse_window("Fri-Mon").unwrap();
+        assert_eq!(wrapping.days, [true, false, false, false, true, true, true]);
+
+        let lunch = parse_window(" 12:00-13:30 ").unwrap();
+        assert_eq!(lunch.spec, "12:00-13:30");
+        assert!(lunch.contains(3, at(13, 29)) && !lunch.contains(3, at(13, 30)));
+        assert!(parse_window("Mon 00:00-24:00")
+            .unwrap()
+            .contains(MON, at(23, 59)));
+    }
+
+    #[test]
+    fn test_parse_window_rejects_nonsense() {
+        for spec in [
+            "",
+            "Someday",
+            "Mo",
+            "25:00-07:00",
+            "19:00",
+            "10:00-10:00",
+            "Mon 19:00-07:00 Tue",
+        ] {
+            assert!(parse_window(spec).is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn test_parse_local_time_and_load() {
+        assert_eq!(parse_local_time("3 19:45\n"), Some((3, 1185)));
+        assert_eq!(parse_local_time("0 19:45"), None);
+        assert_eq!(parse_load("2.00 1.50 1.00 2/300 4242\n", 4), Some(50));
+        assert_eq!(parse_load("{ 0.50 0.40 0.30 }", 2), Some(25));
+        assert_eq!(parse_load("", 2), None);
+    }
+
+    #[test]
+    fn test_discharging_battery_means_on_battery() {
+        let dir = tempfile::tempdir().unwrap();
+        let supply = |name: &str, kind: &str, status: Option<&str>| {
+            let path = dir.path().join(name);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("type"), format!("{kind}\n")).unwrap();
+            if let Some(status) = status {
+                fs::write(path.join("status"), format!("{status}\n")).unwrap();
+            }
+        };
+        supply("AC", "Mains", None);
+        supply("BAT0", "Battery", Some("Charging"));
+        assert!(!discharging(dir.path()));
+        supply("BAT0", "Battery", Some("Discharging"));
+        assert!(discharging(dir.path()));
+        assert!(!discharging(&dir.path().join("missing")));
+    }
+}
@@ -0,0 +1,291 @@
+//! Space freed by past cleanups. Every cleanup that removed something
+//! appends one line to `cleanup_stats.jsonl` in the state directory, so
+//! `dockerase stats` can add up what the "Space freed" lines of individual
+//! runs never did: totals, the categories that keep coming back and how
+//! much is freed month by month.
+
+use crate::history::now_secs;
+use crate::paths;
+use crate::report::{CleanupReport, ItemStatus};
+use crate::units::format_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+/// Only this many runs are kept on disk.
+const MAX_RUNS: usize = 10_000;
+
+/// Space freed under this category when a run couldn't tell where it
+/// came from.
+const UNATTRIBUTED: &str = "other";
+
+/// One cleanup as recorded in the store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub timestamp: u64,
+    pub command: String,
+    pub space_freed: u64,
+    /// Bytes freed per category; adds up to `space_freed`
+    #[serde(default)]
+    pub freed: BTreeMap<String, u64>,
+}
+
+impl RunRecord {
+    /// The record for `report`, or `None` for dry runs, aborted runs and
+    /// runs that removed nothing.
+    pub fn from_report(report: &CleanupReport, timestamp: u64) -> Option<Self> {
+        let removed = report.items.iter().any(|i| i.status == ItemStatus::Removed);
+        if report.dry_run || report.read_only || report.aborted || !removed {
+            return None;
+        }
+        Some(Self {
+            timestamp,
+            command: report.command.clone(),
+            space_freed: report.space_freed,
+            freed: attribute(report),
+        })
+    }
+}
+
+/// Split a run's `space_freed` over categories: Docker's own measurement
+/// when there is one, else the sizes of the removed items, scaled down when
+/// they add up to more than was freed (caches moved to the trash).
+fn attribute(report: &CleanupReport) -> BTreeMap<String, u64> {
+    let mut freed = report.freed.clone();
+    if freed.is_empty() {
+        for item in &report.items {
+            if let (ItemStatus::Removed, Some(size)) = (item.status, item.size) {
+                *freed.entry(item.category.clone()).or_default() += size;
+            }
+        }
+    }
+
+    let attributed: u64 = freed.values().sum();
+    if attributed > report.space_freed {
+        for size in freed.values_mut() {
+            *size = (*size as u128 * report.space_freed as u128 / attributed as u128) as u64;
+        }
+        freed.retain(|_, size| *size > 0);
+    }
+    let attributed: u64 = freed.values().sum();
+    if attributed < report.space_freed {
+        *freed.entry(UNATTRIBUTED.to_string()).or_default() += report.space_freed - attributed;
+    }
+    freed
+}
+
+/// Append `report` to the store if it freed anything.
+pub fn record(report: &CleanupReport) -> Result<(), String> {
+    let Some(run) = RunRecord::from_report(report, now_secs()) else {
+        return Ok(());
+    };
+    let path = paths::cleanup_stats().ok_or("Could not determine state directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let mut runs = load();
+    if runs.len() >= MAX_RUNS {
+        // Rewrite the file keeping only the most recent entries
+        runs.push(run);
+        let content: String = runs[runs.len() - MAX_RUNS..]
+            .iter()
+            .filter_map(|r| serde_json::to_string(r).ok())
+            .map(|l| l + "\n")
+            .collect();
+        fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    } else {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let line = serde_json::to_string(&run).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Every recorded run, oldest first.
+pub fn load() -> Vec<RunRecord> {
+    paths::cleanup_stats()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|content| parse_runs(&content))
+        .unwrap_or_default()
+}
+
+fn parse_runs(content: &str) -> Vec<RunRecord> {
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+/// Space freed in one category over all runs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryTotal {
+    pub category: String,
+    pub space_freed: u64,
+    /// Runs that freed something in this category
+    pub runs: usize,
+}
+
+/// Runs within one calendar month (UTC).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Period {
+    /// `YYYY-MM`
+    pub month: String,
+    pub runs: usize,
+    pub space_freed: u64,
+}
+
+/// Totals over a set of runs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub runs: usize,
+    pub space_freed: u64,
+    pub first_run: Option<u64>,
+    pub last_run: Option<u64>,
+    /// Largest first
+    pub categories: Vec<CategoryTotal>,
+    /// Oldest first
+    pub timeline: Vec<Period>,
+}
+
+impl StatsReport {
+    pub fn new(runs: &[RunRecord]) -> Self {
+        let mut categories: BTreeMap<&str, CategoryTotal> = BTreeMap::new();
+        let mut timeline: BTreeMap<String, Period> = BTreeMap::new();
+        for run in runs {
+            for (category, &size) in &run.freed {
+                let total = categories.entry(category).or_insert_with(|| CategoryTotal {
+                    category: category.clone(),
+                    space_freed: 0,
+                    runs: 0,
+                });
+                total.space_freed += size;
+                total.runs += 1;
+            }
+            let month = format_timestamp(run.timestamp)[..7].to_string();
+            let period = timeline.entry(month.clone()).or_insert(Period {
+                month,
+                runs: 0,
+                space_freed: 0,
+            });
+            period.runs += 1;
+            period.space_freed += run.space_freed;
+        }
+
+        let mut categories: Vec<CategoryTotal> = categories.into_values().collect();
+        categories.sort_by_key(|c| std::cmp::Reverse(c.space_freed));
+        Self {
+            runs: runs.len(),
+            space_freed: runs.iter().map(|r| r.space_freed).sum(),
+            first_run: runs.iter().map(|r| r.timestamp).min(),
+            last_run: runs.iter().map(|r| r.timestamp).max(),
+            categories,
+            timeline: timeline.into_values().collect(),
+        }
+    }
+
+    /// The category that freed the most, if any freed anything.
+    pub fn biggest_offender(&self) -> Option<&CategoryTotal> {
+        self.categories.first().filter(|c| c.space_freed > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::ReportItem;
+
+    fn run(timestamp: u64, freed: &[(&str, u64)]) -> RunRecord {
+        RunRecord {
+            timestamp,
+            command: "purge".to_string(),
+            space_freed: freed.iter().map(|(_, s)| s).sum(),
+            freed: freed.iter().map(|(c, s)| (c.to_string(), *s)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_only_runs_that_removed_something_are_recorded() {
+        let mut report = CleanupReport::new("system purge", false);
+        report
+            .items
+            .push(ReportItem::planned("cache", "npm", Some(300)));
+        assert_eq!(RunRecord::from_report(&report, 1), None);
+
+        report.mark_all_removed();
+        report
+            .items
+            .push(ReportItem::planned("cache", "Yarn", Some(100)));
+        report.space_freed = 150;
+        let run = RunRecord::from_report(&report, 1).unwrap();
+        // npm was removed for 300 bytes but only 150 were freed
+        assert_eq!(run.freed, BTreeMap::from([("cache".to_string(), 150)]));
+
+        let dry_run = CleanupReport::new("purge", true);
+        assert_eq!(RunRecord::from_report(&dry_run, 1), None);
+    }
+
+    #[test]
+    fn test_unmeasured_space_is_attributed_to_other() {
+        let mut report = CleanupReport::new("purge", false);
+        report
+            .items
+            .push(ReportItem::planned("containers", "stopped", None));
+        report.mark_all_removed();
+        report.space_freed = 40;
+        report.freed = BTreeMap::from([("images".to_string(), 30)]);
+        let run = RunRecord::from_report(&report, 1).unwrap();
+        assert_eq!(
+            run.freed,
+            BTreeMap::from([("images".to_string(), 30), ("other".to_string(), 10)])
+        );
+    }
+
+    #[test]
+    fn test_summary_totals_categories_and_months() {
+        let oct = 1_791_000_000; // 2026-10
+        let nov = oct + 31 * 86_400;
+        let runs = vec![
+            run(oct, &[("build_cache", 500), ("images", 100)]),
+            run(oct + 60, &[("build_cache", 200)]),
+            run(nov, &[("images", 300)]),
+        ];
+        let summary = StatsReport::new(&runs);
+        assert_eq!(summary.runs, 3);
+        assert_eq!(summary.space_freed, 1_100);
+        assert_eq!(summary.first_run, Some(oct));
+
+        let offender = summary.biggest_offender().unwrap();
+        assert_eq!(
+            (offender.category.as_str(), offender.runs),
+            ("build_cache", 2)
+        );
+        assert_eq!(offender.space_freed, 700);
+
+        let months: Vec<(&str, usize, u64)> = summary
+            .timeline
+            .iter()
+            .map(|p| (p.month.as_str(), p.runs, p.space_freed))
+            .collect();
+        assert_eq!(months, vec![("2026-10", 2, 800), ("2026-11", 1, 300)]);
+
+        assert!(StatsReport::new(&[]).biggest_offender().is_none());
+    }
+
+    #[test]
+    fn test_parse_runs_skips_invalid_lines() {
+        let content = "{\"timestamp\":1,\"command\":\"purge\",\"space_freed\":2}\nnope\n";
+        let runs = parse_runs(content);
+        assert_eq!(runs.len(), 1);
+        assert!(runs[0].freed.is_empty());
+    }
+}
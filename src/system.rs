@@ -1,44 +1,509 @@
+use crate::bazel::{self, OutputBase};
+use crate::config::{expand_home, AfterPurge, CacheSettings, Config};
+use crate::display::print_warning;
+use crate::nix;
+use crate::paths::{installed, xdg_dir};
+use crate::plugins::{self, Plugin, PluginItem};
+use crate::policy;
+use crate::read_only;
+use crate::trash::{Trash, TrashEntry};
+use serde::Serialize;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CacheInfo {
     pub name: String,
     pub path: PathBuf,
+    /// `path` with symlinks resolved; sizing and purging operate on this
+    pub real_path: PathBuf,
     pub size: u64,
+    /// Part of `size` not used within `STALE_AFTER`
+    pub stale_size: u64,
+    #[serde(skip)]
     pub exists: bool,
     pub description: String,
+    #[serde(skip)]
+    pub post_purge: PostPurge,
+    /// Only entries unused for at least this long are sized and purged
+    #[serde(skip)]
+    pub min_age: Option<Duration>,
+    /// Move purged entries here instead of deleting them
+    #[serde(skip)]
+    pub trash: Option<Trash>,
+    /// Set for entries dockerase only measures and never deletes; says where
+    /// the data should be managed instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_only: Option<String>,
+    /// Name of another discovered cache whose directory contains this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nested_in: Option<String>,
+    /// The external plugin that listed this cache and purges it; see
+    /// `plugins`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<Plugin>,
+    /// The tool that frees this cache with its own command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<Tool>,
+}
+
+/// A cache freed by the tool that owns it rather than deleted file by file,
+/// because deleting it behind the tool's back would break it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Tool {
+    /// `nix store gc`; with an age, profile generations older than that
+    /// are deleted first
+    Nix {
+        #[serde(skip)]
+        older_than: Option<Duration>,
+    },
+    /// `bazel clean --expunge` for the output base of `workspace`
+    Bazel {
+        #[serde(skip)]
+        program: &'static str,
+        workspace: PathBuf,
+    },
+}
+
+impl Tool {
+    /// The command that frees the cache, as shown to the user.
+    pub fn command(&self) -> String {
+        match self {
+            Tool::Nix { older_than: None } => "nix store gc".to_string(),
+            Tool::Nix {
+                older_than: Some(_),
+            } => "nix-collect-garbage --delete-older-than".to_string(),
+            Tool::Bazel { program, .. } => format!("{} clean --expunge", program),
+        }
+    }
+
+    /// Whether the tool can keep what was used recently.
+    fn can_trim(&self) -> bool {
+        matches!(self, Tool::Nix { .. })
+    }
+
+    fn older_than(self, age: Duration) -> Self {
+        match self {
+            Tool::Nix { .. } => Tool::Nix {
+                older_than: Some(age),
+            },
+            tool => tool,
+        }
+    }
+
+    /// Have the tool free `cache`; returns the bytes freed, the measured size
+    /// when the tool doesn't say.
+    fn purge(&self, cache: &CacheInfo) -> Result<u64, String> {
+        match self {
+            Tool::Nix { older_than } => {
+                Ok(nix::collect_garbage(*older_than)?.unwrap_or(cache.size))
+            }
+            Tool::Bazel { program, workspace } => {
+                bazel::expunge(
+                    program,
+                    &OutputBase {
+                        path: cache.real_path.clone(),
+                        workspace: workspace.clone(),
+                    },
+                )?;
+                Ok(cache.size)
+            }
+        }
+    }
+}
+
+/// What `purge_cache` does with the cache directory once it is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostPurge {
+    pub action: AfterPurge,
+    /// Permissions for a recreated directory; defaults to the original's
+    pub mode: Option<u32>,
+    /// Ownership for a recreated directory; defaults to the original's
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl Default for PostPurge {
+    fn default() -> Self {
+        Self {
+            action: AfterPurge::Recreate,
+            mode: None,
+            uid: None,
+            gid: None,
+        }
+    }
 }
 
 impl CacheInfo {
     fn new(name: &str, path: PathBuf, description: &str) -> Self {
+        Self::build(name, path, description, None)
+    }
+
+    /// A cache that is trimmed rather than emptied: only entries not
+    /// modified or accessed within `min_age` count towards its size and get
+    /// removed.
+    fn aged(name: &str, path: PathBuf, description: &str, min_age: Duration) -> Self {
+        Self::build(name, path, description, Some(min_age))
+    }
+
+    fn build(name: &str, path: PathBuf, description: &str, min_age: Option<Duration>) -> Self {
         let exists = path.exists();
-        let size = if exists {
-            dir_size(&path).unwrap_or(0)
-        } else {
-            0
+        let real_path = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        let (size, stale_size) = match (exists, min_age) {
+            (false, _) => (0, 0),
+            (true, None) => sizes(&real_path, cutoff(STALE_AFTER)),
+            (true, Some(age)) => {
+                // Only old entries count, so all of it is stale
+                let size = aged_size(&real_path, cutoff(age));
+                (size, size)
+            }
         };
 
         Self {
             name: name.to_string(),
             path,
+            real_path,
             size,
+            stale_size,
             exists,
             description: description.to_string(),
+            post_purge: PostPurge::default(),
+            min_age,
+            trash: None,
+            report_only: None,
+            nested_in: None,
+            plugin: None,
+            tool: None,
+        }
+    }
+
+    /// An item listed by a cache plugin, which measured it and purges it.
+    fn from_plugin(plugin: Plugin, item: PluginItem) -> Self {
+        let real_path = fs::canonicalize(&item.path).unwrap_or_else(|_| item.path.clone());
+        Self {
+            name: item.name,
+            path: item.path,
+            real_path,
+            size: item.size,
+            stale_size: 0,
+            exists: true,
+            description: item
+                .description
+                .unwrap_or_else(|| format!("From the {} plugin", plugin.name)),
+            post_purge: PostPurge::default(),
+            min_age: None,
+            trash: None,
+            report_only: None,
+            nested_in: None,
+            plugin: Some(plugin),
+            tool: None,
+        }
+    }
+
+    /// A cache measured by the caller as `size`, which only its owning tool
+    /// frees; see `with_tool`.
+    fn measured(name: &str, path: PathBuf, description: &str, size: u64) -> Self {
+        let real_path = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        Self {
+            name: name.to_string(),
+            path,
+            real_path,
+            size,
+            stale_size: 0,
+            exists: true,
+            description: description.to_string(),
+            post_purge: PostPurge::default(),
+            min_age: None,
+            trash: None,
+            report_only: None,
+            nested_in: None,
+            plugin: None,
+            tool: None,
+        }
+    }
+
+    fn with_tool(mut self, tool: Tool) -> Self {
+        self.tool = Some(tool);
+        self
+    }
+
+    /// The same cache, trimmed rather than emptied: only entries unused for
+    /// `age`, or for the cache's own minimum age if that is longer, are
+    /// sized and purged.
+    pub fn older_than(mut self, age: Duration) -> Self {
+        if let Some(tool) = self.tool.take() {
+            self.tool = Some(tool.older_than(age));
+            return self;
+        }
+        let min_age = self.min_age.map_or(age, |own| own.max(age));
+        Self {
+            post_purge: self.post_purge,
+            trash: self.trash,
+            report_only: self.report_only,
+            nested_in: self.nested_in,
+            ..Self::build(&self.name, self.path, &self.description, Some(min_age))
         }
     }
+
+    /// Measure this entry but never purge it.
+    fn report_only(mut self, note: &str) -> Self {
+        self.report_only = Some(note.to_string());
+        self
+    }
+
+    pub fn is_purgeable(&self) -> bool {
+        self.report_only.is_none()
+    }
+
+    /// Whether a plugin or the owning tool frees this cache, in place and
+    /// without going through the trash.
+    pub fn is_freed_in_place(&self) -> bool {
+        self.plugin.is_some() || self.tool.is_some()
+    }
+
+    /// Whether `older_than` can keep the recently used part of this cache.
+    pub fn can_trim(&self) -> bool {
+        self.plugin.is_none() && self.tool.as_ref().is_none_or(Tool::can_trim)
+    }
+
+    fn after_purge(mut self, action: AfterPurge) -> Self {
+        self.post_purge.action = action;
+        self
+    }
+
+    fn apply_settings(&mut self, settings: &CacheSettings) {
+        if let Some(action) = settings.after_purge {
+            self.post_purge.action = action;
+        }
+        self.post_purge.mode = settings.mode.or(self.post_purge.mode);
+        self.post_purge.uid = settings.uid.or(self.post_purge.uid);
+        self.post_purge.gid = settings.gid.or(self.post_purge.gid);
+    }
 }
 
 pub fn get_home_dir() -> Option<PathBuf> {
     dirs::home_dir()
 }
 
-pub fn discover_caches() -> Vec<CacheInfo> {
+/// Every cache, largest first. This includes the Nix store's garbage and
+/// Bazel's output bases, which only their tools free and which take a
+/// while to size; see [`discover_quick_caches`].
+pub fn discover_caches(config: &Config) -> Vec<CacheInfo> {
+    discover(config, true)
+}
+
+/// The caches [`discover_caches`] finds, less those owned by Nix and Bazel,
+/// for summaries such as the health score that shouldn't wait on a garbage
+/// collector's scan or on walking every output base.
+pub fn discover_quick_caches(config: &Config) -> Vec<CacheInfo> {
+    discover(config, false)
+}
+
+fn discover(config: &Config, tools: bool) -> Vec<CacheInfo> {
     let home = match get_home_dir() {
         Some(h) => h,
         None => return vec![],
     };
 
+    let platform = Platform::current();
+    let mut caches = builtin_caches(platform, &home);
+
+    if config.include_advanced && platform == Platform::MacOs {
+        caches.extend(advanced_caches(&home));
+    }
+
+    for custom in &config.custom_caches {
+        caches.push(CacheInfo::new(
+            &custom.name,
+            expand_home(&custom.path, &home),
+            custom
+                .description
+                .as_deref()
+                .unwrap_or("User-defined cache"),
+        ));
+    }
+
+    if tools && platform != Platform::Windows {
+        caches.extend(nix_store());
+        let cache = xdg_dir("XDG_CACHE_HOME", &home, ".cache");
+        caches.extend(bazel_caches(&bazel::output_root(&cache), &home));
+    }
+
+    caches.extend(
+        plugins::items()
+            .into_iter()
+            .map(|(plugin, item)| CacheInfo::from_plugin(plugin, item)),
+    );
+
+    for cache in &mut caches {
+        if let Some(settings) = config.cache_settings(&cache.name) {
+            cache.apply_settings(settings);
+        }
+    }
+
+    // Filter to only existing caches with size > 0
+    caches.retain(|c| c.exists && c.size > 0);
+
+    // The same directory can be reachable under several names (symlinked
+    // home, case-insensitive APFS, relocated folders); keep the first
+    dedupe_same_directory(&mut caches);
+
+    // User-defined caches may contain built-in ones (or each other)
+    let nested: Vec<Option<String>> = {
+        let refs: Vec<&CacheInfo> = caches.iter().collect();
+        refs.iter()
+            .map(|c| containing_cache(c, &refs).map(|p| p.name.clone()))
+            .collect()
+    };
+    for (cache, parent) in caches.iter_mut().zip(nested) {
+        cache.nested_in = parent;
+    }
+
+    // Sort by size descending
+    caches.sort_by_key(|c| std::cmp::Reverse(c.size));
+
+    caches
+}
+
+/// The Nix store's garbage, when Nix is installed: what the garbage
+/// collector would delete now.
+fn nix_store() -> Option<CacheInfo> {
+    let store = nix::store_dir();
+    if !store.is_dir() || !installed("nix-store") {
+        return None;
+    }
+    let dead = match nix::dead_paths(&store) {
+        Ok(dead) => dead,
+        Err(e) => {
+            print_warning(&format!("Skipping the Nix store: {}", e));
+            return None;
+        }
+    };
+    let cache = CacheInfo::measured(
+        "Nix Store",
+        store,
+        "Nix store paths no profile or GC root refers to",
+        dead.iter().map(|path| disk_size(path)).sum(),
+    );
+    Some(cache.with_tool(Tool::Nix { older_than: None }))
+}
+
+/// Bazel's output bases under `root`, one per workspace, and the repository
+/// cache when it is inside `home`. An output base is only freed by Bazel,
+/// from its workspace; one whose workspace is gone is reported instead.
+fn bazel_caches(root: &Path, home: &Path) -> Vec<CacheInfo> {
+    let program = bazel::program();
+    let mut caches = Vec::new();
+    for user_root in bazel::user_roots(root) {
+        for base in bazel::output_bases(&user_root) {
+            let workspace = match base.workspace.strip_prefix(home) {
+                Ok(relative) => Path::new("~").join(relative),
+                Err(_) => base.workspace.clone(),
+            };
+            let name = format!("Bazel: {}", workspace.display());
+            let description = "Bazel build outputs and external repositories";
+            let size = disk_size(&base.path);
+            let cache = CacheInfo::measured(&name, base.path, description, size);
+            caches.push(match program {
+                Some(program) if base.workspace.is_dir() => cache.with_tool(Tool::Bazel {
+                    program,
+                    workspace: base.workspace,
+                }),
+                Some(_) => {
+                    let note = format!(
+                        "its workspace is gone; remove it with `chmod -R u+w {0} && rm -rf {0}`",
+                        cache.path.display()
+                    );
+                    cache.report_only(&note)
+                }
+                None => cache.report_only("install bazel to free it with `bazel clean --expunge`"),
+            });
+        }
+        let repos = user_root.join("cache/repos");
+        if repos.starts_with(home) {
+            caches.push(CacheInfo::new(
+                "Bazel Repository Cache",
+                repos,
+                "Archives Bazel downloads for external repositories",
+            ));
+        }
+    }
+    caches
+}
+
+/// Which catalog of cache locations applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Platform {
+    MacOs,
+    Linux,
+    Windows,
+}
+
+impl Platform {
+    /// The platform dockerase was built for; other Unixes use the Linux
+    /// (XDG) layout.
+    fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else if cfg!(windows) {
+            Platform::Windows
+        } else {
+            Platform::Linux
+        }
+    }
+}
+
+/// The built-in caches for `platform`, whether or not they exist.
+fn builtin_caches(platform: Platform, home: &Path) -> Vec<CacheInfo> {
+    match platform {
+        Platform::MacOs => macos_caches(home),
+        Platform::Linux => linux_caches(home, &xdg_dir("XDG_CACHE_HOME", home, ".cache")),
+        Platform::Windows => windows_caches(home, &local_app_data(home)),
+    }
+}
+
+/// `%LOCALAPPDATA%`, else its default location under the profile.
+fn local_app_data(home: &Path) -> PathBuf {
+    std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home.join("AppData").join("Local"))
+}
+
+/// Caches in the same place on every platform.
+fn shared_caches(home: &Path) -> Vec<CacheInfo> {
+    vec![
+        CacheInfo::new(
+            "Cargo Registry",
+            home.join(".cargo/registry"),
+            "Rust crates registry cache",
+        ),
+        CacheInfo::new(
+            "Cargo Git",
+            home.join(".cargo/git"),
+            "Rust git dependencies cache",
+        ),
+        CacheInfo::new("Gradle", home.join(".gradle/caches"), "Gradle build cache"),
+        CacheInfo::new(
+            "Maven",
+            home.join(".m2/repository"),
+            "Maven local repository",
+        ),
+        CacheInfo::new(
+            "Go Modules",
+            home.join("go/pkg/mod/cache"),
+            "Go module cache",
+        ),
+    ]
+}
+
+fn macos_caches(home: &Path) -> Vec<CacheInfo> {
     let mut caches = vec![
         // Homebrew
         CacheInfo::new(
@@ -64,18 +529,6 @@ pub fn discover_caches() -> Vec<CacheInfo> {
             home.join("Library/pnpm/store"),
             "pnpm package store",
         ),
-        // Cargo registry
-        CacheInfo::new(
-            "Cargo Registry",
-            home.join(".cargo/registry"),
-            "Rust crates registry cache",
-        ),
-        // Cargo git
-        CacheInfo::new(
-            "Cargo Git",
-            home.join(".cargo/git"),
-            "Rust git dependencies cache",
-        ),
         // pip
         CacheInfo::new(
             "pip",
@@ -100,19 +553,11 @@ pub fn discover_caches() -> Vec<CacheInfo> {
             home.join("Library/Caches/CocoaPods"),
             "CocoaPods spec and pod cache",
         ),
-        // Gradle
-        CacheInfo::new("Gradle", home.join(".gradle/caches"), "Gradle build cache"),
-        // Maven
+        // Go build cache
         CacheInfo::new(
-            "Maven",
-            home.join(".m2/repository"),
-            "Maven local repository",
-        ),
-        // Go modules
-        CacheInfo::new(
-            "Go Modules",
-            home.join("go/pkg/mod/cache"),
-            "Go module cache",
+            "Go Build",
+            home.join("Library/Caches/go-build"),
+            "Go compiler build cache",
         ),
         // Composer (PHP)
         CacheInfo::new(
@@ -120,56 +565,755 @@ pub fn discover_caches() -> Vec<CacheInfo> {
             home.join(".composer/cache"),
             "PHP Composer cache",
         ),
-        // Trash
-        CacheInfo::new("Trash", home.join(".Trash"), "Files in Trash"),
+        // Trash - macOS protects the .Trash directory from being removed
+        CacheInfo::new("Trash", home.join(".Trash"), "Files in Trash")
+            .after_purge(AfterPurge::KeepDir),
+        // Mail attachments - the app keeps its own index of these
+        CacheInfo::new(
+            "Mail Downloads",
+            home.join("Library/Containers/com.apple.mail/Data/Library/Mail Downloads"),
+            "Attachments opened from Mail",
+        )
+        .report_only("Manage in Mail"),
+        // Messages attachments - deleting them breaks conversation history
+        CacheInfo::new(
+            "Messages Attachments",
+            home.join("Library/Messages/Attachments"),
+            "Photos and files received in Messages",
+        )
+        .report_only("Manage in Messages (Settings > General > Keep Messages)"),
+    ];
+    caches.extend(shared_caches(home));
+    caches
+}
+
+/// XDG locations; `cache` is `$XDG_CACHE_HOME` or `~/.cache`.
+fn linux_caches(home: &Path, cache: &Path) -> Vec<CacheInfo> {
+    let mut caches = vec![
+        CacheInfo::new(
+            "Homebrew",
+            cache.join("Homebrew"),
+            "Homebrew package downloads and cache",
+        ),
+        CacheInfo::new(
+            "npm",
+            home.join(".npm/_cacache"),
+            "Node.js npm package cache",
+        ),
+        CacheInfo::new("Yarn", cache.join("yarn"), "Yarn package cache"),
+        CacheInfo::new(
+            "pnpm",
+            xdg_dir("XDG_DATA_HOME", home, ".local/share").join("pnpm/store"),
+            "pnpm package store",
+        ),
+        CacheInfo::new("pip", cache.join("pip"), "Python pip package cache"),
+        CacheInfo::new(
+            "Go Build",
+            cache.join("go-build"),
+            "Go compiler build cache",
+        ),
+        CacheInfo::new("Composer", cache.join("composer"), "PHP Composer cache"),
+        // Trash keeps `files` and `info` side by side; only empty them
+        CacheInfo::new(
+            "Trash",
+            xdg_dir("XDG_DATA_HOME", home, ".local/share").join("Trash"),
+            "Files in Trash",
+        )
+        .after_purge(AfterPurge::KeepDir),
+        // System-wide and root-owned; apt manages it
+        CacheInfo::new(
+            "APT",
+            PathBuf::from("/var/cache/apt/archives"),
+            "Downloaded Debian packages",
+        )
+        .report_only("Run `sudo apt-get clean`"),
+    ];
+    caches.extend(shared_caches(home));
+    caches
+}
+
+/// `local` is `%LOCALAPPDATA%`.
+fn windows_caches(home: &Path, local: &Path) -> Vec<CacheInfo> {
+    let mut caches = vec![
+        CacheInfo::new(
+            "npm",
+            local.join("npm-cache/_cacache"),
+            "Node.js npm package cache",
+        ),
+        CacheInfo::new("Yarn", local.join("Yarn/Cache"), "Yarn package cache"),
+        CacheInfo::new("pnpm", local.join("pnpm/store"), "pnpm package store"),
+        CacheInfo::new("pip", local.join("pip/Cache"), "Python pip package cache"),
+        CacheInfo::new(
+            "Go Build",
+            local.join("go-build"),
+            "Go compiler build cache",
+        ),
+        CacheInfo::new("Composer", local.join("Composer"), "PHP Composer cache"),
+        CacheInfo::new("NuGet", home.join(".nuget/packages"), "NuGet package cache"),
+    ];
+    caches.extend(shared_caches(home));
+    caches
+}
+
+/// Files used more recently than this are never trimmed from the macOS
+/// per-user temp and cache directories; running apps keep live state there.
+const DARWIN_MIN_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Logs and crash reports younger than this are kept for troubleshooting.
+const LOG_MIN_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Opt-in caches outside the usual package-manager locations, enabled with
+/// `include_advanced = true` in the config.
+fn advanced_caches(home: &Path) -> Vec<CacheInfo> {
+    let mut caches = vec![
+        // Application logs
+        CacheInfo::aged(
+            "Logs",
+            home.join("Library/Logs"),
+            "Application logs (older than 30 days)",
+            LOG_MIN_AGE,
+        )
+        .after_purge(AfterPurge::KeepDir),
+        // Crash and hang reports, also covered by Logs
+        CacheInfo::aged(
+            "Diagnostic Reports",
+            home.join("Library/Logs/DiagnosticReports"),
+            "Crash and diagnostic reports (older than 30 days)",
+            LOG_MIN_AGE,
+        )
+        .after_purge(AfterPurge::KeepDir),
+        // Legacy crash reporter state
+        CacheInfo::aged(
+            "CrashReporter",
+            home.join("Library/Application Support/CrashReporter"),
+            "Crash reporter data (older than 30 days)",
+            LOG_MIN_AGE,
+        )
+        .after_purge(AfterPurge::KeepDir),
     ];
+    if let Some(dir) = darwin_user_dir("DARWIN_USER_CACHE_DIR") {
+        caches.push(
+            CacheInfo::aged(
+                "Darwin User Cache",
+                dir,
+                "Per-user caches in /private/var/folders (unused for 7+ days)",
+                DARWIN_MIN_AGE,
+            )
+            .after_purge(AfterPurge::KeepDir),
+        );
+    }
+    if let Some(dir) = darwin_user_dir("DARWIN_USER_TEMP_DIR") {
+        caches.push(
+            CacheInfo::aged(
+                "Darwin User Temp",
+                dir,
+                "Per-user temporary files in /private/var/folders (unused for 7+ days)",
+                DARWIN_MIN_AGE,
+            )
+            .after_purge(AfterPurge::KeepDir),
+        );
+    }
+    caches
+}
+
+/// A per-user directory as reported by `getconf`, e.g. `DARWIN_USER_TEMP_DIR`
+/// is `/var/folders/xx/<id>/T/`. Only exists on macOS.
+fn darwin_user_dir(name: &str) -> Option<PathBuf> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+    let output = Command::new("getconf").arg(name).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!dir.is_empty()).then(|| PathBuf::from(dir))
+}
+
+/// The `/var/folders/xx/<id>` directory holding the per-user `C` and `T`
+/// directories; purges beneath it are allowed when advanced caches are on.
+fn darwin_user_root() -> Option<PathBuf> {
+    darwin_user_dir("DARWIN_USER_TEMP_DIR")?
+        .parent()
+        .map(Path::to_path_buf)
+}
+
+/// Identity of a directory on disk, independent of the path used to reach it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+fn file_id(cache: &CacheInfo) -> FileId {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(meta) = fs::metadata(&cache.real_path) {
+            return FileId::Inode(meta.dev(), meta.ino());
+        }
+    }
+    FileId::Path(PathBuf::from(
+        cache.real_path.to_string_lossy().to_lowercase(),
+    ))
+}
+
+fn dedupe_same_directory(caches: &mut Vec<CacheInfo>) {
+    let mut seen = std::collections::HashSet::new();
+    caches.retain(|c| seen.insert(file_id(c)));
+}
+
+/// The cache in `caches` whose directory strictly contains `cache`, if any.
+pub fn containing_cache<'a>(cache: &CacheInfo, caches: &[&'a CacheInfo]) -> Option<&'a CacheInfo> {
+    caches
+        .iter()
+        .filter(|other| other.real_path != cache.real_path)
+        .filter(|other| cache.real_path.starts_with(&other.real_path))
+        // Report the outermost container
+        .min_by_key(|other| other.real_path.components().count())
+        .copied()
+}
+
+/// Drop caches that are nested inside another cache of the same set, so
+/// nothing is purged twice.
+pub fn outermost<'a>(caches: &[&'a CacheInfo]) -> Vec<&'a CacheInfo> {
+    caches
+        .iter()
+        .filter(|c| containing_cache(c, caches).is_none())
+        .copied()
+        .collect()
+}
+
+/// Combined size of the purgeable caches in a set, without double-counting
+/// nested ones.
+pub fn total_size(caches: &[&CacheInfo]) -> u64 {
+    let purgeable: Vec<&CacheInfo> = caches
+        .iter()
+        .copied()
+        .filter(|c| c.is_purgeable())
+        .collect();
+    outermost(&purgeable).iter().map(|c| c.size).sum()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiskSpace {
+    pub total: u64,
+    pub available: u64,
+}
+
+/// Query the filesystem containing `path` using POSIX `df`.
+pub fn disk_space(path: &Path) -> Option<DiskSpace> {
+    let output = Command::new("df").arg("-Pk").arg(path).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_df_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_df_output(output: &str) -> Option<DiskSpace> {
+    // Filesystem 1024-blocks Used Available Capacity Mounted on
+    let line = output.lines().nth(1)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    let total: u64 = fields[1].parse().ok()?;
+    let available: u64 = fields[3].parse().ok()?;
+    Some(DiskSpace {
+        total: total * 1024,
+        available: available * 1024,
+    })
+}
+
+impl DiskSpace {
+    pub fn used_percent(&self) -> u8 {
+        if self.total == 0 {
+            return 0;
+        }
+        let used = self.total.saturating_sub(self.available);
+        ((used as f64 / self.total as f64) * 100.0).round() as u8
+    }
+}
+
+/// An entry left in place because it couldn't be removed, typically a file
+/// locked by a running app or one we lack permission to delete.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Paths with fewer components than this (e.g. `/`, `/Users`) are never purged.
+const MIN_PURGE_DEPTH: usize = 3;
+
+/// Safety check applied before any cache is deleted: the target must live
+/// strictly inside the home directory or an explicitly allowed root, even
+/// after resolving symlinks. Protects against malformed config entries.
+pub struct PurgeGuard {
+    roots: Vec<PathBuf>,
+}
+
+impl PurgeGuard {
+    pub fn new(home: PathBuf, allowed: Vec<PathBuf>) -> Self {
+        let mut roots = vec![home];
+        roots.extend(allowed);
+        // Also match the resolved form, so a symlinked home still counts
+        let resolved: Vec<PathBuf> = roots
+            .iter()
+            .filter_map(|r| fs::canonicalize(r).ok())
+            .filter(|r| !roots.contains(r))
+            .collect();
+        roots.extend(resolved);
+        Self { roots }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let home = get_home_dir().ok_or("Could not determine home directory")?;
+        let mut allowed: Vec<PathBuf> = config
+            .allowed_paths
+            .iter()
+            .map(|p| expand_home(p, &home))
+            .collect();
+        if config.include_advanced {
+            allowed.extend(darwin_user_root());
+        }
+        Ok(Self::new(home, allowed))
+    }
+
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        let refuse =
+            |reason: &str| Err(format!("Refusing to purge {}: {}", path.display(), reason));
+
+        check_allowed(&format!("purge {}", path.display()))?;
+
+        if !path.is_absolute() {
+            return refuse("not an absolute path");
+        }
+        if path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+        {
+            return refuse("path contains '..'");
+        }
+        if path.components().count() < MIN_PURGE_DEPTH {
+            return refuse("path is too short to be a cache");
+        }
+        if self.roots.iter().any(|root| root.starts_with(path)) {
+            return refuse("path is the home directory or one of its parents");
+        }
+
+        // Resolve symlinks on both sides so a link pointing elsewhere is caught
+        let real = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.roots.iter().any(|root| root.starts_with(&real)) {
+            return refuse("path resolves to the home directory or one of its parents");
+        }
+        let inside = self
+            .roots
+            .iter()
+            .any(|root| real.starts_with(root) && &real != root);
+        if !inside {
+            return refuse("path is outside the home directory and allowed_paths");
+        }
+
+        Ok(())
+    }
+}
+
+/// Read-only mode and `[policy]` stand in front of every purge, whoever
+/// does the deleting.
+fn check_allowed(action: &str) -> Result<(), String> {
+    read_only::check(action)?;
+    policy::check(policy::Category::SystemCaches, action)
+}
+
+/// Running totals for an in-progress purge.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeleteProgress {
+    pub files_removed: u64,
+    pub bytes_freed: u64,
+    /// Set when the purge stopped early because cancellation was requested
+    pub cancelled: bool,
+    pub skipped: Vec<SkippedEntry>,
+    /// Entries left in place because they were used within the cache's
+    /// minimum age
+    pub kept: u64,
+}
+
+/// How often progress callbacks fire while deleting.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Deleter<'a> {
+    cancel: &'a AtomicBool,
+    /// Entries used after this instant are kept
+    keep_newer_than: Option<SystemTime>,
+    /// The cache root and the trash entry files are moved into, rather
+    /// than deleted
+    trash: Option<(&'a Path, TrashEntry)>,
+    progress: DeleteProgress,
+    last_report: Instant,
+    on_progress: &'a mut dyn FnMut(&DeleteProgress),
+}
+
+impl Deleter<'_> {
+    /// Delete everything inside `dir` file by file, depth-first. Directories
+    /// are only removed once empty, so cancelling leaves a consistent tree
+    /// with no half-deleted entries. Entries that can't be removed are
+    /// recorded as skipped and the walk continues with the rest.
+    fn remove_contents(&mut self, dir: &Path) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return self.skip(dir, &e),
+        };
+
+        for entry in entries {
+            if self.cancel.load(Ordering::SeqCst) {
+                self.progress.cancelled = true;
+                return;
+            }
+
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    self.skip(dir, &e);
+                    continue;
+                }
+            };
+            // symlink_metadata so symlinks are removed, never followed
+            let meta = match fs::symlink_metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    self.skip(&path, &e);
+                    continue;
+                }
+            };
+
+            if meta.is_dir() {
+                let before = (self.progress.skipped.len(), self.progress.kept);
+                self.remove_contents(&path);
+                if self.progress.cancelled {
+                    return;
+                }
+                // A directory holding skipped or kept entries can't be empty.
+                // `meta` predates the walk, so emptying it doesn't make it recent.
+                if (self.progress.skipped.len(), self.progress.kept) == before {
+                    if self.is_recent(&meta) {
+                        self.progress.kept += 1;
+                    } else if let Err(e) = fs::remove_dir(&path) {
+                        self.skip(&path, &e);
+                    }
+                }
+            } else if self.is_recent(&meta) {
+                self.progress.kept += 1;
+            } else {
+                self.remove_file(&path, meta.len());
+            }
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path, len: u64) {
+        let removed = match &self.trash {
+            Some((root, entry)) => {
+                // A cache that is a single file keeps its name in the entry
+                let relative = path
+                    .strip_prefix(root)
+                    .ok()
+                    .filter(|r| !r.as_os_str().is_empty())
+                    .or(path.file_name().map(Path::new))
+                    .unwrap_or(path);
+                entry.take(path, relative)
+            }
+            None => fs::remove_file(path),
+        };
+        if let Err(e) = removed {
+            return self.skip(path, &e);
+        }
+        self.progress.files_removed += 1;
+        self.progress.bytes_freed += len;
+
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            (self.on_progress)(&self.progress);
+        }
+    }
+
+    fn is_recent(&self, meta: &fs::Metadata) -> bool {
+        match self.keep_newer_than {
+            Some(cutoff) => last_used(meta).is_none_or(|t| t > cutoff),
+            None => false,
+        }
+    }
+
+    fn skip(&mut self, path: &Path, error: &std::io::Error) {
+        self.progress.skipped.push(SkippedEntry {
+            path: path.to_path_buf(),
+            reason: error.to_string(),
+        });
+    }
+}
+
+/// Purge a cache, reporting progress as files are deleted. Stops between
+/// files once `cancel` is set and returns what was freed so far. Files that
+/// are locked or not deletable are skipped and listed in the result; only a
+/// cache root that can't be read at all is an error. A cache with a `trash`
+/// has its files moved there instead, in one entry per purge.
+pub fn purge_cache(
+    cache: &CacheInfo,
+    guard: &PurgeGuard,
+    cancel: &AtomicBool,
+    on_progress: &mut dyn FnMut(&DeleteProgress),
+) -> Result<DeleteProgress, String> {
+    if !cache.exists {
+        return Ok(DeleteProgress::default());
+    }
+    if let Some(note) = &cache.report_only {
+        return Err(format!("{} is report-only: {}", cache.name, note));
+    }
+    if let Some(plugin) = &cache.plugin {
+        check_allowed(&format!(
+            "purge {} with the {} plugin",
+            cache.name, plugin.name
+        ))?;
+        return Ok(DeleteProgress {
+            bytes_freed: plugin.purge(&cache.name, cache.size)?,
+            ..Default::default()
+        });
+    }
+    if let Some(tool) = &cache.tool {
+        check_allowed(&format!("purge {} with `{}`", cache.name, tool.command()))?;
+        return Ok(DeleteProgress {
+            bytes_freed: tool.purge(cache)?,
+            ..Default::default()
+        });
+    }
+
+    let path = &cache.real_path;
+    guard.check(path)?;
+
+    let meta = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return Ok(DeleteProgress::default()),
+    };
+
+    let trash = match &cache.trash {
+        Some(trash) => Some((path.as_path(), trash.entry_for(path)?)),
+        None => None,
+    };
+    let mut deleter = Deleter {
+        cancel,
+        keep_newer_than: cache.min_age.map(cutoff),
+        trash,
+        progress: DeleteProgress::default(),
+        last_report: Instant::now(),
+        on_progress,
+    };
+
+    if meta.is_dir() {
+        fs::read_dir(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        deleter.remove_contents(path);
+
+        let emptied = !deleter.progress.cancelled
+            && deleter.progress.skipped.is_empty()
+            && deleter.progress.kept == 0;
+        if emptied {
+            finish_directory(path, &cache.post_purge, &meta)?;
+        }
+    } else if deleter.is_recent(&meta) {
+        deleter.progress.kept += 1;
+    } else {
+        deleter.remove_file(path, meta.len());
+    }
 
-    // Filter to only existing caches with size > 0
-    caches.retain(|c| c.exists && c.size > 0);
+    if let Some((_, entry)) = &deleter.trash {
+        entry.discard_if_empty();
+    }
+    Ok(deleter.progress)
+}
 
-    // Sort by size descending
-    caches.sort_by(|a, b| b.size.cmp(&a.size));
+/// Apply the post-purge policy to an emptied cache directory.
+fn finish_directory(path: &Path, post: &PostPurge, original: &fs::Metadata) -> Result<(), String> {
+    if post.action == AfterPurge::KeepDir {
+        return Ok(());
+    }
 
-    caches
+    fs::remove_dir(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+
+    if post.action == AfterPurge::Recreate {
+        fs::create_dir_all(path)
+            .map_err(|e| format!("Failed to recreate {}: {}", path.display(), e))?;
+        restore_permissions(path, post, original)?;
+    }
+
+    Ok(())
 }
 
-pub fn purge_cache(cache: &CacheInfo) -> Result<u64, String> {
-    if !cache.exists {
-        return Ok(0);
-    }
-
-    let size = cache.size;
-
-    if cache.path.is_dir() {
-        // Special handling for Trash - remove contents but not the directory itself
-        // macOS protects the .Trash directory from being removed
-        if cache.name == "Trash" {
-            for entry in fs::read_dir(&cache.path)
-                .map_err(|e| format!("Failed to read {}: {}", cache.path.display(), e))?
-            {
-                let entry = entry.map_err(|e| format!("Failed to read entry in Trash: {}", e))?;
-                let path = entry.path();
-                if path.is_dir() {
-                    fs::remove_dir_all(&path)
-                        .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+#[cfg(unix)]
+fn restore_permissions(
+    path: &Path,
+    post: &PostPurge,
+    original: &fs::Metadata,
+) -> Result<(), String> {
+    use std::os::unix::fs::{chown, MetadataExt, PermissionsExt};
+
+    let mode = post.mode.unwrap_or(original.mode() & 0o7777);
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Failed to set permissions on {}: {}", path.display(), e))?;
+
+    let uid = post.uid.unwrap_or(original.uid());
+    let gid = post.gid.unwrap_or(original.gid());
+    let meta = fs::metadata(path).map_err(|e| e.to_string())?;
+    if meta.uid() != uid || meta.gid() != gid {
+        chown(path, Some(uid), Some(gid))
+            .map_err(|e| format!("Failed to set owner on {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restore_permissions(
+    _path: &Path,
+    _post: &PostPurge,
+    _original: &fs::Metadata,
+) -> Result<(), String> {
+    Ok(())
+}
+
+/// Purge caches on up to `jobs` worker threads. Each cache is purged
+/// independently: one failing doesn't stop the others, and caches not yet
+/// started once `cancel` is set are reported as cancelled. `on_progress` and
+/// `on_done` are called from the workers; results are returned in input order.
+pub fn purge_caches_parallel<P, F>(
+    caches: &[&CacheInfo],
+    jobs: usize,
+    guard: &PurgeGuard,
+    cancel: &AtomicBool,
+    on_progress: P,
+    on_done: F,
+) -> Vec<Result<DeleteProgress, String>>
+where
+    P: Fn(&CacheInfo, &DeleteProgress) + Sync,
+    F: Fn(&CacheInfo, &Result<DeleteProgress, String>) + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<DeleteProgress, String>>>> =
+        Mutex::new((0..caches.len()).map(|_| None).collect());
+    let workers = jobs.clamp(1, caches.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+                let Some(cache) = caches.get(idx) else {
+                    break;
+                };
+                let result = if cancel.load(Ordering::SeqCst) {
+                    Err("Cancelled before starting".to_string())
                 } else {
-                    fs::remove_file(&path)
-                        .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+                    purge_cache(cache, guard, cancel, &mut |p| on_progress(cache, p))
+                };
+                on_done(cache, &result);
+                if let Ok(mut results) = results.lock() {
+                    results[idx] = Some(result);
                 }
-            }
-        } else {
-            fs::remove_dir_all(&cache.path)
-                .map_err(|e| format!("Failed to remove {}: {}", cache.path.display(), e))?;
-
-            // Recreate empty directory (some tools expect it to exist)
-            fs::create_dir_all(&cache.path).ok();
+            });
         }
-    } else if cache.path.is_file() {
-        fs::remove_file(&cache.path)
-            .map_err(|e| format!("Failed to remove {}: {}", cache.path.display(), e))?;
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| Err("Purge did not complete".to_string())))
+        .collect()
+}
+
+/// A top-level entry inside a cache with its total size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// The `n` largest direct children of a cache directory, largest first.
+pub fn largest_entries(path: &Path, n: usize) -> Vec<CacheEntry> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut sized: Vec<CacheEntry> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| {
+            let path = e.path();
+            let size = dir_size(&path).unwrap_or(0);
+            CacheEntry { path, size }
+        })
+        .collect();
+
+    sized.sort_by_key(|e| std::cmp::Reverse(e.size));
+    sized.truncate(n);
+    sized
+}
+
+/// Cache entries unused for this long count as stale.
+pub const STALE_AFTER: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Total size of the files under `path`, and how much of it was not used
+/// since `cutoff`.
+fn sizes(path: &Path, cutoff: SystemTime) -> (u64, u64) {
+    let Ok(meta) = fs::metadata(path) else {
+        return (0, 0);
+    };
+    if meta.is_file() {
+        let stale = last_used(&meta).is_some_and(|t| t <= cutoff);
+        return (meta.len(), if stale { meta.len() } else { 0 });
+    }
+    if !meta.is_dir() {
+        return (0, 0);
     }
+    match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| sizes(&e.path(), cutoff))
+            .fold((0, 0), |(size, stale), (s, st)| (size + s, stale + st)),
+        Err(_) => (0, 0),
+    }
+}
 
-    Ok(size)
+/// The instant before which an entry counts as unused for `min_age`.
+fn cutoff(min_age: Duration) -> SystemTime {
+    SystemTime::now().checked_sub(min_age).unwrap_or(UNIX_EPOCH)
+}
+
+/// When an entry was last modified or accessed, whichever is later.
+fn last_used(meta: &fs::Metadata) -> Option<SystemTime> {
+    let modified = meta.modified().ok()?;
+    Some(meta.accessed().map_or(modified, |a| a.max(modified)))
+}
+
+/// Total size of the files under `path` not used since `cutoff`. Symlinks
+/// are not followed.
+fn aged_size(path: &Path, cutoff: SystemTime) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        let old = last_used(&meta).is_some_and(|t| t <= cutoff);
+        return if old { meta.len() } else { 0 };
+    }
+    match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| aged_size(&e.path(), cutoff))
+            .sum(),
+        Err(_) => 0,
+    }
+}
+
+/// Total size of the files under `path`. Symlinks are not followed, so
+/// links into other store paths or back into a workspace aren't counted.
+fn disk_size(path: &Path) -> u64 {
+    aged_size(path, SystemTime::now())
 }
 
 fn dir_size(path: &PathBuf) -> Result<u64, std::io::Error> {
@@ -292,14 +1436,28 @@ mod tests {
         let cache = CacheInfo {
             name: "Test".to_string(),
             path: PathBuf::from("/nonexistent"),
+            real_path: PathBuf::from("/nonexistent"),
             size: 0,
+            stale_size: 0,
             exists: false,
             description: "Test".to_string(),
+            post_purge: PostPurge::default(),
+            min_age: None,
+            trash: None,
+            report_only: None,
+            nested_in: None,
+            plugin: None,
+            tool: None,
         };
 
-        let result = purge_cache(&cache);
+        let result = purge_cache(
+            &cache,
+            &guard(Path::new("/nonexistent")),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        );
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), 0);
+        assert_eq!(result.unwrap().bytes_freed, 0);
     }
 
     #[test]
@@ -315,14 +1473,30 @@ mod tests {
         let cache = CacheInfo {
             name: "TestCache".to_string(),
             path: cache_dir.clone(),
+            real_path: cache_dir.clone(),
             size: 9,
+            stale_size: 0,
             exists: true,
             description: "Test".to_string(),
+            post_purge: PostPurge::default(),
+            min_age: None,
+            trash: None,
+            report_only: None,
+            nested_in: None,
+            plugin: None,
+            tool: None,
         };
 
-        let result = purge_cache(&cache);
+        let result = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        );
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), 9);
+        let progress = result.unwrap();
+        assert_eq!(progress.bytes_freed, 9);
+        assert_eq!(progress.files_removed, 1);
 
         // Directory should be recreated but empty
         assert!(cache_dir.exists());
@@ -347,12 +1521,29 @@ mod tests {
         let cache = CacheInfo {
             name: "Trash".to_string(),
             path: trash_dir.clone(),
+            real_path: trash_dir.clone(),
             size: 100,
+            stale_size: 0,
             exists: true,
             description: "Test Trash".to_string(),
+            post_purge: PostPurge {
+                action: AfterPurge::KeepDir,
+                ..PostPurge::default()
+            },
+            min_age: None,
+            trash: None,
+            report_only: None,
+            nested_in: None,
+            plugin: None,
+            tool: None,
         };
 
-        let result = purge_cache(&cache);
+        let result = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        );
         assert!(result.is_ok());
 
         // Trash directory should still exist but be empty
@@ -360,9 +1551,590 @@ mod tests {
         assert!(fs::read_dir(&trash_dir).unwrap().next().is_none());
     }
 
+    #[test]
+    fn test_parse_df_output() {
+        let output = "Filesystem 1024-blocks Used Available Capacity Mounted on\n\
+                      /dev/disk1 1000 400 600 40% /\n";
+        let space = parse_df_output(output).unwrap();
+        assert_eq!(space.total, 1000 * 1024);
+        assert_eq!(space.available, 600 * 1024);
+    }
+
+    #[test]
+    fn test_disk_space_used_percent() {
+        let space = DiskSpace {
+            total: 1000,
+            available: 150,
+        };
+        assert_eq!(space.used_percent(), 85);
+        let empty = DiskSpace {
+            total: 0,
+            available: 0,
+        };
+        assert_eq!(empty.used_percent(), 0);
+    }
+
+    #[test]
+    fn test_parse_df_output_invalid() {
+        assert!(parse_df_output("").is_none());
+        assert!(parse_df_output("header\nbroken line").is_none());
+    }
+
+    /// A guard allowing purges inside a test's temp directory.
+    fn guard(dir: &Path) -> PurgeGuard {
+        PurgeGuard::new(PathBuf::from("/nonexistent/home"), vec![dir.to_path_buf()])
+    }
+
+    fn make_cache(dir: &std::path::Path, name: &str, bytes: usize) -> CacheInfo {
+        let path = dir.join(name);
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("data.bin"), vec![0u8; bytes]).unwrap();
+        CacheInfo::new(name, path, "Test")
+    }
+
+    #[test]
+    fn test_purge_caches_parallel_preserves_order() {
+        let dir = tempdir().unwrap();
+        let caches: Vec<CacheInfo> = (1..=5)
+            .map(|i| make_cache(dir.path(), &format!("cache{}", i), i * 10))
+            .collect();
+        let refs: Vec<&CacheInfo> = caches.iter().collect();
+
+        let done = AtomicUsize::new(0);
+        let results = purge_caches_parallel(
+            &refs,
+            3,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            |_, _| {},
+            |_, _| {
+                done.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+
+        assert_eq!(done.load(Ordering::SeqCst), 5);
+        let sizes: Vec<u64> = results
+            .into_iter()
+            .map(|r| r.unwrap().bytes_freed)
+            .collect();
+        assert_eq!(sizes, vec![10, 20, 30, 40, 50]);
+        for cache in &caches {
+            assert!(fs::read_dir(&cache.path).unwrap().next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_purge_caches_parallel_isolates_errors() {
+        let dir = tempdir().unwrap();
+        let good = make_cache(dir.path(), "good", 4);
+        let mut bad = make_cache(dir.path(), "bad", 4);
+        bad.real_path = PathBuf::from("/outside/the/guard");
+
+        let results = purge_caches_parallel(
+            &[&bad, &good],
+            2,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            |_, _| {},
+            |_, _| {},
+        );
+
+        // The failing entry doesn't abort the good one
+        assert!(results[0].is_err());
+        assert_eq!(results[1].as_ref().unwrap().bytes_freed, 4);
+        assert!(!good.path.join("data.bin").exists());
+    }
+
+    #[test]
+    fn test_purge_caches_parallel_cancelled_before_start() {
+        let dir = tempdir().unwrap();
+        let cache = make_cache(dir.path(), "cache", 8);
+
+        let results = purge_caches_parallel(
+            &[&cache],
+            1,
+            &guard(dir.path()),
+            &AtomicBool::new(true),
+            |_, _| {},
+            |_, _| {},
+        );
+
+        assert!(results[0].is_err());
+        assert!(cache.path.join("data.bin").exists());
+    }
+
+    #[test]
+    fn test_purge_cache_cancelled_leaves_directory() {
+        let dir = tempdir().unwrap();
+        let cache = make_cache(dir.path(), "cache", 8);
+
+        let progress = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(true),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert!(progress.cancelled);
+        assert_eq!(progress.files_removed, 0);
+        assert!(cache.path.join("data.bin").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_purge_cache_removes_symlink_without_following() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("keep.txt"), "keep").unwrap();
+
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir(&cache_dir).unwrap();
+        std::os::unix::fs::symlink(&target, cache_dir.join("link")).unwrap();
+
+        let cache = CacheInfo::new("cache", cache_dir.clone(), "Test");
+        purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert!(!cache_dir.join("link").exists());
+        assert!(target.join("keep.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_purge_cache_skips_undeletable_entries() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let locked = cache_dir.join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        fs::write(locked.join("in-use.db"), "data").unwrap();
+        fs::write(cache_dir.join("free.txt"), "12345").unwrap();
+
+        // A read-only directory makes its children undeletable (except for root)
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+        let is_root = fs::remove_file(locked.join("in-use.db")).is_ok();
+
+        let cache = CacheInfo::new("cache", cache_dir.clone(), "Test");
+        let progress = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).ok();
+
+        assert!(!cache_dir.join("free.txt").exists());
+        if !is_root {
+            assert_eq!(progress.skipped.len(), 1);
+            assert!(progress.skipped[0].path.ends_with("in-use.db"));
+            assert!(locked.join("in-use.db").exists());
+        }
+    }
+
+    #[test]
+    fn test_largest_entries_sorted_and_truncated() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("small.bin"), vec![0u8; 10]).unwrap();
+        fs::write(dir.path().join("large.bin"), vec![0u8; 300]).unwrap();
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        fs::write(nested.join("a.bin"), vec![0u8; 100]).unwrap();
+        fs::write(nested.join("b.bin"), vec![0u8; 100]).unwrap();
+
+        let entries = largest_entries(dir.path(), 2);
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].path.ends_with("large.bin"));
+        assert_eq!(entries[0].size, 300);
+        assert!(entries[1].path.ends_with("nested"));
+        assert_eq!(entries[1].size, 200);
+    }
+
+    #[test]
+    fn test_largest_entries_missing_dir() {
+        assert!(largest_entries(Path::new("/nonexistent/cache"), 5).is_empty());
+    }
+
+    #[test]
+    fn test_purge_cache_leave_absent() {
+        let dir = tempdir().unwrap();
+        let mut cache = make_cache(dir.path(), "cache", 4);
+        cache.post_purge.action = AfterPurge::Absent;
+
+        purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert!(!cache.path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_purge_cache_recreate_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let cache = make_cache(dir.path(), "cache", 4);
+        fs::set_permissions(&cache.path, fs::Permissions::from_mode(0o700)).unwrap();
+
+        purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        let mode = fs::metadata(&cache.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_purge_cache_recreate_with_configured_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let mut cache = make_cache(dir.path(), "cache", 4);
+        cache.apply_settings(&CacheSettings {
+            mode: Some(0o750),
+            ..CacheSettings::default()
+        });
+
+        purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        let mode = fs::metadata(&cache.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+
+    #[test]
+    fn test_apply_settings_overrides_action() {
+        let mut cache = CacheInfo::new("Trash", PathBuf::from("/nonexistent"), "Test")
+            .after_purge(AfterPurge::KeepDir);
+        cache.apply_settings(&CacheSettings::default());
+        assert_eq!(cache.post_purge.action, AfterPurge::KeepDir);
+
+        cache.apply_settings(&CacheSettings {
+            after_purge: Some(AfterPurge::Absent),
+            ..CacheSettings::default()
+        });
+        assert_eq!(cache.post_purge.action, AfterPurge::Absent);
+    }
+
+    #[test]
+    fn test_guard_allows_paths_inside_roots() {
+        let dir = tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        fs::create_dir(&cache).unwrap();
+
+        assert!(guard(dir.path()).check(&cache).is_ok());
+        // Not-yet-existing paths are checked lexically
+        assert!(guard(dir.path()).check(&dir.path().join("missing")).is_ok());
+    }
+
+    #[test]
+    fn test_guard_refuses_dangerous_paths() {
+        let home = PathBuf::from("/Users/dev");
+        let guard = PurgeGuard::new(home.clone(), vec![]);
+
+        assert!(guard.check(Path::new("/")).is_err());
+        assert!(guard.check(Path::new("/Users")).is_err());
+        assert!(guard.check(&home).is_err());
+        assert!(guard.check(Path::new("relative/cache")).is_err());
+        assert!(guard.check(Path::new("/Users/dev/../other/cache")).is_err());
+        assert!(guard.check(Path::new("/opt/homebrew/cache")).is_err());
+        assert!(guard.check(Path::new("/Users/dev/.npm/_cacache")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_guard_refuses_symlink_escaping_roots() {
+        let root = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let link = root.path().join("cache");
+        std::os::unix::fs::symlink(outside.path(), &link).unwrap();
+
+        let err = guard(root.path()).check(&link).unwrap_err();
+        assert!(err.contains("outside"));
+    }
+
+    #[test]
+    fn test_purge_cache_refuses_unsafe_path() {
+        let dir = tempdir().unwrap();
+        let cache = make_cache(dir.path(), "cache", 4);
+        let strict = PurgeGuard::new(PathBuf::from("/nonexistent/home"), vec![]);
+
+        let result = purge_cache(&cache, &strict, &AtomicBool::new(false), &mut |_| {});
+
+        assert!(result.is_err());
+        assert!(cache.path.join("data.bin").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_info_resolves_symlinked_path() {
+        let dir = tempdir().unwrap();
+        let real = make_cache(dir.path(), "real", 7);
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real.path, &link).unwrap();
+
+        let cache = CacheInfo::new("Linked", link.clone(), "Test");
+
+        assert_eq!(cache.path, link);
+        assert_eq!(cache.real_path, fs::canonicalize(&real.path).unwrap());
+        assert_eq!(cache.size, 7);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_purge_symlinked_cache_keeps_link() {
+        let dir = tempdir().unwrap();
+        let real = make_cache(dir.path(), "real", 7);
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real.path, &link).unwrap();
+
+        let cache = CacheInfo::new("Linked", link.clone(), "Test");
+        let progress = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(progress.bytes_freed, 7);
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::read_dir(&real.path).unwrap().next().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dedupe_same_directory() {
+        let dir = tempdir().unwrap();
+        let original = make_cache(dir.path(), "original", 5);
+        let other = make_cache(dir.path(), "other", 3);
+        let link = dir.path().join("alias");
+        std::os::unix::fs::symlink(&original.path, &link).unwrap();
+        let alias = CacheInfo::new("Alias", link, "Test");
+
+        let mut caches = vec![original, alias, other];
+        dedupe_same_directory(&mut caches);
+
+        // The symlink is the same directory as `original`, seen first
+        let names: Vec<&str> = caches.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["original", "other"]);
+    }
+
+    fn set_last_used(path: &Path, age: Duration) {
+        let when = SystemTime::now() - age;
+        let times = fs::FileTimes::new().set_modified(when).set_accessed(when);
+        File::options()
+            .write(true)
+            .open(path)
+            .and_then(|f| f.set_times(times))
+            .unwrap();
+    }
+
+    fn make_aged_cache(dir: &Path) -> PathBuf {
+        let path = dir.join("T");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("old.tmp"), vec![0u8; 100]).unwrap();
+        fs::write(path.join("fresh.tmp"), vec![0u8; 10]).unwrap();
+        set_last_used(&path.join("old.tmp"), Duration::from_secs(10 * 86400));
+        path
+    }
+
+    #[test]
+    fn test_aged_cache_counts_only_old_files() {
+        let dir = tempdir().unwrap();
+        let path = make_aged_cache(dir.path());
+
+        let cache = CacheInfo::aged("Temp", path, "Test", Duration::from_secs(7 * 86400));
+        assert_eq!(cache.size, 100);
+    }
+
+    #[test]
+    fn test_purge_aged_cache_keeps_recent_files() {
+        let dir = tempdir().unwrap();
+        let path = make_aged_cache(dir.path());
+        let cache = CacheInfo::aged("Temp", path.clone(), "Test", Duration::from_secs(7 * 86400))
+            .after_purge(AfterPurge::KeepDir);
+
+        let progress = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(progress.files_removed, 1);
+        assert_eq!(progress.bytes_freed, 100);
+        assert_eq!(progress.kept, 1);
+        assert!(!path.join("old.tmp").exists());
+        assert!(path.join("fresh.tmp").exists());
+    }
+
+    #[test]
+    fn test_purge_cache_moves_files_to_the_trash() {
+        let dir = tempdir().unwrap();
+        let mut cache = make_cache(dir.path(), "npm", 10);
+        fs::create_dir(cache.path.join("sub")).unwrap();
+        fs::write(cache.path.join("sub/blob"), vec![0u8; 5]).unwrap();
+        cache.trash = Some(Trash::xdg(dir.path().join("Trash")));
+
+        let progress = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(progress.bytes_freed, 15);
+        // The cache is recreated empty; its files wait in the trash
+        assert!(fs::read_dir(&cache.path).unwrap().next().is_none());
+        let entry = dir.path().join("Trash/files/npm");
+        assert_eq!(fs::read(entry.join("sub/blob")).unwrap().len(), 5);
+        assert_eq!(fs::read_dir(&entry).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_cache_older_than_trims_instead_of_emptying() {
+        let dir = tempdir().unwrap();
+        let path = make_aged_cache(dir.path());
+        let week = Duration::from_secs(7 * 86400);
+
+        // A cache's own, longer minimum age still wins
+        let own = CacheInfo::aged("Temp", path.clone(), "Test", 4 * week).older_than(week);
+        assert_eq!(own.min_age, Some(4 * week));
+        assert_eq!(own.size, 0);
+
+        let cache = CacheInfo::new("Temp", path.clone(), "Test")
+            .after_purge(AfterPurge::KeepDir)
+            .older_than(week);
+        assert_eq!((cache.size, cache.stale_size), (100, 100));
+        assert_eq!(cache.post_purge.action, AfterPurge::KeepDir);
+
+        let progress = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!((progress.bytes_freed, progress.kept), (100, 1));
+        assert!(path.join("fresh.tmp").exists());
+    }
+
+    #[test]
+    fn test_advanced_caches_age_filter_logs() {
+        let home = tempdir().unwrap();
+        let reports = home.path().join("Library/Logs/DiagnosticReports");
+        fs::create_dir_all(&reports).unwrap();
+        fs::write(reports.join("old.crash"), vec![0u8; 50]).unwrap();
+        fs::write(reports.join("new.crash"), vec![0u8; 5]).unwrap();
+        set_last_used(&reports.join("old.crash"), Duration::from_secs(40 * 86400));
+
+        let caches = advanced_caches(home.path());
+        let logs = caches.iter().find(|c| c.name == "Logs").unwrap();
+        let diag = caches
+            .iter()
+            .find(|c| c.name == "Diagnostic Reports")
+            .unwrap();
+
+        assert_eq!(logs.size, 50);
+        assert_eq!(diag.size, 50);
+        assert_eq!(diag.post_purge.action, AfterPurge::KeepDir);
+        assert_eq!(total_size(&[logs, diag]), 50);
+    }
+
+    #[test]
+    fn test_report_only_cache_is_never_purged() {
+        let dir = tempdir().unwrap();
+        let cache = make_cache(dir.path(), "Messages", 20).report_only("Manage in Messages");
+        let other = make_cache(dir.path(), "npm", 3);
+
+        assert_eq!(total_size(&[&cache, &other]), 3);
+        let result = purge_cache(
+            &cache,
+            &guard(dir.path()),
+            &AtomicBool::new(false),
+            &mut |_| {},
+        );
+        assert!(result.unwrap_err().contains("report-only"));
+        assert!(cache.real_path.join("data.bin").exists());
+    }
+
+    #[test]
+    fn test_cache_info_measures_stale_size() {
+        let dir = tempdir().unwrap();
+        let cache = make_cache(dir.path(), "npm", 10);
+        fs::write(cache.path.join("old.bin"), vec![0u8; 30]).unwrap();
+        set_last_used(&cache.path.join("old.bin"), Duration::from_secs(60 * 86400));
+
+        let cache = CacheInfo::new("npm", cache.path.clone(), "Test");
+        assert_eq!(cache.size, 40);
+        assert_eq!(cache.stale_size, 30);
+    }
+
+    #[test]
+    fn test_nested_caches_not_double_counted() {
+        let dir = tempdir().unwrap();
+        let outer = make_cache(dir.path(), "outer", 10);
+        let inner_path = outer.path.join("inner");
+        fs::create_dir(&inner_path).unwrap();
+        fs::write(inner_path.join("data.bin"), vec![0u8; 5]).unwrap();
+        let outer = CacheInfo::new("outer", outer.path.clone(), "Test");
+        let inner = CacheInfo::new("inner", inner_path, "Test");
+        let other = make_cache(dir.path(), "other", 3);
+
+        let all = vec![&inner, &outer, &other];
+
+        assert_eq!(containing_cache(&inner, &all).unwrap().name, "outer");
+        assert!(containing_cache(&outer, &all).is_none());
+        let names: Vec<&str> = outermost(&all).iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["outer", "other"]);
+        assert_eq!(total_size(&all), 15 + 3);
+
+        // Without its container, the inner cache counts on its own
+        assert_eq!(total_size(&[&inner, &other]), 5 + 3);
+    }
+
+    #[test]
+    fn test_containing_cache_ignores_sibling_prefixes() {
+        let dir = tempdir().unwrap();
+        let npm = make_cache(dir.path(), "npm", 1);
+        let npm_other = make_cache(dir.path(), "npm-other", 1);
+
+        assert!(containing_cache(&npm_other, &[&npm, &npm_other]).is_none());
+    }
+
     #[test]
     fn test_discover_caches_returns_sorted() {
-        let caches = discover_caches();
+        let caches = discover_caches(&Config::default());
 
         // Verify sorted by size descending
         for window in caches.windows(2) {
@@ -375,4 +2147,51 @@ mod tests {
             assert!(cache.size > 0);
         }
     }
+
+    #[test]
+    fn test_linux_caches_use_xdg_cache_dir() {
+        let home = Path::new("/home/dev");
+        let caches = linux_caches(home, Path::new("/data/cache"));
+        let path = |name: &str| {
+            caches
+                .iter()
+                .find(|c| c.name == name)
+                .map(|c| c.path.clone())
+                .unwrap()
+        };
+
+        assert_eq!(path("pip"), PathBuf::from("/data/cache/pip"));
+        assert_eq!(path("Go Build"), PathBuf::from("/data/cache/go-build"));
+        assert_eq!(path("npm"), PathBuf::from("/home/dev/.npm/_cacache"));
+        assert_eq!(path("Cargo Git"), PathBuf::from("/home/dev/.cargo/git"));
+        let apt = caches.iter().find(|c| c.name == "APT").unwrap();
+        assert!(apt.report_only.is_some());
+        assert!(!caches.iter().any(|c| c.name.starts_with("Xcode")));
+    }
+
+    #[test]
+    fn test_windows_caches_live_in_local_app_data() {
+        let home = Path::new("/Users/dev");
+        let local = home.join("AppData/Local");
+        let caches = windows_caches(home, &local);
+
+        for name in ["npm", "Yarn", "pnpm", "pip", "Go Build", "Composer"] {
+            let cache = caches.iter().find(|c| c.name == name).unwrap();
+            assert!(cache.path.starts_with(&local), "{}", name);
+        }
+        assert!(caches.iter().any(|c| c.name == "NuGet"));
+        assert!(!caches.iter().any(|c| c.name == "Trash"));
+    }
+
+    #[test]
+    fn test_every_platform_has_unique_cache_names() {
+        let home = Path::new("/home/dev");
+        for platform in [Platform::MacOs, Platform::Linux, Platform::Windows] {
+            let caches = builtin_caches(platform, home);
+            let mut names: Vec<&str> = caches.iter().map(|c| c.name.as_str()).collect();
+            names.sort_unstable();
+            names.dedup();
+            assert_eq!(names.len(), caches.len(), "{:?}", platform);
+        }
+    }
 }
@@ -0,0 +1,76 @@
+//! The session transcript from `--record`: everything dockerase shows on the
+//! terminal, the questions it asks and the answers given, as plain text, so
+//! a cleanup can be reviewed afterwards. `display` writes every line here.
+
+use crate::history::now_secs;
+use crate::units::format_timestamp;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+static TRANSCRIPT: Mutex<Option<File>> = Mutex::new(None);
+
+/// Start recording to `path`, appending so several runs make up one log.
+/// `command` is written first, with the time.
+pub fn start(path: &Path, command: &str) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open transcript {}: {}", path.display(), e))?;
+    writeln!(
+        file,
+        "=== {} UTC: {} ===",
+        format_timestamp(now_secs()),
+        command
+    )
+    .map_err(|e| format!("Failed to write transcript {}: {}", path.display(), e))?;
+    *TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+    Ok(())
+}
+
+/// Add `text` to the transcript, if one is being recorded, without colors.
+pub fn record(text: &str) {
+    let mut transcript = TRANSCRIPT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(file) = transcript.as_mut() {
+        // Best effort: a full disk shouldn't stop the cleanup
+        writeln!(file, "{}", strip_ansi(text)).ok();
+    }
+}
+
+/// `text` without terminal escape sequences (colors, bold, ...).
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            // Parameters up to the final byte, e.g. the `m` of `\x1b[1;31m`
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_keeps_only_the_text() {
+        assert_eq!(
+            strip_ansi("\x1b[1;31m⚠ danger\x1b[0m done"),
+            "⚠ danger done"
+        );
+        assert_eq!(strip_ansi("\x1b[2K\rplain"), "\rplain");
+        assert_eq!(strip_ansi("no escapes"), "no escapes");
+    }
+}
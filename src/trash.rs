@@ -0,0 +1,240 @@
+//! The desktop trash, where `system purge --trash` moves what it would
+//! otherwise delete: `~/.Trash` on macOS, the freedesktop.org trash
+//! (`$XDG_DATA_HOME/Trash`) elsewhere. Each purged cache becomes one entry
+//! in the trash, named after its directory, so restoring it from the file
+//! manager puts it back in place.
+
+use crate::history::now_secs;
+use crate::paths::xdg_dir;
+use crate::system::get_home_dir;
+use crate::units::format_timestamp;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// Entries sit directly in the trash directory
+    MacOs,
+    /// Entries go in `files`, each with a `.trashinfo` in `info` that
+    /// records where it came from
+    Xdg,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trash {
+    root: PathBuf,
+    layout: Layout,
+}
+
+/// A directory in the trash that a cache's contents are moved into.
+#[derive(Debug)]
+pub struct TrashEntry {
+    pub dir: PathBuf,
+    info: Option<PathBuf>,
+}
+
+impl Trash {
+    /// The current user's trash.
+    pub fn open() -> Result<Self, String> {
+        if cfg!(windows) {
+            return Err(
+                "Moving caches to the Recycle Bin isn't supported; pass --permanent".to_string(),
+            );
+        }
+        let home = get_home_dir().ok_or("Could not determine home directory")?;
+        Ok(if cfg!(target_os = "macos") {
+            Self::macos(home.join(".Trash"))
+        } else {
+            Self::xdg(xdg_dir("XDG_DATA_HOME", &home, ".local/share").join("Trash"))
+        })
+    }
+
+    pub fn macos(root: PathBuf) -> Self {
+        Self {
+            root,
+            layout: Layout::MacOs,
+        }
+    }
+
+    pub fn xdg(root: PathBuf) -> Self {
+        Self {
+            root,
+            layout: Layout::Xdg,
+        }
+    }
+
+    /// Where entries are created.
+    fn files_dir(&self) -> PathBuf {
+        match self.layout {
+            Layout::MacOs => self.root.clone(),
+            Layout::Xdg => self.root.join("files"),
+        }
+    }
+
+    /// Whether `path` is inside the trash or holds it; moving that to the
+    /// trash would move the trash into itself.
+    pub fn overlaps(&self, path: &Path) -> bool {
+        let root = fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        [&root, &self.root]
+            .iter()
+            .any(|root| path.starts_with(root) || root.starts_with(path))
+    }
+
+    /// Create an empty entry for `original`, recording where it came from.
+    /// Files are moved, not copied, so `original` must be on the trash's disk.
+    pub fn entry_for(&self, original: &Path) -> Result<TrashEntry, String> {
+        let files = self.files_dir();
+        fs::create_dir_all(&files)
+            .map_err(|e| format!("Failed to create {}: {}", files.display(), e))?;
+        same_disk(original, &files)?;
+
+        let name = original
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "cache".to_string());
+        let (dir, entry_name) = (1..)
+            .map(|n| match n {
+                1 => name.clone(),
+                n => format!("{} {}", name, n),
+            })
+            .find_map(|candidate| {
+                let dir = files.join(&candidate);
+                match fs::create_dir(&dir) {
+                    Ok(()) => Some(Ok((dir, candidate))),
+                    Err(e) if e.kind() == ErrorKind::AlreadyExists => None,
+                    Err(e) => Some(Err(format!("Failed to create {}: {}", dir.display(), e))),
+                }
+            })
+            .expect("some name is free")?;
+
+        let info = match self.layout {
+            Layout::MacOs => None,
+            Layout::Xdg => {
+                let info_dir = self.root.join("info");
+                let info = info_dir.join(format!("{}.trashinfo", entry_name));
+                let deleted = format_timestamp(now_secs()).replace(' ', "T");
+                let written = fs::create_dir_all(&info_dir).and_then(|()| {
+                    fs::write(
+                        &info,
+                        format!(
+                            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                            percent_encode(original),
+                            deleted
+                        ),
+                    )
+                });
+                if let Err(e) = written {
+                    fs::remove_dir(&dir).ok();
+                    return Err(format!("Failed to write {}: {}", info.display(), e));
+                }
+                Some(info)
+            }
+        };
+        Ok(TrashEntry { dir, info })
+    }
+}
+
+impl TrashEntry {
+    /// Move `path`, which sits at `relative` inside the original, into the entry.
+    pub fn take(&self, path: &Path, relative: &Path) -> std::io::Result<()> {
+        let target = self.dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(path, target)
+    }
+
+    /// Remove the entry again if nothing was moved into it.
+    pub fn discard_if_empty(&self) {
+        let empty = |dir: &Path| -> bool {
+            let mut dirs = vec![dir.to_path_buf()];
+            while let Some(dir) = dirs.pop() {
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    return false;
+                };
+                for entry in entries.flatten() {
+                    match entry.file_type() {
+                        Ok(t) if t.is_dir() => dirs.push(entry.path()),
+                        _ => return false,
+                    }
+                }
+            }
+            true
+        };
+        if empty(&self.dir) && fs::remove_dir_all(&self.dir).is_ok() {
+            if let Some(info) = &self.info {
+                fs::remove_file(info).ok();
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_disk(path: &Path, trash: &Path) -> Result<(), String> {
+    use std::os::unix::fs::MetadataExt;
+    let device = |p: &Path| fs::metadata(p).map(|m| m.dev()).ok();
+    if device(path) != device(trash) {
+        return Err(format!(
+            "{} is on another disk than the trash ({}); pass --permanent to delete it",
+            path.display(),
+            trash.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn same_disk(_path: &Path, _trash: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// `path` as a URI path, as `.trashinfo` files record it.
+fn percent_encode(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xdg_entry_records_origin_and_gets_a_free_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = Trash::xdg(dir.path().join("Trash"));
+        let cache = dir.path().join("my cache");
+        fs::create_dir_all(cache.join("sub")).unwrap();
+        fs::write(cache.join("sub/blob"), b"x").unwrap();
+
+        let entry = trash.entry_for(&cache).unwrap();
+        entry
+            .take(&cache.join("sub/blob"), Path::new("sub/blob"))
+            .unwrap();
+        assert!(dir.path().join("Trash/files/my cache/sub/blob").exists());
+        let info = fs::read_to_string(dir.path().join("Trash/info/my cache.trashinfo")).unwrap();
+        assert!(
+            info.contains(&format!("Path={}/my%20cache\n", dir.path().display())),
+            "{info}"
+        );
+
+        // A second purge of the same cache doesn't collide; an unused
+        // entry is taken back out
+        let again = trash.entry_for(&cache).unwrap();
+        assert_eq!(again.dir, dir.path().join("Trash/files/my cache 2"));
+        again.discard_if_empty();
+        assert!(!again.dir.exists());
+        assert!(!dir.path().join("Trash/info/my cache 2.trashinfo").exists());
+
+        assert!(trash.overlaps(&dir.path().join("Trash/files")));
+        assert!(trash.overlaps(dir.path()));
+        assert!(!trash.overlaps(&cache));
+    }
+}
@@ -0,0 +1,447 @@
+use crate::display::{print_info, print_warning};
+use crate::docker;
+use crate::history::now_secs;
+use crate::paths;
+use crate::resources::{Container, Image};
+use crate::units::format_timestamp;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// An image as it was before removal, with what it takes to pull it back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageRecord {
+    pub id: String,
+    pub repository: String,
+    pub tag: String,
+    /// Registry digest (`sha256:...`); empty for images that were never
+    /// pushed or pulled
+    #[serde(default)]
+    pub digest: String,
+}
+
+impl ImageRecord {
+    /// Whether the image had a name to pull it back by.
+    pub fn is_restorable(&self) -> bool {
+        !(self.repository.is_empty()
+            || self.repository == "<none>"
+            || self.tag.is_empty()
+            || self.tag == "<none>")
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}:{}", self.repository, self.tag)
+    }
+
+    /// What to pull: the exact digest when known, else the tag.
+    pub fn pull_reference(&self) -> String {
+        if self.digest.is_empty() {
+            self.name()
+        } else {
+            format!("{}@{}", self.repository, self.digest)
+        }
+    }
+}
+
+impl From<&Image> for ImageRecord {
+    fn from(image: &Image) -> Self {
+        Self {
+            id: image.id.clone(),
+            repository: image.repository.clone(),
+            tag: image.tag.clone(),
+            digest: image.digest().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerRecord {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub state: String,
+    pub created_at: String,
+    /// Comma-separated `key=value` pairs
+    pub labels: String,
+}
+
+impl From<&Container> for ContainerRecord {
+    fn from(container: &Container) -> Self {
+        Self {
+            id: container.id.clone(),
+            name: container.names.clone(),
+            image: container.image.clone(),
+            state: container.state.clone(),
+            created_at: container.created_at.clone(),
+            labels: container.labels.clone(),
+        }
+    }
+}
+
+/// Everything one cleanup run was about to remove, written before it
+/// removed anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub id: String,
+    pub timestamp: u64,
+    pub command: String,
+    #[serde(default)]
+    pub images: Vec<ImageRecord>,
+    #[serde(default)]
+    pub containers: Vec<ContainerRecord>,
+    #[serde(default)]
+    pub volumes: Vec<String>,
+    #[serde(default)]
+    pub networks: Vec<String>,
+    #[serde(default)]
+    pub caches: Vec<PathBuf>,
+}
+
+impl Manifest {
+    pub fn new(command: &str) -> Self {
+        let timestamp = now_secs();
+        Self {
+            id: manifest_id(timestamp),
+            timestamp,
+            command: command.to_string(),
+            images: Vec::new(),
+            containers: Vec::new(),
+            volumes: Vec::new(),
+            networks: Vec::new(),
+            caches: Vec::new(),
+        }
+    }
+
+    pub fn add_images<'a>(&mut self, images: impl IntoIterator<Item = &'a Image>) {
+        for image in images {
+            if !self.images.iter().any(|i| i.id == image.id) {
+                self.images.push(image.into());
+            }
+        }
+    }
+
+    pub fn add_containers<'a>(&mut self, containers: impl IntoIterator<Item = &'a Container>) {
+        for container in containers {
+            if !self.containers.iter().any(|c| c.id == container.id) {
+                self.containers.push(container.into());
+            }
+        }
+    }
+
+    pub fn add_volumes<S: AsRef<str>>(&mut self, names: impl IntoIterator<Item = S>) {
+        add_names(&mut self.volumes, names);
+    }
+
+    pub fn add_networks<S: AsRef<str>>(&mut self, names: impl IntoIterator<Item = S>) {
+        add_names(&mut self.networks, names);
+    }
+
+    pub fn add_cache(&mut self, path: &Path) {
+        self.caches.push(path.to_path_buf());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+            && self.containers.is_empty()
+            && self.volumes.is_empty()
+            && self.networks.is_empty()
+            && self.caches.is_empty()
+    }
+
+    pub fn restorable_images(&self) -> impl Iterator<Item = &ImageRecord> {
+        self.images.iter().filter(|i| i.is_restorable())
+    }
+}
+
+/// How the images a cleanup removed are written out for pulling back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A shell script of pull commands
+    Script,
+    /// A compose file with a service per image, for `docker compose pull`
+    Compose,
+}
+
+impl ExportFormat {
+    /// Compose for `.yml` and `.yaml` files, a script otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yml" | "yaml") => ExportFormat::Compose,
+            _ => ExportFormat::Script,
+        }
+    }
+}
+
+impl Manifest {
+    /// The restorable images in `format`. The script pulls by digest when
+    /// the digest is known and tags the image again, as `restore` does.
+    pub fn export(&self, format: ExportFormat) -> String {
+        let header = format!(
+            "# Images removed by `dockerase {}` on {} UTC (cleanup {})",
+            self.command,
+            format_timestamp(self.timestamp),
+            self.id
+        );
+        match format {
+            ExportFormat::Script => {
+                let cli = docker::runtime().binary();
+                let mut script = format!("#!/bin/sh\n{}\nset -e\n", header);
+                for image in self.restorable_images() {
+                    script += &match image.digest.as_str() {
+                        "" => format!("{} pull '{}'\n", cli, image.name()),
+                        _ => format!(
+                            "{cli} pull '{0}' && {cli} tag '{0}' '{1}'\n",
+                            image.pull_reference(),
+                            image.name()
+                        ),
+                    };
+                }
+                script
+            }
+            ExportFormat::Compose => {
+                let mut compose = format!(
+                    "{}\n# Pull them back with `docker compose -f <this file> pull`\nservices:\n",
+                    header
+                );
+                let mut services: Vec<String> = Vec::new();
+                for image in self.restorable_images() {
+                    let base = service_name(image);
+                    let name = (1..)
+                        .map(|n| match n {
+                            1 => base.clone(),
+                            n => format!("{}-{}", base, n),
+                        })
+                        .find(|name| !services.contains(name))
+                        .expect("some name is free");
+                    compose += &format!("  {}:\n    image: \"{}\"\n", name, image.name());
+                    if !image.digest.is_empty() {
+                        compose += &format!("    # was {}\n", image.digest);
+                    }
+                    services.push(name);
+                }
+                compose
+            }
+        }
+    }
+}
+
+/// A compose service name for `image`: its repository's last part and
+/// its tag, in the characters compose allows.
+fn service_name(image: &ImageRecord) -> String {
+    let repository = image.repository.rsplit('/').next().unwrap_or_default();
+    format!("{}-{}", repository, image.tag)
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '-' | '_' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect()
+}
+
+static EXPORT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Also write the tagged images of each cleanup recorded from now on to
+/// `path`, ready to pull back (`--export-images`).
+pub fn export_to(path: PathBuf) {
+    EXPORT.set(path).ok();
+}
+
+/// Write the restorable images of `manifest` to `path`, in the format its
+/// extension calls for. Returns how many were written.
+pub fn write_export(manifest: &Manifest, path: &Path) -> Result<usize, String> {
+    let export = manifest.export(ExportFormat::for_path(path));
+    fs::write(path, export).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    if ExportFormat::for_path(path) == ExportFormat::Script {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).ok();
+    }
+    Ok(manifest.restorable_images().count())
+}
+
+fn add_names<S: AsRef<str>>(list: &mut Vec<String>, names: impl IntoIterator<Item = S>) {
+    for name in names {
+        let name = name.as_ref();
+        if !list.iter().any(|n| n == name) {
+            list.push(name.to_string());
+        }
+    }
+}
+
+/// `YYYYMMDD-HHMMSS` (UTC), which also sorts by age.
+fn manifest_id(timestamp: u64) -> String {
+    format_timestamp(timestamp)
+        .chars()
+        .filter_map(|c| match c {
+            '-' | ':' => None,
+            ' ' => Some('-'),
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Write `manifest` to the history directory, under a fresh ID if another
+/// run saved one in the same second.
+fn save(manifest: &mut Manifest) -> Result<PathBuf, String> {
+    let dir = paths::undo_history().ok_or("Could not determine state directory")?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let base = manifest.id.clone();
+    let mut n = 1;
+    while dir.join(format!("{}.json", manifest.id)).exists() {
+        n += 1;
+        manifest.id = format!("{}-{}", base, n);
+    }
+
+    let path = dir.join(format!("{}.json", manifest.id));
+    let json = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+    fs::write(&path, json + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Save the manifest before a cleanup starts removing things. Failing to
+/// save is reported but doesn't stop the cleanup.
+pub fn record(mut manifest: Manifest) {
+    if manifest.is_empty() {
+        return;
+    }
+    match save(&mut manifest) {
+        Ok(_) => print_info(&format!(
+            "Saved undo manifest {} (see `dockerase history`)",
+            manifest.id
+        )),
+        Err(e) => print_warning(&format!("Could not save undo manifest: {}", e)),
+    }
+    // Cache-only runs (`system purge` after a `check --auto` purge) have
+    // no images and leave the export alone
+    if let Some(path) = EXPORT.get().filter(|_| !manifest.images.is_empty()) {
+        match write_export(&manifest, path) {
+            Ok(count) => print_info(&format!(
+                "Wrote the {} tagged images being removed to {}, ready to pull back",
+                count,
+                path.display()
+            )),
+            Err(e) => print_warning(&format!("Could not export the removed images: {}", e)),
+        }
+    }
+}
+
+/// Every saved manifest, newest first. Unreadable files are left out.
+pub fn list() -> Vec<Manifest> {
+    let Some(dir) = paths::undo_history() else {
+        return vec![];
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return vec![];
+    };
+    let mut manifests: Vec<Manifest> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .filter_map(|json| serde_json::from_str(&json).ok())
+        .collect();
+    manifests.sort_by(|a, b| (b.timestamp, &b.id).cmp(&(a.timestamp, &a.id)));
+    manifests
+}
+
+pub fn load(id: &str) -> Result<Manifest, String> {
+    list()
+        .into_iter()
+        .find(|m| m.id == id)
+        .ok_or_else(|| format!("No undo manifest '{}'; see `dockerase history`", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(repository: &str, tag: &str, digest: &str) -> ImageRecord {
+        ImageRecord {
+            id: "sha256:abc".to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            digest: digest.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_manifest_id_sorts_by_time() {
+        assert_eq!(manifest_id(1_700_000_000), "20231114-221320");
+        assert!(manifest_id(1_700_000_000) < manifest_id(1_700_000_001));
+    }
+
+    #[test]
+    fn test_image_record_pull_reference() {
+        assert_eq!(image("postgres", "16", "").pull_reference(), "postgres:16");
+        assert_eq!(
+            image("postgres", "16", "sha256:feed").pull_reference(),
+            "postgres@sha256:feed"
+        );
+        assert!(image("postgres", "16", "").is_restorable());
+        assert!(!image("<none>", "<none>", "").is_restorable());
+    }
+
+    #[test]
+    fn test_export_pulls_back_restorable_images() {
+        let mut manifest = Manifest::new("nuclear");
+        manifest.images = vec![
+            image("postgres", "16", "sha256:feed"),
+            image("ghcr.io/acme/Web", "1.2", ""),
+            image("<none>", "<none>", ""),
+            image("registry.local/web", "1.2", ""),
+        ];
+
+        let script = manifest.export(ExportFormat::Script);
+        let cli = docker::runtime().binary();
+        let pulls: Vec<&str> = script.lines().filter(|l| l.contains(" pull ")).collect();
+        assert_eq!(
+            pulls,
+            [
+                format!("{cli} pull 'postgres@sha256:feed' && {cli} tag 'postgres@sha256:feed' 'postgres:16'"),
+                format!("{cli} pull 'ghcr.io/acme/Web:1.2'"),
+                format!("{cli} pull 'registry.local/web:1.2'"),
+            ]
+        );
+        assert!(script.starts_with("#!/bin/sh\n# Images removed by `dockerase nuclear`"));
+
+        let compose = manifest.export(ExportFormat::Compose);
+        assert!(
+            compose.contains(
+                "services:\n  postgres-16:\n    image: \"postgres:16\"\n    # was sha256:feed\n"
+            ),
+            "{compose}"
+        );
+        // Service names are unique and lowercase
+        assert!(
+            compose.contains("  web-1-2:\n    image: \"ghcr.io/acme/Web:1.2\"\n"),
+            "{compose}"
+        );
+        assert!(
+            compose.contains("  web-1-2-2:\n    image: \"registry.local/web:1.2\"\n"),
+            "{compose}"
+        );
+
+        assert_eq!(
+            ExportFormat::for_path(Path::new("pulls.yaml")),
+            ExportFormat::Compose
+        );
+        assert_eq!(
+            ExportFormat::for_path(Path::new("pulls.sh")),
+            ExportFormat::Script
+        );
+    }
+
+    #[test]
+    fn test_manifest_dedupes_names() {
+        let mut manifest = Manifest::new("nuclear");
+        assert!(manifest.is_empty());
+        manifest.add_volumes(["pgdata", "scratch"]);
+        manifest.add_volumes(vec!["pgdata".to_string()]);
+        assert_eq!(manifest.volumes, vec!["pgdata", "scratch"]);
+        assert!(!manifest.is_empty());
+    }
+}
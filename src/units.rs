@@ -0,0 +1,301 @@
+use bytesize::ByteSize;
+use std::time::Duration;
+
+/// Parse a human duration such as `30m`, `6h`, `7d` or `2w`.
+/// A bare number is interpreted as seconds. Zero, or more seconds than fit
+/// in a `u64`, is an error.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num_str, unit) = s.split_at(split);
+
+    let num: u64 = num_str
+        .parse()
+        .map_err(|_| format!("Invalid duration '{}' (expected e.g. 30m, 6h, 7d)", s))?;
+
+    let multiplier = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        other => {
+            return Err(format!(
+                "Unknown duration unit '{}' (use s, m, h, d or w)",
+                other
+            ))
+        }
+    };
+
+    match num.checked_mul(multiplier) {
+        Some(0) => Err(format!("Duration '{}' must be longer than zero", s)),
+        Some(secs) => Ok(Duration::from_secs(secs)),
+        None => Err(format!("Duration '{}' is too long", s)),
+    }
+}
+
+/// A duration in its largest whole unit, as `parse_duration` reads it:
+/// `90d`, `5h`, `12m`.
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        86_400.. => format!("{}d", secs / 86_400),
+        3_600.. => format!("{}h", secs / 3_600),
+        60.. => format!("{}m", secs / 60),
+        _ => format!("{}s", secs),
+    }
+}
+
+/// Parse a size such as `20GB`, `500MB` or `1.5GiB`.
+pub fn parse_byte_size(s: &str) -> Result<u64, String> {
+    s.trim()
+        .parse::<ByteSize>()
+        .map(|b| b.as_u64())
+        .map_err(|_| format!("Invalid size '{}' (expected e.g. 500MB, 20GB)", s))
+}
+
+/// Format a size the way `docker system df` does: decimal units, four
+/// significant digits and no space, such as `1.235GB`, `500MB` or `0B`.
+/// `docker images` rounds to three digits instead (`1.23GB`).
+pub fn format_docker_size(bytes: u64) -> String {
+    const UNITS: [&str; 7] = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    // Go's %.4g: at most four significant digits, trailing zeros dropped
+    let digits = if size < 1.0 {
+        0
+    } else {
+        3usize.saturating_sub(size.log10().floor() as usize)
+    };
+    let mut number = format!("{:.*}", digits, size);
+    if number.contains('.') {
+        number = number
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string();
+    }
+    format!("{}{}", number, UNITS[unit])
+}
+
+/// Parse a percentage such as `85%` or `85`.
+pub fn parse_percent(s: &str) -> Result<u8, String> {
+    let value: u8 = s
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("Invalid percentage '{}'", s))?;
+    if value > 100 {
+        return Err(format!(
+            "Percentage must be between 0 and 100, got {}",
+            value
+        ));
+    }
+    Ok(value)
+}
+
+/// Format a UNIX timestamp as `YYYY-MM-DD HH:MM:SS` (UTC).
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        (rem % 3_600) / 60,
+        rem % 60
+    )
+}
+
+/// Parse a docker CLI timestamp such as `2024-01-05 10:30:00 +0100 CET`
+/// into a UNIX timestamp.
+pub fn parse_docker_timestamp(s: &str) -> Option<u64> {
+    let mut parts = s.split_whitespace();
+    let date = parts.next()?;
+    let time = parts.next()?;
+    let offset = parts.next().unwrap_or("+0000");
+
+    let mut ymd = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (ymd.next()??, ymd.next()??, ymd.next()??);
+    let mut hms = time.splitn(3, ':').map(|p| p.parse::<f64>().ok());
+    let (hour, minute, second) = (hms.next()??, hms.next()??, hms.next()??);
+
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits = offset.trim_start_matches(['+', '-']);
+    let offset_secs = match (digits.get(..2), digits.get(2..4)) {
+        (Some(h), Some(m)) => sign * (h.parse::<i64>().ok()? * 3_600 + m.parse::<i64>().ok()? * 60),
+        _ => 0,
+    };
+
+    let days = days_from_civil(year, month as u32, day as u32);
+    let secs =
+        days * 86_400 + hour as i64 * 3_600 + minute as i64 * 60 + second as i64 - offset_secs;
+    u64::try_from(secs).ok()
+}
+
+/// Parse a relative time as the docker CLI prints it, such as `3 weeks
+/// ago`, `About an hour ago` or `Less than a second ago`. Months are 30
+/// days and years 365, as Docker counts them.
+pub fn parse_docker_age(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let s = s.strip_suffix(" ago").unwrap_or(s);
+    let secs = match s {
+        "Less than a second" => 0,
+        "About a minute" => 60,
+        "About an hour" => 3_600,
+        _ => {
+            let (num, unit) = s.split_once(' ')?;
+            let num: u64 = num.parse().ok()?;
+            let multiplier = match unit.trim_end_matches('s') {
+                "second" => 1,
+                "minute" => 60,
+                "hour" => 3_600,
+                "day" => 86_400,
+                "week" => 7 * 86_400,
+                "month" => 30 * 86_400,
+                "year" => 365 * 86_400,
+                _ => return None,
+            };
+            num * multiplier
+        }
+    };
+    Some(Duration::from_secs(secs))
+}
+
+// Howard Hinnant's civil-to-days algorithm
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Howard Hinnant's days-to-civil algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_docker_size_matches_docker_system_df() {
+        assert_eq!(format_docker_size(0), "0B");
+        assert_eq!(format_docker_size(999), "999B");
+        assert_eq!(format_docker_size(1_000), "1kB");
+        assert_eq!(format_docker_size(77_800_000), "77.8MB");
+        assert_eq!(format_docker_size(500_000_000), "500MB");
+        assert_eq!(format_docker_size(1_234_567_890), "1.235GB");
+        assert_eq!(format_docker_size(12_345_678_901), "12.35GB");
+        assert_eq!(format_docker_size(999_960_000), "1000MB");
+        assert_eq!(format_docker_size(2_000_000_000_000), "2TB");
+    }
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(parse_duration("6h").unwrap(), Duration::from_secs(21_600));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            Duration::from_secs(1_209_600)
+        );
+    }
+
+    #[test]
+    fn test_parse_docker_age() {
+        let days = |n: u64| Some(Duration::from_secs(n * 86_400));
+        assert_eq!(parse_docker_age("3 weeks ago"), days(21));
+        assert_eq!(parse_docker_age("1 day ago"), days(1));
+        assert_eq!(parse_docker_age("2 months ago"), days(60));
+        assert_eq!(
+            parse_docker_age("About an hour ago"),
+            Some(Duration::from_secs(3_600))
+        );
+        assert_eq!(
+            parse_docker_age("Less than a second ago"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_docker_age(""), None);
+        assert_eq!(parse_docker_age("a while ago"), None);
+    }
+
+    #[test]
+    fn test_format_age_round_trips() {
+        assert_eq!(format_age(Duration::from_secs(60 * 86_400 + 5)), "60d");
+        assert_eq!(format_age(Duration::from_secs(7_300)), "2h");
+        assert_eq!(format_age(Duration::from_secs(59)), "59s");
+        assert_eq!(
+            parse_duration(&format_age(Duration::from_secs(720))).unwrap(),
+            Duration::from_secs(720)
+        );
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("10y").is_err());
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("0d").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("20GB").unwrap(), 20_000_000_000);
+        assert_eq!(parse_byte_size("500 MB").unwrap(), 500_000_000);
+        assert_eq!(parse_byte_size("1024").unwrap(), 1024);
+        assert!(parse_byte_size("lots").is_err());
+    }
+
+    #[test]
+    fn test_parse_docker_timestamp() {
+        assert_eq!(
+            parse_docker_timestamp("2023-11-14 22:13:20 +0000 UTC"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(
+            parse_docker_timestamp("2023-11-14 23:13:20 +0100 CET"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(parse_docker_timestamp("1970-01-01 00:00:00"), Some(0));
+        assert_eq!(parse_docker_timestamp("3 days ago"), None);
+        assert_eq!(parse_docker_timestamp(""), None);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("85%").unwrap(), 85);
+        assert_eq!(parse_percent("0").unwrap(), 0);
+        assert!(parse_percent("101%").is_err());
+        assert!(parse_percent("high").is_err());
+    }
+}
@@ -1,7 +1,14 @@
 use std::process::Command;
 
 fn dockerase() -> Command {
-    Command::new(env!("CARGO_BIN_EXE_dockerase"))
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+    // Keep tests independent of the developer's own config
+    cmd.env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml");
+    cmd.env(
+        "DOCKERASE_SYSTEM_CONFIG",
+        "/nonexistent/dockerase/system-config.toml",
+    );
+    cmd
 }
 
 #[test]
@@ -69,6 +76,131 @@ fn test_select_help() {
     assert!(stdout.contains("--dry-run"));
 }
 
+#[test]
+fn test_watch_help() {
+    let output = dockerase()
+        .args(["watch", "--help"])
+        .output()
+        .expect("Failed to run");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("advisory"));
+    assert!(stdout.contains("--interval"));
+    assert!(stdout.contains("--webhook"));
+}
+
+#[test]
+fn test_contexts_help() {
+    let output = dockerase()
+        .args(["contexts", "--help"])
+        .output()
+        .expect("Failed to run");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("contexts"));
+}
+
+#[test]
+fn test_watch_rejects_invalid_interval() {
+    let output = dockerase()
+        .args(["watch", "--interval", "soon"])
+        .output()
+        .expect("Failed to run");
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_system_list_json_output() {
+    let output = dockerase()
+        .args(["system", "--output", "json"])
+        .output()
+        .expect("Failed to run");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let report: serde_json::Value = serde_json::from_str(&stdout).expect("Expected valid JSON");
+    assert_eq!(report["kind"], "caches");
+    assert!(report["caches"].is_array());
+}
+
+#[test]
+fn test_system_purge_dry_run_quiet_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let report_path = dir.path().join("report.json");
+
+    let output = dockerase()
+        .args(["system", "purge", "--dry-run", "--quiet", "--report"])
+        .arg(&report_path)
+        .output()
+        .expect("Failed to run");
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty(), "Quiet mode should print nothing");
+
+    let written = std::fs::read_to_string(&report_path).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&written).unwrap();
+    assert_eq!(report["kind"], "cleanup");
+    assert_eq!(report["dry_run"], true);
+}
+
+#[test]
+fn test_invalid_config_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(&config_path, "[caches.npm]\nafter_purge = \"explode\"\n").unwrap();
+
+    let output = dockerase()
+        .env("DOCKERASE_CONFIG", &config_path)
+        .arg("system")
+        .output()
+        .expect("Failed to run");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid config"));
+
+    // `--version` still works, without the aliases
+    let output = dockerase()
+        .env("DOCKERASE_CONFIG", &config_path)
+        .arg("--version")
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn test_config_alias_runs_its_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[alias]\nwhere = \"paths\"\nw = [\"where\"]\n",
+    )
+    .unwrap();
+
+    let output = dockerase()
+        .env("DOCKERASE_CONFIG", &config_path)
+        .args(["w", "--output", "json"])
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["kind"], "paths");
+
+    std::fs::write(&config_path, "[alias]\npurge = \"paths\"\n").unwrap();
+    let output = dockerase()
+        .env("DOCKERASE_CONFIG", &config_path)
+        .arg("paths")
+        .output()
+        .expect("Failed to run");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("built-in command"), "{stderr}");
+}
+
 #[test]
 fn test_invalid_command() {
     let output = dockerase()
@@ -158,3 +290,73 @@ fn test_select_dry_run_force() {
         );
     }
 }
+
+#[test]
+fn test_purge_rejects_invalid_older_than() {
+    let output = dockerase()
+        .args(["purge", "--older-than", "soon"])
+        .output()
+        .expect("Failed to run");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--older-than"));
+}
+
+#[test]
+fn test_influx_output_is_usage_only() {
+    let output = dockerase()
+        .args(["purge", "--dry-run", "--output", "influx"])
+        .output()
+        .expect("Failed to run");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("dockerase usage"));
+}
+
+#[test]
+fn test_paths_follow_xdg_dirs() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dockerase()
+        .args(["paths", "--output", "json"])
+        .env_remove("DOCKERASE_CONFIG")
+        .env("XDG_CONFIG_HOME", dir.path().join("config"))
+        .env("XDG_STATE_HOME", dir.path().join("state"))
+        .env("XDG_CACHE_HOME", dir.path().join("cache"))
+        .env("XDG_DATA_HOME", dir.path().join("data"))
+        .output()
+        .expect("Failed to run");
+
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let path = |name: &str| {
+        report["paths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"] == name)
+            .and_then(|p| p["path"].as_str())
+            .map(std::path::PathBuf::from)
+            .unwrap()
+    };
+    assert_eq!(
+        path("config"),
+        dir.path().join("config/dockerase/config.toml")
+    );
+    assert_eq!(
+        path("undo history"),
+        dir.path().join("state/dockerase/history")
+    );
+    assert_eq!(path("cache"), dir.path().join("cache/dockerase"));
+}
+
+#[test]
+fn test_interactive_needs_a_terminal() {
+    let output = dockerase()
+        .arg("interactive")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to run");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs a terminal"));
+}
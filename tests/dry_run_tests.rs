@@ -0,0 +1,2681 @@
+//! Runs each cleanup command against a fake docker CLI
+//! (`tests/fixtures/fake-docker.sh`, fed by the fixtures in
+//! `tests/fixtures/docker`) and checks that a dry run plans exactly what a
+//! real run then does, and that dry runs and unconfirmed runs never touch
+//! anything.
+#![cfg(unix)]
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+/// Invocations that only read state; anything else counts as a change.
+const READ_ONLY: &[&str] = &[
+    "version",
+    "system df",
+    "info",
+    "images",
+    "ps",
+    "volume ls",
+    "volume inspect",
+    "network ls",
+    "inspect",
+    "image inspect",
+    "history",
+    "scout",
+    "buildx ls",
+    "buildx du",
+];
+
+/// The fixtures' anonymous volume, left behind by a removed container.
+const ORPHAN: &str = "4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f4f";
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+struct FakeDocker {
+    dir: TempDir,
+    fixtures: PathBuf,
+    /// Commands starting with this fail with "no space left on device"
+    no_space: Option<&'static str>,
+}
+
+impl FakeDocker {
+    fn new() -> Self {
+        Self {
+            dir: tempfile::tempdir().unwrap(),
+            fixtures: fixtures_dir().join("docker"),
+            no_space: None,
+        }
+    }
+
+    /// A daemon serving the fixtures in `tests/fixtures/<name>` instead.
+    fn with_fixtures(name: &str) -> Self {
+        Self {
+            fixtures: fixtures_dir().join(name),
+            ..Self::new()
+        }
+    }
+
+    /// A daemon whose disk is full, failing every command starting with `prefix`.
+    fn out_of_space(prefix: &'static str) -> Self {
+        Self {
+            no_space: Some(prefix),
+            ..Self::new()
+        }
+    }
+
+    fn log_path(&self) -> PathBuf {
+        self.dir.path().join("docker.log")
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+        cmd.args(args)
+            .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+            .env(
+                "DOCKERASE_SYSTEM_CONFIG",
+                "/nonexistent/dockerase/system-config.toml",
+            )
+            .env("DOCKERASE_DOCKER", fixtures_dir().join("fake-docker.sh"))
+            .env("FAKE_DOCKER_FIXTURES", &self.fixtures)
+            .env("FAKE_DOCKER_LOG", self.log_path())
+            // Undo manifests and usage history stay out of the real state
+            // directory, and old state in the data directory is left alone
+            .env("XDG_STATE_HOME", self.dir.path())
+            .env("XDG_DATA_HOME", self.dir.path())
+            // No terminal: any confirmation prompt fails instead of waiting
+            .stdin(Stdio::null());
+        if let Some(prefix) = self.no_space {
+            cmd.env("FAKE_DOCKER_NO_SPACE", prefix);
+        }
+        cmd
+    }
+
+    /// Run with `--output json` and return whether it succeeded and the report.
+    fn run(&self, args: &[&str]) -> (bool, Value) {
+        run_json(self.command(args))
+    }
+
+    /// Docker invocations that would change something, in order.
+    fn changes(&self) -> Vec<String> {
+        self.calls()
+            .into_iter()
+            .filter(|line| {
+                let command = without_target(line);
+                !READ_ONLY.iter().any(|cmd| command.starts_with(cmd))
+            })
+            .collect()
+    }
+
+    /// Every docker invocation, in order.
+    fn calls(&self) -> Vec<String> {
+        fs::read_to_string(self.log_path())
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Like [`FakeDocker::command`], reading `config` as the config file.
+    fn command_with_config(&self, args: &[&str], config: &str) -> Command {
+        let path = self.dir.path().join("config.toml");
+        fs::write(&path, config).unwrap();
+        let mut cmd = self.command(args);
+        cmd.env("DOCKERASE_CONFIG", path);
+        cmd
+    }
+
+    fn clear_log(&self) {
+        fs::remove_file(self.log_path()).ok();
+    }
+
+    /// Serve a copy of the fixtures from the tempdir, so a test can change
+    /// them. Returns the copy.
+    fn own_fixtures(&mut self) -> PathBuf {
+        let fixtures = self.dir.path().join("fixtures");
+        if fixtures.exists() {
+            return fixtures;
+        }
+        fs::create_dir(&fixtures).unwrap();
+        for entry in fs::read_dir(&self.fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_file() {
+                fs::copy(&path, fixtures.join(path.file_name().unwrap())).unwrap();
+            }
+        }
+        self.fixtures = fixtures.clone();
+        fixtures
+    }
+
+    /// Give each named container a json-file log of `size` bytes in the
+    /// tempdir. Returns the paths.
+    fn with_logs(&mut self, logs: &[(&str, usize)]) -> Vec<PathBuf> {
+        let fixtures = self.own_fixtures();
+        let mut paths = Vec::new();
+        let mut listing = String::new();
+        for (name, size) in logs {
+            let log = self.dir.path().join(format!("{name}-json.log"));
+            fs::write(&log, vec![b'x'; *size]).unwrap();
+            listing += &format!("/{}\t{}\n", name, log.display());
+            paths.push(log);
+        }
+        fs::write(fixtures.join("log_paths.txt"), listing).unwrap();
+        paths
+    }
+}
+
+/// `line` without a leading `--context <name>` or `--host <host>` (or
+/// podman's `--connection` and `--url`).
+fn without_target(line: &str) -> &str {
+    match line.split_once(' ') {
+        Some(("--context" | "--host" | "--connection" | "--url", rest)) => {
+            rest.split_once(' ').map_or("", |(_, cmd)| cmd)
+        }
+        _ => line,
+    }
+}
+
+fn run_json(mut cmd: Command) -> (bool, Value) {
+    let output = cmd
+        .args(["--output", "json"])
+        .output()
+        .expect("Failed to run");
+    let report = serde_json::from_slice(&output.stdout).unwrap_or(Value::Null);
+    (output.status.success(), report)
+}
+
+/// `(category, name)` of each item in a cleanup report.
+fn plan(report: &Value) -> Vec<(String, String)> {
+    report["items"]
+        .as_array()
+        .expect("report has items")
+        .iter()
+        .map(|item| {
+            (
+                item["category"].as_str().unwrap().to_string(),
+                item["name"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn statuses(report: &Value) -> Vec<&str> {
+    report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["status"].as_str().unwrap())
+        .collect()
+}
+
+/// Dry-run, then run for real, checking the plan is unchanged and nothing
+/// happened during the dry run. Returns the plan and the changes made.
+fn dry_run_then_execute(
+    docker: &FakeDocker,
+    dry_run: &[&str],
+    execute: &[&str],
+) -> (Vec<(String, String)>, Vec<String>) {
+    let (ok, planned) = docker.run(dry_run);
+    assert!(ok, "dry run failed: {planned}");
+    assert_eq!(planned["dry_run"], true);
+    assert!(docker.changes().is_empty(), "dry run changed something");
+    assert!(statuses(&planned).iter().all(|s| *s == "planned"));
+
+    docker.clear_log();
+    let (ok, executed) = docker.run(execute);
+    assert!(ok, "execution failed: {executed}");
+    assert_eq!(executed["dry_run"], false);
+    assert!(statuses(&executed).iter().all(|s| *s == "removed"));
+
+    assert_eq!(plan(&planned), plan(&executed));
+    (plan(&executed), docker.changes())
+}
+
+#[test]
+fn test_purge_executes_exactly_the_plan() {
+    let docker = FakeDocker::new();
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["purge", "--dry-run", "--force"],
+        &["purge", "--force"],
+    );
+
+    // Only the orphaned anonymous volume goes; the unmounted named
+    // "scratch" volume is kept
+    assert!(plan.contains(&("volumes".to_string(), ORPHAN.to_string())));
+    assert!(!plan.iter().any(|(_, name)| name == "scratch"));
+    assert_eq!(
+        changes,
+        vec![
+            "container prune -f".to_string(),
+            "image prune -f".to_string(),
+            format!("volume rm -f {ORPHAN}"),
+            "network prune -f".to_string(),
+            "builder prune -f".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_select_executes_exactly_the_plan() {
+    let docker = FakeDocker::new();
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["select", "--dry-run", "--force", "--include-active"],
+        &["select", "--force", "--include-active"],
+    );
+
+    let categories: Vec<&str> = plan.iter().map(|(c, _)| c.as_str()).collect();
+    assert_eq!(
+        categories,
+        vec![
+            "containers",
+            "images",
+            "all_images",
+            "orphaned_volumes",
+            "volumes",
+            "all_volumes",
+            "networks",
+            "build_cache"
+        ]
+    );
+    // "ALL" selections subsume their narrower counterparts
+    assert_eq!(
+        changes,
+        vec![
+            "container prune -f".to_string(),
+            "image prune -af".to_string(),
+            format!("volume rm -f pgdata scratch {ORPHAN}"),
+            "network prune -f".to_string(),
+            "builder prune -af".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_nuclear_executes_exactly_the_plan() {
+    let docker = FakeDocker::new();
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["--nuclear", "--dry-run", "--include-active"],
+        &["--nuclear", "--force", "--include-active"],
+    );
+
+    let names = |category: &str| -> Vec<String> {
+        plan.iter()
+            .filter(|(c, _)| c == category)
+            .map(|(_, n)| n.clone())
+            .collect()
+    };
+    assert_eq!(names("containers"), vec!["db", "old-job"]);
+    assert_eq!(
+        names("images"),
+        vec!["postgres:16", "bbb222bbb222", "alpine:latest"]
+    );
+    assert_eq!(names("volumes"), vec!["pgdata", "scratch", ORPHAN]);
+    assert_eq!(names("networks"), vec!["app-net"]);
+    assert_eq!(names("build_cache"), vec!["all build cache"]);
+
+    assert_eq!(
+        changes,
+        vec![
+            "stop c0ffee000001".to_string(),
+            "rm -f c0ffee000001 c0ffee000002".to_string(),
+            "rmi -f postgres:16 sha256:bbb222bbb222bbb222 alpine:latest".to_string(),
+            format!("volume rm -f pgdata scratch {ORPHAN}"),
+            "network rm net000app".to_string(),
+            "builder prune -af".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_nuclear_dry_run_lists_each_operation_in_order() {
+    let docker = FakeDocker::new();
+    let output = docker
+        .command(&["--nuclear", "--dry-run", "--older-than", "7d"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let operations: Vec<&str> = stdout
+        .lines()
+        .skip_while(|l| *l != "Would run, in order:")
+        .skip(1)
+        .take_while(|l| !l.is_empty())
+        .map(|l| l.split_once(". ").unwrap().1)
+        .collect();
+    assert_eq!(
+        operations,
+        [
+            "stop db",
+            "rm -f db",
+            "rm -f old-job",
+            "rmi -f postgres:16",
+            "rmi -f bbb222bbb222",
+            "rmi -f alpine:latest",
+            "network rm app-net",
+            "builder prune --all --filter until=604800s",
+        ]
+    );
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_nuclear_stops_after_a_failed_removal_unless_continuing() {
+    let docker = FakeDocker::new();
+    let status = |report: &Value, name: &str| -> String {
+        let item = report["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|i| i["name"] == name)
+            .unwrap();
+        item["status"].as_str().unwrap().to_string()
+    };
+    let run = |extra: &[&str]| -> (Option<i32>, Value) {
+        let mut args = vec![
+            "--nuclear",
+            "--force",
+            "--include-active",
+            "--output",
+            "json",
+        ];
+        args.extend(extra);
+        let output = docker
+            .command(&args)
+            .env("FAKE_DOCKER_REFUSE", "sha256:bbb222bbb222bbb222")
+            .env("FAKE_DOCKER_IN_USE", "scratch")
+            .output()
+            .unwrap();
+        let report = serde_json::from_slice(&output.stdout).unwrap();
+        (output.status.code(), report)
+    };
+
+    // The failed batch is retried one image at a time; the refused image
+    // isn't in use, so it isn't tried again at the end
+    let (code, report) = run(&[]);
+    assert_eq!(code, Some(2));
+    assert_eq!(status(&report, "postgres:16"), "removed");
+    assert_eq!(status(&report, "bbb222bbb222"), "failed");
+    assert_eq!(status(&report, "alpine:latest"), "removed");
+    let refused = report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|i| i["name"] == "bbb222bbb222")
+        .unwrap();
+    assert!(refused["error"]
+        .as_str()
+        .unwrap()
+        .ends_with("device or resource busy"));
+    assert_eq!(
+        docker
+            .changes()
+            .iter()
+            .filter(|c| c.as_str() == "rmi -f sha256:bbb222bbb222bbb222")
+            .count(),
+        1
+    );
+    // Nothing after the images was attempted
+    assert_eq!(status(&report, "pgdata"), "failed");
+    assert!(!docker.changes().iter().any(|c| c.starts_with("volume rm")));
+
+    docker.clear_log();
+    let (code, report) = run(&["--continue-on-error"]);
+    assert_eq!(code, Some(2));
+    assert_eq!(status(&report, "bbb222bbb222"), "failed");
+    assert_eq!(status(&report, "pgdata"), "removed");
+    // A volume still in use is tried once more at the end, when what
+    // used it may be gone, then skipped rather than failed
+    assert_eq!(status(&report, "scratch"), "skipped");
+    assert_eq!(
+        docker
+            .changes()
+            .iter()
+            .filter(|c| c.as_str() == "volume rm -f scratch")
+            .count(),
+        2
+    );
+    assert_eq!(status(&report, "app-net"), "removed");
+    assert_eq!(status(&report, "all build cache"), "removed");
+}
+
+#[test]
+fn test_nuclear_removes_many_resources_in_batches() {
+    let mut docker = FakeDocker::new();
+    let fixtures = docker.own_fixtures();
+    let extra: Vec<String> = (1..=42).map(|i| format!("v{i:02}")).collect();
+    let mut listing = fs::read_to_string(fixtures.join("volumes.jsonl")).unwrap();
+    for name in &extra {
+        listing += &format!(
+            "{{\"Name\":\"{name}\",\"Driver\":\"local\",\"Mountpoint\":\"/var/lib/docker/volumes/{name}/_data\",\"Labels\":\"\"}}\n"
+        );
+    }
+    fs::write(fixtures.join("volumes.jsonl"), listing).unwrap();
+
+    let (ok, report) = docker.run(&["--nuclear", "--force", "--include-active"]);
+    assert!(ok, "{report}");
+    // Twenty at a time, so a long removal shows progress and can stop
+    // between batches
+    let removals: Vec<String> = docker
+        .changes()
+        .into_iter()
+        .filter(|c| c.starts_with("volume rm"))
+        .collect();
+    let mut names = vec![
+        "pgdata".to_string(),
+        "scratch".to_string(),
+        ORPHAN.to_string(),
+    ];
+    names.extend(extra);
+    let expected: Vec<String> = names
+        .chunks(20)
+        .map(|batch| format!("volume rm -f {}", batch.join(" ")))
+        .collect();
+    assert_eq!(removals, expected);
+    assert!(
+        statuses(&report).iter().all(|s| *s == "removed"),
+        "{report}"
+    );
+}
+
+#[test]
+fn test_dry_run_without_force_changes_nothing() {
+    for args in [
+        &["purge", "--dry-run"][..],
+        &["--dry-run", "purge"],
+        &["--nuclear", "--dry-run"],
+    ] {
+        let docker = FakeDocker::new();
+        let (ok, report) = docker.run(args);
+        assert!(ok, "{args:?} failed");
+        assert_eq!(report["dry_run"], true, "{args:?}");
+        assert!(docker.changes().is_empty(), "{args:?} changed something");
+    }
+}
+
+#[test]
+fn test_unconfirmed_run_changes_nothing() {
+    // Without --force the confirmation prompt can't be answered here, so
+    // the command must stop before doing anything
+    for args in [&["purge"][..], &["select"], &["--nuclear"]] {
+        let docker = FakeDocker::new();
+        let (ok, _) = docker.run(args);
+        assert!(!ok, "{args:?} ran without confirmation");
+        assert!(docker.changes().is_empty(), "{args:?} changed something");
+    }
+}
+
+#[test]
+fn test_global_force_matches_subcommand_force() {
+    let global = FakeDocker::new();
+    let (ok, _) = global.run(&["--force", "purge"]);
+    assert!(ok);
+
+    let local = FakeDocker::new();
+    let (ok, _) = local.run(&["purge", "--force"]);
+    assert!(ok);
+
+    assert!(!local.changes().is_empty());
+    assert_eq!(global.changes(), local.changes());
+}
+
+#[test]
+fn test_system_purge_executes_exactly_the_plan() {
+    // Caches live under a throwaway home directory
+    let home = tempfile::tempdir().unwrap();
+    let npm = home.path().join(".npm/_cacache");
+    let cargo = home.path().join(".cargo/git");
+    for dir in [&npm, &cargo] {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("blob"), vec![0u8; 64]).unwrap();
+    }
+
+    let system = |args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+        cmd.args(args)
+            .env("HOME", home.path())
+            // Cache locations that would point outside the fake home
+            .env_remove("XDG_CACHE_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("LOCALAPPDATA")
+            // Nix and Bazel state the fake home has none of
+            .env("NIX_STORE_DIR", home.path().join("nix/store"))
+            .env("TEST_TMPDIR", home.path().join(".cache/bazel"))
+            .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+            .env(
+                "DOCKERASE_SYSTEM_CONFIG",
+                "/nonexistent/dockerase/system-config.toml",
+            )
+            .stdin(Stdio::null());
+        run_json(cmd)
+    };
+
+    let (ok, planned) = system(&["system", "purge", "--dry-run"]);
+    assert!(ok);
+    assert!(npm.join("blob").exists() && cargo.join("blob").exists());
+    assert!(statuses(&planned).iter().all(|s| *s == "planned"));
+
+    let mut names: Vec<String> = plan(&planned).into_iter().map(|(_, n)| n).collect();
+    names.sort();
+    // Only the fake home's caches; checked before purging anything for real
+    assert_eq!(names, vec!["Cargo Git", "npm"]);
+
+    let (ok, executed) = system(&["system", "purge", "--force", "--jobs", "1"]);
+    assert!(ok);
+    assert!(statuses(&executed).iter().all(|s| *s == "removed"));
+
+    assert_eq!(plan(&planned), plan(&executed));
+    assert!(!npm.join("blob").exists() && !cargo.join("blob").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_system_purge_runs_cache_plugins_from_path() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = tempfile::tempdir().unwrap();
+    let bin = home.path().join("bin");
+    let log = home.path().join("plugin.log");
+    fs::create_dir_all(&bin).unwrap();
+    let plugin = bin.join("dockerase-cache-bazel");
+    fs::write(
+        &plugin,
+        format!(
+            r#"#!/bin/sh
+case "$1" in
+    list) echo '{{"items": [{{"name": "Bazel disk cache", "path": "{}/.cache/bazel", "size": 4096}}]}}' ;;
+    purge) echo "purge $2" >> "{}"; echo '{{"freed": 1000}}' ;;
+    *) exit 1 ;;
+esac
+"#,
+            home.path().display(),
+            log.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let system = |args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+        cmd.args(args)
+            .env("HOME", home.path())
+            .env("PATH", &path)
+            .env_remove("XDG_CACHE_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("LOCALAPPDATA")
+            .env("NIX_STORE_DIR", home.path().join("nix/store"))
+            .env("TEST_TMPDIR", home.path().join(".cache/bazel"))
+            .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+            .env(
+                "DOCKERASE_SYSTEM_CONFIG",
+                "/nonexistent/dockerase/system-config.toml",
+            )
+            .stdin(Stdio::null());
+        run_json(cmd)
+    };
+
+    let (ok, planned) = system(&["system", "purge", "--dry-run"]);
+    assert!(ok, "{planned}");
+    assert_eq!(
+        plan(&planned),
+        vec![("cache".to_string(), "Bazel disk cache".to_string())]
+    );
+    assert!(!log.exists());
+
+    // The plugin can't honor an age, so it is left alone
+    let (ok, report) = system(&["system", "purge", "--force", "--older-than", "30d"]);
+    assert!(ok, "{report}");
+    assert_eq!(statuses(&report), vec!["skipped"]);
+    assert!(!log.exists());
+
+    let (ok, report) = system(&["system", "purge", "--force"]);
+    assert!(ok, "{report}");
+    assert_eq!(statuses(&report), vec!["removed"]);
+    assert_eq!(report["space_freed"], 1000);
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "purge Bazel disk cache\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_system_purge_frees_nix_and_bazel_with_their_own_tools() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = tempfile::tempdir().unwrap();
+    let bin = home.path().join("bin");
+    let log = home.path().join("tools.log");
+    let store = home.path().join("nix/store");
+    let bazel_root = home.path().join(".cache/bazel");
+    fs::create_dir_all(&bin).unwrap();
+    fs::create_dir_all(store.join("aaa-dead")).unwrap();
+    fs::write(store.join("aaa-dead/lib"), vec![0u8; 100]).unwrap();
+    fs::create_dir_all(store.join("bbb-live")).unwrap();
+    fs::write(store.join("bbb-live/lib"), vec![0u8; 50]).unwrap();
+
+    let app = home.path().join("code/app");
+    fs::create_dir_all(&app).unwrap();
+    let user_root = bazel_root.join("_bazel_dev");
+    for (hash, workspace) in [
+        ("1111", app.clone()),
+        ("2222", home.path().join("code/gone")),
+    ] {
+        let base = user_root.join(hash);
+        fs::create_dir_all(base.join("execroot")).unwrap();
+        fs::write(base.join("execroot/out"), vec![0u8; 64]).unwrap();
+        fs::write(
+            base.join("DO_NOT_BUILD_HERE"),
+            workspace.display().to_string(),
+        )
+        .unwrap();
+    }
+    fs::create_dir_all(user_root.join("cache/repos")).unwrap();
+    fs::write(user_root.join("cache/repos/archive"), vec![0u8; 32]).unwrap();
+
+    let tools = [
+        (
+            "nix-store",
+            format!(
+                r#"echo "finding garbage collector roots..." >&2
+echo "{}/aaa-dead""#,
+                store.display()
+            ),
+        ),
+        (
+            "nix",
+            format!(
+                r#"echo "nix $*" >> "{}"; echo "1 store paths deleted, 0.10 KiB freed" >&2"#,
+                log.display()
+            ),
+        ),
+        (
+            "nix-collect-garbage",
+            format!(r#"echo "nix-collect-garbage $*" >> "{}""#, log.display()),
+        ),
+        (
+            "bazel",
+            format!(r#"echo "bazel $* in $PWD" >> "{}""#, log.display()),
+        ),
+    ];
+    for (name, script) in tools {
+        let path = bin.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let system = |args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+        cmd.args(args)
+            .env("HOME", home.path())
+            .env("PATH", &path)
+            .env("NIX_STORE_DIR", &store)
+            .env("TEST_TMPDIR", &bazel_root)
+            .env_remove("XDG_CACHE_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("LOCALAPPDATA")
+            .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+            .env(
+                "DOCKERASE_SYSTEM_CONFIG",
+                "/nonexistent/dockerase/system-config.toml",
+            )
+            .stdin(Stdio::null());
+        run_json(cmd)
+    };
+
+    let (ok, listed) = system(&["system"]);
+    assert!(ok, "{listed}");
+    let caches = listed["caches"].as_array().unwrap();
+    let cache = |name: &str| caches.iter().find(|c| c["name"] == name).unwrap();
+    // Only the dead store path counts
+    assert_eq!(cache("Nix Store")["size"], 100);
+    assert_eq!(cache("Nix Store")["tool"]["name"], "nix");
+    assert_eq!(cache("Bazel: ~/code/app")["tool"]["name"], "bazel");
+    assert!(cache("Bazel: ~/code/gone")["report_only"]
+        .as_str()
+        .unwrap()
+        .contains("rm -rf"));
+
+    let (ok, planned) = system(&["system", "purge", "--dry-run"]);
+    assert!(ok, "{planned}");
+    let mut names: Vec<String> = plan(&planned).into_iter().map(|(_, n)| n).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec!["Bazel Repository Cache", "Bazel: ~/code/app", "Nix Store"]
+    );
+    assert!(!log.exists());
+
+    // Nix can trim by age through its generations; a Bazel output base can't
+    let (ok, report) = system(&["system", "purge", "--force", "--older-than", "30d"]);
+    assert!(ok, "{report}");
+    let items = report["items"].as_array().unwrap();
+    let status = |name: &str| items.iter().find(|i| i["name"] == name).unwrap()["status"].clone();
+    assert_eq!(status("Nix Store"), "removed");
+    assert_eq!(status("Bazel: ~/code/app"), "skipped");
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "nix-collect-garbage --delete-older-than 30d\n"
+    );
+    fs::remove_file(&log).unwrap();
+
+    let (ok, report) = system(&["system", "purge", "--force", "--jobs", "1"]);
+    assert!(ok, "{report}");
+    assert!(statuses(&report).iter().all(|s| *s == "removed"));
+    let mut calls: Vec<String> = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    calls.sort();
+    assert_eq!(
+        calls,
+        vec![
+            format!(
+                "bazel --output_base={} clean --expunge in {}",
+                user_root.join("1111").display(),
+                app.display()
+            ),
+            "nix --extra-experimental-features nix-command store gc".to_string(),
+        ]
+    );
+    // The repository cache is plain files; the gone workspace's output base
+    // is left for the user
+    assert!(!user_root.join("cache/repos/archive").exists());
+    assert!(user_root.join("2222/execroot/out").exists());
+}
+
+#[test]
+fn test_system_purge_trash_moves_caches_to_the_trash() {
+    let home = tempfile::tempdir().unwrap();
+    let npm = home.path().join(".npm/_cacache");
+    let trash = home.path().join(".local/share/Trash");
+    fs::create_dir_all(&npm).unwrap();
+    fs::write(npm.join("blob"), vec![0u8; 64]).unwrap();
+    fs::create_dir_all(trash.join("files")).unwrap();
+    fs::write(trash.join("files/old-download"), b"x").unwrap();
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+    cmd.args(["system", "purge", "--trash", "--force", "--jobs", "1"])
+        .env("HOME", home.path())
+        .env_remove("XDG_CACHE_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("XDG_STATE_HOME")
+        .env_remove("LOCALAPPDATA")
+        .env("NIX_STORE_DIR", home.path().join("nix/store"))
+        .env("TEST_TMPDIR", home.path().join(".cache/bazel"))
+        .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+        .env(
+            "DOCKERASE_SYSTEM_CONFIG",
+            "/nonexistent/dockerase/system-config.toml",
+        )
+        .stdin(Stdio::null());
+    let (ok, report) = run_json(cmd);
+    assert!(ok);
+
+    let items = report["items"].as_array().unwrap();
+    let item = |name: &str| items.iter().find(|i| i["name"] == name).unwrap();
+    assert_eq!(item("npm")["status"], "removed");
+    assert_eq!(item("npm")["note"], "moved to the trash");
+    // The trash itself isn't emptied
+    assert_eq!(item("Trash")["status"], "skipped");
+    assert!(trash.join("files/old-download").exists());
+
+    assert!(!npm.join("blob").exists());
+    assert!(trash.join("files/_cacache/blob").exists());
+    let info = fs::read_to_string(trash.join("info/_cacache.trashinfo")).unwrap();
+    assert!(info.contains(&format!("Path={}", npm.display())), "{info}");
+    assert_eq!(report["space_freed"], 0);
+}
+
+#[test]
+fn test_system_purge_older_than_trims_each_cache() {
+    let home = tempfile::tempdir().unwrap();
+    let npm = home.path().join(".npm/_cacache");
+    fs::create_dir_all(&npm).unwrap();
+    fs::write(npm.join("old"), vec![0u8; 64]).unwrap();
+    fs::write(npm.join("fresh"), vec![0u8; 8]).unwrap();
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(90 * 86_400);
+    let file = fs::File::options()
+        .write(true)
+        .open(npm.join("old"))
+        .unwrap();
+    file.set_times(fs::FileTimes::new().set_accessed(old).set_modified(old))
+        .unwrap();
+
+    let system = |args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+        cmd.args(["system", "purge", "--older-than", "30d"])
+            .args(args)
+            .env("HOME", home.path())
+            .env_remove("XDG_CACHE_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("LOCALAPPDATA")
+            .env("NIX_STORE_DIR", home.path().join("nix/store"))
+            .env("TEST_TMPDIR", home.path().join(".cache/bazel"))
+            .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+            .env(
+                "DOCKERASE_SYSTEM_CONFIG",
+                "/nonexistent/dockerase/system-config.toml",
+            )
+            .stdin(Stdio::null());
+        run_json(cmd)
+    };
+
+    let (ok, planned) = system(&["--dry-run"]);
+    assert!(ok);
+    let npm_item = |report: &Value| {
+        report["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|i| i["name"] == "npm")
+            .cloned()
+            .unwrap()
+    };
+    // Only the old file counts
+    assert_eq!(npm_item(&planned)["size"], 64);
+
+    let (ok, executed) = system(&["--force", "--jobs", "1"]);
+    assert!(ok);
+    let item = npm_item(&executed);
+    assert_eq!(item["status"], "removed");
+    assert!(
+        item["note"].as_str().unwrap().starts_with("64 B freed"),
+        "{item}"
+    );
+    assert!(!npm.join("old").exists() && npm.join("fresh").exists());
+}
+
+#[test]
+fn test_system_scan_deletes_exactly_the_stale_artifacts() {
+    let home = tempfile::tempdir().unwrap();
+    let code = home.path().join("code");
+    let old = code.join("shop/node_modules");
+    let fresh = code.join("blog/node_modules");
+    let month = std::time::Duration::from_secs(30 * 24 * 60 * 60);
+    for (dir, age) in [(&old, 3 * month), (&fresh, month)] {
+        fs::create_dir_all(dir).unwrap();
+        let file = dir.join("index.js");
+        fs::write(&file, vec![0u8; 64]).unwrap();
+        let modified = std::time::SystemTime::now() - age;
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    let scan = |args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+        cmd.args(["system", "scan"])
+            .arg(&code)
+            .args(args)
+            .env("HOME", home.path())
+            .env_remove("XDG_STATE_HOME")
+            .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+            .env(
+                "DOCKERASE_SYSTEM_CONFIG",
+                "/nonexistent/dockerase/system-config.toml",
+            )
+            .stdin(Stdio::null());
+        run_json(cmd)
+    };
+
+    let (ok, planned) = scan(&["--dry-run"]);
+    assert!(ok);
+    assert!(old.exists() && fresh.exists());
+    let old = fs::canonicalize(&old).unwrap();
+    assert_eq!(
+        plan(&planned),
+        [("artifact".to_string(), old.display().to_string())]
+    );
+
+    let (ok, executed) = scan(&["--force"]);
+    assert!(ok);
+    assert_eq!(statuses(&executed), ["removed"]);
+    assert_eq!(plan(&planned), plan(&executed));
+    assert!(!old.exists() && fresh.exists());
+
+    // A shorter threshold takes the younger one too
+    let (_, planned) = scan(&["--dry-run", "--older-than", "7d"]);
+    assert_eq!(plan(&planned).len(), 1);
+}
+
+#[test]
+fn test_system_scan_takes_rust_targets_a_shared_target_dir_replaced() {
+    let home = tempfile::tempdir().unwrap();
+    let cargo_config = home.path().join(".cargo/config.toml");
+    fs::create_dir_all(cargo_config.parent().unwrap()).unwrap();
+    fs::write(&cargo_config, "[build]\ntarget-dir = \"builds\"\n").unwrap();
+    let code = home.path().join("code");
+    for project in ["api", "cli", "web"] {
+        let deps = code.join(project).join("target/debug/deps");
+        fs::create_dir_all(&deps).unwrap();
+        fs::write(deps.join("libserde-1a2b.rlib"), vec![0u8; 64]).unwrap();
+        fs::write(code.join(project).join("Cargo.toml"), "[package]\n").unwrap();
+    }
+
+    let scan = |args: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+        cmd.args(["system", "scan"])
+            .arg(&code)
+            .args(args)
+            .env("HOME", home.path())
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("CARGO_HOME")
+            .env_remove("CARGO_TARGET_DIR")
+            .env_remove("RUSTC_WRAPPER")
+            .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+            .env(
+                "DOCKERASE_SYSTEM_CONFIG",
+                "/nonexistent/dockerase/system-config.toml",
+            )
+            .stdin(Stdio::null());
+        run_json(cmd)
+    };
+
+    // Freshly built, so not offered on age alone
+    let (ok, planned) = scan(&["--dry-run"]);
+    assert!(ok);
+    assert!(plan(&planned).is_empty(), "{planned}");
+
+    let (ok, planned) = scan(&["--dry-run", "--redundant-targets"]);
+    assert!(ok);
+    assert_eq!(plan(&planned).len(), 3, "{planned}");
+    let builds = home.path().join("builds");
+    assert_eq!(
+        planned["items"][0]["note"],
+        format!("builds go to {}", builds.display())
+    );
+
+    let (ok, executed) = scan(&["--force", "--redundant-targets"]);
+    assert!(ok);
+    assert_eq!(statuses(&executed), ["removed"; 3]);
+    assert!(!code.join("api/target").exists() && code.join("api/Cargo.toml").exists());
+}
+
+#[test]
+fn test_full_daemon_suggests_rescue_without_running_it() {
+    let docker = FakeDocker::out_of_space("system df");
+    let output = docker.command(&["purge", "--force"]).output().unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("dockerase rescue"), "{stdout}");
+    // Rescue ignores filters and [protect], so it never runs on its own
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_rescue_dry_run_changes_nothing() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["rescue", "--dry-run"]);
+    assert!(ok);
+    assert!(docker.changes().is_empty());
+    let categories: Vec<String> = plan(&report).into_iter().map(|(c, _)| c).collect();
+    assert_eq!(categories, vec!["build_cache", "containers", "images"]);
+}
+
+#[test]
+fn test_rescue_truncates_container_logs() {
+    let mut docker = FakeDocker::new();
+    let log = docker.with_logs(&[("db", 256)]).remove(0);
+
+    let (ok, report) = docker.run(&["rescue", "--dry-run"]);
+    assert!(ok);
+    assert_eq!(fs::metadata(&log).unwrap().len(), 256);
+    assert!(plan(&report).contains(&("container_logs".to_string(), "db".to_string())));
+
+    let (ok, report) = docker.run(&["rescue", "--force"]);
+    assert!(ok);
+    assert_eq!(fs::metadata(&log).unwrap().len(), 0);
+    assert_eq!(report["space_freed"], 256);
+}
+
+#[test]
+fn test_logs_prune_truncates_large_or_named_logs() {
+    let mut docker = FakeDocker::new();
+    let logs = docker.with_logs(&[("db", 4096), ("old-job", 64)]);
+    let size = |i: usize| fs::metadata(&logs[i]).unwrap().len();
+
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["logs", "prune", "--min-size", "1KB", "--dry-run"],
+        &["logs", "prune", "--min-size", "1KB", "--force"],
+    );
+    assert_eq!(plan, [("container_logs".to_string(), "db".to_string())]);
+    assert!(changes.is_empty());
+    assert_eq!((size(0), size(1)), (0, 64));
+
+    // A named container is truncated whatever its size
+    let (ok, report) = docker.run(&["logs", "prune", "old-job", "--force"]);
+    assert!(ok, "logs prune failed: {report}");
+    assert_eq!(size(1), 0);
+    assert_eq!(report["space_freed"], 64);
+
+    let (ok, _) = docker.run(&["logs", "prune", "missing", "--force"]);
+    assert!(!ok);
+}
+
+#[test]
+fn test_volumes_lists_size_age_and_users() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["volumes"]);
+    assert!(ok, "volumes failed: {report}");
+    assert_eq!(report["kind"], "volumes");
+
+    let volumes = report["volumes"].as_array().unwrap();
+    let names: Vec<&str> = volumes
+        .iter()
+        .map(|v| v["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["pgdata", "scratch", ORPHAN]);
+    assert_eq!(volumes[0]["used_by"], serde_json::json!(["db"]));
+    assert_eq!(volumes[0]["state"], "in use");
+    assert_eq!(volumes[1]["created_at"], "2024-02-10T08:15:00Z");
+    assert_eq!(volumes[1]["state"], "not mounted");
+    assert_eq!(volumes[2]["state"], "orphaned");
+    assert_eq!(report["total_size"], 300_000_000);
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_a_volume_gone_since_listing_leaves_the_others_dated() {
+    let mut docker = FakeDocker::new();
+    let fixtures = docker.own_fixtures();
+    let mut volumes = fs::read_to_string(fixtures.join("volumes.jsonl")).unwrap();
+    volumes += r#"{"Name":"gone","Driver":"local","Mountpoint":"","Labels":""}"#;
+    fs::write(fixtures.join("volumes.jsonl"), volumes + "\n").unwrap();
+
+    let (ok, report) = docker.run(&["volumes"]);
+    assert!(ok, "volumes failed: {report}");
+    let created: Vec<&Value> = report["volumes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| &v["created_at"])
+        .collect();
+    assert!(created.contains(&&Value::from("2024-02-10T08:15:00Z")));
+    assert!(docker
+        .calls()
+        .contains(&"volume inspect --format {{.Name}}\t{{.CreatedAt}} scratch".to_string()));
+}
+
+#[test]
+fn test_noted_volume_is_listed_and_kept_until_the_note_is_removed() {
+    let docker = FakeDocker::new();
+    let (ok, _) = docker.run(&["note", "volume", "scratch", "fixtures for the 2024 audit"]);
+    assert!(ok);
+
+    let (ok, report) = docker.run(&["note"]);
+    assert!(ok);
+    assert_eq!(report["kind"], "notes");
+    assert_eq!(report["notes"][0]["name"], "scratch");
+    assert_eq!(report["notes"][0]["kind"], "volume");
+
+    let (ok, report) = docker.run(&["volumes"]);
+    assert!(ok);
+    assert_eq!(report["volumes"][1]["note"], "fixtures for the 2024 audit");
+    assert!(report["volumes"][0].get("note").is_none());
+
+    let (ok, report) = docker.run(&["purge", "--dry-run", "--named-volumes"]);
+    assert!(ok, "{report}");
+    let items = report["items"].as_array().unwrap();
+    let scratch = items.iter().find(|i| i["name"] == "scratch").unwrap();
+    assert_eq!(scratch["status"], "skipped");
+    assert_eq!(scratch["note"], "noted: fixtures for the 2024 audit");
+
+    let (ok, _) = docker.run(&["note", "volume", "scratch", "--remove"]);
+    assert!(ok);
+    let (ok, report) = docker.run(&["purge", "--dry-run", "--named-volumes"]);
+    assert!(ok);
+    assert!(plan(&report).contains(&("volumes".to_string(), "scratch".to_string())));
+
+    let (ok, _) = docker.run(&["note", "volume", "scratch", "--remove"]);
+    assert!(!ok);
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_noted_image_is_shown_in_the_overview() {
+    let docker = FakeDocker::new();
+    let (ok, _) = docker.run(&["note", "image", "postgres", "base for the audit dumps"]);
+    assert!(ok);
+
+    let (ok, report) = docker.run(&["usage"]);
+    assert!(ok, "{report}");
+    let images = report["resources"]["images"].as_array().unwrap();
+    let postgres = images.iter().find(|i| i["name"] == "postgres:16").unwrap();
+    assert_eq!(postgres["note"], "base for the audit dumps");
+    let alpine = images
+        .iter()
+        .find(|i| i["name"] == "alpine:latest")
+        .unwrap();
+    assert!(alpine.get("note").is_none());
+
+    // Text output has no listing to gather
+    docker.clear_log();
+    let output = docker.command(&["usage", "--plain"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(!docker.calls().iter().any(|c| c.starts_with("images")));
+}
+
+#[test]
+fn test_corrupt_notes_stop_cleanups_and_are_not_overwritten() {
+    let docker = FakeDocker::new();
+    let notes = docker.dir.path().join("dockerase").join("notes.json");
+    fs::create_dir_all(notes.parent().unwrap()).unwrap();
+    fs::write(&notes, "[{\"kind\": \"volume\", \"name\": \"scr").unwrap();
+
+    let (ok, _) = docker.run(&["purge", "--force", "--named-volumes"]);
+    assert!(!ok);
+    let (ok, _) = docker.run(&["note", "volume", "pgdata", "keep"]);
+    assert!(!ok);
+    assert_eq!(
+        fs::read_to_string(&notes).unwrap(),
+        "[{\"kind\": \"volume\", \"name\": \"scr"
+    );
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_buildx_builders_are_listed_and_pruned_on_request() {
+    let mut docker = FakeDocker::new();
+    let fixtures = docker.own_fixtures();
+    fs::write(
+        fixtures.join("builders.jsonl"),
+        concat!(
+            r#"{"Name":"default","Driver":"docker","Nodes":[{"Status":"running"}]}"#,
+            "\n",
+            r#"{"Name":"ci","Driver":"docker-container","Nodes":[{"Status":"running"}]}"#,
+            "\n",
+            r#"{"Name":"arm","Driver":"docker-container","Nodes":[{"Status":"inactive"}]}"#,
+            "\n",
+            r#"{"Name":"team","Driver":"remote","Nodes":[{"Status":"running"}]}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    fs::write(
+        fixtures.join("buildx_du.txt"),
+        "Reclaimable:\t12GB\nTotal:\t\t12GB\n",
+    )
+    .unwrap();
+
+    // The default builder shares the daemon's cache; a stopped one isn't
+    // started just to measure it
+    let (ok, report) = docker.run(&["builders"]);
+    assert!(ok, "builders failed: {report}");
+    let builders = report["builders"].as_array().unwrap();
+    let names: Vec<&str> = builders
+        .iter()
+        .map(|b| b["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["ci", "team", "arm"]);
+    assert_eq!(builders[2]["cache_size"], Value::Null);
+    assert_eq!(report["total_size"], 24_000_000_000u64);
+    assert_eq!(
+        docker
+            .calls()
+            .iter()
+            .filter(|c| c.starts_with("buildx du"))
+            .count(),
+        2
+    );
+
+    // Plain purge leaves them alone
+    docker.clear_log();
+    let (ok, _) = docker.run(&["purge", "--force"]);
+    assert!(ok);
+    assert!(!docker.calls().iter().any(|c| c.starts_with("buildx")));
+
+    let (ok, report) = docker.run(&["purge", "--dry-run", "--builder", "ci"]);
+    assert!(ok, "{report}");
+    assert!(plan(&report).contains(&("build_cache".to_string(), "builder ci".to_string())));
+
+    docker.clear_log();
+    let (ok, _) = docker.run(&["purge", "--force", "--all-builders"]);
+    assert!(ok);
+    let pruned: Vec<String> = docker
+        .changes()
+        .into_iter()
+        .filter(|c| c.starts_with("buildx prune"))
+        .collect();
+    assert_eq!(
+        pruned,
+        [
+            "buildx prune --builder ci -f",
+            "buildx prune --builder arm -f"
+        ]
+    );
+
+    // A remote builder's cache may be shared, so only naming it prunes it
+    docker.clear_log();
+    let (ok, _) = docker.run(&["purge", "--force", "--builder", "team"]);
+    assert!(ok);
+    assert!(docker
+        .changes()
+        .contains(&"buildx prune --builder team -f".to_string()));
+
+    let (ok, _) = docker.run(&["purge", "--dry-run", "--builder", "nope"]);
+    assert!(!ok);
+
+    // --nuclear clears the running local builders before their containers
+    // go, leaving remote ones and never starting stopped ones
+    docker.clear_log();
+    let (ok, _) = docker.run(&["--nuclear", "--force"]);
+    assert!(ok);
+    let pruned: Vec<String> = docker
+        .changes()
+        .into_iter()
+        .filter(|c| c.starts_with("buildx prune"))
+        .collect();
+    assert_eq!(pruned, ["buildx prune --builder ci -f --all"]);
+    assert_eq!(docker.changes()[0], "buildx prune --builder ci -f --all");
+}
+
+#[test]
+fn test_bench_times_each_strategy_on_its_own_resources() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["bench", "--count", "2", "--jobs", "2"]);
+    assert!(ok, "bench failed: {report}");
+    assert_eq!(report["kind"], "bench");
+
+    // The API isn't timed against a substitute CLI's daemon
+    let results = report["results"].as_array().unwrap();
+    let runs: Vec<(&str, &str)> = results
+        .iter()
+        .map(|r| (r["kind"].as_str().unwrap(), r["strategy"].as_str().unwrap()))
+        .collect();
+    assert_eq!(runs.len(), 9);
+    assert_eq!(
+        &runs[..3],
+        [
+            ("containers", "batched"),
+            ("containers", "serial"),
+            ("containers", "parallel")
+        ]
+    );
+    assert!(results
+        .iter()
+        .all(|r| r["backend"] == "cli" && r["error"].is_null()));
+    assert_eq!(report["fastest"]["backend"], "cli");
+
+    // Everything created and removed is the run's own, down to the image
+    // the containers came from
+    let run = format!("dockerase-bench-{}", report["run_id"].as_str().unwrap());
+    let changes = docker.changes();
+    assert!(changes.iter().all(|c| c.contains(&run)), "{changes:#?}");
+    let created = changes
+        .iter()
+        .filter(|c| {
+            c.starts_with("import") || c.starts_with("create") || c.starts_with("volume create")
+        })
+        .count();
+    assert_eq!(created, 1 + 9 * 2);
+    assert_eq!(changes.last().unwrap(), &format!("rmi -f {run}:base"));
+
+    // Read-only mode creates nothing either
+    docker.clear_log();
+    let (ok, _) = run_json(docker.command_with_config(&["bench"], "read_only = true\n"));
+    assert!(!ok);
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_volumes_duplicates_reports_shared_files() {
+    let mut docker = FakeDocker::new();
+    let fixtures = docker.own_fixtures();
+    let mut listing = String::new();
+    for name in ["web-deps", "worker-deps", "pgdata"] {
+        let mountpoint = docker.dir.path().join("volumes").join(name);
+        fs::create_dir_all(mountpoint.join("node_modules")).unwrap();
+        let contents = if name == "pgdata" { b'p' } else { b'n' };
+        fs::write(
+            mountpoint.join("node_modules/react.js"),
+            vec![contents; 50_000],
+        )
+        .unwrap();
+        listing += &serde_json::json!({"Name": name, "Driver": "local", "Mountpoint": mountpoint})
+            .to_string();
+        listing += "\n";
+    }
+    fs::write(fixtures.join("volumes.jsonl"), listing).unwrap();
+
+    let (ok, report) = docker.run(&["volumes", "duplicates"]);
+    assert!(ok, "volumes duplicates failed: {report}");
+    assert_eq!(report["kind"], "duplicates");
+    assert_eq!(report["volumes_scanned"], 3);
+    assert_eq!(report["total_duplicated"], 50_000);
+    assert_eq!(
+        report["groups"][0]["volumes"],
+        serde_json::json!(["web-deps", "worker-deps"])
+    );
+    assert!(docker.changes().is_empty());
+
+    let (ok, _) = docker.run(&["volumes", "duplicates", "missing"]);
+    assert!(!ok);
+}
+
+#[test]
+fn test_reclaim_trims_the_docker_desktop_disk() {
+    let docker = FakeDocker::new();
+    let home = docker.dir.path().join("home");
+    let reclaim = |args: &[&str]| {
+        let mut cmd = docker.command(args);
+        cmd.env("HOME", &home);
+        run_json(cmd)
+    };
+
+    let (ok, _) = reclaim(&["reclaim", "--dry-run"]);
+    assert!(!ok, "no disk image to reclaim");
+
+    let data = home.join(".docker/desktop/vms/0/data");
+    fs::create_dir_all(&data).unwrap();
+    let disk = fs::File::create(data.join("Docker.raw")).unwrap();
+    disk.set_len(64_000_000_000).unwrap();
+
+    let (ok, report) = reclaim(&["reclaim", "--dry-run"]);
+    assert!(ok, "dry run failed: {report}");
+    let planned = plan(&report);
+    assert_eq!(planned.len(), 1);
+    assert_eq!(planned[0].0, "disk_image");
+    assert!(planned[0].1.ends_with("Docker.raw"));
+    assert!(docker.changes().is_empty());
+
+    // A native engine or remote host is never trimmed, even with a disk
+    // image on this machine
+    let mut native = docker.command(&["reclaim", "--force"]);
+    native
+        .env("HOME", &home)
+        .env("FAKE_DOCKER_OS", "Ubuntu 24.04 LTS");
+    let (ok, _) = run_json(native);
+    assert!(!ok);
+    assert!(docker.changes().is_empty());
+
+    let (ok, report) = reclaim(&["reclaim", "--force"]);
+    assert!(ok, "reclaim failed: {report}");
+    assert_eq!(statuses(&report), ["removed"]);
+    assert_eq!(
+        docker.changes(),
+        ["run --rm --privileged --pid=host alpine nsenter -t 1 -m -- fstrim -av"]
+    );
+}
+
+#[test]
+fn test_purge_passes_age_and_label_filters_to_prune() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&[
+        "purge",
+        "--force",
+        "--older-than",
+        "30d",
+        "--label",
+        "env=dev",
+    ]);
+    assert!(ok, "{report}");
+
+    assert_eq!(
+        docker.changes(),
+        vec![
+            "container prune -f --filter label=env=dev --filter until=2592000s",
+            "image prune -f --filter label=env=dev --filter until=2592000s",
+            "network prune -f --filter label=env=dev --filter until=2592000s",
+        ]
+    );
+    // Docker can't age-filter volumes or label-filter build cache
+    assert_eq!(
+        statuses(&report),
+        vec!["removed", "removed", "skipped", "removed", "skipped"]
+    );
+}
+
+#[test]
+fn test_purge_keep_removes_only_unmatched_resources() {
+    let docker = FakeDocker::new();
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["purge", "--dry-run", "--keep", "pg*"],
+        &["purge", "--force", "--keep", "pg*"],
+    );
+
+    assert!(plan.contains(&("containers".to_string(), "old-job".to_string())));
+    assert_eq!(
+        changes,
+        vec![
+            "rm -f c0ffee000002".to_string(),
+            "image prune -f".to_string(),
+            format!("volume rm -f {ORPHAN}"),
+            "network rm net000app".to_string(),
+            "builder prune -f".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_nuclear_keep_spares_matching_resources() {
+    let docker = FakeDocker::new();
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &[
+            "--nuclear",
+            "--dry-run",
+            "--include-active",
+            "--keep",
+            "postgres*",
+            "--keep",
+            "db",
+        ],
+        &[
+            "--nuclear",
+            "--force",
+            "--include-active",
+            "--keep",
+            "postgres*",
+            "--keep",
+            "db",
+        ],
+    );
+
+    assert!(!plan
+        .iter()
+        .any(|(_, name)| name == "db" || name == "postgres:16"));
+    // The running db container is kept, so nothing needs stopping
+    assert_eq!(
+        changes,
+        vec![
+            "rm -f c0ffee000002".to_string(),
+            "rmi -f sha256:bbb222bbb222bbb222 alpine:latest".to_string(),
+            format!("volume rm -f pgdata scratch {ORPHAN}"),
+            "network rm net000app".to_string(),
+            "builder prune -af".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_nuclear_leaves_active_resources_alone_by_default() {
+    let mut docker = FakeDocker::new();
+    let note = |report: &Value, name: &str| -> String {
+        let item = report["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|i| i["name"] == name)
+            .unwrap();
+        assert_eq!(item["status"], "skipped");
+        item["note"].as_str().unwrap().to_string()
+    };
+
+    // pgdata is mounted by the running db
+    let (ok, report) = docker.run(&["--nuclear", "--force"]);
+    assert!(ok, "{report}");
+    assert_eq!(
+        note(&report, "pgdata"),
+        "In active use: mounted by running db"
+    );
+    assert!(docker
+        .changes()
+        .contains(&format!("volume rm -f scratch {ORPHAN}")));
+
+    // A container docker restarts by itself keeps its image too
+    let fixtures = docker.own_fixtures();
+    fs::write(
+        fixtures.join("restart_policies.txt"),
+        "/db\tno\n/old-job\talways\n",
+    )
+    .unwrap();
+    docker.clear_log();
+    let (ok, report) = docker.run(&["--nuclear", "--force"]);
+    assert!(ok, "{report}");
+    assert_eq!(
+        note(&report, "old-job"),
+        "In active use: restarts by itself (restart: always)"
+    );
+    assert_eq!(
+        note(&report, "alpine:latest"),
+        "In active use: used by 'old-job', which restarts by itself (restart: always)"
+    );
+    let changes = docker.changes();
+    assert!(changes.contains(&"rm -f c0ffee000001".to_string()));
+    assert!(!changes.iter().any(|c| c.contains("ccc333")));
+
+    // --include-active takes them all
+    docker.clear_log();
+    let (ok, _) = docker.run(&["--nuclear", "--force", "--include-active"]);
+    assert!(ok);
+    assert!(docker
+        .changes()
+        .contains(&format!("volume rm -f pgdata scratch {ORPHAN}")));
+}
+
+#[test]
+fn test_select_offers_images_with_critical_cves() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["select", "--dry-run", "--force", "--cves"]);
+    assert!(ok, "{report}");
+    assert!(docker.changes().is_empty());
+
+    // postgres:16 has a critical CVE but the running db container uses it
+    let item = report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["category"] == "vulnerable_images")
+        .expect("vulnerable images offered");
+    assert!(item["name"].as_str().unwrap().contains("(1 images"));
+
+    let log = fs::read_to_string(docker.log_path()).unwrap();
+    assert!(
+        log.contains("scout cves --only-severity critical --format sarif local://alpine:latest")
+    );
+    // Dangling images aren't scanned
+    assert!(!log.contains("bbb222"));
+}
+
+#[test]
+fn test_purge_named_volumes_also_removes_unmounted_volumes() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["purge", "--force", "--named-volumes"]);
+    assert!(ok, "{report}");
+    // pgdata is mounted by the db container, so it stays either way
+    assert!(docker
+        .changes()
+        .contains(&format!("volume rm -f {ORPHAN} scratch")));
+}
+
+#[test]
+fn test_purge_ends_with_next_steps_for_what_is_left() {
+    let docker = FakeDocker::new();
+    let home = tempfile::tempdir().unwrap();
+    let run = |args: &[&str]| {
+        let output = docker
+            .command(args)
+            .arg("--plain")
+            .env("HOME", home.path())
+            .env_remove("XDG_CACHE_HOME")
+            .env_remove("LOCALAPPDATA")
+            .env("NIX_STORE_DIR", home.path().join("nix/store"))
+            .env("TEST_TMPDIR", home.path().join(".cache/bazel"))
+            .output()
+            .expect("Failed to run");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // The fixtures keep 1.5GB of unused images, which purge leaves alone
+    let stdout = run(&["purge", "--force"]);
+    assert!(stdout.contains("Next steps:"), "{stdout}");
+    assert!(
+        stdout.contains("1.5 GB of images no container uses - run dockerase select"),
+        "{stdout}"
+    );
+
+    // Nothing changed, so nothing to suggest
+    assert!(!run(&["purge", "--dry-run"]).contains("Next steps:"));
+}
+
+#[test]
+fn test_docker_sizes_match_the_docker_cli() {
+    let docker = FakeDocker::new();
+    let output = docker
+        .command(&["usage", "--plain", "--sizes", "docker"])
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // As df.jsonl has them, so the table lines up with `docker system df`
+    assert!(stdout.contains("Images        2.5GB   1.5GB"), "{stdout}");
+    assert!(stdout.contains("Total Reclaimable: 2.62GB"), "{stdout}");
+}
+
+#[test]
+fn test_quota_nags_every_run_while_exceeded() {
+    let docker = FakeDocker::new();
+    let over = "[quota]\nmax_usage = \"2GB\"\n";
+    let output = docker
+        .command_with_config(&["usage", "--plain"], over)
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Docker is using 3.6 GB, over its 2.0 GB quota"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("postgres:16"), "{stdout}");
+    assert!(stdout.contains("Run dockerase purge"), "{stdout}");
+
+    // Structured output stays parseable
+    let (ok, report) = run_json(docker.command_with_config(&["quota"], over));
+    assert!(ok, "{report}");
+    assert_eq!(report["exceeded"], true);
+    assert_eq!(report["offenders"][0]["name"], "postgres:16");
+
+    // The shell hook is silent within the quota
+    let within = "[quota]\nmax_usage = \"100GB\"\n";
+    let output = docker
+        .command_with_config(&["quota", "--nag"], within)
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty(), "{output:?}");
+
+    // Commands that don't talk to docker aren't held up measuring it, and
+    // the last hour's usage within the quota isn't measured again
+    let measured = |args: &[&str], config: &str| {
+        docker.clear_log();
+        let output = docker.command_with_config(args, config).output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        docker.calls().iter().any(|c| c.contains("system df"))
+    };
+    assert!(!measured(&["paths"], over));
+    assert!(measured(&["builders"], over));
+    assert!(!measured(&["builders"], within));
+
+    let output = docker.command(&["hook", "bash"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("dockerase quota --nag"));
+}
+
+#[test]
+fn test_record_appends_a_transcript_of_each_run() {
+    let docker = FakeDocker::new();
+    let transcript = docker.dir.path().join("transcript.txt");
+    let record = transcript.to_str().unwrap();
+    for args in [
+        vec!["purge", "--dry-run", "--record", record],
+        vec!["purge", "--dry-run", "--output", "json", "--record", record],
+    ] {
+        let output = docker.command(&args).output().expect("Failed to run");
+        assert!(output.status.success());
+    }
+
+    let text = fs::read_to_string(&transcript).unwrap();
+    let runs: Vec<&str> = text.split("=== ").skip(1).collect();
+    assert_eq!(runs.len(), 2, "{text}");
+    assert!(runs[0].contains("UTC: "), "{text}");
+    assert!(runs[0].contains("purge --dry-run --record"), "{text}");
+    assert!(
+        runs[0].contains("[DRY RUN] No changes will be made"),
+        "{text}"
+    );
+    assert!(runs[0].contains("Dry run - no changes made"), "{text}");
+    // Structured output is recorded as printed
+    assert!(runs[1].contains("\"dry_run\": true"), "{text}");
+}
+
+#[test]
+fn test_purge_dry_run_yaml_output() {
+    let docker = FakeDocker::new();
+    let output = docker
+        .command(&["purge", "--dry-run", "--output", "yaml"])
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("kind: cleanup"), "{stdout}");
+    assert!(stdout.contains("dry_run: true"));
+    assert!(stdout.contains(&format!("name: {ORPHAN}")));
+    // No human-oriented text mixed in
+    assert!(!stdout.contains("DRY RUN"));
+}
+
+#[test]
+fn test_nuclear_spares_protected_resources() {
+    let docker = FakeDocker::new();
+    let config = "[protect]\nimages = [\"postgres\"]\nvolumes = [\"pgdata\"]\n";
+    let (ok, report) = run_json(docker.command_with_config(&["--nuclear", "--force"], config));
+    assert!(ok, "{report}");
+
+    let protected: Vec<&str> = report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|item| item["note"] == "protected by config")
+        .map(|item| item["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(protected, vec!["postgres:16", "pgdata"]);
+    assert_eq!(
+        docker.changes(),
+        vec![
+            "stop c0ffee000001".to_string(),
+            "rm -f c0ffee000001 c0ffee000002".to_string(),
+            "rmi -f sha256:bbb222bbb222bbb222 alpine:latest".to_string(),
+            format!("volume rm -f scratch {ORPHAN}"),
+            "network rm net000app".to_string(),
+            "builder prune -af".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_a_spared_tag_survives_a_sibling_tag_of_the_same_image() {
+    // myorg/api:stable and myorg/api:latest are one image
+    let removals = |docker: &FakeDocker| -> Vec<String> {
+        docker
+            .changes()
+            .into_iter()
+            .filter(|c| c.starts_with("rmi"))
+            .collect()
+    };
+    let protected = "[protect]\nimages = [\"myorg/api:stable\"]\n";
+    for args in [&["--nuclear", "--force"][..], &["select", "--force"]] {
+        let docker = FakeDocker::with_fixtures("shared_tags");
+        let (ok, report) = run_json(docker.command_with_config(args, protected));
+        assert!(ok, "{args:?} failed: {report}");
+        let removals = removals(&docker);
+        assert!(
+            removals.iter().any(|c| c.contains("myorg/api:latest")),
+            "{args:?}: {removals:?}"
+        );
+        assert!(
+            !removals
+                .iter()
+                .any(|c| c.contains("fff666") || c.contains("myorg/api:stable")),
+            "{args:?}: {removals:?}"
+        );
+    }
+
+    // Kept by --keep, or by a note
+    let docker = FakeDocker::with_fixtures("shared_tags");
+    let (ok, report) = docker.run(&["--nuclear", "--force", "--keep", "myorg/api:stable"]);
+    assert!(ok, "{report}");
+    assert!(!removals(&docker).iter().any(|c| c.contains("fff666")));
+
+    let docker = FakeDocker::with_fixtures("shared_tags");
+    let (ok, _) = docker.run(&["note", "image", "myorg/api:stable", "what prod runs"]);
+    assert!(ok);
+    let (ok, report) = docker.run(&["--nuclear", "--force"]);
+    assert!(ok, "{report}");
+    let removals = removals(&docker);
+    assert!(removals.iter().any(|c| c.contains("myorg/api:latest")));
+    assert!(!removals
+        .iter()
+        .any(|c| c.contains("fff666") || c.contains("myorg/api:stable")));
+}
+
+#[test]
+fn test_select_spares_protected_volumes() {
+    let docker = FakeDocker::new();
+    let config = "[protect]\nvolumes = [\"scratch\"]\n";
+    let (ok, report) =
+        run_json(docker.command_with_config(&["select", "--force", "--include-active"], config));
+    assert!(ok, "{report}");
+    assert!(docker
+        .changes()
+        .contains(&format!("volume rm -f pgdata {ORPHAN}")));
+}
+
+#[test]
+fn test_config_default_dry_run_needs_no_dry_run_to_change_anything() {
+    let docker = FakeDocker::new();
+    let config = "[defaults]\ndry_run = true\nnamed_volumes = true\n";
+    let (ok, report) = run_json(docker.command_with_config(&["purge", "--force"], config));
+    assert!(ok, "{report}");
+    assert_eq!(report["dry_run"], true);
+    assert!(docker.changes().is_empty());
+
+    let (ok, report) =
+        run_json(docker.command_with_config(&["purge", "--force", "--no-dry-run"], config));
+    assert!(ok, "{report}");
+    assert_eq!(report["dry_run"], false);
+    assert!(docker
+        .changes()
+        .contains(&format!("volume rm -f {ORPHAN} scratch")));
+}
+
+#[test]
+fn test_host_overlay_for_the_current_context_applies() {
+    let config = "[hosts.\"prod-*\"]\ndefaults.dry_run = true\n";
+    for (context, changed) in [("prod-eu", false), ("dev", true)] {
+        let docker = FakeDocker::new();
+        let (ok, report) = run_json({
+            let mut cmd = docker.command_with_config(&["purge", "--force"], config);
+            cmd.env("DOCKER_CONTEXT", context);
+            cmd
+        });
+        assert!(ok, "{report}");
+        assert_eq!(!docker.changes().is_empty(), changed, "{context}");
+    }
+}
+
+#[test]
+fn test_system_config_policy_holds_against_the_user_config() {
+    let docker = FakeDocker::new();
+    let system = docker.dir.path().join("system.toml");
+    fs::write(
+        &system,
+        "[policy]\ndisable_nuclear = true\ndisabled = [\"volumes\"]\n",
+    )
+    .unwrap();
+    // The user config can't loosen the policy
+    let user = "[policy]\ndisable_nuclear = false\ndisabled = []\n";
+    let command = |args: &[&str]| {
+        let mut cmd = docker.command_with_config(args, user);
+        cmd.env("DOCKERASE_SYSTEM_CONFIG", &system);
+        cmd
+    };
+
+    let output = command(&["--nuclear", "--dry-run"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--nuclear is disabled"));
+
+    for args in [&["purge", "--force"][..], &["select", "--force"]] {
+        docker.clear_log();
+        let (ok, report) = run_json(command(args));
+        assert!(ok, "{args:?} failed: {report}");
+        let changes = docker.changes();
+        assert!(!changes.is_empty(), "{args:?}");
+        assert!(
+            !changes.iter().any(|c| c.starts_with("volume")),
+            "{args:?}: {changes:?}"
+        );
+    }
+    let (_, report) = run_json(command(&["purge", "--dry-run"]));
+    assert!(report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|item| item["category"] == "volumes" && item["note"] == "disabled by policy"));
+}
+
+#[test]
+fn test_usage_influx_output() {
+    let docker = FakeDocker::new();
+    let output = docker
+        .command(&["usage", "--output", "influx"])
+        .output()
+        .expect("Failed to run");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 5, "{stdout}");
+    assert!(lines[0].starts_with("docker_disk,type=images size="));
+    assert!(lines[4].starts_with("dockerase total_size="));
+    assert!(lines[4].contains("orphaned_volumes=1i"));
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_compose_groups_resources_by_project() {
+    let docker = FakeDocker::with_fixtures("compose");
+    let (ok, report) = docker.run(&["compose"]);
+    assert!(ok, "{report}");
+    assert_eq!(report["kind"], "compose");
+
+    let projects = report["projects"].as_array().unwrap();
+    let names: Vec<&str> = projects
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["blog", "shop"]);
+    let shop = &projects[1];
+    assert_eq!(shop["containers"], 2);
+    assert_eq!(shop["running"], 0);
+    assert_eq!(shop["images"], 1);
+    assert_eq!(shop["volumes"], 2);
+    assert_eq!(shop["networks"], 1);
+    // Writable layers (40MB + 10MB) plus the built shop-web image
+    assert_eq!(shop["size"], 350_000_000);
+    assert_eq!(projects[0]["running"], 1);
+}
+
+#[test]
+fn test_compose_purge_executes_exactly_the_plan() {
+    let docker = FakeDocker::with_fixtures("compose");
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["compose", "purge", "shop", "--dry-run"],
+        &["compose", "purge", "shop", "--force"],
+    );
+
+    // The pulled postgres image is shared, so it stays
+    assert!(!plan.iter().any(|(_, name)| name == "postgres:16"));
+    assert_eq!(
+        changes,
+        vec![
+            "rm -f 5b0b00000001 5b0b00000002",
+            "rmi -f sha256:5b0b5b0b5b0b5b0b5b",
+            "volume rm -f shop_pgdata shop_uploads",
+            "network rm net000shop",
+        ]
+    );
+}
+
+#[test]
+fn test_compose_purge_refuses_running_or_unknown_projects() {
+    for project in ["blog", "nope"] {
+        let docker = FakeDocker::with_fixtures("compose");
+        let output = docker
+            .command(&["compose", "purge", project, "--force"])
+            .output()
+            .expect("Failed to run");
+        assert!(!output.status.success(), "{project}");
+        assert!(docker.changes().is_empty(), "{project} changed something");
+    }
+}
+
+#[test]
+fn test_read_only_mode_never_changes_anything() {
+    for args in [
+        &["purge", "--force"][..],
+        &["select", "--force", "--no-dry-run"],
+        &["--nuclear", "--force"],
+        &["rescue", "--force"],
+    ] {
+        let docker = FakeDocker::new();
+        let (ok, report) = run_json({
+            let mut cmd = docker.command(args);
+            cmd.env("DOCKERASE_READ_ONLY", "1");
+            cmd
+        });
+        assert!(ok, "{args:?} failed: {report}");
+        assert_eq!(report["dry_run"], true, "{args:?}");
+        assert_eq!(report["read_only"], true, "{args:?}");
+        assert!(docker.changes().is_empty(), "{args:?} changed something");
+    }
+
+    // The same from the config file
+    let docker = FakeDocker::new();
+    let (ok, report) = run_json(
+        docker.command_with_config(&["purge", "--force", "--no-dry-run"], "read_only = true\n"),
+    );
+    assert!(ok, "{report}");
+    assert_eq!(report["read_only"], true);
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_read_only_mode_keeps_system_caches() {
+    let home = tempfile::tempdir().unwrap();
+    let npm = home.path().join(".npm/_cacache");
+    fs::create_dir_all(&npm).unwrap();
+    fs::write(npm.join("blob"), vec![0u8; 64]).unwrap();
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_dockerase"));
+    cmd.args(["system", "purge", "--force"])
+        .env("HOME", home.path())
+        .env_remove("XDG_CACHE_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("XDG_STATE_HOME")
+        .env_remove("LOCALAPPDATA")
+        .env("NIX_STORE_DIR", home.path().join("nix/store"))
+        .env("TEST_TMPDIR", home.path().join(".cache/bazel"))
+        .env("DOCKERASE_CONFIG", "/nonexistent/dockerase/config.toml")
+        .env(
+            "DOCKERASE_SYSTEM_CONFIG",
+            "/nonexistent/dockerase/system-config.toml",
+        )
+        .env("DOCKERASE_READ_ONLY", "1")
+        .stdin(Stdio::null());
+    let (ok, report) = run_json(cmd);
+    assert!(ok, "{report}");
+    assert_eq!(report["read_only"], true);
+    assert!(npm.join("blob").exists());
+}
+
+#[test]
+fn test_cleanup_records_undo_manifest() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["--nuclear", "--force"]);
+    assert!(ok, "{report}");
+
+    let (ok, history) = docker.run(&["history"]);
+    assert!(ok, "{history}");
+    let manifests = history["manifests"].as_array().unwrap();
+    assert_eq!(manifests.len(), 1);
+    assert_eq!(manifests[0]["command"], "nuclear");
+    let images: Vec<&str> = manifests[0]["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["repository"].as_str().unwrap())
+        .collect();
+    assert!(images.contains(&"postgres") && images.contains(&"<none>"));
+    assert!(manifests[0]["volumes"]
+        .as_array()
+        .unwrap()
+        .contains(&Value::from(ORPHAN)));
+
+    // Dry runs record nothing
+    let docker = FakeDocker::new();
+    docker.run(&["purge", "--dry-run"]);
+    let (_, history) = docker.run(&["history"]);
+    assert_eq!(history["manifests"], Value::Array(vec![]));
+}
+
+#[test]
+fn test_restore_pulls_back_missing_tagged_images() {
+    let docker = FakeDocker::new();
+    let history = docker.dir.path().join("dockerase/history");
+    fs::create_dir_all(&history).unwrap();
+    fs::write(
+        history.join("20240101-000000.json"),
+        r#"{"id":"20240101-000000","timestamp":1704067200,"command":"nuclear",
+            "images":[
+              {"id":"sha256:aaa111aaa111aaa111","repository":"postgres","tag":"16"},
+              {"id":"sha256:fff","repository":"redis","tag":"7","digest":"sha256:feed"},
+              {"id":"sha256:eee","repository":"<none>","tag":"<none>"}
+            ],
+            "volumes":["pgdata"]}"#,
+    )
+    .unwrap();
+
+    let (ok, planned) = docker.run(&["restore", "20240101-000000", "--dry-run"]);
+    assert!(ok, "{planned}");
+    assert!(docker.changes().is_empty());
+
+    let (ok, report) = docker.run(&["restore", "20240101-000000", "--force"]);
+    assert!(ok, "{report}");
+    // postgres:16 is still there and the untagged image has no name to pull
+    assert_eq!(
+        docker.changes(),
+        vec![
+            "pull --quiet redis@sha256:feed",
+            "tag redis@sha256:feed redis:7"
+        ]
+    );
+    assert_eq!(
+        statuses(&report),
+        vec!["skipped", "restored", "skipped", "skipped"]
+    );
+    assert_eq!(plan(&planned), plan(&report));
+
+    let (ok, _) = docker.run(&["restore", "nope", "--force"]);
+    assert!(!ok);
+}
+
+#[test]
+fn test_nuclear_exports_the_tagged_images_it_removes() {
+    let docker = FakeDocker::new();
+    let script = docker.dir.path().join("pulls.sh");
+    let (ok, report) = docker.run(&[
+        "--nuclear",
+        "--force",
+        "--export-images",
+        script.to_str().unwrap(),
+    ]);
+    assert!(ok, "{report}");
+    let pulls: Vec<String> = fs::read_to_string(&script)
+        .unwrap()
+        .lines()
+        .filter(|l| !l.starts_with('#') && l.contains(" pull "))
+        .map(str::to_string)
+        .collect();
+    // The untagged image has no name to pull back by
+    assert_eq!(
+        pulls,
+        ["docker pull 'postgres:16'", "docker pull 'alpine:latest'"]
+    );
+
+    // The same list from the history, as a compose file
+    let (_, history) = docker.run(&["history"]);
+    let id = history["manifests"][0]["id"].as_str().unwrap();
+    let compose = docker.dir.path().join("pulls.yml");
+    let (ok, _) = docker.run(&["restore", id, "--export", compose.to_str().unwrap()]);
+    assert!(ok);
+    let compose = fs::read_to_string(compose).unwrap();
+    assert!(
+        compose.contains("services:\n  postgres-16:\n    image: \"postgres:16\"\n"),
+        "{compose}"
+    );
+    assert!(docker.changes().iter().all(|c| !c.starts_with("pull")));
+}
+
+#[test]
+fn test_stats_count_real_cleanups_only() {
+    let docker = FakeDocker::new();
+    let (ok, stats) = docker.run(&["stats"]);
+    assert!(ok);
+    assert_eq!(stats["runs"], 0);
+
+    let (ok, _) = docker.run(&["--nuclear", "--dry-run"]);
+    assert!(ok);
+    let (ok, cleanup) = docker.run(&["--nuclear", "--force"]);
+    assert!(ok, "{cleanup}");
+
+    let (ok, stats) = docker.run(&["stats"]);
+    assert!(ok);
+    assert_eq!(stats["kind"], "stats");
+    assert_eq!(stats["runs"], 1);
+    assert_eq!(stats["space_freed"], cleanup["space_freed"]);
+    assert_eq!(stats["timeline"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_analyze_images_counts_shared_layers_once() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["analyze", "images", "--top", "2"]);
+    assert!(ok, "{report}");
+    assert_eq!(report["kind"], "layers");
+    // postgres:16 and the dangling image share their base and packages
+    assert_eq!(report["listed_size"], 2_500_000_000u64);
+    assert_eq!(report["disk_size"], 1_820_000_000u64);
+
+    let freed: Vec<(&str, u64)> = report["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| (i["name"].as_str().unwrap(), i["unique"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(
+        freed,
+        vec![
+            ("postgres:16", 520_000_000),
+            ("alpine:latest", 500_000_000),
+            ("bbb222bbb222", 120_000_000),
+        ]
+    );
+    assert_eq!(report["images"][0]["containers"], serde_json::json!(["db"]));
+    assert_eq!(report["shared_layers"].as_array().unwrap().len(), 2);
+    assert_eq!(report["largest_unique_layers"].as_array().unwrap().len(), 2);
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_analyze_ages_buckets_images_and_build_cache() {
+    let mut docker = FakeDocker::new();
+    let fixtures = docker.own_fixtures();
+    fs::write(
+        fixtures.join("df_volumes.json"),
+        r#"{"Volumes":[],"BuildCache":[
+            {"ID":"a1","Size":"300MB","CreatedSince":"2 months ago","LastUsedSince":"2 days ago"},
+            {"ID":"b2","Size":"200MB","CreatedSince":"6 weeks ago","LastUsedSince":"6 weeks ago"},
+            {"ID":"c3","Size":"10MB","CreatedSince":"","LastUsedSince":""}
+        ]}"#,
+    )
+    .unwrap();
+
+    let (ok, report) = docker.run(&["analyze", "ages"]);
+    assert!(ok, "{report}");
+    assert_eq!(report["kind"], "ages");
+    let buckets: Vec<(&str, u64, u64)> = report["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| {
+            (
+                b["label"].as_str().unwrap(),
+                b["images"].as_u64().unwrap(),
+                b["build_cache"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    // The fixture images were all built in January 2024
+    assert_eq!(
+        buckets,
+        vec![
+            ("<1w", 0, 300_000_000),
+            ("1-4w", 0, 0),
+            ("1-3mo", 0, 200_000_000),
+            (">3mo", 2_500_000_000, 0),
+            ("unknown", 0, 10_000_000),
+        ]
+    );
+    assert!(docker.changes().is_empty());
+
+    let output = docker.command(&["analyze", "ages"]).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("the most `--older-than 90d` could free"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_check_exits_3_above_the_limit_without_changing_anything() {
+    let docker = FakeDocker::new();
+    let output = docker
+        .command(&["check", "--max-reclaimable", "1GB", "--output", "json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["kind"], "check");
+    assert_eq!(report["exceeded"], true);
+    assert!(docker.changes().is_empty());
+
+    let (ok, report) = docker.run(&["check", "--fail-above", "100GB"]);
+    assert!(ok);
+    assert_eq!(report["exceeded"], false);
+
+    // --auto in a dry run frees nothing, so the check still fails
+    let output = docker
+        .command(&["check", "--max-reclaimable", "1GB", "--auto", "--dry-run"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_check_auto_purges_without_prompting() {
+    let mut docker = FakeDocker::new();
+    let fixtures = docker.own_fixtures();
+    fs::write(
+        fixtures.join("df_pruned.jsonl"),
+        concat!(
+            "{\"Active\":\"1\",\"Reclaimable\":\"600MB (40%)\",\"Size\":\"1.5GB\",\"TotalCount\":2,\"Type\":\"Images\"}\n",
+            "{\"Active\":\"1\",\"Reclaimable\":\"0B (0%)\",\"Size\":\"30MB\",\"TotalCount\":1,\"Type\":\"Containers\"}\n",
+            "{\"Active\":\"0\",\"Reclaimable\":\"0B\",\"Size\":\"0B\",\"TotalCount\":0,\"Type\":\"Local Volumes\"}\n",
+            "{\"Active\":\"0\",\"Reclaimable\":\"0B\",\"Size\":\"0B\",\"TotalCount\":0,\"Type\":\"Build Cache\"}\n",
+        ),
+    )
+    .unwrap();
+    let (ok, report) = docker.run(&["check", "--max-reclaimable", "1GB", "--auto"]);
+    assert!(ok, "check failed: {report}");
+    assert_eq!(report["reclaimable_after"], 600_000_000);
+    assert_eq!(report["cleanups"][0]["command"], "purge");
+    assert!(statuses(&report["cleanups"][0])
+        .iter()
+        .all(|s| *s == "removed"));
+    assert_eq!(
+        docker.changes(),
+        vec![
+            "container prune -f".to_string(),
+            "image prune -f".to_string(),
+            format!("volume rm -f {ORPHAN}"),
+            "network prune -f".to_string(),
+            "builder prune -f".to_string(),
+        ]
+    );
+
+    // A purge that leaves more than the limit doesn't pass the check
+    let output = docker
+        .command(&[
+            "check",
+            "--max-reclaimable",
+            "500MB",
+            "--auto",
+            "--output",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn test_watch_logs_each_run_and_only_purges_above_a_threshold() {
+    let docker = FakeDocker::new();
+    let output = docker
+        .command(&[
+            "watch",
+            "--once",
+            "--purge",
+            "--when-disk-above",
+            "99%",
+            "--when-reclaimable-above",
+            "100GB",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(docker.changes().is_empty());
+
+    let log = fs::read_to_string(docker.dir.path().join("dockerase/watch_log.jsonl")).unwrap();
+    let runs: Vec<Value> = log
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0]["reclaimable"], 2_620_000_000u64);
+    assert_eq!(runs[0]["breaches"], serde_json::json!([]));
+    assert!(runs[0].get("space_freed").is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_watch_postpones_the_purge_outside_its_window_or_during_a_build() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut docker = FakeDocker::new();
+    let fixtures = docker.own_fixtures();
+    // Wednesday noon, local time
+    let bin = docker.dir.path().join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    fs::write(bin.join("date"), "#!/bin/sh\necho '3 12:00'\n").unwrap();
+    fs::set_permissions(bin.join("date"), fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let watch = |extra: &[&str]| {
+        let mut args = vec![
+            "watch",
+            "--once",
+            "--purge",
+            "--when-reclaimable-above",
+            "1GB",
+        ];
+        args.extend_from_slice(extra);
+        let output = docker.command(&args).env("PATH", &path).output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let stdout = watch(&["--window", "Mon-Fri 19:00-07:00", "--window", "Sat-Sun"]);
+    assert!(
+        stdout.contains("Purging only within Mon-Fri 19:00-07:00 or Sat-Sun"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("Purge postponed: outside Mon-Fri 19:00-07:00 or Sat-Sun"),
+        "{stdout}"
+    );
+    assert!(docker.changes().is_empty());
+
+    fs::write(
+        fixtures.join("df_volumes.json"),
+        r#"{"Volumes":[],"BuildCache":[{"ID":"a1","Size":"1GB","LastUsedSince":"Less than a second ago","InUse":true}]}"#,
+    )
+    .unwrap();
+    let stdout = watch(&["--window", "Wed 11:00-13:00", "--when-idle"]);
+    assert!(
+        stdout.contains("Purge postponed: a docker build is running"),
+        "{stdout}"
+    );
+    assert!(docker.changes().is_empty());
+
+    let log = fs::read_to_string(docker.dir.path().join("dockerase/watch_log.jsonl")).unwrap();
+    let postponed: Vec<String> = log
+        .lines()
+        .map(|l| serde_json::from_str::<Value>(l).unwrap()["postponed"].to_string())
+        .collect();
+    assert_eq!(
+        postponed,
+        vec![
+            "\"outside Mon-Fri 19:00-07:00 or Sat-Sun\"",
+            "\"a docker build is running\"",
+        ]
+    );
+
+    // Inside the window, the purge runs
+    let stdout = watch(&["--window", "Wed 11:00-13:00"]);
+    assert!(!stdout.contains("Purge postponed"), "{stdout}");
+    assert!(!docker.changes().is_empty());
+}
+
+#[test]
+fn test_context_and_host_flags_target_every_docker_call() {
+    let docker = FakeDocker::new();
+    let (ok, report) = docker.run(&["purge", "--force", "--context", "remote-builder"]);
+    assert!(ok, "purge failed: {report}");
+    assert!(docker
+        .calls()
+        .iter()
+        .all(|c| c.starts_with("--context remote-builder ")));
+    assert_eq!(docker.changes().len(), 5);
+
+    docker.clear_log();
+    let (ok, _) = docker.run(&["purge", "--dry-run", "-H", "ssh://builder"]);
+    assert!(ok);
+    let calls = docker.calls();
+    assert!(!calls.is_empty());
+    assert!(calls.iter().all(|c| c.starts_with("--host ssh://builder ")));
+
+    // Host overlays match the context picked on the command line
+    docker.clear_log();
+    let (_, report) = run_json(docker.command_with_config(
+        &["purge", "--force", "--context", "remote-builder"],
+        "[hosts.remote-builder]\nread_only = true\n",
+    ));
+    assert_eq!(report["read_only"], true);
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_grace_period_holds_tagged_images_until_a_later_purge() {
+    let docker = FakeDocker::with_fixtures("grace");
+    let holds_path = docker.dir.path().join("dockerase/image_holds.json");
+
+    // select moves the unused tagged image to a holding tag
+    let (ok, report) = docker.run(&["select", "--force", "--grace-period", "7d"]);
+    assert!(ok, "select failed: {report}");
+    let changes = docker.changes();
+    let tagged = changes
+        .iter()
+        .position(|c| c == "tag sha256:eee555eee555eee555 dockerase-grace:eee555eee555")
+        .expect("held under a holding tag");
+    assert_eq!(changes[tagged + 1], "rmi myapp:1.0");
+    assert!(!changes.iter().any(|c| c.starts_with("image prune -a")));
+    let holds: Value = serde_json::from_str(&fs::read_to_string(&holds_path).unwrap()).unwrap();
+    assert_eq!(holds[0]["image"]["repository"], "myapp");
+    assert!(holds[0]["expires_at"].as_u64() > holds[0]["held_at"].as_u64());
+
+    // Within the grace period purge doesn't delete held images (the
+    // fixtures still list myapp:1.0, so at most the hold is released)
+    docker.clear_log();
+    let (ok, _) = docker.run(&["purge", "--force"]);
+    assert!(ok);
+    assert!(!docker
+        .changes()
+        .contains(&"rmi sha256:eee555eee555eee555".to_string()));
+
+    // Once it's over: delete what is still only held, release what was
+    // pulled back (postgres:16) and forget what is gone
+    let hold = |id: &str, repository: &str| {
+        serde_json::json!({
+            "image": {"id": id, "repository": repository, "tag": "1"},
+            "held_at": 0,
+            "expires_at": 1
+        })
+    };
+    let expired = serde_json::json!([
+        hold("sha256:ddd444ddd444ddd444", "old-app"),
+        hold("sha256:aaa111aaa111aaa111", "postgres"),
+        hold("sha256:fff666fff666fff666", "gone"),
+    ]);
+    fs::write(&holds_path, expired.to_string()).unwrap();
+
+    docker.clear_log();
+    let (ok, report) = docker.run(&["purge", "--dry-run"]);
+    assert!(ok);
+    assert!(plan(&report).contains(&("held_images".to_string(), "old-app:1".to_string())));
+    assert!(docker.changes().is_empty());
+
+    let (ok, report) = docker.run(&["purge", "--force"]);
+    assert!(ok, "purge failed: {report}");
+    let changes = docker.changes();
+    assert!(changes.contains(&"rmi sha256:ddd444ddd444ddd444".to_string()));
+    assert!(changes.contains(&"rmi dockerase-grace:aaa111aaa111".to_string()));
+    assert_eq!(fs::read_to_string(&holds_path).unwrap().trim(), "[]");
+}
+
+#[test]
+fn test_podman_runtime_executes_the_same_plans() {
+    let docker = FakeDocker::with_fixtures("podman");
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["purge", "--dry-run", "--force", "--runtime", "podman"],
+        &["purge", "--force", "--runtime", "podman"],
+    );
+    assert!(plan.contains(&("volumes".to_string(), ORPHAN.to_string())));
+    assert!(!plan.iter().any(|(_, name)| name == "scratch"));
+    assert_eq!(
+        changes,
+        vec![
+            "container prune -f".to_string(),
+            "image prune -f".to_string(),
+            format!("volume rm -f {ORPHAN}"),
+            "network prune -f".to_string(),
+        ]
+    );
+
+    docker.clear_log();
+    let (plan, changes) = dry_run_then_execute(
+        &docker,
+        &["--nuclear", "--dry-run", "--runtime", "podman"],
+        &["--nuclear", "--force", "--runtime", "podman"],
+    );
+    // Podman's own network is left alone
+    assert!(plan.contains(&("networks".to_string(), "app-net".to_string())));
+    assert!(!plan.iter().any(|(_, name)| name == "podman"));
+    assert_eq!(
+        changes,
+        vec![
+            "stop c0ffee000001".to_string(),
+            "rm -f c0ffee000001 c0ffee000002".to_string(),
+            "rmi -f docker.io/library/postgres:16 bbb222bbb222bbb222bbb222 docker.io/library/alpine:latest"
+                .to_string(),
+            format!("volume rm -f pgdata scratch {ORPHAN}"),
+            "network rm 9a9a9a9a9a9a9a9".to_string(),
+        ]
+    );
+
+    // Contexts become podman connections
+    docker.clear_log();
+    let (ok, _) = docker.run(&[
+        "purge",
+        "--dry-run",
+        "--runtime",
+        "podman",
+        "--context",
+        "vm",
+    ]);
+    assert!(ok);
+    assert!(docker
+        .calls()
+        .iter()
+        .all(|c| c.starts_with("--connection vm ")));
+}
+
+#[test]
+fn test_production_hosts_need_their_name_confirmed() {
+    let docker = FakeDocker::new();
+    let config = "[hosts.\"prod-*\"]\nproduction = true\n";
+    let args = ["purge", "--force", "--context", "prod-db"];
+
+    // --force alone is not enough, and nothing can be typed without a terminal
+    let output = docker.command_with_config(&args, config).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--confirm-production prod-db"));
+    assert!(docker.changes().is_empty());
+
+    let mut cmd = docker.command_with_config(&args, config);
+    cmd.args(["--confirm-production", "prod-web"]);
+    assert!(!cmd.output().unwrap().status.success());
+    assert!(docker.changes().is_empty());
+
+    // Previews never ask
+    let (ok, _) = run_json(
+        docker.command_with_config(&["purge", "--dry-run", "--context", "prod-db"], config),
+    );
+    assert!(ok);
+
+    let mut cmd = docker.command_with_config(&args, config);
+    cmd.args(["--confirm-production", "prod-db"]);
+    let (ok, report) = run_json(cmd);
+    assert!(ok, "purge failed: {report}");
+    assert_eq!(docker.changes().len(), 5);
+
+    // A remote daemon matches by the machine it is on
+    docker.clear_log();
+    let args = ["purge", "--force", "--host", "ssh://deploy@prod-db:22"];
+    let output = docker.command_with_config(&args, config).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--confirm-production prod-db"));
+    let output = docker
+        .command_with_config(&["purge", "--force"], config)
+        .env("DOCKER_HOST", "tcp://prod-db.internal:2376")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--confirm-production prod-db.internal")
+    );
+    assert!(docker.changes().is_empty());
+}
+
+#[test]
+fn test_images_prune_keeps_the_newest_tags_per_repository() {
+    let docker = FakeDocker::with_fixtures("retention");
+    let decisions = |report: &Value| -> Vec<(String, String)> {
+        report["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["name"].as_str().unwrap().to_string(),
+                    item["status"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    };
+
+    let (ok, planned) = docker.run(&["images", "prune", "--keep-latest", "2", "--dry-run"]);
+    assert!(ok, "dry run failed: {planned}");
+    assert!(docker.changes().is_empty());
+    let expected = [
+        ("alpine:latest", "skipped"),
+        ("myapp:1.2", "skipped"),
+        ("myapp:latest", "skipped"),
+        ("myapp:1.1", "planned"),
+        ("myapp:1.0", "planned"),
+        // postgres:16 is used by a container, so 15 is still among the newest 2
+        ("postgres:16", "skipped"),
+        ("postgres:15", "skipped"),
+    ]
+    .map(|(name, status)| (name.to_string(), status.to_string()));
+    assert_eq!(decisions(&planned), expected);
+
+    // myapp:1.0 was pushed, but 1.1 only ever existed here
+    let output = docker
+        .command(&[
+            "images",
+            "prune",
+            "--keep-latest",
+            "2",
+            "--dry-run",
+            "--plain",
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("1 of them was built locally and never pushed"),
+        "{stdout}"
+    );
+
+    let (ok, executed) = docker.run(&["images", "prune", "--keep-latest", "2", "--force"]);
+    assert!(ok, "prune failed: {executed}");
+    // Removed by name, so the kept tags of a shared image survive
+    assert_eq!(docker.changes(), ["rmi -f myapp:1.1 myapp:1.0"]);
+    assert_eq!(statuses(&executed)[3..5], ["removed", "removed"]);
+
+    // Held tags are still there, so they aren't reported removed
+    docker.clear_log();
+    let (ok, held) = docker.run(&[
+        "images",
+        "prune",
+        "--keep-latest",
+        "2",
+        "--force",
+        "--grace-period",
+        "7d",
+    ]);
+    assert!(ok, "prune failed: {held}");
+    assert!(docker.changes().contains(&"rmi myapp:1.1".to_string()));
+    assert_eq!(statuses(&held)[3..5], ["skipped", "skipped"]);
+    assert!(held["items"][3]["note"]
+        .as_str()
+        .unwrap()
+        .starts_with("Held for"));
+
+    // Without --keep-latest or a configured policy there is nothing to go by
+    let (ok, _) = docker.run(&["images", "prune", "--dry-run"]);
+    assert!(!ok);
+}
@@ -0,0 +1,124 @@
+#!/bin/sh
+# Fixture-driven stand-in for the docker CLI, used by tests/dry_run_tests.rs.
+# Read-only commands print the matching file from $FAKE_DOCKER_FIXTURES;
+# everything else just succeeds. Every invocation is appended to
+# $FAKE_DOCKER_LOG so tests can compare what ran against the plan.
+# Commands starting with $FAKE_DOCKER_NO_SPACE fail like a full daemon.
+# Removals fail for the IDs listed in $FAKE_DOCKER_REFUSE, or as still in
+# use for those in $FAKE_DOCKER_IN_USE, while the rest are removed.
+# The daemon reports itself as Docker Desktop unless $FAKE_DOCKER_OS says
+# otherwise.
+# A leading --context or --host (podman's --connection or --url) is logged
+# but otherwise ignored. Podman's `--format json` lists come from the .json
+# fixtures.
+
+echo "$*" >> "$FAKE_DOCKER_LOG"
+fixtures="$FAKE_DOCKER_FIXTURES"
+
+# Global flags pick the daemon; the fixtures stand in for any of them
+case "$1" in
+    --context|--host|--connection|--url) shift 2 ;;
+esac
+
+if [ -n "$FAKE_DOCKER_NO_SPACE" ]; then
+    case "$*" in
+        "$FAKE_DOCKER_NO_SPACE"*)
+            echo "Error response from daemon: mkdir /var/lib/docker/tmp/x: no space left on device" >&2
+            exit 1
+            ;;
+    esac
+fi
+
+case "$*" in
+    "rm "*|"rmi "*|"volume rm "*|"network rm "*)
+        failed=
+        for arg; do
+            case " $FAKE_DOCKER_REFUSE " in
+                *" $arg "*)
+                    echo "Error response from daemon: could not remove $arg: device or resource busy" >&2
+                    failed=1
+                    ;;
+            esac
+            case " $FAKE_DOCKER_IN_USE " in
+                *" $arg "*)
+                    echo "Error response from daemon: remove $arg: volume is in use - [c0ffee000001]" >&2
+                    failed=1
+                    ;;
+            esac
+        done
+        [ -z "$failed" ] || exit 1
+        ;;
+esac
+
+case "$*" in
+    *"--format json"*)
+        case "$*" in
+            "system df"*) cat "$fixtures/df.json" ;;
+            "images"*) cat "$fixtures/images.json" ;;
+            "ps -a"*) cat "$fixtures/containers.json" ;;
+            "ps"*) cat "$fixtures/running_containers.json" ;;
+            "volume ls"*"dangling=true"*) cat "$fixtures/dangling_volumes.json" ;;
+            "volume ls"*) cat "$fixtures/volumes.json" ;;
+            "network ls"*) cat "$fixtures/networks.json" ;;
+            # Only tests that write builders.jsonl have buildx builders
+            "buildx ls"*) [ ! -f "$fixtures/builders.jsonl" ] || cat "$fixtures/builders.jsonl" ;;
+        esac
+        ;;
+    version*) echo "fake-docker" ;;
+    "system df -v"*) cat "$fixtures/df_volumes.json" ;;
+    "system df"*)
+        # What is left once something was pruned, for tests that provide it
+        if [ -f "$fixtures/df_pruned.jsonl" ] && grep -q " prune " "$FAKE_DOCKER_LOG"; then
+            cat "$fixtures/df_pruned.jsonl"
+        else
+            cat "$fixtures/df.jsonl"
+        fi
+        ;;
+    "info --format {{.OperatingSystem}}") echo "${FAKE_DOCKER_OS:-Docker Desktop}" ;;
+    "info"*) echo "/nonexistent/docker" ;;
+    "images"*"com.docker.compose.project="*)
+        # Images compose built for the project are named after it
+        args="$*"
+        project="${args##*com.docker.compose.project=}"
+        grep "\"Repository\":\"${project%% *}-" "$fixtures/images.jsonl" || true
+        ;;
+    "images"*) cat "$fixtures/images.jsonl" ;;
+    "ps -a --no-trunc"*) [ ! -f "$fixtures/volume_users.txt" ] || cat "$fixtures/volume_users.txt" ;;
+    "ps -a"*) cat "$fixtures/containers.jsonl" ;;
+    "ps"*) grep '"State":"running"' "$fixtures/containers.jsonl" || true ;;
+    "volume ls"*"dangling=true"*) cat "$fixtures/dangling_volumes.jsonl" ;;
+    "volume ls"*) cat "$fixtures/volumes.jsonl" ;;
+    "volume inspect"*)
+        # Like docker, a volume that is gone fails the whole call after the
+        # others are printed
+        shift 4
+        status=0
+        for name; do
+            grep "^$name$(printf '\t')" "$fixtures/volumes_created.txt" 2>/dev/null || {
+                echo "Error response from daemon: get $name: no such volume" >&2
+                status=1
+            }
+        done
+        exit $status
+        ;;
+    "network ls"*) cat "$fixtures/networks.jsonl" ;;
+    "inspect"*RestartPolicy*)
+        [ ! -f "$fixtures/restart_policies.txt" ] || cat "$fixtures/restart_policies.txt"
+        ;;
+    "inspect"*) cat "$fixtures/log_paths.txt" ;;
+    "image inspect"*) cat "$fixtures/image_layers.txt" ;;
+    "history"*)
+        # Steps of the image (last argument), e.g. history/aaa111aaa111aaa111.txt
+        for image; do :; done
+        cat "$fixtures/history/${image#sha256:}.txt" 2>/dev/null || true
+        ;;
+    "buildx du"*) [ ! -f "$fixtures/buildx_du.txt" ] || cat "$fixtures/buildx_du.txt" ;;
+    "scout version"*) echo "fake-scout" ;;
+    "scout cves"*)
+        # Report for the image (last argument), e.g. scout/alpine_latest.sarif
+        for image; do :; done
+        report="$fixtures/scout/$(echo "${image#local://}" | tr ':/' '__').sarif"
+        if [ -f "$report" ]; then cat "$report"; else echo '{"runs":[{"results":[]}]}'; fi
+        ;;
+    *) echo "Total reclaimed space: 0B" ;;
+esac